@@ -0,0 +1,132 @@
+//! Compares sending and receiving datagrams one at a time against sending them in batches with
+//! `sendmmsg` and receiving them in batches with `recvmmsg`, over loopback.
+//!
+//! Run with `cargo bench --features mmsg`.
+
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use rusty_enet as enet;
+
+const ROUNDS: usize = 2000;
+const DATAGRAMS_PER_ROUND: usize = 64;
+
+/// Sends and receives one datagram per system call, using the default [`enet::Socket`] batch
+/// implementations.
+struct Baseline(UdpSocket);
+
+impl enet::Socket for Baseline {
+    type Address = SocketAddr;
+    type Error = io::Error;
+
+    fn init(&mut self, socket_options: enet::SocketOptions) -> Result<(), io::Error> {
+        enet::Socket::init(&mut self.0, socket_options)
+    }
+
+    fn send(&mut self, address: SocketAddr, buffer: &[u8]) -> Result<usize, io::Error> {
+        enet::Socket::send(&mut self.0, address, buffer)
+    }
+
+    fn receive(
+        &mut self,
+        buffer: &mut [u8; enet::MTU_MAX],
+    ) -> Result<Option<(SocketAddr, enet::PacketReceived)>, io::Error> {
+        enet::Socket::receive(&mut self.0, buffer)
+    }
+}
+
+fn bind() -> UdpSocket {
+    UdpSocket::bind(SocketAddr::from_str("127.0.0.1:0").unwrap()).unwrap()
+}
+
+fn host<S: enet::Socket>(
+    socket: S,
+    receive_batch_size: usize,
+    send_batch_size: usize,
+) -> enet::Host<S> {
+    enet::Host::new(
+        socket,
+        enet::HostSettings {
+            peer_limit: 1,
+            channel_limit: 1,
+            receive_batch_size,
+            send_batch_size,
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+/// Returns the time spent sending and receiving, in that order.
+fn bench<C: enet::Socket, S: enet::Socket>(
+    mut client: enet::Host<C>,
+    mut server: enet::Host<S>,
+    server_address: C::Address,
+) -> (Duration, Duration) {
+    let peer = client.connect(server_address, 1, 0).unwrap().id();
+    let mut connected = false;
+    while !connected {
+        while client.service().unwrap().is_some() {}
+        while let Some(event) = server.service().unwrap() {
+            connected |= matches!(event, enet::Event::Connect { .. });
+        }
+    }
+    while !client.peer(peer).connected() {
+        client.service().unwrap();
+    }
+
+    // Packets too large to share a datagram, so each round sends one datagram per packet.
+    let packet = enet::Packet::unreliable(&[0; 1000][..]);
+    let mut send_elapsed = Duration::ZERO;
+    let mut receive_elapsed = Duration::ZERO;
+    for _ in 0..ROUNDS {
+        for _ in 0..DATAGRAMS_PER_ROUND {
+            client.peer_mut(peer).send(0, &packet).unwrap();
+        }
+        let start = Instant::now();
+        client.flush();
+        send_elapsed += start.elapsed();
+        let start = Instant::now();
+        let mut received = 0;
+        while received < DATAGRAMS_PER_ROUND && start.elapsed() < Duration::from_secs(1) {
+            while let Some(event) = server.service().unwrap() {
+                if matches!(event, enet::Event::Receive { .. }) {
+                    received += 1;
+                }
+            }
+        }
+        receive_elapsed += start.elapsed();
+        while client.service().unwrap().is_some() {}
+    }
+    (send_elapsed, receive_elapsed)
+}
+
+fn print(name: &str, (send_elapsed, receive_elapsed): (Duration, Duration)) {
+    let datagrams = (ROUNDS * DATAGRAMS_PER_ROUND) as f64;
+    println!(
+        "{name:<14}: send {:>8.1} ns, receive {:>8.1} ns per datagram",
+        send_elapsed.as_nanos() as f64 / datagrams,
+        receive_elapsed.as_nanos() as f64 / datagrams,
+    );
+}
+
+fn main() {
+    let server = host(Baseline(bind()), 1, 1);
+    let server_address = server.socket().0.local_addr().unwrap();
+    print(
+        "baseline",
+        bench(host(Baseline(bind()), 1, 1), server, server_address),
+    );
+    for batch_size in [1, 8, 32] {
+        let server = host(bind(), batch_size, batch_size);
+        let server_address = server.socket().local_addr().unwrap();
+        print(
+            &format!("batch size {batch_size:>2}"),
+            bench(host(bind(), batch_size, batch_size), server, server_address),
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Measures the cost of ENet's command queues, by pushing many small packets between two hosts
+//! over an in-memory socket. Datagrams are delivered out of order, so incoming commands queue up
+//! waiting for the ones before them.
+//!
+//! The queues are also measured on their own, against the intrusive lists they replaced, by
+//! running the same sequence of queue operations on both.
+//!
+//! Run with `cargo bench --bench queues`.
+
+use std::{
+    convert::Infallible,
+    time::{Duration, Instant},
+    vec::Vec,
+};
+
+use rusty_enet as enet;
+
+/// The arena-backed lists the command queues are built on.
+#[allow(dead_code)]
+#[path = "../src/c/list.rs"]
+mod arena;
+
+/// The intrusive lists the command queues used before, where each command was allocated on its
+/// own and embedded the node linking it into its queue.
+mod intrusive {
+    #[derive(Copy, Clone)]
+    #[repr(C)]
+    pub struct ENetListNode {
+        pub next: *mut ENetListNode,
+        pub previous: *mut ENetListNode,
+    }
+    pub type ENetListIterator = *mut ENetListNode;
+    #[repr(C)]
+    pub struct ENetList {
+        pub sentinel: ENetListNode,
+    }
+    pub unsafe fn enet_list_clear(list: *mut ENetList) {
+        (*list).sentinel.next = &mut (*list).sentinel;
+        (*list).sentinel.previous = &mut (*list).sentinel;
+    }
+    pub unsafe fn enet_list_insert(position: ENetListIterator, data: *mut u8) -> ENetListIterator {
+        let result: ENetListIterator = data.cast();
+        (*result).previous = (*position).previous;
+        (*result).next = position;
+        (*(*result).previous).next = result;
+        (*position).previous = result;
+        result
+    }
+    pub unsafe fn enet_list_remove(position: ENetListIterator) -> *mut u8 {
+        (*(*position).previous).next = (*position).next;
+        (*(*position).next).previous = (*position).previous;
+        position.cast()
+    }
+    pub unsafe fn enet_list_move(
+        position: ENetListIterator,
+        data_first: *mut u8,
+        data_last: *mut u8,
+    ) -> ENetListIterator {
+        let first: ENetListIterator = data_first.cast();
+        let last: ENetListIterator = data_last.cast();
+        (*(*first).previous).next = (*last).next;
+        (*(*last).next).previous = (*first).previous;
+        (*first).previous = (*position).previous;
+        (*last).next = position;
+        (*(*first).previous).next = first;
+        (*position).previous = last;
+        first
+    }
+}
+
+type Host = enet::Host<enet::ReadWrite<(), Infallible>>;
+
+const ROUNDS: usize = 2000;
+const PACKETS_PER_ROUND: usize = 64;
+
+fn host() -> Host {
+    enet::Host::new(
+        enet::ReadWrite::new(),
+        enet::HostSettings {
+            peer_limit: 1,
+            channel_limit: 4,
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+/// Deliver every datagram `from` has sent to `to`, reversing the order of each group of four.
+/// Returns `false` if there was nothing to deliver.
+fn deliver(from: &mut Host, to: &mut Host) -> bool {
+    let mut datagrams = Vec::new();
+    while let Some((address, datagram)) = from.socket_mut().read() {
+        datagrams.push((address, datagram));
+    }
+    for group in datagrams.chunks_mut(4) {
+        group.reverse();
+    }
+    let delivered = !datagrams.is_empty();
+    for (address, datagram) in datagrams {
+        to.socket_mut().write(address, datagram);
+    }
+    delivered
+}
+
+/// Service both hosts and exchange their datagrams. Returns `false` once neither host has anything
+/// to send.
+fn update(client: &mut Host, server: &mut Host) -> bool {
+    while server.service().unwrap().is_some() {}
+    while client.service().unwrap().is_some() {}
+    let sent = deliver(client, server);
+    deliver(server, client) || sent
+}
+
+fn bench(packet: &enet::Packet) -> Duration {
+    let mut client = host();
+    let mut server = host();
+    let peer = client.connect((), 4, 0).unwrap().id();
+    while !client.peer(peer).connected() {
+        update(&mut client, &mut server);
+    }
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for i in 0..PACKETS_PER_ROUND {
+            client.peer_mut(peer).send(i as u8 % 4, packet).unwrap();
+        }
+        // Late unreliable packets are dropped, so run until there is nothing left to deliver.
+        while update(&mut client, &mut server) {}
+    }
+    start.elapsed()
+}
+
+/// The order commands are acknowledged and received in: each group of four is reversed, as
+/// [`deliver`] does with datagrams.
+fn shuffled() -> impl Iterator<Item = u32> {
+    (0..PACKETS_PER_ROUND as u32).map(|i| i ^ 3)
+}
+
+/// A command with the node linking it into an intrusive list, allocated on its own.
+#[repr(C)]
+struct IntrusiveCommand {
+    node: intrusive::ENetListNode,
+    sequence_number: u32,
+}
+
+/// Queues commands, sends and acknowledges them, then receives and dispatches them, using
+/// intrusive lists.
+unsafe fn bench_intrusive() -> Duration {
+    use intrusive::*;
+    let mut outgoing = Box::new(ENetList {
+        sentinel: ENetListNode {
+            next: std::ptr::null_mut(),
+            previous: std::ptr::null_mut(),
+        },
+    });
+    let mut sent = Box::new(ENetList { ..*outgoing });
+    let mut incoming = Box::new(ENetList { ..*outgoing });
+    let mut dispatched = Box::new(ENetList { ..*outgoing });
+    for list in [&mut outgoing, &mut sent, &mut incoming, &mut dispatched] {
+        enet_list_clear(&mut **list);
+    }
+    let end = |list: &mut ENetList| -> ENetListIterator { &mut list.sentinel };
+    let sequence_number =
+        |position: ENetListIterator| (*position.cast::<IntrusiveCommand>()).sequence_number;
+    let new = |sequence_number: u32| {
+        Box::into_raw(Box::new(IntrusiveCommand {
+            node: ENetListNode {
+                next: std::ptr::null_mut(),
+                previous: std::ptr::null_mut(),
+            },
+            sequence_number,
+        }))
+        .cast::<u8>()
+    };
+    let free = |data: *mut u8| drop(Box::from_raw(data.cast::<IntrusiveCommand>()));
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for i in 0..PACKETS_PER_ROUND as u32 {
+            enet_list_insert(end(&mut outgoing), new(i));
+        }
+        while outgoing.sentinel.next != end(&mut outgoing) {
+            let command = enet_list_remove(outgoing.sentinel.next);
+            enet_list_insert(end(&mut sent), command);
+        }
+        for i in shuffled() {
+            let mut current = sent.sentinel.next;
+            while sequence_number(current) != i {
+                current = (*current).next;
+            }
+            free(enet_list_remove(current));
+        }
+        for i in shuffled() {
+            let mut current = incoming.sentinel.previous;
+            while current != end(&mut incoming) && sequence_number(current) > i {
+                current = (*current).previous;
+            }
+            enet_list_insert((*current).next, new(i));
+        }
+        enet_list_move(
+            end(&mut dispatched),
+            incoming.sentinel.next.cast(),
+            incoming.sentinel.previous.cast(),
+        );
+        while dispatched.sentinel.next != end(&mut dispatched) {
+            free(enet_list_remove(dispatched.sentinel.next));
+        }
+    }
+    start.elapsed()
+}
+
+/// Runs the same queue operations as [`bench_intrusive`] on arena-backed lists.
+fn bench_arena() -> Duration {
+    use arena::*;
+    let mut commands = ENetArena::new();
+    let mut outgoing = ENetList::new();
+    let mut sent = ENetList::new();
+    let mut incoming = ENetList::new();
+    let mut dispatched = ENetList::new();
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for i in 0..PACKETS_PER_ROUND as u32 {
+            commands.insert(&mut outgoing, ENET_LIST_END, i);
+        }
+        while !outgoing.is_empty() {
+            let current = outgoing.begin();
+            commands.transfer(&mut outgoing, current, &mut sent, ENET_LIST_END);
+        }
+        for i in shuffled() {
+            let mut current = sent.begin();
+            while commands[current] != i {
+                current = commands.next(current);
+            }
+            commands.remove(&mut sent, current);
+        }
+        for i in shuffled() {
+            let mut current = commands.previous(&incoming, ENET_LIST_END);
+            while current != ENET_LIST_END && commands[current] > i {
+                current = commands.previous(&incoming, current);
+            }
+            let position = if current == ENET_LIST_END {
+                incoming.begin()
+            } else {
+                commands.next(current)
+            };
+            commands.insert(&mut incoming, position, i);
+        }
+        let (first, last) = (
+            incoming.begin(),
+            commands.previous(&incoming, ENET_LIST_END),
+        );
+        commands.move_range(&mut incoming, first, last, &mut dispatched, ENET_LIST_END);
+        while commands.pop_front(&mut dispatched).is_some() {}
+    }
+    start.elapsed()
+}
+
+fn main() {
+    for (name, packet) in [
+        ("reliable", enet::Packet::reliable(&[0; 32][..])),
+        ("unreliable", enet::Packet::unreliable(&[0; 32][..])),
+        ("fragmented", enet::Packet::reliable(&[0; 4000][..])),
+    ] {
+        let elapsed = bench(&packet);
+        println!(
+            "{name:>10}: {:>8.1} ns per packet",
+            elapsed.as_nanos() as f64 / (ROUNDS * PACKETS_PER_ROUND) as f64
+        );
+    }
+    // SAFETY: the intrusive lists only link nodes allocated by the benchmark itself.
+    for (name, elapsed) in [
+        ("intrusive", unsafe { bench_intrusive() }),
+        ("arena", bench_arena()),
+    ] {
+        println!(
+            "{name:>10}: {:>8.1} ns per command",
+            elapsed.as_nanos() as f64 / (ROUNDS * PACKETS_PER_ROUND) as f64
+        );
+    }
+}
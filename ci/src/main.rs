@@ -49,10 +49,18 @@ fn main() -> anyhow::Result<()> {
 
     let sh = Shell::new()?;
     if what_to_run.contains(Check::CHECK) {
-        check(&sh, Target::Default, Features(&["std", "connected"]))?;
+        check(
+            &sh,
+            Target::Default,
+            Features(&["std", "connected", "tokio", "mmsg", "chacha20poly1305"]),
+        )?;
     }
     if what_to_run.contains(Check::WASM_CHECK) {
-        check(&sh, Target::Wasm, Features(&["std", "connected"]))?;
+        check(
+            &sh,
+            Target::Wasm,
+            Features(&["std", "connected", "chacha20poly1305"]),
+        )?;
     }
     if what_to_run.contains(Check::EXAMPLE_CHECK) {
         example_check(&sh)?;
@@ -101,7 +109,11 @@ fn fmt(sh: &Shell) -> anyhow::Result<()> {
 }
 
 fn test(sh: &Shell) -> anyhow::Result<()> {
-    cmd!(sh, "cargo test --workspace --lib --bins --tests").run()?;
+    cmd!(
+        sh,
+        "cargo test --workspace --all-features --lib --bins --tests"
+    )
+    .run()?;
     Ok(())
 }
 
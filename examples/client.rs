@@ -29,11 +29,21 @@ fn main() {
                 enet::Event::Connect { peer, .. } => {
                     println!("Connected");
                     let packet = enet::Packet::reliable("hello world".as_bytes());
-                    _ = peer.send(0, &packet);
+                    _ = peer.send_tracked(0, &packet);
                 }
                 enet::Event::Disconnect { .. } => {
                     println!("Disconnected");
                 }
+                enet::Event::ConnectFailed { reason, .. } => {
+                    println!("Failed to connect: {:?}", reason);
+                }
+                enet::Event::Acknowledged { .. } => {
+                    println!("Server received hello");
+                }
+                enet::Event::Dropped { .. } => {
+                    println!("Server did not receive hello");
+                }
+                enet::Event::Writable { .. } | enet::Event::SendFailed { .. } => {}
                 enet::Event::Receive { packet, .. } => {
                     if let Ok(message) = str::from_utf8(packet.data()) {
                         println!("Received packet: {:?}", message);
@@ -24,18 +24,18 @@ fn main() {
         };
     }
 
-    host1.connect((), 255, 100).unwrap();
+    let peer = host1.connect((), 255, 100).unwrap().id();
 
     update!();
 
     host1
-        .peer_mut(enet::PeerID(0))
+        .peer_mut(peer)
         .send(0, &enet::Packet::reliable("Hello!".as_bytes()))
         .unwrap();
 
     update!();
 
-    host1.peer_mut(enet::PeerID(0)).disconnect(32);
+    host1.peer_mut(peer).disconnect(32);
 
     update!();
 }
@@ -55,14 +55,34 @@ fn update_host(
                     data
                 );
             }
-            enet::Event::Disconnect { peer, data } => {
+            enet::Event::Disconnect { peer, reason, .. } => {
                 println!(
-                    "[{}] Disconnected from {:?} with data: {}",
+                    "[{}] Disconnected from {:?} with reason: {:?}",
                     name,
                     peer.id(),
-                    data
+                    reason
+                );
+            }
+            enet::Event::ConnectFailed { peer, reason } => {
+                println!(
+                    "[{}] Failed to connect to {:?} with reason: {:?}",
+                    name,
+                    peer.id(),
+                    reason
                 );
             }
+            enet::Event::Acknowledged { peer, token } => {
+                println!("[{}] {:?} acknowledged {:?}", name, peer, token);
+            }
+            enet::Event::Dropped { peer, token } => {
+                println!("[{}] {:?} dropped {:?}", name, peer, token);
+            }
+            enet::Event::Writable { peer } => {
+                println!("[{}] {:?} is writable", name, peer.id());
+            }
+            enet::Event::SendFailed { peer, error, .. } => {
+                println!("[{}] Failed to send to {:?}: {}", name, peer, error);
+            }
             enet::Event::Receive {
                 peer,
                 packet,
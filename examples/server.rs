@@ -24,11 +24,16 @@ fn main() {
         while let Some(event) = host.service().unwrap() {
             match event {
                 enet::Event::Connect { peer, .. } => {
-                    println!("Peer {} connected", peer.id().0);
+                    println!("Peer {} connected", peer.id().index());
                 }
                 enet::Event::Disconnect { peer, .. } => {
-                    println!("Peer {} disconnected", peer.id().0);
+                    println!("Peer {} disconnected", peer.id().index());
                 }
+                enet::Event::ConnectFailed { .. }
+                | enet::Event::Acknowledged { .. }
+                | enet::Event::Dropped { .. }
+                | enet::Event::Writable { .. }
+                | enet::Event::SendFailed { .. } => {}
                 enet::Event::Receive {
                     peer,
                     channel_id,
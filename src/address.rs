@@ -1,4 +1,7 @@
-use core::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
+use core::{
+    hash::{Hash, Hasher},
+    net::{SocketAddr, SocketAddrV4, SocketAddrV6},
+};
 
 /// An address type, for use with the [`Socket`](`crate::Socket`) trait.
 pub trait Address: Sized + Clone {
@@ -14,6 +17,13 @@ pub trait Address: Sized + Clone {
     ///
     /// For IP based addresses, checks if this is the IPv4 broadcast address.
     fn is_broadcast(&self) -> bool;
+    /// Feed this address into a hasher.
+    ///
+    /// Used to bind connect cookies to the address they were sent to, see
+    /// [`Host::set_connect_cookies`](`crate::Host::set_connect_cookies`). Addresses which compare
+    /// equal with [`Address::same`] must hash equally, and distinct addresses should feed in
+    /// distinct data, or a cookie answered to one address is accepted from the others.
+    fn hash_address(&self, state: &mut dyn Hasher);
 }
 
 impl Address for () {
@@ -28,6 +38,10 @@ impl Address for () {
     fn is_broadcast(&self) -> bool {
         false
     }
+
+    fn hash_address(&self, _state: &mut dyn Hasher) {
+        // There is only one address, so there is nothing to bind to.
+    }
 }
 
 impl Address for SocketAddrV4 {
@@ -42,6 +56,10 @@ impl Address for SocketAddrV4 {
     fn is_broadcast(&self) -> bool {
         self.ip().is_broadcast()
     }
+
+    fn hash_address(&self, mut state: &mut dyn Hasher) {
+        self.hash(&mut state);
+    }
 }
 
 impl Address for SocketAddrV6 {
@@ -56,6 +74,10 @@ impl Address for SocketAddrV6 {
     fn is_broadcast(&self) -> bool {
         false
     }
+
+    fn hash_address(&self, mut state: &mut dyn Hasher) {
+        self.hash(&mut state);
+    }
 }
 
 impl Address for SocketAddr {
@@ -73,4 +95,8 @@ impl Address for SocketAddr {
             _ => false,
         }
     }
+
+    fn hash_address(&self, mut state: &mut dyn Hasher) {
+        self.hash(&mut state);
+    }
 }
@@ -0,0 +1,67 @@
+use core::hash::Hasher;
+
+use crate::siphash::SipHasher24;
+
+/// The maximum size of a tag produced by an [`Authenticator`].
+pub const AUTHENTICATION_TAG_MAX: usize = 32;
+
+/// An interface for authenticating ENet datagrams, with a key for each peer set by
+/// [`Peer::set_auth_key`](`crate::Peer::set_auth_key`).
+///
+/// Each datagram sent to a peer with a key carries a tag computed from the key and the rest of the
+/// datagram, which the receiving host recomputes and compares. Datagrams from a peer with a key
+/// which fail to authenticate are discarded, and counted in
+/// [`HostStatistics::total_unauthenticated_packets`](`crate::HostStatistics::total_unauthenticated_packets`).
+pub trait Authenticator {
+    /// Check if `key` can be used with this authenticator.
+    fn valid_key(&self, key: &[u8]) -> bool;
+    /// The size of the tags produced by [`Authenticator::authenticate`]. Cannot be 0 or greater
+    /// than [`AUTHENTICATION_TAG_MAX`].
+    fn tag_size(&self) -> usize;
+    /// Compute the tag of a datagram with `key`, writing it to `tag`, which is
+    /// [`Authenticator::tag_size`] bytes long.
+    ///
+    /// The incoming buffers should be treated as one contiguous datagram, since the sending and
+    /// receiving hosts may split it up differently.
+    fn authenticate(&mut self, key: &[u8], in_buffers: &[&[u8]], tag: &mut [u8]);
+}
+
+/// SipHash-2-4, with 16 byte keys and 8 byte tags.
+///
+/// A fast keyed hash which stops datagrams from being spoofed by anyone who does not know the key,
+/// unlike [`HostSettings::checksum`](`crate::HostSettings::checksum`), which is only seeded with a
+/// value sent in the clear during the handshake. It does not hide the contents of datagrams, see
+/// [`Cipher`](`crate::Cipher`) for that.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SipHash;
+
+impl SipHash {
+    /// Create a SipHash authenticator.
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Authenticator for SipHash {
+    fn valid_key(&self, key: &[u8]) -> bool {
+        key.len() == 16
+    }
+
+    fn tag_size(&self) -> usize {
+        8
+    }
+
+    fn authenticate(&mut self, key: &[u8], in_buffers: &[&[u8]], tag: &mut [u8]) {
+        let mut key0 = [0; 8];
+        let mut key1 = [0; 8];
+        key0.copy_from_slice(&key[..8]);
+        key1.copy_from_slice(&key[8..16]);
+        let mut hasher =
+            SipHasher24::new_with_keys(u64::from_le_bytes(key0), u64::from_le_bytes(key1));
+        for in_buffer in in_buffers {
+            hasher.write(in_buffer);
+        }
+        tag.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+}
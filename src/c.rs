@@ -2,6 +2,7 @@ use core::ptr::NonNull;
 
 use crate::Socket;
 
+#[cfg(test)]
 mod compress;
 mod event;
 mod host;
@@ -11,6 +12,7 @@ mod packet;
 mod peer;
 mod protocol;
 
+#[cfg(test)]
 pub(crate) use compress::*;
 pub(crate) use event::*;
 pub(crate) use host::*;
@@ -37,18 +39,17 @@ pub(crate) struct ENetChannel {
     pub(crate) incoming_unreliable_sequence_number: u16,
     pub(crate) incoming_reliable_commands: ENetList,
     pub(crate) incoming_unreliable_commands: ENetList,
+    pub(crate) priority: u8,
 }
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub(crate) struct ENetAcknowledgement {
-    pub(crate) acknowledgement_list: ENetListNode,
     pub(crate) sent_time: u32,
     pub(crate) command: ENetProtocol,
 }
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub(crate) struct ENetOutgoingCommand {
-    pub(crate) outgoing_command_list: ENetListNode,
     pub(crate) reliable_sequence_number: u16,
     pub(crate) unreliable_sequence_number: u16,
     pub(crate) sent_time: u32,
@@ -59,11 +60,15 @@ pub(crate) struct ENetOutgoingCommand {
     pub(crate) send_attempts: u16,
     pub(crate) command: ENetProtocol,
     pub(crate) packet: *mut ENetPacket,
+    pub(crate) send_token: u64,
+    pub(crate) priority: u8,
+    pub(crate) virtual_finish_time: u64,
+    pub(crate) expire_time: Option<u32>,
+    pub(crate) supersede_key: Option<u32>,
 }
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub(crate) struct ENetIncomingCommand {
-    pub(crate) incoming_command_list: ENetListNode,
     pub(crate) reliable_sequence_number: u16,
     pub(crate) unreliable_sequence_number: u16,
     pub(crate) command: ENetProtocol,
@@ -20,6 +20,14 @@ pub(crate) struct ENetSymbol {
     pub(crate) total: u16,
     pub(crate) parent: u16,
 }
+/// The state of the adaptive model between calls, used to start from a model primed with a
+/// dictionary instead of an empty one. A `next_symbol` of `0` means no model has been built yet.
+#[derive(Copy, Clone, Default, Debug)]
+pub(crate) struct ENetRangeCoderModel {
+    pub(crate) next_symbol: usize,
+    pub(crate) predicted: u16,
+    pub(crate) order: usize,
+}
 pub(crate) const ENET_CONTEXT_SYMBOL_MINIMUM: u32 = 1;
 pub(crate) const ENET_CONTEXT_ESCAPE_MINIMUM: u32 = 1;
 pub(crate) const ENET_SUBCONTEXT_ORDER: u32 = 2;
@@ -59,6 +67,7 @@ unsafe fn enet_symbol_rescale(mut symbol: *mut ENetSymbol) -> u16 {
 }
 pub(crate) unsafe fn enet_range_coder_compress(
     context: *mut u8,
+    model: Option<&mut ENetRangeCoderModel>,
     mut in_buffers: *const ENetBuffer,
     mut in_buffer_count: usize,
     in_limit: usize,
@@ -86,19 +95,25 @@ pub(crate) unsafe fn enet_range_coder_compress(
     let fresh0 = next_symbol;
     next_symbol = next_symbol.wrapping_add(1);
     root = ((*range_coder).symbols).as_mut_ptr().add(fresh0);
-    (*root).value = 0_i32 as u8;
-    (*root).count = 0_i32 as u8;
-    (*root).under = 0_i32 as u16;
-    (*root).left = 0_i32 as u16;
-    (*root).right = 0_i32 as u16;
-    (*root).symbols = 0_i32 as u16;
-    (*root).escapes = 0_i32 as u16;
-    (*root).total = 0_i32 as u16;
-    (*root).parent = 0_i32 as u16;
-    (*root).escapes = ENET_CONTEXT_ESCAPE_MINIMUM as i32 as u16;
-    (*root).total =
-        (ENET_CONTEXT_ESCAPE_MINIMUM as i32 + 256_i32 * ENET_CONTEXT_SYMBOL_MINIMUM as i32) as u16;
-    (*root).symbols = 0_i32 as u16;
+    if let Some(model) = model.as_deref().filter(|model| model.next_symbol > 0) {
+        next_symbol = model.next_symbol;
+        predicted = model.predicted;
+        order = model.order;
+    } else {
+        (*root).value = 0_i32 as u8;
+        (*root).count = 0_i32 as u8;
+        (*root).under = 0_i32 as u16;
+        (*root).left = 0_i32 as u16;
+        (*root).right = 0_i32 as u16;
+        (*root).symbols = 0_i32 as u16;
+        (*root).escapes = 0_i32 as u16;
+        (*root).total = 0_i32 as u16;
+        (*root).parent = 0_i32 as u16;
+        (*root).escapes = ENET_CONTEXT_ESCAPE_MINIMUM as i32 as u16;
+        (*root).total = (ENET_CONTEXT_ESCAPE_MINIMUM as i32
+            + 256_i32 * ENET_CONTEXT_SYMBOL_MINIMUM as i32) as u16;
+        (*root).symbols = 0_i32 as u16;
+    }
     let mut current_block_237: u64;
     loop {
         let mut subcontext: *mut ENetSymbol;
@@ -458,10 +473,18 @@ pub(crate) unsafe fn enet_range_coder_compress(
         *fresh12 = (encode_low >> 24_i32) as u8;
         encode_low <<= 8_i32;
     }
+    if let Some(model) = model {
+        *model = ENetRangeCoderModel {
+            next_symbol,
+            predicted,
+            order,
+        };
+    }
     out_data.offset_from(out_start) as i64 as usize
 }
 pub(crate) unsafe fn enet_range_coder_decompress(
     context: *mut u8,
+    model: Option<&ENetRangeCoderModel>,
     mut in_data: *const u8,
     in_limit: usize,
     mut out_data: *mut u8,
@@ -484,19 +507,25 @@ pub(crate) unsafe fn enet_range_coder_decompress(
     let fresh13 = next_symbol;
     next_symbol = next_symbol.wrapping_add(1);
     root = ((*range_coder).symbols).as_mut_ptr().add(fresh13);
-    (*root).value = 0_i32 as u8;
-    (*root).count = 0_i32 as u8;
-    (*root).under = 0_i32 as u16;
-    (*root).left = 0_i32 as u16;
-    (*root).right = 0_i32 as u16;
-    (*root).symbols = 0_i32 as u16;
-    (*root).escapes = 0_i32 as u16;
-    (*root).total = 0_i32 as u16;
-    (*root).parent = 0_i32 as u16;
-    (*root).escapes = ENET_CONTEXT_ESCAPE_MINIMUM as i32 as u16;
-    (*root).total =
-        (ENET_CONTEXT_ESCAPE_MINIMUM as i32 + 256_i32 * ENET_CONTEXT_SYMBOL_MINIMUM as i32) as u16;
-    (*root).symbols = 0_i32 as u16;
+    if let Some(model) = model.filter(|model| model.next_symbol > 0) {
+        next_symbol = model.next_symbol;
+        predicted = model.predicted;
+        order = model.order;
+    } else {
+        (*root).value = 0_i32 as u8;
+        (*root).count = 0_i32 as u8;
+        (*root).under = 0_i32 as u16;
+        (*root).left = 0_i32 as u16;
+        (*root).right = 0_i32 as u16;
+        (*root).symbols = 0_i32 as u16;
+        (*root).escapes = 0_i32 as u16;
+        (*root).total = 0_i32 as u16;
+        (*root).parent = 0_i32 as u16;
+        (*root).escapes = ENET_CONTEXT_ESCAPE_MINIMUM as i32 as u16;
+        (*root).total = (ENET_CONTEXT_ESCAPE_MINIMUM as i32
+            + 256_i32 * ENET_CONTEXT_SYMBOL_MINIMUM as i32) as u16;
+        (*root).symbols = 0_i32 as u16;
+    }
     if in_data < in_end {
         let fresh14 = in_data;
         in_data = in_data.offset(1);
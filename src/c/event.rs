@@ -1,10 +1,20 @@
 use crate::{ENetPacket, ENetPeer, Socket};
 
 pub(crate) type ENetEventType = u32;
+pub(crate) const ENET_EVENT_TYPE_DROPPED: ENetEventType = 6;
+pub(crate) const ENET_EVENT_TYPE_WRITABLE: ENetEventType = 7;
+pub(crate) const ENET_EVENT_TYPE_ACKNOWLEDGED: ENetEventType = 5;
+pub(crate) const ENET_EVENT_TYPE_CONNECT_FAILED: ENetEventType = 4;
 pub(crate) const ENET_EVENT_TYPE_RECEIVE: ENetEventType = 3;
 pub(crate) const ENET_EVENT_TYPE_DISCONNECT: ENetEventType = 2;
 pub(crate) const ENET_EVENT_TYPE_CONNECT: ENetEventType = 1;
 pub(crate) const ENET_EVENT_TYPE_NONE: ENetEventType = 0;
+pub(crate) type ENetDisconnectReason = u32;
+pub(crate) const ENET_DISCONNECT_REASON_CONNECT_FAILED: ENetDisconnectReason = 4;
+pub(crate) const ENET_DISCONNECT_REASON_TIMEOUT: ENetDisconnectReason = 3;
+pub(crate) const ENET_DISCONNECT_REASON_LOCAL: ENetDisconnectReason = 2;
+pub(crate) const ENET_DISCONNECT_REASON_REFUSED: ENetDisconnectReason = 1;
+pub(crate) const ENET_DISCONNECT_REASON_REMOTE: ENetDisconnectReason = 0;
 pub(crate) type ENetPacketFlag = u32;
 pub(crate) const ENET_PACKET_FLAG_SENT: ENetPacketFlag = 256;
 pub(crate) const ENET_PACKET_FLAG_UNRELIABLE_FRAGMENT: ENetPacketFlag = 8;
@@ -17,7 +27,10 @@ pub(crate) const ENET_PACKET_FLAG_RELIABLE: ENetPacketFlag = 1;
 pub(crate) struct ENetEvent<S: Socket> {
     pub(crate) type_0: ENetEventType,
     pub(crate) peer: *mut ENetPeer<S>,
+    pub(crate) peer_generation: u32,
     pub(crate) channel_id: u8,
     pub(crate) data: u32,
+    pub(crate) disconnect_reason: ENetDisconnectReason,
     pub(crate) packet: *mut ENetPacket,
+    pub(crate) send_token: u64,
 }
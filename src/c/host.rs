@@ -1,13 +1,21 @@
-use core::{alloc::Layout, mem::MaybeUninit, ptr::write_bytes, time::Duration};
+use core::{
+    alloc::Layout,
+    hash::Hasher,
+    mem::MaybeUninit,
+    ptr::{addr_of_mut, drop_in_place, write_bytes},
+    sync::atomic::Ordering,
+    time::Duration,
+};
 
 use crate::{
-    consts::*, enet_free, enet_list_clear, enet_malloc, enet_packet_destroy,
-    enet_peer_queue_outgoing_command, enet_peer_reset, enet_peer_send, enet_time_get, Box,
-    Compressor, ENetBuffer, ENetChannel, ENetList, ENetPacket, ENetPeer, ENetProtocol,
-    ENetProtocolCommandHeader, Socket, SocketOptions, ENET_PEER_STATE_CONNECTED,
-    ENET_PEER_STATE_CONNECTING, ENET_PEER_STATE_DISCONNECTED, ENET_PEER_STATE_DISCONNECT_LATER,
-    ENET_PROTOCOL_COMMAND_BANDWIDTH_LIMIT, ENET_PROTOCOL_COMMAND_CONNECT,
-    ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE,
+    consts::*, enet_free, enet_malloc, enet_packet_destroy, enet_peer_queue_outgoing_command,
+    enet_peer_reset, enet_peer_send, enet_time_get, siphash::SipHasher24, AcceptConnection,
+    Authenticator, BTreeMap, Box, Cipher, CompressionStatistics, Compressor, ENetBuffer,
+    ENetChannel, ENetEventType, ENetList, ENetPacket, ENetPeer, ENetPeerQueues, ENetProtocol,
+    ENetProtocolCommandHeader, Intercept, PacketReceived, PeerID, Socket, SocketOptions, Vec,
+    VecDeque, ENET_PEER_STATE_CONNECTED, ENET_PEER_STATE_CONNECTING, ENET_PEER_STATE_DISCONNECTED,
+    ENET_PEER_STATE_DISCONNECT_LATER, ENET_PROTOCOL_COMMAND_BANDWIDTH_LIMIT,
+    ENET_PROTOCOL_COMMAND_CONNECT, ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE,
 };
 
 #[allow(clippy::type_complexity)]
@@ -23,7 +31,9 @@ pub(crate) struct ENetHost<S: Socket> {
     pub(crate) peer_count: usize,
     pub(crate) channel_limit: usize,
     pub(crate) service_time: u32,
-    pub(crate) dispatch_queue: ENetList,
+    pub(crate) dispatch_queue: MaybeUninit<VecDeque<usize>>,
+    pub(crate) pending_events: MaybeUninit<VecDeque<(ENetEventType, PeerID, u64)>>,
+    pub(crate) send_token: u64,
     pub(crate) total_queued: u32,
     pub(crate) packet_size: usize,
     pub(crate) header_flags: u16,
@@ -34,20 +44,46 @@ pub(crate) struct ENetHost<S: Socket> {
     pub(crate) checksum: MaybeUninit<Option<Box<dyn Fn(&[&[u8]]) -> u32>>>,
     pub(crate) time: MaybeUninit<Box<dyn Fn() -> Duration>>,
     pub(crate) compressor: MaybeUninit<Option<Box<dyn Compressor>>>,
+    pub(crate) cipher: MaybeUninit<Option<Box<dyn Cipher>>>,
+    pub(crate) authenticator: MaybeUninit<Option<Box<dyn Authenticator>>>,
+    pub(crate) intercept: MaybeUninit<
+        Option<Box<dyn FnMut(&mut S, &S::Address, &[u8]) -> Intercept<S::Error> + Send>>,
+    >,
+    pub(crate) accept_connection: MaybeUninit<
+        Option<Box<dyn FnMut(&S::Address, usize, u32, usize) -> AcceptConnection + Send>>,
+    >,
     pub(crate) packet_data: [[u8; PROTOCOL_MAXIMUM_MTU]; 2],
+    pub(crate) send_scratch: MaybeUninit<Vec<u8>>,
     pub(crate) received_address: MaybeUninit<Option<S::Address>>,
     pub(crate) received_data: *mut u8,
     pub(crate) received_data_length: usize,
+    pub(crate) receive_buffers: MaybeUninit<Vec<[u8; PROTOCOL_MAXIMUM_MTU]>>,
+    pub(crate) received_batch: MaybeUninit<Vec<Option<(S::Address, PacketReceived)>>>,
+    pub(crate) received_batch_index: usize,
+    pub(crate) received_batch_count: usize,
+    pub(crate) send_batch_size: usize,
+    pub(crate) send_batch_buffers: MaybeUninit<Vec<[u8; PROTOCOL_MAXIMUM_MTU]>>,
+    pub(crate) send_batch: MaybeUninit<Vec<(S::Address, usize)>>,
     pub(crate) total_sent_data: u32,
     pub(crate) total_sent_packets: u32,
     pub(crate) total_received_data: u32,
     pub(crate) total_received_packets: u32,
+    pub(crate) total_unauthenticated_packets: u32,
     pub(crate) connected_peers: usize,
     pub(crate) bandwidth_limited_peers: usize,
     pub(crate) duplicate_peers: usize,
+    pub(crate) connect_cookies: bool,
+    pub(crate) adaptive_compression: bool,
+    pub(crate) compression_statistics: CompressionStatistics,
+    pub(crate) connect_cookie_secret: Option<[u8; 16]>,
+    pub(crate) connect_cookie_keys: [[u64; 2]; 2],
+    pub(crate) connect_cookie_rotation: u64,
+    pub(crate) connect_cookie_epoch: u32,
     pub(crate) maximum_packet_size: usize,
     pub(crate) maximum_waiting_data: usize,
+    pub(crate) channel_priorities: [u8; PROTOCOL_MAXIMUM_CHANNEL_COUNT as usize],
 }
+#[allow(clippy::too_many_arguments)]
 pub(crate) unsafe fn enet_host_create<S: Socket>(
     mut socket: S,
     peer_count: usize,
@@ -56,6 +92,8 @@ pub(crate) unsafe fn enet_host_create<S: Socket>(
     outgoing_bandwidth: u32,
     time: Box<dyn Fn() -> Duration>,
     seed: Option<u32>,
+    receive_batch_size: usize,
+    send_batch_size: usize,
 ) -> Result<*mut ENetHost<S>, S::Error> {
     let mut current_peer: *mut ENetPeer<S>;
     let host: *mut ENetHost<S> = enet_malloc(Layout::new::<ENetHost<S>>()).cast();
@@ -94,18 +132,59 @@ pub(crate) unsafe fn enet_host_create<S: Socket>(
     (*host).received_address.write(None);
     (*host).received_data = core::ptr::null_mut();
     (*host).received_data_length = 0_i32 as usize;
+    (*host)
+        .send_scratch
+        .write(Vec::with_capacity(PROTOCOL_MAXIMUM_MTU));
+    (*host).receive_buffers.write(
+        (0..receive_batch_size)
+            .map(|_| [0; PROTOCOL_MAXIMUM_MTU])
+            .collect(),
+    );
+    (*host)
+        .received_batch
+        .write((0..receive_batch_size).map(|_| None).collect());
+    (*host).received_batch_index = 0_i32 as usize;
+    (*host).received_batch_count = 0_i32 as usize;
+    (*host).send_batch_size = send_batch_size;
+    (*host).send_batch_buffers.write(if send_batch_size > 1 {
+        (0..send_batch_size)
+            .map(|_| [0; PROTOCOL_MAXIMUM_MTU])
+            .collect()
+    } else {
+        Vec::new()
+    });
+    (*host)
+        .send_batch
+        .write(Vec::with_capacity(send_batch_size));
     (*host).total_sent_data = 0_i32 as u32;
     (*host).total_sent_packets = 0_i32 as u32;
     (*host).total_received_data = 0_i32 as u32;
     (*host).total_received_packets = 0_i32 as u32;
+    (*host).total_unauthenticated_packets = 0_i32 as u32;
     (*host).total_queued = 0_i32 as u32;
     (*host).connected_peers = 0_i32 as usize;
     (*host).bandwidth_limited_peers = 0_i32 as usize;
     (*host).duplicate_peers = PROTOCOL_MAXIMUM_PEER_ID as i32 as usize;
+    (*host).connect_cookies = false;
+    (*host).connect_cookie_secret = None;
+    (*host).connect_cookie_keys = [[0_i32 as u64; 2]; 2];
+    (*host).connect_cookie_rotation = 0_i32 as u64;
+    (*host).connect_cookie_epoch = 0_i32 as u32;
+    (*host).adaptive_compression = true;
+    (*host).compression_statistics = CompressionStatistics::default();
     (*host).maximum_packet_size = HOST_DEFAULT_MAXIMUM_PACKET_SIZE as i32 as usize;
     (*host).maximum_waiting_data = HOST_DEFAULT_MAXIMUM_WAITING_DATA as i32 as usize;
+    (*host).channel_priorities = [0_i32 as u8; PROTOCOL_MAXIMUM_CHANNEL_COUNT as usize];
     (*host).compressor.write(None);
-    enet_list_clear(&mut (*host).dispatch_queue);
+    (*host).cipher.write(None);
+    (*host).authenticator.write(None);
+    (*host).intercept.write(None);
+    (*host).accept_connection.write(None);
+    (*host)
+        .dispatch_queue
+        .write(VecDeque::with_capacity(peer_count));
+    (*host).pending_events.write(VecDeque::new());
+    (*host).send_token = 0_i32 as u64;
     current_peer = (*host).peers;
     while current_peer < ((*host).peers).add((*host).peer_count) {
         (*current_peer).host = host;
@@ -113,12 +192,11 @@ pub(crate) unsafe fn enet_host_create<S: Socket>(
         (*current_peer).incoming_session_id = 0xff_i32 as u8;
         (*current_peer).outgoing_session_id = (*current_peer).incoming_session_id;
         (*current_peer).address.write(None);
-        (*current_peer).data = core::ptr::null_mut();
-        enet_list_clear(&mut (*current_peer).acknowledgements);
-        enet_list_clear(&mut (*current_peer).sent_reliable_commands);
-        enet_list_clear(&mut (*current_peer).outgoing_commands);
-        enet_list_clear(&mut (*current_peer).outgoing_send_reliable_commands);
-        enet_list_clear(&mut (*current_peer).dispatched_commands);
+        (*current_peer).data.write(None);
+        (*current_peer).cipher_key.write(None);
+        (*current_peer).auth_key.write(None);
+        addr_of_mut!((*current_peer).queues).write(ENetPeerQueues::new());
+        (*current_peer).send_tokens.write(BTreeMap::new());
         enet_peer_reset(current_peer);
         current_peer = current_peer.offset(1);
     }
@@ -134,12 +212,28 @@ pub(crate) unsafe fn enet_host_destroy<S: Socket>(host: *mut ENetHost<S>) {
     while current_peer < ((*host).peers).add((*host).peer_count) {
         enet_peer_reset(current_peer);
         (*current_peer).address.assume_init_drop();
+        (*current_peer).data.assume_init_drop();
+        (*current_peer).cipher_key.assume_init_drop();
+        (*current_peer).auth_key.assume_init_drop();
+        drop_in_place(addr_of_mut!((*current_peer).queues));
+        (*current_peer).send_tokens.assume_init_drop();
         current_peer = current_peer.offset(1);
     }
     (*host).checksum.assume_init_drop();
     (*host).time.assume_init_drop();
     (*host).compressor.assume_init_drop();
+    (*host).cipher.assume_init_drop();
+    (*host).authenticator.assume_init_drop();
+    (*host).intercept.assume_init_drop();
+    (*host).accept_connection.assume_init_drop();
     (*host).received_address.assume_init_drop();
+    (*host).send_scratch.assume_init_drop();
+    (*host).receive_buffers.assume_init_drop();
+    (*host).received_batch.assume_init_drop();
+    (*host).send_batch_buffers.assume_init_drop();
+    (*host).send_batch.assume_init_drop();
+    (*host).dispatch_queue.assume_init_drop();
+    (*host).pending_events.assume_init_drop();
     enet_free(
         (*host).peers.cast(),
         Layout::array::<ENetPeer<S>>((*host).peer_count).unwrap(),
@@ -153,6 +247,27 @@ pub(crate) unsafe fn enet_host_random<S: Socket>(host: *mut ENetHost<S>) -> u32
     n ^= n.wrapping_add((n ^ n >> 7_i32).wrapping_mul(n | 61_u32));
     n ^ n >> 14_i32
 }
+/// Replaces the key used to create connect cookies, keeping the previous key so that cookies
+/// handed out shortly before the rotation still verify.
+///
+/// Keys are derived from the host's connect cookie secret and a rotation counter, never from
+/// [`enet_host_random`], whose state is small and leaks through the connect IDs sent in the clear.
+pub(crate) unsafe fn enet_host_rotate_connect_cookie_secret<S: Socket>(host: *mut ENetHost<S>) {
+    let secret = (*host).connect_cookie_secret.unwrap();
+    let secret = [
+        u64::from_le_bytes(secret[0..8].try_into().unwrap()),
+        u64::from_le_bytes(secret[8..16].try_into().unwrap()),
+    ];
+    (*host).connect_cookie_keys[1] = (*host).connect_cookie_keys[0];
+    for (index, key) in (*host).connect_cookie_keys[0].iter_mut().enumerate() {
+        let mut hasher = SipHasher24::new_with_keys(secret[0], secret[1]);
+        hasher.write_u64((*host).connect_cookie_rotation);
+        hasher.write_usize(index);
+        *key = hasher.finish();
+    }
+    (*host).connect_cookie_rotation = (*host).connect_cookie_rotation.wrapping_add(1);
+    (*host).connect_cookie_epoch = (*host).service_time;
+}
 pub(crate) unsafe fn enet_host_connect<S: Socket>(
     host: *mut ENetHost<S>,
     address: S::Address,
@@ -187,8 +302,13 @@ pub(crate) unsafe fn enet_host_connect<S: Socket>(
         enet_malloc(Layout::array::<ENetChannel>(channel_count).unwrap()).cast();
     (*current_peer).channel_count = channel_count;
     (*current_peer).state = ENET_PEER_STATE_CONNECTING;
+    *(*current_peer).data.assume_init_mut() = None;
+    (*current_peer).generation = (*current_peer).generation.wrapping_add(1);
     *(*current_peer).address.assume_init_mut() = Some(address);
     (*current_peer).connect_id = enet_host_random(host);
+    (*current_peer).connect_time = enet_time_get(host);
+    (*current_peer).connect_timeout = 0_i32 as u32;
+    (*current_peer).connect_retry_limit = u32::MAX;
     (*current_peer).mtu = (*host).mtu;
     if (*host).outgoing_bandwidth == 0_i32 as u32 {
         (*current_peer).window_size = PROTOCOL_MAXIMUM_WINDOW_SIZE as i32 as u32;
@@ -208,10 +328,12 @@ pub(crate) unsafe fn enet_host_connect<S: Socket>(
         (*channel).outgoing_unreliable_sequence_number = 0_i32 as u16;
         (*channel).incoming_reliable_sequence_number = 0_i32 as u16;
         (*channel).incoming_unreliable_sequence_number = 0_i32 as u16;
-        enet_list_clear(&mut (*channel).incoming_reliable_commands);
-        enet_list_clear(&mut (*channel).incoming_unreliable_commands);
+        (*channel).incoming_reliable_commands = ENetList::new();
+        (*channel).incoming_unreliable_commands = ENetList::new();
         (*channel).used_reliable_windows = 0_i32 as u16;
         write_bytes(((*channel).reliable_windows).as_mut_ptr(), 0, 16);
+        (*channel).priority =
+            (*host).channel_priorities[channel.offset_from((*current_peer).channels) as usize];
         channel = channel.offset(1);
     }
     command.header.command = (ENET_PROTOCOL_COMMAND_CONNECT as i32
@@ -251,11 +373,11 @@ pub(crate) unsafe fn enet_host_broadcast<S: Socket>(
     while current_peer < ((*host).peers).add((*host).peer_count) {
         if (*current_peer).state == ENET_PEER_STATE_CONNECTED as i32 as u32 {
             // TODO: do we really want to ignore the result type here?
-            _ = enet_peer_send(current_peer, channel_id, packet);
+            _ = enet_peer_send(current_peer, channel_id, packet, 0_i32 as u64, None, None);
         }
         current_peer = current_peer.offset(1);
     }
-    if (*packet).reference_count == 0_i32 as usize {
+    if (*packet).reference_count.load(Ordering::Acquire) == 0_i32 as usize {
         enet_packet_destroy(packet);
     }
 }
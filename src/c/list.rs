@@ -1,47 +1,170 @@
+use core::ops::{Index, IndexMut};
+
+use crate::Vec;
+
+pub(crate) type ENetListIterator = usize;
+/// The iterator one past the last node of every list.
+pub(crate) const ENET_LIST_END: ENetListIterator = usize::MAX;
+/// A doubly linked list whose nodes live in an [`ENetArena`].
+///
+/// A list only remembers its first and last node, so several lists can share one arena and nodes
+/// can move between them without being reallocated.
 #[derive(Copy, Clone)]
-#[repr(C)]
-pub(crate) struct ENetListNode {
-    pub(crate) next: *mut ENetListNode,
-    pub(crate) previous: *mut ENetListNode,
-}
-pub(crate) type ENetListIterator = *mut ENetListNode;
-#[derive(Copy, Clone)]
-#[repr(C)]
 pub(crate) struct ENetList {
-    pub(crate) sentinel: ENetListNode,
+    head: ENetListIterator,
+    tail: ENetListIterator,
+}
+impl ENetList {
+    pub(crate) const fn new() -> Self {
+        Self {
+            head: ENET_LIST_END,
+            tail: ENET_LIST_END,
+        }
+    }
+    pub(crate) fn begin(&self) -> ENetListIterator {
+        self.head
+    }
+    pub(crate) fn is_empty(&self) -> bool {
+        self.head == ENET_LIST_END
+    }
+}
+struct ENetListNode<T> {
+    next: ENetListIterator,
+    previous: ENetListIterator,
+    value: Option<T>,
 }
-pub(crate) unsafe fn enet_list_clear(list: *mut ENetList) {
-    (*list).sentinel.next = &mut (*list).sentinel;
-    (*list).sentinel.previous = &mut (*list).sentinel;
+/// Storage for the nodes of one or more [`ENetList`]s.
+///
+/// Removed nodes are kept on a free list and reused, so a busy peer stops allocating once its
+/// queues have reached their working size.
+pub(crate) struct ENetArena<T> {
+    nodes: Vec<ENetListNode<T>>,
+    free: ENetListIterator,
 }
-pub(crate) unsafe fn enet_list_insert(
-    position: ENetListIterator,
-    data: *mut u8,
-) -> ENetListIterator {
-    let result: ENetListIterator = data as ENetListIterator;
-    (*result).previous = (*position).previous;
-    (*result).next = position;
-    (*(*result).previous).next = result;
-    (*position).previous = result;
-    result
+impl<T> ENetArena<T> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: ENET_LIST_END,
+        }
+    }
+    pub(crate) fn next(&self, position: ENetListIterator) -> ENetListIterator {
+        self.nodes[position].next
+    }
+    pub(crate) fn previous(&self, list: &ENetList, position: ENetListIterator) -> ENetListIterator {
+        if position == ENET_LIST_END {
+            list.tail
+        } else {
+            self.nodes[position].previous
+        }
+    }
+    /// Inserts `value` before `position`, or at the back of the list if `position` is
+    /// [`ENET_LIST_END`].
+    pub(crate) fn insert(
+        &mut self,
+        list: &mut ENetList,
+        position: ENetListIterator,
+        value: T,
+    ) -> ENetListIterator {
+        let node = if self.free == ENET_LIST_END {
+            self.nodes.push(ENetListNode {
+                next: ENET_LIST_END,
+                previous: ENET_LIST_END,
+                value: Some(value),
+            });
+            self.nodes.len() - 1
+        } else {
+            let node = self.free;
+            self.free = self.nodes[node].next;
+            self.nodes[node].value = Some(value);
+            node
+        };
+        self.link(list, node, node, position);
+        node
+    }
+    pub(crate) fn remove(&mut self, list: &mut ENetList, position: ENetListIterator) -> T {
+        self.unlink(list, position, position);
+        let node = &mut self.nodes[position];
+        node.next = self.free;
+        node.previous = ENET_LIST_END;
+        self.free = position;
+        node.value.take().unwrap()
+    }
+    /// Removes the first node of `list`, if there is one.
+    pub(crate) fn pop_front(&mut self, list: &mut ENetList) -> Option<T> {
+        if list.is_empty() {
+            None
+        } else {
+            Some(self.remove(list, list.begin()))
+        }
+    }
+    /// Moves the nodes from `first` to `last` inclusive out of `from` and inserts them before
+    /// `position` in `to`.
+    pub(crate) fn move_range(
+        &mut self,
+        from: &mut ENetList,
+        first: ENetListIterator,
+        last: ENetListIterator,
+        to: &mut ENetList,
+        position: ENetListIterator,
+    ) {
+        self.unlink(from, first, last);
+        self.link(to, first, last, position);
+    }
+    /// Moves a single node out of `from` and inserts it before `position` in `to`.
+    pub(crate) fn transfer(
+        &mut self,
+        from: &mut ENetList,
+        node: ENetListIterator,
+        to: &mut ENetList,
+        position: ENetListIterator,
+    ) {
+        self.move_range(from, node, node, to, position);
+    }
+    fn unlink(&mut self, list: &mut ENetList, first: ENetListIterator, last: ENetListIterator) {
+        let previous = self.nodes[first].previous;
+        let next = self.nodes[last].next;
+        if previous == ENET_LIST_END {
+            list.head = next;
+        } else {
+            self.nodes[previous].next = next;
+        }
+        if next == ENET_LIST_END {
+            list.tail = previous;
+        } else {
+            self.nodes[next].previous = previous;
+        }
+    }
+    fn link(
+        &mut self,
+        list: &mut ENetList,
+        first: ENetListIterator,
+        last: ENetListIterator,
+        position: ENetListIterator,
+    ) {
+        let previous = self.previous(list, position);
+        self.nodes[first].previous = previous;
+        self.nodes[last].next = position;
+        if previous == ENET_LIST_END {
+            list.head = first;
+        } else {
+            self.nodes[previous].next = first;
+        }
+        if position == ENET_LIST_END {
+            list.tail = last;
+        } else {
+            self.nodes[position].previous = last;
+        }
+    }
 }
-pub(crate) unsafe fn enet_list_remove(position: ENetListIterator) -> *mut u8 {
-    (*(*position).previous).next = (*position).next;
-    (*(*position).next).previous = (*position).previous;
-    position.cast()
+impl<T> Index<ENetListIterator> for ENetArena<T> {
+    type Output = T;
+    fn index(&self, position: ENetListIterator) -> &T {
+        self.nodes[position].value.as_ref().unwrap()
+    }
 }
-pub(crate) unsafe fn enet_list_move(
-    position: ENetListIterator,
-    data_first: *mut u8,
-    data_last: *mut u8,
-) -> ENetListIterator {
-    let first: ENetListIterator = data_first as ENetListIterator;
-    let last: ENetListIterator = data_last as ENetListIterator;
-    (*(*first).previous).next = (*last).next;
-    (*(*last).next).previous = (*first).previous;
-    (*first).previous = (*position).previous;
-    (*last).next = position;
-    (*(*first).previous).next = first;
-    (*position).previous = last;
-    first
+impl<T> IndexMut<ENetListIterator> for ENetArena<T> {
+    fn index_mut(&mut self, position: ENetListIterator) -> &mut T {
+        self.nodes[position].value.as_mut().unwrap()
+    }
 }
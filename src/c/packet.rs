@@ -1,13 +1,19 @@
-use core::{alloc::Layout, ffi::c_void, ptr::copy_nonoverlapping};
+use core::{
+    alloc::Layout,
+    ffi::c_void,
+    ptr::copy_nonoverlapping,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use crate::{enet_free, enet_malloc, ENET_PACKET_FLAG_NO_ALLOCATE};
 
 pub(crate) type ENetPacketFreeCallback = unsafe fn(*mut ENetPacket);
 
-#[derive(Copy, Clone)]
+// The reference count is atomic, since a Packet may be cloned and dropped on other threads while
+// the host holds references to it.
 #[repr(C)]
 pub(crate) struct ENetPacket {
-    pub(crate) reference_count: usize,
+    pub(crate) reference_count: AtomicUsize,
     pub(crate) flags: u32,
     pub(crate) data: *mut u8,
     pub(crate) data_length: usize,
@@ -30,7 +36,7 @@ pub(crate) unsafe fn enet_packet_create(
             copy_nonoverlapping(data, (*packet).data, data_length);
         }
     }
-    (*packet).reference_count = 0_i32 as usize;
+    (*packet).reference_count = AtomicUsize::new(0_i32 as usize);
     (*packet).flags = flags;
     (*packet).data_length = data_length;
     (*packet).free_callback = None;
@@ -54,3 +60,11 @@ pub(crate) unsafe fn enet_packet_destroy(packet: *mut ENetPacket) {
     }
     enet_free(packet.cast(), Layout::new::<ENetPacket>());
 }
+/// Drops a reference to `packet`, destroying it if it was the last one. `flags` are set on the
+/// packet before it is destroyed.
+pub(crate) unsafe fn enet_packet_release(packet: *mut ENetPacket, flags: u32) {
+    if !packet.is_null() && (*packet).reference_count.fetch_sub(1, Ordering::AcqRel) == 1 {
+        (*packet).flags |= flags;
+        enet_packet_destroy(packet);
+    }
+}
@@ -1,22 +1,27 @@
 use core::{
     alloc::Layout,
-    mem::MaybeUninit,
-    ptr::{addr_of_mut, write_bytes},
+    any::Any,
+    mem::{zeroed, MaybeUninit},
+    ptr::write_bytes,
+    sync::atomic::Ordering,
 };
 
 use crate::{
-    consts::*, enet_free, enet_host_flush, enet_list_clear, enet_list_insert, enet_list_move,
-    enet_list_remove, enet_malloc, enet_packet_create, enet_packet_destroy,
-    enet_protocol_command_size, error::PeerSendError, ENetAcknowledgement, ENetChannel,
-    ENetIncomingCommand, ENetList, ENetListIterator, ENetListNode, ENetOutgoingCommand, ENetPacket,
-    ENetProtocol, ENetProtocolAcknowledge, ENetProtocolCommandHeader, ENetProtocolHeader,
-    ENetProtocolSendFragment, Socket, ENET_PACKET_FLAG_RELIABLE,
+    consts::*, enet_free, enet_host_flush, enet_malloc, enet_packet_create, enet_packet_destroy,
+    enet_packet_release, enet_protocol_command_size, enet_time_get, error::PeerSendError, BTreeMap,
+    Box, CompressionStatistics, ENetAcknowledgement, ENetArena, ENetChannel, ENetDisconnectReason,
+    ENetEventType, ENetIncomingCommand, ENetList, ENetListIterator, ENetOutgoingCommand,
+    ENetPacket, ENetProtocol, ENetProtocolAcknowledge, ENetProtocolCommandHeader,
+    ENetProtocolHeader, ENetProtocolSendFragment, NonceCounter, PeerID, ReplayWindow, Socket, Vec,
+    ENET_DISCONNECT_REASON_REMOTE, ENET_EVENT_TYPE_ACKNOWLEDGED, ENET_EVENT_TYPE_DROPPED,
+    ENET_EVENT_TYPE_WRITABLE, ENET_LIST_END, ENET_PACKET_FLAG_RELIABLE,
     ENET_PACKET_FLAG_UNRELIABLE_FRAGMENT, ENET_PACKET_FLAG_UNSEQUENCED,
-    ENET_PROTOCOL_COMMAND_DISCONNECT, ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE,
-    ENET_PROTOCOL_COMMAND_FLAG_UNSEQUENCED, ENET_PROTOCOL_COMMAND_MASK, ENET_PROTOCOL_COMMAND_PING,
-    ENET_PROTOCOL_COMMAND_SEND_FRAGMENT, ENET_PROTOCOL_COMMAND_SEND_RELIABLE,
-    ENET_PROTOCOL_COMMAND_SEND_UNRELIABLE, ENET_PROTOCOL_COMMAND_SEND_UNRELIABLE_FRAGMENT,
-    ENET_PROTOCOL_COMMAND_SEND_UNSEQUENCED, ENET_PROTOCOL_COMMAND_THROTTLE_CONFIGURE,
+    ENET_PROTOCOL_COMMAND_CONNECT, ENET_PROTOCOL_COMMAND_DISCONNECT,
+    ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE, ENET_PROTOCOL_COMMAND_FLAG_UNSEQUENCED,
+    ENET_PROTOCOL_COMMAND_MASK, ENET_PROTOCOL_COMMAND_PING, ENET_PROTOCOL_COMMAND_SEND_FRAGMENT,
+    ENET_PROTOCOL_COMMAND_SEND_RELIABLE, ENET_PROTOCOL_COMMAND_SEND_UNRELIABLE,
+    ENET_PROTOCOL_COMMAND_SEND_UNRELIABLE_FRAGMENT, ENET_PROTOCOL_COMMAND_SEND_UNSEQUENCED,
+    ENET_PROTOCOL_COMMAND_THROTTLE_CONFIGURE,
 };
 
 use super::ENetHost;
@@ -34,11 +39,11 @@ pub(crate) const ENET_PEER_STATE_ACKNOWLEDGING_CONNECT: _ENetPeerState = 2;
 pub(crate) const ENET_PEER_STATE_CONNECTING: _ENetPeerState = 1;
 pub(crate) const ENET_PEER_STATE_DISCONNECTED: _ENetPeerState = 0;
 pub(crate) type _ENetPeerFlag = u32;
+pub(crate) const ENET_PEER_FLAG_CONNECT_FAILED: _ENetPeerFlag = 4;
 pub(crate) const ENET_PEER_FLAG_CONTINUE_SENDING: _ENetPeerFlag = 2;
 pub(crate) const ENET_PEER_FLAG_NEEDS_DISPATCH: _ENetPeerFlag = 1;
 #[repr(C)]
 pub(crate) struct ENetPeer<S: Socket> {
-    pub(crate) dispatch_list: ENetListNode,
     pub(crate) host: *mut ENetHost<S>,
     pub(crate) outgoing_peer_id: u16,
     pub(crate) incoming_peer_id: u16,
@@ -46,7 +51,19 @@ pub(crate) struct ENetPeer<S: Socket> {
     pub(crate) outgoing_session_id: u8,
     pub(crate) incoming_session_id: u8,
     pub(crate) address: MaybeUninit<Option<S::Address>>,
-    pub(crate) data: *mut u8,
+    pub(crate) data: MaybeUninit<Option<Box<dyn Any + Send + Sync>>>,
+    pub(crate) generation: u32,
+    pub(crate) cipher_key: MaybeUninit<Option<Box<[u8]>>>,
+    pub(crate) cipher_replay_window: ReplayWindow,
+    pub(crate) cipher_nonce_counter: NonceCounter,
+    pub(crate) auth_key: MaybeUninit<Option<Box<[u8]>>>,
+    pub(crate) compression: bool,
+    pub(crate) compression_statistics: CompressionStatistics,
+    pub(crate) compression_probe_attempts: u32,
+    pub(crate) compression_probe_bytes_in: u32,
+    pub(crate) compression_probe_bytes_out: u32,
+    pub(crate) compression_bypassed: bool,
+    pub(crate) compression_bypass_epoch: u32,
     pub(crate) state: ENetPeerState,
     pub(crate) channels: *mut ENetChannel,
     pub(crate) channel_count: usize,
@@ -60,6 +77,9 @@ pub(crate) struct ENetPeer<S: Socket> {
     pub(crate) last_receive_time: u32,
     pub(crate) next_timeout: u32,
     pub(crate) earliest_timeout: u32,
+    pub(crate) connect_time: u32,
+    pub(crate) connect_timeout: u32,
+    pub(crate) connect_retry_limit: u32,
     pub(crate) packet_loss_epoch: u32,
     pub(crate) packets_sent: u32,
     pub(crate) packets_lost: u32,
@@ -86,18 +106,850 @@ pub(crate) struct ENetPeer<S: Socket> {
     pub(crate) window_size: u32,
     pub(crate) reliable_data_in_transit: u32,
     pub(crate) outgoing_reliable_sequence_number: u16,
-    pub(crate) acknowledgements: ENetList,
-    pub(crate) sent_reliable_commands: ENetList,
-    pub(crate) outgoing_send_reliable_commands: ENetList,
-    pub(crate) outgoing_commands: ENetList,
-    pub(crate) dispatched_commands: ENetList,
+    pub(crate) queues: ENetPeerQueues,
+    pub(crate) send_tokens: MaybeUninit<BTreeMap<u64, u32>>,
     pub(crate) flags: u16,
     pub(crate) reserved: u16,
     pub(crate) incoming_unsequenced_group: u16,
     pub(crate) outgoing_unsequenced_group: u16,
     pub(crate) unsequenced_window: [u32; 32],
     pub(crate) event_data: u32,
+    pub(crate) disconnect_reason: ENetDisconnectReason,
     pub(crate) total_waiting_data: usize,
+    pub(crate) queued_data: usize,
+    pub(crate) queue_low_watermark: usize,
+    pub(crate) queue_high_watermark: usize,
+    pub(crate) queue_above_high_watermark: bool,
+}
+/// One of the queues outgoing commands move between, see [`ENetPeerQueues`].
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ENetOutgoingQueue {
+    /// Commands waiting to be sent, other than reliable commands carrying a packet.
+    Outgoing,
+    /// Reliable commands carrying a packet, waiting for room in the reliable window.
+    OutgoingSendReliable,
+    /// Reliable commands waiting to be acknowledged.
+    SentReliable,
+    /// Unreliable commands in the datagrams being sent, released once they are sent.
+    SentUnreliable,
+}
+impl ENetOutgoingQueue {
+    /// The index of an unsent queue in the per queue fields of [`ENetPriorityClass`].
+    fn unsent_index(self) -> Option<usize> {
+        match self {
+            ENetOutgoingQueue::Outgoing => Some(0),
+            ENetOutgoingQueue::OutgoingSendReliable => Some(1),
+            ENetOutgoingQueue::SentReliable | ENetOutgoingQueue::SentUnreliable => None,
+        }
+    }
+}
+/// The unsent commands of one priority.
+///
+/// Priorities share a peer's datagrams by weighted fair queuing. A command is stamped with a
+/// virtual finish time when it is queued: the virtual finish time of the previous command of its
+/// priority, or the peer's virtual time if that is later, plus its size divided by the weight of
+/// its priority. The unsent queues are ordered by virtual finish time, and sending a command
+/// advances the virtual time to its virtual finish time. So while several priorities have commands
+/// queued, each is sent a share of the data in proportion to its weight, a priority with nothing
+/// queued can not save up a share for later, and the commands of a priority stay in order.
+#[derive(Copy, Clone)]
+pub(crate) struct ENetPriorityClass {
+    priority: u8,
+    last_virtual_finish_time: u64,
+    /// How many commands of this priority are queued in each unsent queue.
+    counts: [usize; 2],
+    /// The last command of this priority in each unsent queue, or [`ENET_LIST_END`] when it is
+    /// unknown.
+    tails: [ENetListIterator; 2],
+}
+/// The weight of `priority` when priorities share a peer's datagrams, see [`ENetPriorityClass`].
+/// Every priority has a weight, so every channel makes progress.
+fn enet_priority_weight(priority: u8) -> u64 {
+    u64::from(priority) + 1
+}
+/// The virtual time a byte sent at the lowest priority takes up.
+const ENET_PRIORITY_VIRTUAL_TIME_SCALE: u64 = 256;
+/// The command queues of a peer, and the arenas their nodes live in.
+///
+/// Queue operations are safe methods. Commands still point to their packets, so the packets of
+/// removed commands are released by the caller, or by the `release` callback of methods removing
+/// several commands at once.
+pub(crate) struct ENetPeerQueues {
+    pub(crate) acknowledgements: ENetList,
+    pub(crate) sent_reliable_commands: ENetList,
+    pub(crate) outgoing_send_reliable_commands: ENetList,
+    pub(crate) outgoing_commands: ENetList,
+    pub(crate) sent_unreliable_commands: ENetList,
+    pub(crate) dispatched_commands: ENetList,
+    pub(crate) acknowledgement_arena: ENetArena<ENetAcknowledgement>,
+    pub(crate) outgoing_command_arena: ENetArena<ENetOutgoingCommand>,
+    pub(crate) incoming_command_arena: ENetArena<ENetIncomingCommand>,
+    /// The virtual finish time of the last command sent, see [`ENetPriorityClass`].
+    virtual_time: u64,
+    priority_classes: Vec<ENetPriorityClass>,
+}
+impl ENetPeerQueues {
+    pub(crate) const fn new() -> Self {
+        Self {
+            acknowledgements: ENetList::new(),
+            sent_reliable_commands: ENetList::new(),
+            outgoing_send_reliable_commands: ENetList::new(),
+            outgoing_commands: ENetList::new(),
+            sent_unreliable_commands: ENetList::new(),
+            dispatched_commands: ENetList::new(),
+            acknowledgement_arena: ENetArena::new(),
+            outgoing_command_arena: ENetArena::new(),
+            incoming_command_arena: ENetArena::new(),
+            virtual_time: 0,
+            priority_classes: Vec::new(),
+        }
+    }
+    pub(crate) fn list(&self, queue: ENetOutgoingQueue) -> &ENetList {
+        match queue {
+            ENetOutgoingQueue::Outgoing => &self.outgoing_commands,
+            ENetOutgoingQueue::OutgoingSendReliable => &self.outgoing_send_reliable_commands,
+            ENetOutgoingQueue::SentReliable => &self.sent_reliable_commands,
+            ENetOutgoingQueue::SentUnreliable => &self.sent_unreliable_commands,
+        }
+    }
+    fn outgoing_list_mut(
+        &mut self,
+        queue: ENetOutgoingQueue,
+    ) -> (&mut ENetArena<ENetOutgoingCommand>, &mut ENetList) {
+        let list = match queue {
+            ENetOutgoingQueue::Outgoing => &mut self.outgoing_commands,
+            ENetOutgoingQueue::OutgoingSendReliable => &mut self.outgoing_send_reliable_commands,
+            ENetOutgoingQueue::SentReliable => &mut self.sent_reliable_commands,
+            ENetOutgoingQueue::SentUnreliable => &mut self.sent_unreliable_commands,
+        };
+        (&mut self.outgoing_command_arena, list)
+    }
+    pub(crate) fn has_outgoing_commands(&self) -> bool {
+        !self.outgoing_commands.is_empty()
+            || !self.outgoing_send_reliable_commands.is_empty()
+            || !self.sent_reliable_commands.is_empty()
+    }
+    pub(crate) fn queue_acknowledgement(
+        &mut self,
+        acknowledgement: ENetAcknowledgement,
+    ) -> ENetListIterator {
+        self.acknowledgement_arena.insert(
+            &mut self.acknowledgements,
+            ENET_LIST_END,
+            acknowledgement,
+        )
+    }
+    pub(crate) fn pop_acknowledgement(&mut self) -> Option<ENetAcknowledgement> {
+        self.acknowledgement_arena
+            .pop_front(&mut self.acknowledgements)
+    }
+    fn priority_class_mut(&mut self, priority: u8) -> &mut ENetPriorityClass {
+        if let Some(index) = self
+            .priority_classes
+            .iter()
+            .position(|priority_class| priority_class.priority == priority)
+        {
+            return &mut self.priority_classes[index];
+        }
+        self.priority_classes.push(ENetPriorityClass {
+            priority,
+            last_virtual_finish_time: 0,
+            counts: [0; 2],
+            tails: [ENET_LIST_END; 2],
+        });
+        self.priority_classes.last_mut().unwrap()
+    }
+    /// Stamps a command with the virtual finish time it is sent by at its priority, see
+    /// [`ENetPriorityClass`].
+    fn schedule_outgoing_command(&mut self, position: ENetListIterator) {
+        let outgoing_command = &self.outgoing_command_arena[position];
+        let priority = outgoing_command.priority;
+        let size = (enet_protocol_command_size(outgoing_command.command.header().command)
+            + outgoing_command.fragment_length as usize) as u64;
+        let virtual_time = self.virtual_time;
+        let priority_class = self.priority_class_mut(priority);
+        priority_class.last_virtual_finish_time =
+            priority_class.last_virtual_finish_time.max(virtual_time)
+                + (size * ENET_PRIORITY_VIRTUAL_TIME_SCALE / enet_priority_weight(priority)).max(1);
+        let virtual_finish_time = priority_class.last_virtual_finish_time;
+        self.outgoing_command_arena[position].virtual_finish_time = virtual_finish_time;
+    }
+    /// Advances the virtual time past a command put in a datagram.
+    pub(crate) fn sent_outgoing_command(&mut self, position: ENetListIterator) {
+        self.virtual_time = self
+            .virtual_time
+            .max(self.outgoing_command_arena[position].virtual_finish_time);
+    }
+    /// Counts a command linked into `queue` against its priority. `last` is set when it was linked
+    /// behind every command of its priority.
+    fn track_outgoing_command(
+        &mut self,
+        queue: ENetOutgoingQueue,
+        position: ENetListIterator,
+        last: bool,
+    ) {
+        let Some(index) = queue.unsent_index() else {
+            return;
+        };
+        let priority = self.outgoing_command_arena[position].priority;
+        let priority_class = self.priority_class_mut(priority);
+        if last || priority_class.counts[index] == 0 {
+            priority_class.tails[index] = position;
+        }
+        priority_class.counts[index] += 1;
+    }
+    /// Stops counting a command about to be unlinked from `queue` against its priority.
+    fn untrack_outgoing_command(&mut self, queue: ENetOutgoingQueue, position: ENetListIterator) {
+        let Some(index) = queue.unsent_index() else {
+            return;
+        };
+        let priority = self.outgoing_command_arena[position].priority;
+        let priority_class = self.priority_class_mut(priority);
+        priority_class.counts[index] -= 1;
+        if priority_class.tails[index] == position {
+            priority_class.tails[index] = ENET_LIST_END;
+        }
+    }
+    /// Finds where the command at `position`, not linked into any list yet, is queued in `queue`:
+    /// behind every command of its priority, and ahead of the commands with a later virtual finish
+    /// time. Searches from the last command of the priority when it is known, so queuing behind a
+    /// long backlog of other priorities does not walk the whole queue.
+    fn outgoing_command_position(
+        &self,
+        queue: ENetOutgoingQueue,
+        position: ENetListIterator,
+    ) -> ENetListIterator {
+        let outgoing_commands = &self.outgoing_command_arena;
+        let list = self.list(queue);
+        let index = queue.unsent_index().unwrap();
+        let priority = outgoing_commands[position].priority;
+        let virtual_finish_time = outgoing_commands[position].virtual_finish_time;
+        let later = |current_command: ENetListIterator| {
+            outgoing_commands[current_command].virtual_finish_time > virtual_finish_time
+        };
+        let (count, tail) = self
+            .priority_classes
+            .iter()
+            .find(|priority_class| priority_class.priority == priority)
+            .map_or((0, ENET_LIST_END), |priority_class| {
+                (priority_class.counts[index], priority_class.tails[index])
+            });
+        if count == 0 {
+            // Nothing of this priority is queued, so search from both ends at once.
+            let mut front = list.begin();
+            let mut back = ENET_LIST_END;
+            loop {
+                if front == ENET_LIST_END || later(front) {
+                    return front;
+                }
+                let previous = outgoing_commands.previous(list, back);
+                if previous == ENET_LIST_END || !later(previous) {
+                    return back;
+                }
+                front = outgoing_commands.next(front);
+                back = previous;
+            }
+        }
+        if tail == ENET_LIST_END {
+            // Searching from the back never passes a command of the same priority, as they all
+            // have earlier virtual finish times.
+            let mut current_command = ENET_LIST_END;
+            loop {
+                let previous = outgoing_commands.previous(list, current_command);
+                if previous == ENET_LIST_END || !later(previous) {
+                    return current_command;
+                }
+                current_command = previous;
+            }
+        }
+        let mut current_command = outgoing_commands.next(tail);
+        while current_command != ENET_LIST_END && !later(current_command) {
+            current_command = outgoing_commands.next(current_command);
+        }
+        current_command
+    }
+    /// Links the command at `position` into `queue` by its virtual finish time.
+    fn requeue_outgoing_command(
+        &mut self,
+        from: &mut ENetList,
+        queue: ENetOutgoingQueue,
+        position: ENetListIterator,
+    ) {
+        let before = self.outgoing_command_position(queue, position);
+        let (outgoing_commands, list) = self.outgoing_list_mut(queue);
+        outgoing_commands.transfer(from, position, list, before);
+        self.track_outgoing_command(queue, position, true);
+    }
+    /// Queues a command by weighted fair queuing between priorities, see [`ENetPriorityClass`].
+    /// Commands on a channel stay in order, and with every channel at the same priority commands
+    /// are sent in the order they were queued.
+    pub(crate) fn queue_outgoing_command(
+        &mut self,
+        outgoing_command: ENetOutgoingCommand,
+    ) -> ENetListIterator {
+        let queue = if outgoing_command.command.header().command as i32
+            & ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE as i32
+            != 0_i32
+            && !(outgoing_command.packet).is_null()
+        {
+            ENetOutgoingQueue::OutgoingSendReliable
+        } else {
+            ENetOutgoingQueue::Outgoing
+        };
+        let mut pending = ENetList::new();
+        let position =
+            self.outgoing_command_arena
+                .insert(&mut pending, ENET_LIST_END, outgoing_command);
+        self.schedule_outgoing_command(position);
+        self.requeue_outgoing_command(&mut pending, queue, position);
+        position
+    }
+    pub(crate) fn remove_outgoing_command(
+        &mut self,
+        queue: ENetOutgoingQueue,
+        position: ENetListIterator,
+    ) -> ENetOutgoingCommand {
+        self.untrack_outgoing_command(queue, position);
+        let (outgoing_commands, list) = self.outgoing_list_mut(queue);
+        outgoing_commands.remove(list, position)
+    }
+    pub(crate) fn pop_outgoing_command(
+        &mut self,
+        queue: ENetOutgoingQueue,
+    ) -> Option<ENetOutgoingCommand> {
+        let position = self.list(queue).begin();
+        if position == ENET_LIST_END {
+            None
+        } else {
+            Some(self.remove_outgoing_command(queue, position))
+        }
+    }
+    pub(crate) fn front_outgoing_command(
+        &self,
+        queue: ENetOutgoingQueue,
+    ) -> Option<&ENetOutgoingCommand> {
+        let list = self.list(queue);
+        if list.is_empty() {
+            None
+        } else {
+            Some(&self.outgoing_command_arena[list.begin()])
+        }
+    }
+    /// Moves the command at `position` out of `from` and inserts it before `before` in `to`.
+    pub(crate) fn transfer_outgoing_command(
+        &mut self,
+        from: ENetOutgoingQueue,
+        position: ENetListIterator,
+        to: ENetOutgoingQueue,
+        before: ENetListIterator,
+    ) {
+        debug_assert!(from != to);
+        self.untrack_outgoing_command(from, position);
+        let mut from_list = *self.list(from);
+        let mut to_list = *self.list(to);
+        self.outgoing_command_arena
+            .transfer(&mut from_list, position, &mut to_list, before);
+        *self.outgoing_list_mut(from).1 = from_list;
+        *self.outgoing_list_mut(to).1 = to_list;
+        self.track_outgoing_command(to, position, false);
+    }
+    /// Finds an unacknowledged reliable command in one of the unsent queues which was sent at least
+    /// once, and timed out since.
+    fn find_sent_reliable_command(
+        &self,
+        queue: ENetOutgoingQueue,
+        reliable_sequence_number: u16,
+        channel_id: u8,
+    ) -> ENetListIterator {
+        let outgoing_commands = &self.outgoing_command_arena;
+        let mut current_command = self.list(queue).begin();
+        while current_command != ENET_LIST_END {
+            let outgoing_command = &outgoing_commands[current_command];
+            if outgoing_command.command.header().command as i32
+                & ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE as i32
+                != 0
+            {
+                if (outgoing_command.send_attempts as i32) < 1_i32 {
+                    break;
+                }
+                if outgoing_command.reliable_sequence_number as i32
+                    == reliable_sequence_number as i32
+                    && outgoing_command.command.header().channel_id as i32 == channel_id as i32
+                {
+                    return current_command;
+                }
+            }
+            current_command = outgoing_commands.next(current_command);
+        }
+        ENET_LIST_END
+    }
+    /// Removes the reliable command acknowledged by `reliable_sequence_number` on `channel_id`.
+    /// Commands which timed out since they were sent are looked up in the unsent queues too, in
+    /// which case the returned flag is `false`.
+    pub(crate) fn remove_sent_reliable_command(
+        &mut self,
+        reliable_sequence_number: u16,
+        channel_id: u8,
+    ) -> Option<(ENetOutgoingCommand, bool)> {
+        let outgoing_commands = &self.outgoing_command_arena;
+        let mut current_command = self.sent_reliable_commands.begin();
+        while current_command != ENET_LIST_END {
+            let outgoing_command = &outgoing_commands[current_command];
+            if outgoing_command.reliable_sequence_number as i32 == reliable_sequence_number as i32
+                && outgoing_command.command.header().channel_id as i32 == channel_id as i32
+            {
+                return Some((
+                    self.remove_outgoing_command(ENetOutgoingQueue::SentReliable, current_command),
+                    true,
+                ));
+            }
+            current_command = outgoing_commands.next(current_command);
+        }
+        for queue in [
+            ENetOutgoingQueue::Outgoing,
+            ENetOutgoingQueue::OutgoingSendReliable,
+        ] {
+            let current_command =
+                self.find_sent_reliable_command(queue, reliable_sequence_number, channel_id);
+            if current_command != ENET_LIST_END {
+                return Some((self.remove_outgoing_command(queue, current_command), false));
+            }
+        }
+        None
+    }
+    /// Finds the connect command of a connecting peer, whether or not it was sent yet.
+    pub(crate) fn find_connect_command(&self) -> Option<&ENetOutgoingCommand> {
+        let outgoing_commands = &self.outgoing_command_arena;
+        for list in [&self.sent_reliable_commands, &self.outgoing_commands] {
+            let mut current_command = list.begin();
+            while current_command != ENET_LIST_END {
+                let outgoing_command = &outgoing_commands[current_command];
+                if outgoing_command.command.header().command as i32
+                    & ENET_PROTOCOL_COMMAND_MASK as i32
+                    == ENET_PROTOCOL_COMMAND_CONNECT as i32
+                {
+                    return Some(outgoing_command);
+                }
+                current_command = outgoing_commands.next(current_command);
+            }
+        }
+        None
+    }
+    pub(crate) fn set_channel_priority(&mut self, channel_id: u8, priority: u8) {
+        let outgoing_commands = &mut self.outgoing_command_arena;
+        let mut current_command = self.sent_reliable_commands.begin();
+        while current_command != ENET_LIST_END {
+            let outgoing_command = &mut outgoing_commands[current_command];
+            if outgoing_command.command.header().channel_id == channel_id {
+                outgoing_command.priority = priority;
+            }
+            current_command = outgoing_commands.next(current_command);
+        }
+        // Commands already queued on the channel are taken out in order and queued again at the
+        // new priority, so they keep their order relative to each other and to later sends.
+        for queue in [
+            ENetOutgoingQueue::Outgoing,
+            ENetOutgoingQueue::OutgoingSendReliable,
+        ] {
+            let mut requeue = ENetList::new();
+            let mut current_command = self.list(queue).begin();
+            while current_command != ENET_LIST_END {
+                let next_command = self.outgoing_command_arena.next(current_command);
+                if self.outgoing_command_arena[current_command]
+                    .command
+                    .header()
+                    .channel_id
+                    == channel_id
+                {
+                    self.untrack_outgoing_command(queue, current_command);
+                    let (outgoing_commands, list) = self.outgoing_list_mut(queue);
+                    outgoing_commands.transfer(list, current_command, &mut requeue, ENET_LIST_END);
+                }
+                current_command = next_command;
+            }
+            while !requeue.is_empty() {
+                let current_command = requeue.begin();
+                self.outgoing_command_arena[current_command].priority = priority;
+                self.schedule_outgoing_command(current_command);
+                self.requeue_outgoing_command(&mut requeue, queue, current_command);
+            }
+        }
+    }
+    /// Removes every unsent command queued on `channel_id` with `supersede_key`, passing each one
+    /// to `release`.
+    pub(crate) fn remove_superseded_commands(
+        &mut self,
+        channel_id: u8,
+        supersede_key: u32,
+        mut release: impl FnMut(ENetOutgoingCommand),
+    ) {
+        let mut current_command = self.outgoing_commands.begin();
+        while current_command != ENET_LIST_END {
+            let position = current_command;
+            let outgoing_command = &self.outgoing_command_arena[position];
+            current_command = self.outgoing_command_arena.next(current_command);
+            if outgoing_command.command.header().channel_id == channel_id
+                && outgoing_command.supersede_key == Some(supersede_key)
+            {
+                release(self.remove_outgoing_command(ENetOutgoingQueue::Outgoing, position));
+            }
+        }
+    }
+    /// Empties every queue of the peer except the incoming queues of its channels, passing each
+    /// removed command to `release_outgoing` or `release_incoming`.
+    pub(crate) fn reset(
+        &mut self,
+        mut release_outgoing: impl FnMut(ENetOutgoingCommand),
+        mut release_incoming: impl FnMut(ENetIncomingCommand),
+    ) {
+        while self.pop_acknowledgement().is_some() {}
+        for queue in [
+            ENetOutgoingQueue::SentReliable,
+            ENetOutgoingQueue::Outgoing,
+            ENetOutgoingQueue::OutgoingSendReliable,
+            ENetOutgoingQueue::SentUnreliable,
+        ] {
+            while let Some(outgoing_command) = self.pop_outgoing_command(queue) {
+                release_outgoing(outgoing_command);
+            }
+        }
+        while let Some(incoming_command) = self.pop_dispatched_command() {
+            release_incoming(incoming_command);
+        }
+        self.virtual_time = 0;
+        self.priority_classes.clear();
+    }
+    pub(crate) fn pop_dispatched_command(&mut self) -> Option<ENetIncomingCommand> {
+        self.incoming_command_arena
+            .pop_front(&mut self.dispatched_commands)
+    }
+    /// Finds the command of `channel` after which an incoming command is queued, or
+    /// [`ENET_LIST_END`] to queue it first. Returns `None` if the command is a duplicate or falls
+    /// outside the reliable window.
+    pub(crate) fn find_incoming_command_position(
+        &self,
+        channel: &ENetChannel,
+        command: &ENetProtocol,
+        unreliable_sequence_number: u16,
+    ) -> Option<ENetListIterator> {
+        let incoming_commands = &self.incoming_command_arena;
+        let command_number = command.header().command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32;
+        let unreliable_sequence_number = unreliable_sequence_number as u32;
+        let mut reliable_sequence_number: u32 = 0_i32 as u32;
+        if command_number != ENET_PROTOCOL_COMMAND_SEND_UNSEQUENCED as i32 {
+            reliable_sequence_number = command.header().reliable_sequence_number as u32;
+            let mut reliable_window = reliable_sequence_number
+                .wrapping_div(PEER_RELIABLE_WINDOW_SIZE as i32 as u32)
+                as u16;
+            let current_window = (channel.incoming_reliable_sequence_number as i32
+                / PEER_RELIABLE_WINDOW_SIZE as i32) as u16;
+            if reliable_sequence_number < channel.incoming_reliable_sequence_number as u32 {
+                reliable_window = (reliable_window as i32 + PEER_RELIABLE_WINDOWS as i32) as u16;
+            }
+            if (reliable_window as i32) < current_window as i32
+                || reliable_window as i32
+                    >= current_window as i32 + PEER_FREE_RELIABLE_WINDOWS as i32 - 1_i32
+            {
+                return None;
+            }
+        }
+        match command_number {
+            8 | 6 => {
+                if reliable_sequence_number == channel.incoming_reliable_sequence_number as u32 {
+                    return None;
+                }
+                let queue = &channel.incoming_reliable_commands;
+                let mut current_command = incoming_commands.previous(queue, ENET_LIST_END);
+                while current_command != ENET_LIST_END {
+                    let incoming_command = &incoming_commands[current_command];
+                    if reliable_sequence_number >= channel.incoming_reliable_sequence_number as u32
+                    {
+                        if (incoming_command.reliable_sequence_number as i32)
+                            < channel.incoming_reliable_sequence_number as i32
+                        {
+                            current_command = incoming_commands.previous(queue, current_command);
+                            continue;
+                        }
+                    } else if incoming_command.reliable_sequence_number as i32
+                        >= channel.incoming_reliable_sequence_number as i32
+                    {
+                        break;
+                    }
+                    if incoming_command.reliable_sequence_number as u32 <= reliable_sequence_number
+                    {
+                        if (incoming_command.reliable_sequence_number as u32)
+                            < reliable_sequence_number
+                        {
+                            break;
+                        }
+                        return None;
+                    }
+                    current_command = incoming_commands.previous(queue, current_command);
+                }
+                Some(current_command)
+            }
+            7 | 12 => {
+                if reliable_sequence_number == channel.incoming_reliable_sequence_number as u32
+                    && unreliable_sequence_number
+                        <= channel.incoming_unreliable_sequence_number as u32
+                {
+                    return None;
+                }
+                let queue = &channel.incoming_unreliable_commands;
+                let mut current_command = incoming_commands.previous(queue, ENET_LIST_END);
+                while current_command != ENET_LIST_END {
+                    let incoming_command = &incoming_commands[current_command];
+                    if command_number == ENET_PROTOCOL_COMMAND_SEND_UNSEQUENCED as i32 {
+                        current_command = incoming_commands.previous(queue, current_command);
+                        continue;
+                    }
+                    if reliable_sequence_number >= channel.incoming_reliable_sequence_number as u32
+                    {
+                        if (incoming_command.reliable_sequence_number as i32)
+                            < channel.incoming_reliable_sequence_number as i32
+                        {
+                            current_command = incoming_commands.previous(queue, current_command);
+                            continue;
+                        }
+                    } else if incoming_command.reliable_sequence_number as i32
+                        >= channel.incoming_reliable_sequence_number as i32
+                    {
+                        break;
+                    }
+                    if (incoming_command.reliable_sequence_number as u32) < reliable_sequence_number
+                    {
+                        break;
+                    }
+                    if incoming_command.reliable_sequence_number as u32 <= reliable_sequence_number
+                        && incoming_command.unreliable_sequence_number as u32
+                            <= unreliable_sequence_number
+                    {
+                        if (incoming_command.unreliable_sequence_number as u32)
+                            < unreliable_sequence_number
+                        {
+                            break;
+                        }
+                        return None;
+                    }
+                    current_command = incoming_commands.previous(queue, current_command);
+                }
+                Some(current_command)
+            }
+            9 => Some(ENET_LIST_END),
+            _ => None,
+        }
+    }
+    /// Queues an incoming command in `queue`, after `previous`, or first if `previous` is
+    /// [`ENET_LIST_END`].
+    pub(crate) fn queue_incoming_command(
+        &mut self,
+        queue: &mut ENetList,
+        previous: ENetListIterator,
+        incoming_command: ENetIncomingCommand,
+    ) -> ENetListIterator {
+        let position = if previous == ENET_LIST_END {
+            queue.begin()
+        } else {
+            self.incoming_command_arena.next(previous)
+        };
+        self.incoming_command_arena
+            .insert(queue, position, incoming_command)
+    }
+    /// Removes the commands of `queue` from `start_command` up to `end_command`, except for
+    /// `exclude_command`, passing each one to `release`.
+    pub(crate) fn remove_incoming_commands(
+        &mut self,
+        queue: &mut ENetList,
+        start_command: ENetListIterator,
+        end_command: ENetListIterator,
+        exclude_command: ENetListIterator,
+        mut release: impl FnMut(ENetIncomingCommand),
+    ) {
+        let incoming_commands = &mut self.incoming_command_arena;
+        let mut current_command = start_command;
+        while current_command != end_command {
+            let position = current_command;
+            current_command = incoming_commands.next(current_command);
+            if position != exclude_command {
+                release(incoming_commands.remove(queue, position));
+            }
+        }
+    }
+    pub(crate) fn reset_incoming_commands(
+        &mut self,
+        queue: &mut ENetList,
+        release: impl FnMut(ENetIncomingCommand),
+    ) {
+        let start_command = queue.begin();
+        self.remove_incoming_commands(queue, start_command, ENET_LIST_END, ENET_LIST_END, release);
+    }
+    /// Moves the commands from `start_command` to `last_command` inclusive to the dispatched
+    /// queue.
+    fn dispatch_incoming_commands(
+        &mut self,
+        queue: &mut ENetList,
+        start_command: ENetListIterator,
+        last_command: ENetListIterator,
+    ) {
+        self.incoming_command_arena.move_range(
+            queue,
+            start_command,
+            last_command,
+            &mut self.dispatched_commands,
+            ENET_LIST_END,
+        );
+    }
+    /// Dispatches the unreliable commands of `channel` which are ready, and removes the ones which
+    /// can no longer be, passing them to `release`. Returns `true` if any command was dispatched.
+    pub(crate) fn dispatch_incoming_unreliable_commands(
+        &mut self,
+        channel: &mut ENetChannel,
+        queued_command: ENetListIterator,
+        release: impl FnMut(ENetIncomingCommand),
+    ) -> bool {
+        let mut dispatched = false;
+        let mut dropped_command: ENetListIterator;
+        let mut start_command: ENetListIterator;
+        let mut current_command: ENetListIterator;
+        current_command = channel.incoming_unreliable_commands.begin();
+        start_command = current_command;
+        dropped_command = start_command;
+        while current_command != ENET_LIST_END {
+            let incoming_command: ENetIncomingCommand =
+                self.incoming_command_arena[current_command];
+            if incoming_command.command.header().command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32
+                == ENET_PROTOCOL_COMMAND_SEND_UNSEQUENCED as i32
+            {
+                current_command = self.incoming_command_arena.next(current_command);
+                continue;
+            }
+            if incoming_command.reliable_sequence_number as i32
+                == channel.incoming_reliable_sequence_number as i32
+            {
+                if incoming_command.fragments_remaining <= 0_i32 as u32 {
+                    channel.incoming_unreliable_sequence_number =
+                        incoming_command.unreliable_sequence_number;
+                    current_command = self.incoming_command_arena.next(current_command);
+                    continue;
+                }
+                if start_command != current_command {
+                    let last_command = self
+                        .incoming_command_arena
+                        .previous(&channel.incoming_unreliable_commands, current_command);
+                    self.dispatch_incoming_commands(
+                        &mut channel.incoming_unreliable_commands,
+                        start_command,
+                        last_command,
+                    );
+                    dispatched = true;
+                    dropped_command = current_command;
+                } else if dropped_command != current_command {
+                    dropped_command = self
+                        .incoming_command_arena
+                        .previous(&channel.incoming_unreliable_commands, current_command);
+                }
+            } else {
+                let mut reliable_window: u16 = (incoming_command.reliable_sequence_number as i32
+                    / PEER_RELIABLE_WINDOW_SIZE as i32)
+                    as u16;
+                let current_window: u16 = (channel.incoming_reliable_sequence_number as i32
+                    / PEER_RELIABLE_WINDOW_SIZE as i32)
+                    as u16;
+                if (incoming_command.reliable_sequence_number as i32)
+                    < channel.incoming_reliable_sequence_number as i32
+                {
+                    reliable_window =
+                        (reliable_window as i32 + PEER_RELIABLE_WINDOWS as i32) as u16;
+                }
+                if reliable_window as i32 >= current_window as i32
+                    && (reliable_window as i32)
+                        < current_window as i32 + PEER_FREE_RELIABLE_WINDOWS as i32 - 1_i32
+                {
+                    break;
+                }
+                dropped_command = self.incoming_command_arena.next(current_command);
+                if start_command != current_command {
+                    let last_command = self
+                        .incoming_command_arena
+                        .previous(&channel.incoming_unreliable_commands, current_command);
+                    self.dispatch_incoming_commands(
+                        &mut channel.incoming_unreliable_commands,
+                        start_command,
+                        last_command,
+                    );
+                    dispatched = true;
+                }
+            }
+            start_command = self.incoming_command_arena.next(current_command);
+            current_command = start_command;
+        }
+        if start_command != current_command {
+            let last_command = self
+                .incoming_command_arena
+                .previous(&channel.incoming_unreliable_commands, current_command);
+            self.dispatch_incoming_commands(
+                &mut channel.incoming_unreliable_commands,
+                start_command,
+                last_command,
+            );
+            dispatched = true;
+            dropped_command = current_command;
+        }
+        start_command = channel.incoming_unreliable_commands.begin();
+        self.remove_incoming_commands(
+            &mut channel.incoming_unreliable_commands,
+            start_command,
+            dropped_command,
+            queued_command,
+            release,
+        );
+        dispatched
+    }
+    /// Dispatches the reliable commands of `channel` which are ready, followed by its unreliable
+    /// commands. Returns `true` if any command was dispatched.
+    pub(crate) fn dispatch_incoming_reliable_commands(
+        &mut self,
+        channel: &mut ENetChannel,
+        queued_command: ENetListIterator,
+        release: impl FnMut(ENetIncomingCommand),
+    ) -> bool {
+        let incoming_commands = &self.incoming_command_arena;
+        let mut current_command: ENetListIterator;
+        current_command = channel.incoming_reliable_commands.begin();
+        while current_command != ENET_LIST_END {
+            let incoming_command = &incoming_commands[current_command];
+            if incoming_command.fragments_remaining > 0_i32 as u32
+                || incoming_command.reliable_sequence_number as i32
+                    != (channel.incoming_reliable_sequence_number as i32 + 1_i32) as u16 as i32
+            {
+                break;
+            }
+            channel.incoming_reliable_sequence_number = incoming_command.reliable_sequence_number;
+            if incoming_command.fragment_count > 0_i32 as u32 {
+                channel.incoming_reliable_sequence_number =
+                    (channel.incoming_reliable_sequence_number as u32)
+                        .wrapping_add((incoming_command.fragment_count).wrapping_sub(1_i32 as u32))
+                        as u16 as u16;
+            }
+            current_command = incoming_commands.next(current_command);
+        }
+        let start_command = channel.incoming_reliable_commands.begin();
+        if current_command == start_command {
+            return false;
+        }
+        channel.incoming_unreliable_sequence_number = 0_i32 as u16;
+        let last_command =
+            incoming_commands.previous(&channel.incoming_reliable_commands, current_command);
+        self.dispatch_incoming_commands(
+            &mut channel.incoming_reliable_commands,
+            start_command,
+            last_command,
+        );
+        if !channel.incoming_unreliable_commands.is_empty() {
+            self.dispatch_incoming_unreliable_commands(channel, queued_command, release);
+        }
+        true
+    }
 }
 pub(crate) unsafe fn enet_peer_throttle_configure<S: Socket>(
     peer: *mut ENetPeer<S>,
@@ -159,6 +1011,9 @@ pub(crate) unsafe fn enet_peer_send<S: Socket>(
     peer: *mut ENetPeer<S>,
     channel_id: u8,
     packet: *mut ENetPacket,
+    send_token: u64,
+    time_to_live: Option<u32>,
+    supersede_key: Option<u32>,
 ) -> Result<(), PeerSendError> {
     let mut command: ENetProtocol = ENetProtocol {
         header: ENetProtocolCommandHeader {
@@ -178,6 +1033,8 @@ pub(crate) unsafe fn enet_peer_send<S: Socket>(
         return Err(PeerSendError::PacketTooLarge);
     }
     let channel = ((*peer).channels).offset(channel_id as isize);
+    let expire_time =
+        time_to_live.map(|time_to_live| enet_time_get((*peer).host).wrapping_add(time_to_live));
     fragment_length = ((*peer).mtu as usize)
         .wrapping_sub(::core::mem::size_of::<ENetProtocolHeader>())
         .wrapping_sub(::core::mem::size_of::<ENetProtocolSendFragment>());
@@ -194,16 +1051,12 @@ pub(crate) unsafe fn enet_peer_send<S: Socket>(
         let mut fragment_offset: u32;
         let command_number: u8;
         let start_sequence_number: u16;
-        let mut fragments: ENetList = ENetList {
-            sentinel: ENetListNode {
-                next: core::ptr::null_mut(),
-                previous: core::ptr::null_mut(),
-            },
-        };
-        let mut fragment: *mut ENetOutgoingCommand;
         if fragment_count > PROTOCOL_MAXIMUM_FRAGMENT_COUNT as i32 as u32 {
             return Err(PeerSendError::FragmentsExceeded);
         }
+        if let Some(supersede_key) = supersede_key {
+            enet_peer_supersede_outgoing_commands(peer, channel_id, supersede_key);
+        }
         if (*packet).flags
             & (ENET_PACKET_FLAG_RELIABLE as i32 | ENET_PACKET_FLAG_UNRELIABLE_FRAGMENT as i32)
                 as u32
@@ -219,35 +1072,43 @@ pub(crate) unsafe fn enet_peer_send<S: Socket>(
             start_sequence_number =
                 (((*channel).outgoing_reliable_sequence_number as i32 + 1_i32) as u16).to_be();
         }
-        enet_list_clear(&mut fragments);
+        (*packet)
+            .reference_count
+            .fetch_add(fragment_count as usize, Ordering::Relaxed);
         fragment_number = 0_i32 as u32;
         fragment_offset = 0_i32 as u32;
         while (fragment_offset as usize) < (*packet).data_length {
             if ((*packet).data_length).wrapping_sub(fragment_offset as usize) < fragment_length {
                 fragment_length = ((*packet).data_length).wrapping_sub(fragment_offset as usize);
             }
-            fragment = enet_malloc(Layout::new::<ENetOutgoingCommand>()).cast();
-            (*fragment).fragment_offset = fragment_offset;
-            (*fragment).fragment_length = fragment_length as u16;
-            (*fragment).packet = packet;
-            (*fragment).command.header.command = command_number;
-            (*fragment).command.header.channel_id = channel_id;
-            (*fragment).command.send_fragment.start_sequence_number = start_sequence_number;
-            (*fragment).command.send_fragment.data_length = (fragment_length as u16).to_be();
-            (*fragment).command.send_fragment.fragment_count = fragment_count.to_be();
-            (*fragment).command.send_fragment.fragment_number = fragment_number.to_be();
-            (*fragment).command.send_fragment.total_length = ((*packet).data_length as u32).to_be();
-            (*fragment).command.send_fragment.fragment_offset = u32::from_be(fragment_offset);
-            enet_list_insert(&mut fragments.sentinel, fragment.cast());
+            let mut fragment: ENetOutgoingCommand = zeroed();
+            fragment.fragment_offset = fragment_offset;
+            fragment.fragment_length = fragment_length as u16;
+            fragment.packet = packet;
+            fragment.command.header.command = command_number;
+            fragment.command.header.channel_id = channel_id;
+            fragment.command.send_fragment.start_sequence_number = start_sequence_number;
+            fragment.command.send_fragment.data_length = (fragment_length as u16).to_be();
+            fragment.command.send_fragment.fragment_count = fragment_count.to_be();
+            fragment.command.send_fragment.fragment_number = fragment_number.to_be();
+            fragment.command.send_fragment.total_length = ((*packet).data_length as u32).to_be();
+            fragment.command.send_fragment.fragment_offset = u32::from_be(fragment_offset);
+            fragment.send_token = send_token;
+            if command_number as i32 & ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE as i32 == 0 {
+                fragment.expire_time = expire_time;
+                fragment.supersede_key = supersede_key;
+            }
+            enet_peer_setup_outgoing_command(peer, fragment);
             fragment_number = fragment_number.wrapping_add(1);
             fragment_offset = (fragment_offset as usize).wrapping_add(fragment_length) as u32;
         }
-        (*packet).reference_count =
-            ((*packet).reference_count as u64).wrapping_add(fragment_number as u64) as usize;
-        while fragments.sentinel.next != core::ptr::addr_of_mut!(fragments.sentinel) {
-            fragment = enet_list_remove(fragments.sentinel.next).cast();
-            enet_peer_setup_outgoing_command(peer, fragment);
+        if send_token != 0_i32 as u64 {
+            (*peer)
+                .send_tokens
+                .assume_init_mut()
+                .insert(send_token, fragment_count);
         }
+        enet_peer_check_high_watermark(peer);
         return Ok(());
     }
     command.header.channel_id = channel_id;
@@ -268,140 +1129,162 @@ pub(crate) unsafe fn enet_peer_send<S: Socket>(
         command.header.command = ENET_PROTOCOL_COMMAND_SEND_UNRELIABLE as i32 as u8;
         command.send_unreliable.data_length = ((*packet).data_length as u16).to_be();
     }
-    if (enet_peer_queue_outgoing_command(
+    if let Some(supersede_key) = supersede_key {
+        enet_peer_supersede_outgoing_commands(peer, channel_id, supersede_key);
+    }
+    let outgoing_command = enet_peer_queue_outgoing_command(
         peer,
         &command,
         packet,
         0_i32 as u32,
         (*packet).data_length as u16,
-    ))
-    .is_null()
-    {
-        return Err(PeerSendError::FailedToQueue);
+    );
+    if command.header.command as i32 & ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE as i32 == 0 {
+        let outgoing_command = &mut (*peer).queues.outgoing_command_arena[outgoing_command];
+        outgoing_command.expire_time = expire_time;
+        outgoing_command.supersede_key = supersede_key;
     }
+    if send_token != 0_i32 as u64 {
+        (*peer).queues.outgoing_command_arena[outgoing_command].send_token = send_token;
+        (*peer)
+            .send_tokens
+            .assume_init_mut()
+            .insert(send_token, 1_i32 as u32);
+    }
+    enet_peer_check_high_watermark(peer);
     Ok(())
 }
+unsafe fn enet_peer_check_high_watermark<S: Socket>(peer: *mut ENetPeer<S>) {
+    if (*peer).queued_data > (*peer).queue_high_watermark {
+        (*peer).queue_above_high_watermark = true;
+    }
+}
+/// Queues a writable event once a peer whose queued data rose above its high watermark has
+/// drained to its low watermark.
+pub(crate) unsafe fn enet_peer_check_low_watermark<S: Socket>(peer: *mut ENetPeer<S>) {
+    if (*peer).queue_above_high_watermark && (*peer).queued_data <= (*peer).queue_low_watermark {
+        (*peer).queue_above_high_watermark = false;
+        enet_peer_queue_event(peer, ENET_EVENT_TYPE_WRITABLE, 0_i32 as u64);
+    }
+}
+/// Queues an event to be dispatched before any other by the next service. The event keeps the
+/// peer's current generation, so it is not reported against a later connection reusing the peer.
+unsafe fn enet_peer_queue_event<S: Socket>(
+    peer: *mut ENetPeer<S>,
+    type_0: ENetEventType,
+    send_token: u64,
+) {
+    (*(*peer).host).pending_events.assume_init_mut().push_back((
+        type_0,
+        PeerID::new((*peer).incoming_peer_id as usize, (*peer).generation),
+        send_token,
+    ));
+}
+/// Discards every unsent command queued on `channel_id` with `supersede_key`.
+///
+/// The remaining fragments of a partially sent packet are discarded too. Fragments are only left
+/// queued after a failed send, which already lost the fragment it carried, so the packet can no
+/// longer be completed.
+unsafe fn enet_peer_supersede_outgoing_commands<S: Socket>(
+    peer: *mut ENetPeer<S>,
+    channel_id: u8,
+    supersede_key: u32,
+) {
+    let queued_data = &mut (*peer).queued_data;
+    (*peer)
+        .queues
+        .remove_superseded_commands(channel_id, supersede_key, |outgoing_command| {
+            *queued_data = queued_data.wrapping_sub(outgoing_command.fragment_length as usize);
+            enet_packet_release(outgoing_command.packet, 0);
+        });
+}
+/// Counts an acknowledged command sent with `send_token`, queueing an acknowledged event once
+/// every command sent with it has been acknowledged.
+pub(crate) unsafe fn enet_peer_acknowledge_send_token<S: Socket>(
+    peer: *mut ENetPeer<S>,
+    send_token: u64,
+) {
+    let send_tokens = (*peer).send_tokens.assume_init_mut();
+    let Some(remaining) = send_tokens.get_mut(&send_token) else {
+        return;
+    };
+    *remaining = remaining.wrapping_sub(1);
+    if *remaining == 0_i32 as u32 {
+        send_tokens.remove(&send_token);
+        enet_peer_queue_event(peer, ENET_EVENT_TYPE_ACKNOWLEDGED, send_token);
+    }
+}
 pub(crate) unsafe fn enet_peer_receive<S: Socket>(
     peer: *mut ENetPeer<S>,
     channel_id: *mut u8,
 ) -> *mut ENetPacket {
-    if (*peer).dispatched_commands.sentinel.next
-        == core::ptr::addr_of_mut!((*peer).dispatched_commands.sentinel)
-    {
+    let Some(incoming_command) = (*peer).queues.pop_dispatched_command() else {
         return core::ptr::null_mut();
-    }
-    let incoming_command: *mut ENetIncomingCommand =
-        enet_list_remove((*peer).dispatched_commands.sentinel.next).cast();
+    };
     if !channel_id.is_null() {
-        *channel_id = (*incoming_command).command.header.channel_id;
+        *channel_id = incoming_command.command.header.channel_id;
     }
-    let packet = (*incoming_command).packet;
-    (*packet).reference_count = ((*packet).reference_count).wrapping_sub(1);
-    if !((*incoming_command).fragments).is_null() {
-        let count = (*incoming_command)
+    let packet = incoming_command.packet;
+    (*packet).reference_count.fetch_sub(1, Ordering::AcqRel);
+    if !(incoming_command.fragments).is_null() {
+        let count = incoming_command
             .fragment_count
             .wrapping_add(31_i32 as u32)
             .wrapping_div(32_i32 as u32) as usize;
         enet_free(
-            (*incoming_command).fragments.cast(),
+            incoming_command.fragments.cast(),
             Layout::array::<u32>(count).unwrap(),
         );
     }
-    enet_free(
-        incoming_command.cast(),
-        Layout::new::<ENetIncomingCommand>(),
-    );
     (*peer).total_waiting_data = (*peer)
         .total_waiting_data
-        .wrapping_sub((*packet).data_length) as usize as usize;
+        .wrapping_sub((*packet).data_length);
     packet
 }
-unsafe fn enet_peer_reset_outgoing_commands(queue: *mut ENetList) {
-    let mut outgoing_command: *mut ENetOutgoingCommand;
-    while (*queue).sentinel.next != core::ptr::addr_of_mut!((*queue).sentinel) {
-        outgoing_command = enet_list_remove((*queue).sentinel.next).cast();
-        if !((*outgoing_command).packet).is_null() {
-            (*(*outgoing_command).packet).reference_count =
-                ((*(*outgoing_command).packet).reference_count).wrapping_sub(1);
-            if (*(*outgoing_command).packet).reference_count == 0_i32 as usize {
-                enet_packet_destroy((*outgoing_command).packet);
-            }
-        }
-        enet_free(
-            outgoing_command.cast(),
-            Layout::new::<ENetOutgoingCommand>(),
-        );
-    }
-}
-unsafe fn enet_peer_remove_incoming_commands(
-    mut _queue: *mut ENetList,
-    start_command: ENetListIterator,
-    end_command: ENetListIterator,
-    exclude_command: *mut ENetIncomingCommand,
-) {
-    let mut current_command: ENetListIterator;
-    current_command = start_command;
-    while current_command != end_command {
-        let incoming_command: *mut ENetIncomingCommand = current_command.cast();
-        current_command = (*current_command).next;
-        if incoming_command == exclude_command {
-            continue;
-        }
-        enet_list_remove(&mut (*incoming_command).incoming_command_list);
-        if !((*incoming_command).packet).is_null() {
-            (*(*incoming_command).packet).reference_count =
-                ((*(*incoming_command).packet).reference_count).wrapping_sub(1);
-            if (*(*incoming_command).packet).reference_count == 0_i32 as usize {
-                enet_packet_destroy((*incoming_command).packet);
-            }
-        }
-        if !((*incoming_command).fragments).is_null() {
-            let count = (*incoming_command)
-                .fragment_count
-                .wrapping_add(31_i32 as u32)
-                .wrapping_div(32_i32 as u32) as usize;
-            enet_free(
-                (*incoming_command).fragments.cast(),
-                Layout::array::<u32>(count).unwrap(),
-            );
-        }
+/// Releases the packet and fragment bitmap of an incoming command removed from its queue.
+pub(crate) unsafe fn enet_peer_release_incoming_command(incoming_command: ENetIncomingCommand) {
+    enet_packet_release(incoming_command.packet, 0);
+    if !(incoming_command.fragments).is_null() {
+        let count = incoming_command
+            .fragment_count
+            .wrapping_add(31_i32 as u32)
+            .wrapping_div(32_i32 as u32) as usize;
         enet_free(
-            incoming_command.cast(),
-            Layout::new::<ENetIncomingCommand>(),
+            incoming_command.fragments.cast(),
+            Layout::array::<u32>(count).unwrap(),
         );
     }
 }
-unsafe fn enet_peer_reset_incoming_commands(queue: *mut ENetList) {
-    enet_peer_remove_incoming_commands(
-        queue,
-        (*queue).sentinel.next,
-        &mut (*queue).sentinel,
-        core::ptr::null_mut(),
-    );
-}
 pub(crate) unsafe fn enet_peer_reset_queues<S: Socket>(peer: *mut ENetPeer<S>) {
     let mut channel: *mut ENetChannel;
     if (*peer).flags as i32 & ENET_PEER_FLAG_NEEDS_DISPATCH as i32 != 0 {
-        enet_list_remove(&mut (*peer).dispatch_list);
+        let incoming_peer_id = (*peer).incoming_peer_id as usize;
+        (*(*peer).host)
+            .dispatch_queue
+            .assume_init_mut()
+            .retain(|&peer_id| peer_id != incoming_peer_id);
         (*peer).flags = ((*peer).flags as i32 & !(ENET_PEER_FLAG_NEEDS_DISPATCH as i32)) as u16;
     }
-    while (*peer).acknowledgements.sentinel.next
-        != core::ptr::addr_of_mut!((*peer).acknowledgements.sentinel)
-    {
-        enet_free(
-            enet_list_remove((*peer).acknowledgements.sentinel.next),
-            Layout::new::<ENetAcknowledgement>(),
-        );
+    for (send_token, _) in core::mem::take((*peer).send_tokens.assume_init_mut()) {
+        enet_peer_queue_event(peer, ENET_EVENT_TYPE_DROPPED, send_token);
     }
-    enet_peer_reset_outgoing_commands(&mut (*peer).sent_reliable_commands);
-    enet_peer_reset_outgoing_commands(&mut (*peer).outgoing_commands);
-    enet_peer_reset_outgoing_commands(&mut (*peer).outgoing_send_reliable_commands);
-    enet_peer_reset_incoming_commands(&mut (*peer).dispatched_commands);
+    (*peer).queues.reset(
+        |outgoing_command| enet_packet_release(outgoing_command.packet, 0),
+        |incoming_command| enet_peer_release_incoming_command(incoming_command),
+    );
+    (*peer).queued_data = 0_i32 as usize;
+    (*peer).queue_above_high_watermark = false;
     if !((*peer).channels).is_null() && (*peer).channel_count > 0_i32 as usize {
         channel = (*peer).channels;
         while channel < ((*peer).channels).add((*peer).channel_count) {
-            enet_peer_reset_incoming_commands(&mut (*channel).incoming_reliable_commands);
-            enet_peer_reset_incoming_commands(&mut (*channel).incoming_unreliable_commands);
+            (*peer).queues.reset_incoming_commands(
+                &mut (*channel).incoming_reliable_commands,
+                |incoming_command| enet_peer_release_incoming_command(incoming_command),
+            );
+            (*peer).queues.reset_incoming_commands(
+                &mut (*channel).incoming_unreliable_commands,
+                |incoming_command| enet_peer_release_incoming_command(incoming_command),
+            );
             channel = channel.offset(1);
         }
         enet_free(
@@ -436,6 +1319,18 @@ pub(crate) unsafe fn enet_peer_on_disconnect<S: Socket>(peer: *mut ENetPeer<S>)
 }
 pub(crate) unsafe fn enet_peer_reset<S: Socket>(peer: *mut ENetPeer<S>) {
     enet_peer_on_disconnect(peer);
+    *(*peer).data.assume_init_mut() = None;
+    *(*peer).cipher_key.assume_init_mut() = None;
+    (*peer).cipher_replay_window = ReplayWindow::new();
+    (*peer).cipher_nonce_counter = NonceCounter::new();
+    *(*peer).auth_key.assume_init_mut() = None;
+    (*peer).compression = true;
+    (*peer).compression_statistics = CompressionStatistics::default();
+    (*peer).compression_probe_attempts = 0_i32 as u32;
+    (*peer).compression_probe_bytes_in = 0_i32 as u32;
+    (*peer).compression_probe_bytes_out = 0_i32 as u32;
+    (*peer).compression_bypassed = false;
+    (*peer).compression_bypass_epoch = 0_i32 as u32;
     (*peer).outgoing_peer_id = PROTOCOL_MAXIMUM_PEER_ID as i32 as u16;
     (*peer).connect_id = 0_i32 as u32;
     (*peer).state = ENET_PEER_STATE_DISCONNECTED;
@@ -478,11 +1373,23 @@ pub(crate) unsafe fn enet_peer_reset<S: Socket>(peer: *mut ENetPeer<S>) {
     (*peer).incoming_unsequenced_group = 0_i32 as u16;
     (*peer).outgoing_unsequenced_group = 0_i32 as u16;
     (*peer).event_data = 0_i32 as u32;
+    (*peer).disconnect_reason = ENET_DISCONNECT_REASON_REMOTE;
     (*peer).total_waiting_data = 0_i32 as usize;
+    (*peer).queued_data = 0_i32 as usize;
+    (*peer).queue_low_watermark = 0_i32 as usize;
+    (*peer).queue_high_watermark = usize::MAX;
+    (*peer).queue_above_high_watermark = false;
     (*peer).flags = 0_i32 as u16;
     write_bytes(((*peer).unsequenced_window).as_mut_ptr(), 0, 32);
     enet_peer_reset_queues(peer);
 }
+/// Like [`enet_peer_reset`], but keeps the user data, so it is still available while handling the
+/// disconnect event. The user data is dropped when the peer is reset again or reused.
+pub(crate) unsafe fn enet_peer_reset_keep_data<S: Socket>(peer: *mut ENetPeer<S>) {
+    let data = (*peer).data.assume_init_mut().take();
+    enet_peer_reset(peer);
+    *(*peer).data.assume_init_mut() = data;
+}
 pub(crate) unsafe fn enet_peer_ping<S: Socket>(peer: *mut ENetPeer<S>) {
     let mut command: ENetProtocol = ENetProtocol {
         header: ENetProtocolCommandHeader {
@@ -612,22 +1519,10 @@ pub(crate) unsafe fn enet_peer_disconnect<S: Socket>(peer: *mut ENetPeer<S>, dat
         enet_peer_reset(peer);
     };
 }
-pub(crate) unsafe fn enet_peer_has_outgoing_commands<S: Socket>(peer: *mut ENetPeer<S>) -> i32 {
-    if (*peer).outgoing_commands.sentinel.next
-        == core::ptr::addr_of_mut!((*peer).outgoing_commands.sentinel)
-        && (*peer).outgoing_send_reliable_commands.sentinel.next
-            == core::ptr::addr_of_mut!((*peer).outgoing_send_reliable_commands.sentinel)
-        && (*peer).sent_reliable_commands.sentinel.next
-            == core::ptr::addr_of_mut!((*peer).sent_reliable_commands.sentinel)
-    {
-        return 0_i32;
-    }
-    1_i32
-}
 pub(crate) unsafe fn enet_peer_disconnect_later<S: Socket>(peer: *mut ENetPeer<S>, data: u32) {
     if ((*peer).state == ENET_PEER_STATE_CONNECTED as i32 as u32
         || (*peer).state == ENET_PEER_STATE_DISCONNECT_LATER as i32 as u32)
-        && enet_peer_has_outgoing_commands(peer) != 0
+        && (*peer).queues.has_outgoing_commands()
     {
         (*peer).state = ENET_PEER_STATE_DISCONNECT_LATER;
         (*peer).event_data = data;
@@ -639,7 +1534,7 @@ pub(crate) unsafe fn enet_peer_queue_acknowledgement<S: Socket>(
     peer: *mut ENetPeer<S>,
     command: *const ENetProtocol,
     sent_time: u16,
-) -> *mut ENetAcknowledgement {
+) -> Option<ENetListIterator> {
     if ((*command).header.channel_id as usize) < (*peer).channel_count {
         let channel: *mut ENetChannel =
             ((*peer).channels).offset((*command).header.channel_id as isize);
@@ -656,105 +1551,100 @@ pub(crate) unsafe fn enet_peer_queue_acknowledgement<S: Socket>(
             >= current_window as i32 + PEER_FREE_RELIABLE_WINDOWS as i32 - 1_i32
             && reliable_window as i32 <= current_window as i32 + PEER_FREE_RELIABLE_WINDOWS as i32
         {
-            return core::ptr::null_mut();
+            return None;
         }
     }
-    let acknowledgement: *mut ENetAcknowledgement =
-        enet_malloc(Layout::new::<ENetAcknowledgement>()).cast();
     (*peer).outgoing_data_total = ((*peer).outgoing_data_total as u64)
         .wrapping_add(::core::mem::size_of::<ENetProtocolAcknowledge>() as u64)
         as u32;
-    (*acknowledgement).sent_time = sent_time as u32;
-    (*acknowledgement).command = *command;
-    enet_list_insert(
-        &mut (*peer).acknowledgements.sentinel,
-        acknowledgement.cast(),
-    );
-    acknowledgement
+    Some((*peer).queues.queue_acknowledgement(ENetAcknowledgement {
+        sent_time: sent_time as u32,
+        command: *command,
+    }))
 }
 pub(crate) unsafe fn enet_peer_setup_outgoing_command<S: Socket>(
     peer: *mut ENetPeer<S>,
-    outgoing_command: *mut ENetOutgoingCommand,
-) {
+    mut outgoing_command: ENetOutgoingCommand,
+) -> ENetListIterator {
     (*peer).outgoing_data_total = ((*peer).outgoing_data_total as usize).wrapping_add(
-        (enet_protocol_command_size((*outgoing_command).command.header.command))
-            .wrapping_add((*outgoing_command).fragment_length as usize),
+        (enet_protocol_command_size(outgoing_command.command.header.command))
+            .wrapping_add(outgoing_command.fragment_length as usize),
     ) as u32 as u32;
-    if (*outgoing_command).command.header.channel_id as i32 == 0xff_i32 {
+    if outgoing_command.command.header.channel_id as i32 == 0xff_i32 {
+        outgoing_command.priority = 0_i32 as u8;
         (*peer).outgoing_reliable_sequence_number =
             ((*peer).outgoing_reliable_sequence_number).wrapping_add(1);
-        (*outgoing_command).reliable_sequence_number = (*peer).outgoing_reliable_sequence_number;
-        (*outgoing_command).unreliable_sequence_number = 0_i32 as u16;
+        outgoing_command.reliable_sequence_number = (*peer).outgoing_reliable_sequence_number;
+        outgoing_command.unreliable_sequence_number = 0_i32 as u16;
     } else {
         let channel: *mut ENetChannel =
-            ((*peer).channels).offset((*outgoing_command).command.header.channel_id as isize);
-        if (*outgoing_command).command.header.command as i32
+            ((*peer).channels).offset(outgoing_command.command.header.channel_id as isize);
+        outgoing_command.priority = (*channel).priority;
+        if outgoing_command.command.header.command as i32
             & ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE as i32
             != 0
         {
             (*channel).outgoing_reliable_sequence_number =
                 ((*channel).outgoing_reliable_sequence_number).wrapping_add(1);
             (*channel).outgoing_unreliable_sequence_number = 0_i32 as u16;
-            (*outgoing_command).reliable_sequence_number =
+            outgoing_command.reliable_sequence_number =
                 (*channel).outgoing_reliable_sequence_number;
-            (*outgoing_command).unreliable_sequence_number = 0_i32 as u16;
-        } else if (*outgoing_command).command.header.command as i32
+            outgoing_command.unreliable_sequence_number = 0_i32 as u16;
+        } else if outgoing_command.command.header.command as i32
             & ENET_PROTOCOL_COMMAND_FLAG_UNSEQUENCED as i32
             != 0
         {
             (*peer).outgoing_unsequenced_group =
                 ((*peer).outgoing_unsequenced_group).wrapping_add(1);
-            (*outgoing_command).reliable_sequence_number = 0_i32 as u16;
-            (*outgoing_command).unreliable_sequence_number = 0_i32 as u16;
+            outgoing_command.reliable_sequence_number = 0_i32 as u16;
+            outgoing_command.unreliable_sequence_number = 0_i32 as u16;
         } else {
-            if (*outgoing_command).fragment_offset == 0_i32 as u32 {
+            if outgoing_command.fragment_offset == 0_i32 as u32 {
                 (*channel).outgoing_unreliable_sequence_number =
                     ((*channel).outgoing_unreliable_sequence_number).wrapping_add(1);
             }
-            (*outgoing_command).reliable_sequence_number =
+            outgoing_command.reliable_sequence_number =
                 (*channel).outgoing_reliable_sequence_number;
-            (*outgoing_command).unreliable_sequence_number =
+            outgoing_command.unreliable_sequence_number =
                 (*channel).outgoing_unreliable_sequence_number;
         }
     }
-    (*outgoing_command).send_attempts = 0_i32 as u16;
-    (*outgoing_command).sent_time = 0_i32 as u32;
-    (*outgoing_command).round_trip_timeout = 0_i32 as u32;
-    (*outgoing_command).command.header.reliable_sequence_number =
-        (*outgoing_command).reliable_sequence_number.to_be();
+    outgoing_command.send_attempts = 0_i32 as u16;
+    outgoing_command.sent_time = 0_i32 as u32;
+    outgoing_command.round_trip_timeout = 0_i32 as u32;
+    outgoing_command.command.header.reliable_sequence_number =
+        outgoing_command.reliable_sequence_number.to_be();
     (*(*peer).host).total_queued = ((*(*peer).host).total_queued).wrapping_add(1);
-    (*outgoing_command).queue_time = (*(*peer).host).total_queued;
-    match (*outgoing_command).command.header.command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32 {
+    (*peer).queued_data = (*peer)
+        .queued_data
+        .wrapping_add(outgoing_command.fragment_length as usize);
+    outgoing_command.queue_time = (*(*peer).host).total_queued;
+    match outgoing_command.command.header.command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32 {
         7 => {
-            (*outgoing_command)
+            outgoing_command
                 .command
                 .send_unreliable
-                .unreliable_sequence_number =
-                (*outgoing_command).unreliable_sequence_number.to_be();
+                .unreliable_sequence_number = outgoing_command.unreliable_sequence_number.to_be();
         }
         9 => {
-            (*outgoing_command)
-                .command
-                .send_unsequenced
-                .unsequenced_group = (*peer).outgoing_unsequenced_group.to_be();
+            outgoing_command.command.send_unsequenced.unsequenced_group =
+                (*peer).outgoing_unsequenced_group.to_be();
         }
         _ => {}
     }
-    if (*outgoing_command).command.header.command as i32
-        & ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE as i32
-        != 0_i32
-        && !((*outgoing_command).packet).is_null()
-    {
-        enet_list_insert(
-            &mut (*peer).outgoing_send_reliable_commands.sentinel,
-            outgoing_command.cast(),
-        );
-    } else {
-        enet_list_insert(
-            &mut (*peer).outgoing_commands.sentinel,
-            outgoing_command.cast(),
-        );
-    };
+    (*peer).queues.queue_outgoing_command(outgoing_command)
+}
+pub(crate) unsafe fn enet_peer_set_channel_priority<S: Socket>(
+    peer: *mut ENetPeer<S>,
+    channel_id: u8,
+    priority: u8,
+) {
+    let channel: *mut ENetChannel = ((*peer).channels).offset(channel_id as isize);
+    if (*channel).priority == priority {
+        return;
+    }
+    (*channel).priority = priority;
+    (*peer).queues.set_channel_priority(channel_id, priority);
 }
 pub(crate) unsafe fn enet_peer_queue_outgoing_command<S: Socket>(
     peer: *mut ENetPeer<S>,
@@ -762,185 +1652,57 @@ pub(crate) unsafe fn enet_peer_queue_outgoing_command<S: Socket>(
     packet: *mut ENetPacket,
     offset: u32,
     length: u16,
-) -> *mut ENetOutgoingCommand {
-    let outgoing_command: *mut ENetOutgoingCommand =
-        enet_malloc(Layout::new::<ENetOutgoingCommand>()).cast();
-    if outgoing_command.is_null() {
-        return core::ptr::null_mut();
-    }
-    (*outgoing_command).command = *command;
-    (*outgoing_command).fragment_offset = offset;
-    (*outgoing_command).fragment_length = length;
-    (*outgoing_command).packet = packet;
+) -> ENetListIterator {
+    let mut outgoing_command: ENetOutgoingCommand = zeroed();
+    outgoing_command.command = *command;
+    outgoing_command.fragment_offset = offset;
+    outgoing_command.fragment_length = length;
+    outgoing_command.packet = packet;
     if !packet.is_null() {
-        (*packet).reference_count = ((*packet).reference_count).wrapping_add(1);
+        (*packet).reference_count.fetch_add(1, Ordering::Relaxed);
+    }
+    enet_peer_setup_outgoing_command(peer, outgoing_command)
+}
+/// Adds `peer` to its host's dispatch queue, unless it is already waiting there.
+pub(crate) unsafe fn enet_peer_needs_dispatch<S: Socket>(peer: *mut ENetPeer<S>) {
+    if (*peer).flags as i32 & ENET_PEER_FLAG_NEEDS_DISPATCH as i32 == 0 {
+        (*(*peer).host)
+            .dispatch_queue
+            .assume_init_mut()
+            .push_back((*peer).incoming_peer_id as usize);
+        (*peer).flags = ((*peer).flags as i32 | ENET_PEER_FLAG_NEEDS_DISPATCH as i32) as u16;
     }
-    enet_peer_setup_outgoing_command(peer, outgoing_command);
-    outgoing_command
 }
 pub(crate) unsafe fn enet_peer_dispatch_incoming_unreliable_commands<S: Socket>(
     peer: *mut ENetPeer<S>,
     channel: *mut ENetChannel,
-    queued_command: *mut ENetIncomingCommand,
+    queued_command: ENetListIterator,
 ) {
-    let mut dropped_command: ENetListIterator;
-    let mut start_command: ENetListIterator;
-    let mut current_command: ENetListIterator;
-    let mut current_block_22: u64;
-    current_command = (*channel).incoming_unreliable_commands.sentinel.next;
-    start_command = current_command;
-    dropped_command = start_command;
-    while current_command
-        != core::ptr::addr_of_mut!((*channel).incoming_unreliable_commands.sentinel)
-    {
-        let incoming_command: *mut ENetIncomingCommand = current_command.cast();
-        if (*incoming_command).command.header.command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32
-            != ENET_PROTOCOL_COMMAND_SEND_UNSEQUENCED as i32
-        {
-            if (*incoming_command).reliable_sequence_number as i32
-                == (*channel).incoming_reliable_sequence_number as i32
-            {
-                if (*incoming_command).fragments_remaining <= 0_i32 as u32 {
-                    (*channel).incoming_unreliable_sequence_number =
-                        (*incoming_command).unreliable_sequence_number;
-                    current_block_22 = 11174649648027449784;
-                } else {
-                    if start_command != current_command {
-                        enet_list_move(
-                            &mut (*peer).dispatched_commands.sentinel,
-                            start_command.cast(),
-                            (*current_command).previous.cast(),
-                        );
-                        if (*peer).flags as i32 & ENET_PEER_FLAG_NEEDS_DISPATCH as i32 == 0 {
-                            enet_list_insert(
-                                &mut (*(*peer).host).dispatch_queue.sentinel,
-                                core::ptr::addr_of_mut!((*peer).dispatch_list).cast(),
-                            );
-                            (*peer).flags = ((*peer).flags as i32
-                                | ENET_PEER_FLAG_NEEDS_DISPATCH as i32)
-                                as u16;
-                        }
-                        dropped_command = current_command;
-                    } else if dropped_command != current_command {
-                        dropped_command = (*current_command).previous;
-                    }
-                    current_block_22 = 13472856163611868459;
-                }
-            } else {
-                let mut reliable_window: u16 = ((*incoming_command).reliable_sequence_number as i32
-                    / PEER_RELIABLE_WINDOW_SIZE as i32)
-                    as u16;
-                let current_window: u16 = ((*channel).incoming_reliable_sequence_number as i32
-                    / PEER_RELIABLE_WINDOW_SIZE as i32)
-                    as u16;
-                if ((*incoming_command).reliable_sequence_number as i32)
-                    < (*channel).incoming_reliable_sequence_number as i32
-                {
-                    reliable_window =
-                        (reliable_window as i32 + PEER_RELIABLE_WINDOWS as i32) as u16;
-                }
-                if reliable_window as i32 >= current_window as i32
-                    && (reliable_window as i32)
-                        < current_window as i32 + PEER_FREE_RELIABLE_WINDOWS as i32 - 1_i32
-                {
-                    break;
-                }
-                dropped_command = (*current_command).next;
-                if start_command != current_command {
-                    enet_list_move(
-                        &mut (*peer).dispatched_commands.sentinel,
-                        start_command.cast(),
-                        (*current_command).previous.cast(),
-                    );
-                    if (*peer).flags as i32 & ENET_PEER_FLAG_NEEDS_DISPATCH as i32 == 0 {
-                        enet_list_insert(
-                            &mut (*(*peer).host).dispatch_queue.sentinel,
-                            core::ptr::addr_of_mut!((*peer).dispatch_list).cast(),
-                        );
-                        (*peer).flags =
-                            ((*peer).flags as i32 | ENET_PEER_FLAG_NEEDS_DISPATCH as i32) as u16;
-                    }
-                }
-                current_block_22 = 13472856163611868459;
-            }
-            match current_block_22 {
-                11174649648027449784 => {}
-                _ => {
-                    start_command = (*current_command).next;
-                }
-            }
-        }
-        current_command = (*current_command).next;
-    }
-    if start_command != current_command {
-        enet_list_move(
-            &mut (*peer).dispatched_commands.sentinel,
-            start_command.cast(),
-            (*current_command).previous.cast(),
-        );
-        if (*peer).flags as i32 & ENET_PEER_FLAG_NEEDS_DISPATCH as i32 == 0 {
-            enet_list_insert(
-                &mut (*(*peer).host).dispatch_queue.sentinel,
-                core::ptr::addr_of_mut!((*peer).dispatch_list).cast(),
-            );
-            (*peer).flags = ((*peer).flags as i32 | ENET_PEER_FLAG_NEEDS_DISPATCH as i32) as u16;
-        }
-        dropped_command = current_command;
-    }
-    enet_peer_remove_incoming_commands(
-        &mut (*channel).incoming_unreliable_commands,
-        (*channel).incoming_unreliable_commands.sentinel.next,
-        dropped_command,
+    if (*peer).queues.dispatch_incoming_unreliable_commands(
+        &mut *channel,
         queued_command,
-    );
+        |incoming_command| enet_peer_release_incoming_command(incoming_command),
+    ) {
+        enet_peer_needs_dispatch(peer);
+    }
 }
 pub(crate) unsafe fn enet_peer_dispatch_incoming_reliable_commands<S: Socket>(
     peer: *mut ENetPeer<S>,
     channel: *mut ENetChannel,
-    queued_command: *mut ENetIncomingCommand,
+    queued_command: ENetListIterator,
 ) {
-    let mut current_command: ENetListIterator;
-    current_command = (*channel).incoming_reliable_commands.sentinel.next;
-    while current_command != core::ptr::addr_of_mut!((*channel).incoming_reliable_commands.sentinel)
-    {
-        let incoming_command: *mut ENetIncomingCommand = current_command.cast();
-        if (*incoming_command).fragments_remaining > 0_i32 as u32
-            || (*incoming_command).reliable_sequence_number as i32
-                != ((*channel).incoming_reliable_sequence_number as i32 + 1_i32) as u16 as i32
-        {
-            break;
-        }
-        (*channel).incoming_reliable_sequence_number = (*incoming_command).reliable_sequence_number;
-        if (*incoming_command).fragment_count > 0_i32 as u32 {
-            (*channel).incoming_reliable_sequence_number =
-                ((*channel).incoming_reliable_sequence_number as u32)
-                    .wrapping_add(((*incoming_command).fragment_count).wrapping_sub(1_i32 as u32))
-                    as u16 as u16;
-        }
-        current_command = (*current_command).next;
-    }
-    if current_command == (*channel).incoming_reliable_commands.sentinel.next {
-        return;
-    }
-    (*channel).incoming_unreliable_sequence_number = 0_i32 as u16;
-    enet_list_move(
-        &mut (*peer).dispatched_commands.sentinel,
-        ((*channel).incoming_reliable_commands.sentinel.next).cast(),
-        ((*current_command).previous).cast(),
-    );
-    if (*peer).flags as i32 & ENET_PEER_FLAG_NEEDS_DISPATCH as i32 == 0 {
-        enet_list_insert(
-            &mut (*(*peer).host).dispatch_queue.sentinel,
-            core::ptr::addr_of_mut!((*peer).dispatch_list).cast(),
-        );
-        (*peer).flags = ((*peer).flags as i32 | ENET_PEER_FLAG_NEEDS_DISPATCH as i32) as u16;
-    }
-    if (*channel).incoming_unreliable_commands.sentinel.next
-        != core::ptr::addr_of_mut!((*channel).incoming_unreliable_commands.sentinel)
-    {
-        enet_peer_dispatch_incoming_unreliable_commands(peer, channel, queued_command);
+    if (*peer).queues.dispatch_incoming_reliable_commands(
+        &mut *channel,
+        queued_command,
+        |incoming_command| enet_peer_release_incoming_command(incoming_command),
+    ) {
+        enet_peer_needs_dispatch(peer);
     }
 }
+/// Queues an incoming command on its channel and dispatches whatever it completes.
+///
+/// Returns `None` on error, or [`ENET_LIST_END`] if the command was discarded as a duplicate or
+/// because it fell outside the reliable window.
 pub(crate) unsafe fn enet_peer_queue_incoming_command<S: Socket>(
     peer: *mut ENetPeer<S>,
     command: *const ENetProtocol,
@@ -948,801 +1710,80 @@ pub(crate) unsafe fn enet_peer_queue_incoming_command<S: Socket>(
     data_length: usize,
     flags: u32,
     fragment_count: u32,
-) -> *mut ENetIncomingCommand {
-    static mut DUMMY_COMMAND: ENetIncomingCommand = ENetIncomingCommand {
-        incoming_command_list: ENetListNode {
-            next: 0 as *mut ENetListNode,
-            previous: 0 as *mut ENetListNode,
-        },
-        reliable_sequence_number: 0,
-        unreliable_sequence_number: 0,
-        command: ENetProtocol {
-            header: ENetProtocolCommandHeader {
-                command: 0,
-                channel_id: 0,
-                reliable_sequence_number: 0,
-            },
-        },
-        fragment_count: 0,
-        fragments_remaining: 0,
-        fragments: core::ptr::null_mut(),
-        packet: core::ptr::null_mut(),
-    };
-    let mut current_block: u64;
+) -> Option<ENetListIterator> {
     let channel: *mut ENetChannel =
         ((*peer).channels).offset((*command).header.channel_id as isize);
-    let mut unreliable_sequence_number: u32 = 0_i32 as u32;
-    let mut reliable_sequence_number: u32 = 0_i32 as u32;
-    let mut reliable_window: u16;
-    let current_window: u16;
-    let mut incoming_command: *mut ENetIncomingCommand;
-    let mut current_command: ENetListIterator = core::ptr::null_mut();
-    let mut packet: *mut ENetPacket = core::ptr::null_mut();
-    if (*peer).state == ENET_PEER_STATE_DISCONNECT_LATER as i32 as u32 {
-        current_block = 9207730764507465628;
+    let command_number = (*command).header.command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32;
+    let unreliable_sequence_number = match command_number {
+        7 | 12 => u16::from_be((*command).send_unreliable.unreliable_sequence_number),
+        _ => 0_i32 as u16,
+    };
+    let current_command = if (*peer).state == ENET_PEER_STATE_DISCONNECT_LATER as i32 as u32 {
+        None
     } else {
-        if (*command).header.command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32
-            != ENET_PROTOCOL_COMMAND_SEND_UNSEQUENCED as i32
-        {
-            reliable_sequence_number = (*command).header.reliable_sequence_number as u32;
-            reliable_window = reliable_sequence_number
-                .wrapping_div(PEER_RELIABLE_WINDOW_SIZE as i32 as u32)
-                as u16;
-            current_window = ((*channel).incoming_reliable_sequence_number as i32
-                / PEER_RELIABLE_WINDOW_SIZE as i32) as u16;
-            if reliable_sequence_number < (*channel).incoming_reliable_sequence_number as u32 {
-                reliable_window = (reliable_window as i32 + PEER_RELIABLE_WINDOWS as i32) as u16;
-            }
-            if (reliable_window as i32) < current_window as i32
-                || reliable_window as i32
-                    >= current_window as i32 + PEER_FREE_RELIABLE_WINDOWS as i32 - 1_i32
-            {
-                current_block = 9207730764507465628;
-            } else {
-                current_block = 13183875560443969876;
-            }
-        } else {
-            current_block = 13183875560443969876;
-        }
-        match current_block {
-            9207730764507465628 => {}
-            _ => match (*command).header.command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32 {
-                8 | 6 => {
-                    current_block = 4379360700607281851;
-                    match current_block {
-                        10107555224945550073 => {
-                            current_command = &mut (*channel).incoming_unreliable_commands.sentinel;
-                            current_block = 7746103178988627676;
-                        }
-                        4379360700607281851 => {
-                            if reliable_sequence_number
-                                == (*channel).incoming_reliable_sequence_number as u32
-                            {
-                                current_block = 9207730764507465628;
-                            } else {
-                                current_command =
-                                    (*channel).incoming_reliable_commands.sentinel.previous;
-                                loop {
-                                    if current_command
-                                        == core::ptr::addr_of_mut!(
-                                            (*channel).incoming_reliable_commands.sentinel
-                                        )
-                                    {
-                                        current_block = 7746103178988627676;
-                                        break;
-                                    }
-                                    incoming_command = current_command.cast();
-                                    if reliable_sequence_number
-                                        >= (*channel).incoming_reliable_sequence_number as u32
-                                    {
-                                        if ((*incoming_command).reliable_sequence_number as i32)
-                                            < (*channel).incoming_reliable_sequence_number as i32
-                                        {
-                                            current_block = 1856101646708284338;
-                                        } else {
-                                            current_block = 8457315219000651999;
-                                        }
-                                    } else {
-                                        if (*incoming_command).reliable_sequence_number as i32
-                                            >= (*channel).incoming_reliable_sequence_number as i32
-                                        {
-                                            current_block = 7746103178988627676;
-                                            break;
-                                        }
-                                        current_block = 8457315219000651999;
-                                    }
-                                    if let 8457315219000651999 = current_block {
-                                        if (*incoming_command).reliable_sequence_number as u32
-                                            <= reliable_sequence_number
-                                        {
-                                            if ((*incoming_command).reliable_sequence_number as u32)
-                                                < reliable_sequence_number
-                                            {
-                                                current_block = 7746103178988627676;
-                                                break;
-                                            } else {
-                                                current_block = 9207730764507465628;
-                                                break;
-                                            }
-                                        }
-                                    }
-                                    current_command = (*current_command).previous;
-                                }
-                            }
-                        }
-                        _ => {
-                            unreliable_sequence_number =
-                                u16::from_be((*command).send_unreliable.unreliable_sequence_number)
-                                    as u32;
-                            if reliable_sequence_number
-                                == (*channel).incoming_reliable_sequence_number as u32
-                                && unreliable_sequence_number
-                                    <= (*channel).incoming_unreliable_sequence_number as u32
-                            {
-                                current_block = 9207730764507465628;
-                            } else {
-                                current_command =
-                                    (*channel).incoming_unreliable_commands.sentinel.previous;
-                                loop {
-                                    if current_command
-                                        == core::ptr::addr_of_mut!(
-                                            (*channel).incoming_unreliable_commands.sentinel
-                                        )
-                                    {
-                                        current_block = 7746103178988627676;
-                                        break;
-                                    }
-                                    incoming_command = current_command.cast();
-                                    if (*command).header.command as i32
-                                        & ENET_PROTOCOL_COMMAND_MASK as i32
-                                        != ENET_PROTOCOL_COMMAND_SEND_UNSEQUENCED as i32
-                                    {
-                                        if reliable_sequence_number
-                                            >= (*channel).incoming_reliable_sequence_number as u32
-                                        {
-                                            if ((*incoming_command).reliable_sequence_number as i32)
-                                                < (*channel).incoming_reliable_sequence_number
-                                                    as i32
-                                            {
-                                                current_block = 17478428563724192186;
-                                            } else {
-                                                current_block = 11459959175219260272;
-                                            }
-                                        } else {
-                                            if (*incoming_command).reliable_sequence_number as i32
-                                                >= (*channel).incoming_reliable_sequence_number
-                                                    as i32
-                                            {
-                                                current_block = 7746103178988627676;
-                                                break;
-                                            }
-                                            current_block = 11459959175219260272;
-                                        }
-                                        if current_block != 17478428563724192186 {
-                                            if ((*incoming_command).reliable_sequence_number as u32)
-                                                < reliable_sequence_number
-                                            {
-                                                current_block = 7746103178988627676;
-                                                break;
-                                            }
-                                            if (*incoming_command).reliable_sequence_number as u32
-                                                <= reliable_sequence_number
-                                                && (*incoming_command).unreliable_sequence_number
-                                                    as u32
-                                                    <= unreliable_sequence_number
-                                            {
-                                                if ((*incoming_command).unreliable_sequence_number
-                                                    as u32)
-                                                    < unreliable_sequence_number
-                                                {
-                                                    current_block = 7746103178988627676;
-                                                    break;
-                                                } else {
-                                                    current_block = 9207730764507465628;
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                    }
-                                    current_command = (*current_command).previous;
-                                }
-                            }
-                        }
-                    }
-                    match current_block {
-                        9207730764507465628 => {}
-                        _ => {
-                            if (*peer).total_waiting_data >= (*(*peer).host).maximum_waiting_data {
-                                current_block = 15492018734234176694;
-                            } else {
-                                packet = enet_packet_create(data, data_length, flags);
-                                if packet.is_null() {
-                                    current_block = 15492018734234176694;
-                                } else {
-                                    incoming_command =
-                                        enet_malloc(Layout::new::<ENetIncomingCommand>()).cast();
-                                    if incoming_command.is_null() {
-                                        current_block = 15492018734234176694;
-                                    } else {
-                                        (*incoming_command).reliable_sequence_number =
-                                            (*command).header.reliable_sequence_number;
-                                        (*incoming_command).unreliable_sequence_number =
-                                            (unreliable_sequence_number & 0xffff_i32 as u32) as u16;
-                                        (*incoming_command).command = *command;
-                                        (*incoming_command).fragment_count = fragment_count;
-                                        (*incoming_command).fragments_remaining = fragment_count;
-                                        (*incoming_command).packet = packet;
-                                        (*incoming_command).fragments = core::ptr::null_mut();
-                                        if fragment_count > 0_i32 as u32 {
-                                            if fragment_count
-                                                <= PROTOCOL_MAXIMUM_FRAGMENT_COUNT as i32 as u32
-                                            {
-                                                let count = fragment_count
-                                                    .wrapping_add(31_i32 as u32)
-                                                    .wrapping_div(32_i32 as u32)
-                                                    as usize;
-                                                (*incoming_command).fragments = enet_malloc(
-                                                    Layout::array::<u32>(count).unwrap(),
-                                                )
-                                                .cast();
-                                            }
-                                            if ((*incoming_command).fragments).is_null() {
-                                                enet_free(
-                                                    incoming_command.cast(),
-                                                    Layout::new::<ENetIncomingCommand>(),
-                                                );
-                                                current_block = 15492018734234176694;
-                                            } else {
-                                                write_bytes(
-                                                    (*incoming_command).fragments.cast::<u8>(),
-                                                    0,
-                                                    (fragment_count
-                                                        .wrapping_add(31_i32 as u32)
-                                                        .wrapping_div(32_i32 as u32)
-                                                        as usize)
-                                                        .wrapping_mul(
-                                                            ::core::mem::size_of::<u32>(),
-                                                        ),
-                                                );
-                                                current_block = 13321564401369230990;
-                                            }
-                                        } else {
-                                            current_block = 13321564401369230990;
-                                        }
-                                        if current_block != 15492018734234176694 {
-                                            if !packet.is_null() {
-                                                (*packet).reference_count =
-                                                    ((*packet).reference_count).wrapping_add(1);
-                                                (*peer).total_waiting_data = (*peer)
-                                                    .total_waiting_data
-                                                    .wrapping_add((*packet).data_length);
-                                            }
-                                            enet_list_insert(
-                                                (*current_command).next,
-                                                incoming_command.cast(),
-                                            );
-                                            match (*command).header.command as i32
-                                                & ENET_PROTOCOL_COMMAND_MASK as i32
-                                            {
-                                                8 | 6 => {
-                                                    enet_peer_dispatch_incoming_reliable_commands(
-                                                        peer,
-                                                        channel,
-                                                        incoming_command,
-                                                    );
-                                                }
-                                                _ => {
-                                                    enet_peer_dispatch_incoming_unreliable_commands(
-                                                        peer,
-                                                        channel,
-                                                        incoming_command,
-                                                    );
-                                                }
-                                            }
-                                            return incoming_command;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                7 | 12 => {
-                    current_block = 1195130990526578986;
-                    match current_block {
-                        10107555224945550073 => {
-                            current_command = &mut (*channel).incoming_unreliable_commands.sentinel;
-                            current_block = 7746103178988627676;
-                        }
-                        4379360700607281851 => {
-                            if reliable_sequence_number
-                                == (*channel).incoming_reliable_sequence_number as u32
-                            {
-                                current_block = 9207730764507465628;
-                            } else {
-                                current_command =
-                                    (*channel).incoming_reliable_commands.sentinel.previous;
-                                loop {
-                                    if current_command
-                                        == core::ptr::addr_of_mut!(
-                                            (*channel).incoming_reliable_commands.sentinel
-                                        )
-                                    {
-                                        current_block = 7746103178988627676;
-                                        break;
-                                    }
-                                    incoming_command = current_command.cast();
-                                    if reliable_sequence_number
-                                        >= (*channel).incoming_reliable_sequence_number as u32
-                                    {
-                                        if ((*incoming_command).reliable_sequence_number as i32)
-                                            < (*channel).incoming_reliable_sequence_number as i32
-                                        {
-                                            current_block = 1856101646708284338;
-                                        } else {
-                                            current_block = 8457315219000651999;
-                                        }
-                                    } else {
-                                        if (*incoming_command).reliable_sequence_number as i32
-                                            >= (*channel).incoming_reliable_sequence_number as i32
-                                        {
-                                            current_block = 7746103178988627676;
-                                            break;
-                                        }
-                                        current_block = 8457315219000651999;
-                                    }
-                                    if let 8457315219000651999 = current_block {
-                                        if (*incoming_command).reliable_sequence_number as u32
-                                            <= reliable_sequence_number
-                                        {
-                                            if ((*incoming_command).reliable_sequence_number as u32)
-                                                < reliable_sequence_number
-                                            {
-                                                current_block = 7746103178988627676;
-                                                break;
-                                            } else {
-                                                current_block = 9207730764507465628;
-                                                break;
-                                            }
-                                        }
-                                    }
-                                    current_command = (*current_command).previous;
-                                }
-                            }
-                        }
-                        _ => {
-                            unreliable_sequence_number =
-                                u16::from_be((*command).send_unreliable.unreliable_sequence_number)
-                                    as u32;
-                            if reliable_sequence_number
-                                == (*channel).incoming_reliable_sequence_number as u32
-                                && unreliable_sequence_number
-                                    <= (*channel).incoming_unreliable_sequence_number as u32
-                            {
-                                current_block = 9207730764507465628;
-                            } else {
-                                current_command =
-                                    (*channel).incoming_unreliable_commands.sentinel.previous;
-                                loop {
-                                    if current_command
-                                        == core::ptr::addr_of_mut!(
-                                            (*channel).incoming_unreliable_commands.sentinel
-                                        )
-                                    {
-                                        current_block = 7746103178988627676;
-                                        break;
-                                    }
-                                    incoming_command = current_command.cast();
-                                    if (*command).header.command as i32
-                                        & ENET_PROTOCOL_COMMAND_MASK as i32
-                                        != ENET_PROTOCOL_COMMAND_SEND_UNSEQUENCED as i32
-                                    {
-                                        if reliable_sequence_number
-                                            >= (*channel).incoming_reliable_sequence_number as u32
-                                        {
-                                            if ((*incoming_command).reliable_sequence_number as i32)
-                                                < (*channel).incoming_reliable_sequence_number
-                                                    as i32
-                                            {
-                                                current_block = 17478428563724192186;
-                                            } else {
-                                                current_block = 11459959175219260272;
-                                            }
-                                        } else {
-                                            if (*incoming_command).reliable_sequence_number as i32
-                                                >= (*channel).incoming_reliable_sequence_number
-                                                    as i32
-                                            {
-                                                current_block = 7746103178988627676;
-                                                break;
-                                            }
-                                            current_block = 11459959175219260272;
-                                        }
-                                        if current_block != 17478428563724192186 {
-                                            if ((*incoming_command).reliable_sequence_number as u32)
-                                                < reliable_sequence_number
-                                            {
-                                                current_block = 7746103178988627676;
-                                                break;
-                                            }
-                                            if (*incoming_command).reliable_sequence_number as u32
-                                                <= reliable_sequence_number
-                                                && (*incoming_command).unreliable_sequence_number
-                                                    as u32
-                                                    <= unreliable_sequence_number
-                                            {
-                                                if ((*incoming_command).unreliable_sequence_number
-                                                    as u32)
-                                                    < unreliable_sequence_number
-                                                {
-                                                    current_block = 7746103178988627676;
-                                                    break;
-                                                } else {
-                                                    current_block = 9207730764507465628;
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                    }
-                                    current_command = (*current_command).previous;
-                                }
-                            }
-                        }
-                    }
-                    match current_block {
-                        9207730764507465628 => {}
-                        _ => {
-                            if (*peer).total_waiting_data >= (*(*peer).host).maximum_waiting_data {
-                                current_block = 15492018734234176694;
-                            } else {
-                                packet = enet_packet_create(data, data_length, flags);
-                                if packet.is_null() {
-                                    current_block = 15492018734234176694;
-                                } else {
-                                    incoming_command =
-                                        enet_malloc(Layout::new::<ENetIncomingCommand>()).cast();
-                                    if incoming_command.is_null() {
-                                        current_block = 15492018734234176694;
-                                    } else {
-                                        (*incoming_command).reliable_sequence_number =
-                                            (*command).header.reliable_sequence_number;
-                                        (*incoming_command).unreliable_sequence_number =
-                                            (unreliable_sequence_number & 0xffff_i32 as u32) as u16;
-                                        (*incoming_command).command = *command;
-                                        (*incoming_command).fragment_count = fragment_count;
-                                        (*incoming_command).fragments_remaining = fragment_count;
-                                        (*incoming_command).packet = packet;
-                                        (*incoming_command).fragments = core::ptr::null_mut();
-                                        if fragment_count > 0_i32 as u32 {
-                                            if fragment_count
-                                                <= PROTOCOL_MAXIMUM_FRAGMENT_COUNT as i32 as u32
-                                            {
-                                                let count = fragment_count
-                                                    .wrapping_add(31_i32 as u32)
-                                                    .wrapping_div(32_i32 as u32)
-                                                    as usize;
-                                                (*incoming_command).fragments = enet_malloc(
-                                                    Layout::array::<u32>(count).unwrap(),
-                                                )
-                                                .cast();
-                                            }
-                                            if ((*incoming_command).fragments).is_null() {
-                                                enet_free(
-                                                    incoming_command.cast(),
-                                                    Layout::new::<ENetIncomingCommand>(),
-                                                );
-                                                current_block = 15492018734234176694;
-                                            } else {
-                                                write_bytes(
-                                                    (*incoming_command).fragments.cast::<u8>(),
-                                                    0,
-                                                    (fragment_count
-                                                        .wrapping_add(31_i32 as u32)
-                                                        .wrapping_div(32_i32 as u32)
-                                                        as usize)
-                                                        .wrapping_mul(
-                                                            ::core::mem::size_of::<u32>(),
-                                                        ),
-                                                );
-                                                current_block = 13321564401369230990;
-                                            }
-                                        } else {
-                                            current_block = 13321564401369230990;
-                                        }
-                                        if current_block != 15492018734234176694 {
-                                            if !packet.is_null() {
-                                                (*packet).reference_count =
-                                                    ((*packet).reference_count).wrapping_add(1);
-                                                (*peer).total_waiting_data = (*peer)
-                                                    .total_waiting_data
-                                                    .wrapping_add((*packet).data_length);
-                                            }
-                                            enet_list_insert(
-                                                (*current_command).next,
-                                                incoming_command.cast(),
-                                            );
-                                            match (*command).header.command as i32
-                                                & ENET_PROTOCOL_COMMAND_MASK as i32
-                                            {
-                                                8 | 6 => {
-                                                    enet_peer_dispatch_incoming_reliable_commands(
-                                                        peer,
-                                                        channel,
-                                                        incoming_command,
-                                                    );
-                                                }
-                                                _ => {
-                                                    enet_peer_dispatch_incoming_unreliable_commands(
-                                                        peer,
-                                                        channel,
-                                                        incoming_command,
-                                                    );
-                                                }
-                                            }
-                                            return incoming_command;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                9 => {
-                    current_block = 10107555224945550073;
-                    match current_block {
-                        10107555224945550073 => {
-                            current_command = &mut (*channel).incoming_unreliable_commands.sentinel;
-                            current_block = 7746103178988627676;
-                        }
-                        4379360700607281851 => {
-                            if reliable_sequence_number
-                                == (*channel).incoming_reliable_sequence_number as u32
-                            {
-                                current_block = 9207730764507465628;
-                            } else {
-                                current_command =
-                                    (*channel).incoming_reliable_commands.sentinel.previous;
-                                loop {
-                                    if current_command
-                                        == core::ptr::addr_of_mut!(
-                                            (*channel).incoming_reliable_commands.sentinel
-                                        )
-                                    {
-                                        current_block = 7746103178988627676;
-                                        break;
-                                    }
-                                    incoming_command = current_command.cast();
-                                    if reliable_sequence_number
-                                        >= (*channel).incoming_reliable_sequence_number as u32
-                                    {
-                                        if ((*incoming_command).reliable_sequence_number as i32)
-                                            < (*channel).incoming_reliable_sequence_number as i32
-                                        {
-                                            current_block = 1856101646708284338;
-                                        } else {
-                                            current_block = 8457315219000651999;
-                                        }
-                                    } else {
-                                        if (*incoming_command).reliable_sequence_number as i32
-                                            >= (*channel).incoming_reliable_sequence_number as i32
-                                        {
-                                            current_block = 7746103178988627676;
-                                            break;
-                                        }
-                                        current_block = 8457315219000651999;
-                                    }
-                                    if let 8457315219000651999 = current_block {
-                                        if (*incoming_command).reliable_sequence_number as u32
-                                            <= reliable_sequence_number
-                                        {
-                                            if ((*incoming_command).reliable_sequence_number as u32)
-                                                < reliable_sequence_number
-                                            {
-                                                current_block = 7746103178988627676;
-                                                break;
-                                            } else {
-                                                current_block = 9207730764507465628;
-                                                break;
-                                            }
-                                        }
-                                    }
-                                    current_command = (*current_command).previous;
-                                }
-                            }
-                        }
-                        _ => {
-                            unreliable_sequence_number =
-                                u16::from_be((*command).send_unreliable.unreliable_sequence_number)
-                                    as u32;
-                            if reliable_sequence_number
-                                == (*channel).incoming_reliable_sequence_number as u32
-                                && unreliable_sequence_number
-                                    <= (*channel).incoming_unreliable_sequence_number as u32
-                            {
-                                current_block = 9207730764507465628;
-                            } else {
-                                current_command =
-                                    (*channel).incoming_unreliable_commands.sentinel.previous;
-                                loop {
-                                    if current_command
-                                        == core::ptr::addr_of_mut!(
-                                            (*channel).incoming_unreliable_commands.sentinel
-                                        )
-                                    {
-                                        current_block = 7746103178988627676;
-                                        break;
-                                    }
-                                    incoming_command = current_command.cast();
-                                    if (*command).header.command as i32
-                                        & ENET_PROTOCOL_COMMAND_MASK as i32
-                                        != ENET_PROTOCOL_COMMAND_SEND_UNSEQUENCED as i32
-                                    {
-                                        if reliable_sequence_number
-                                            >= (*channel).incoming_reliable_sequence_number as u32
-                                        {
-                                            if ((*incoming_command).reliable_sequence_number as i32)
-                                                < (*channel).incoming_reliable_sequence_number
-                                                    as i32
-                                            {
-                                                current_block = 17478428563724192186;
-                                            } else {
-                                                current_block = 11459959175219260272;
-                                            }
-                                        } else {
-                                            if (*incoming_command).reliable_sequence_number as i32
-                                                >= (*channel).incoming_reliable_sequence_number
-                                                    as i32
-                                            {
-                                                current_block = 7746103178988627676;
-                                                break;
-                                            }
-                                            current_block = 11459959175219260272;
-                                        }
-                                        if current_block != 17478428563724192186 {
-                                            if ((*incoming_command).reliable_sequence_number as u32)
-                                                < reliable_sequence_number
-                                            {
-                                                current_block = 7746103178988627676;
-                                                break;
-                                            }
-                                            if (*incoming_command).reliable_sequence_number as u32
-                                                <= reliable_sequence_number
-                                                && (*incoming_command).unreliable_sequence_number
-                                                    as u32
-                                                    <= unreliable_sequence_number
-                                            {
-                                                if ((*incoming_command).unreliable_sequence_number
-                                                    as u32)
-                                                    < unreliable_sequence_number
-                                                {
-                                                    current_block = 7746103178988627676;
-                                                    break;
-                                                } else {
-                                                    current_block = 9207730764507465628;
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                    }
-                                    current_command = (*current_command).previous;
-                                }
-                            }
-                        }
-                    }
-                    match current_block {
-                        9207730764507465628 => {}
-                        _ => {
-                            if (*peer).total_waiting_data >= (*(*peer).host).maximum_waiting_data {
-                                current_block = 15492018734234176694;
-                            } else {
-                                packet = enet_packet_create(data, data_length, flags);
-                                if packet.is_null() {
-                                    current_block = 15492018734234176694;
-                                } else {
-                                    incoming_command =
-                                        enet_malloc(Layout::new::<ENetIncomingCommand>()).cast();
-                                    if incoming_command.is_null() {
-                                        current_block = 15492018734234176694;
-                                    } else {
-                                        (*incoming_command).reliable_sequence_number =
-                                            (*command).header.reliable_sequence_number;
-                                        (*incoming_command).unreliable_sequence_number =
-                                            (unreliable_sequence_number & 0xffff_i32 as u32) as u16;
-                                        (*incoming_command).command = *command;
-                                        (*incoming_command).fragment_count = fragment_count;
-                                        (*incoming_command).fragments_remaining = fragment_count;
-                                        (*incoming_command).packet = packet;
-                                        (*incoming_command).fragments = core::ptr::null_mut();
-                                        if fragment_count > 0_i32 as u32 {
-                                            if fragment_count
-                                                <= PROTOCOL_MAXIMUM_FRAGMENT_COUNT as i32 as u32
-                                            {
-                                                let count = fragment_count
-                                                    .wrapping_add(31_i32 as u32)
-                                                    .wrapping_div(32_i32 as u32)
-                                                    as usize;
-                                                (*incoming_command).fragments = enet_malloc(
-                                                    Layout::array::<u32>(count).unwrap(),
-                                                )
-                                                .cast();
-                                            }
-                                            if ((*incoming_command).fragments).is_null() {
-                                                enet_free(
-                                                    incoming_command.cast(),
-                                                    Layout::new::<ENetIncomingCommand>(),
-                                                );
-                                                current_block = 15492018734234176694;
-                                            } else {
-                                                write_bytes(
-                                                    (*incoming_command).fragments.cast::<u8>(),
-                                                    0,
-                                                    (fragment_count
-                                                        .wrapping_add(31_i32 as u32)
-                                                        .wrapping_div(32_i32 as u32)
-                                                        as usize)
-                                                        .wrapping_mul(
-                                                            ::core::mem::size_of::<u32>(),
-                                                        ),
-                                                );
-                                                current_block = 13321564401369230990;
-                                            }
-                                        } else {
-                                            current_block = 13321564401369230990;
-                                        }
-                                        if current_block != 15492018734234176694 {
-                                            if !packet.is_null() {
-                                                (*packet).reference_count =
-                                                    ((*packet).reference_count).wrapping_add(1);
-                                                (*peer).total_waiting_data = (*peer)
-                                                    .total_waiting_data
-                                                    .wrapping_add((*packet).data_length);
-                                            }
-                                            enet_list_insert(
-                                                (*current_command).next,
-                                                incoming_command.cast(),
-                                            );
-                                            match (*command).header.command as i32
-                                                & ENET_PROTOCOL_COMMAND_MASK as i32
-                                            {
-                                                8 | 6 => {
-                                                    enet_peer_dispatch_incoming_reliable_commands(
-                                                        peer,
-                                                        channel,
-                                                        incoming_command,
-                                                    );
-                                                }
-                                                _ => {
-                                                    enet_peer_dispatch_incoming_unreliable_commands(
-                                                        peer,
-                                                        channel,
-                                                        incoming_command,
-                                                    );
-                                                }
-                                            }
-                                            return incoming_command;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                _ => {
-                    current_block = 9207730764507465628;
-                }
-            },
+        (*peer).queues.find_incoming_command_position(
+            &*channel,
+            &*command,
+            unreliable_sequence_number,
+        )
+    };
+    let Some(current_command) = current_command else {
+        if fragment_count > 0_i32 as u32 {
+            return None;
         }
+        return Some(ENET_LIST_END);
+    };
+    if (*peer).total_waiting_data >= (*(*peer).host).maximum_waiting_data {
+        return None;
+    }
+    let packet = enet_packet_create(data, data_length, flags);
+    if packet.is_null() {
+        return None;
     }
-    if let 9207730764507465628 = current_block {
-        if fragment_count <= 0_i32 as u32 {
-            if !packet.is_null() && (*packet).reference_count == 0_i32 as usize {
+    let mut fragments: *mut u32 = core::ptr::null_mut();
+    if fragment_count > 0_i32 as u32 {
+        let count = fragment_count
+            .wrapping_add(31_i32 as u32)
+            .wrapping_div(32_i32 as u32) as usize;
+        if fragment_count <= PROTOCOL_MAXIMUM_FRAGMENT_COUNT as i32 as u32 {
+            fragments = enet_malloc(Layout::array::<u32>(count).unwrap()).cast();
+        }
+        if fragments.is_null() {
+            if (*packet).reference_count.load(Ordering::Acquire) == 0_i32 as usize {
                 enet_packet_destroy(packet);
             }
-            return addr_of_mut!(DUMMY_COMMAND);
+            return None;
         }
+        write_bytes(fragments, 0, count);
     }
-    if !packet.is_null() && (*packet).reference_count == 0_i32 as usize {
-        enet_packet_destroy(packet);
+    (*packet).reference_count.fetch_add(1, Ordering::Relaxed);
+    (*peer).total_waiting_data = (*peer)
+        .total_waiting_data
+        .wrapping_add((*packet).data_length);
+    let queue = match command_number {
+        8 | 6 => &mut (*channel).incoming_reliable_commands,
+        _ => &mut (*channel).incoming_unreliable_commands,
+    };
+    let incoming_command = (*peer).queues.queue_incoming_command(
+        queue,
+        current_command,
+        ENetIncomingCommand {
+            reliable_sequence_number: (*command).header.reliable_sequence_number,
+            unreliable_sequence_number,
+            command: *command,
+            fragment_count,
+            fragments_remaining: fragment_count,
+            fragments,
+            packet,
+        },
+    );
+    match command_number {
+        8 | 6 => {
+            enet_peer_dispatch_incoming_reliable_commands(peer, channel, incoming_command);
+        }
+        _ => {
+            enet_peer_dispatch_incoming_unreliable_commands(peer, channel, incoming_command);
+        }
     }
-    core::ptr::null_mut()
+    Some(incoming_command)
 }
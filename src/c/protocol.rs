@@ -1,40 +1,50 @@
 use core::{
     alloc::Layout,
+    hash::Hasher,
     ptr::{copy_nonoverlapping, write_bytes},
 };
 
 use crate::{
     consts::{
-        BUFFER_MAXIMUM, HOST_BANDWIDTH_THROTTLE_INTERVAL, PEER_FREE_RELIABLE_WINDOWS,
-        PEER_FREE_UNSEQUENCED_WINDOWS, PEER_PACKET_LOSS_INTERVAL, PEER_PACKET_LOSS_SCALE,
-        PEER_PACKET_THROTTLE_COUNTER, PEER_PACKET_THROTTLE_SCALE, PEER_RELIABLE_WINDOWS,
-        PEER_RELIABLE_WINDOW_SIZE, PEER_UNSEQUENCED_WINDOW_SIZE, PEER_WINDOW_SIZE_SCALE,
-        PROTOCOL_MAXIMUM_CHANNEL_COUNT, PROTOCOL_MAXIMUM_FRAGMENT_COUNT, PROTOCOL_MAXIMUM_MTU,
-        PROTOCOL_MAXIMUM_PEER_ID, PROTOCOL_MAXIMUM_WINDOW_SIZE, PROTOCOL_MINIMUM_CHANNEL_COUNT,
-        PROTOCOL_MINIMUM_MTU, PROTOCOL_MINIMUM_WINDOW_SIZE,
+        BUFFER_MAXIMUM, HOST_BANDWIDTH_THROTTLE_INTERVAL, HOST_CONNECT_COOKIE_SECRET_INTERVAL,
+        PEER_COMPRESSION_BYPASS_INTERVAL, PEER_COMPRESSION_MINIMUM_SAVINGS,
+        PEER_COMPRESSION_PROBE_ATTEMPTS, PEER_COMPRESSION_SAVINGS_SCALE,
+        PEER_FREE_RELIABLE_WINDOWS, PEER_FREE_UNSEQUENCED_WINDOWS, PEER_PACKET_LOSS_INTERVAL,
+        PEER_PACKET_LOSS_SCALE, PEER_PACKET_THROTTLE_COUNTER, PEER_PACKET_THROTTLE_SCALE,
+        PEER_RELIABLE_WINDOWS, PEER_RELIABLE_WINDOW_SIZE, PEER_UNSEQUENCED_WINDOW_SIZE,
+        PEER_WINDOW_SIZE_SCALE, PROTOCOL_MAXIMUM_CHANNEL_COUNT, PROTOCOL_MAXIMUM_FRAGMENT_COUNT,
+        PROTOCOL_MAXIMUM_MTU, PROTOCOL_MAXIMUM_PEER_ID, PROTOCOL_MAXIMUM_WINDOW_SIZE,
+        PROTOCOL_MINIMUM_CHANNEL_COUNT, PROTOCOL_MINIMUM_MTU, PROTOCOL_MINIMUM_WINDOW_SIZE,
     },
-    enet_free, enet_host_bandwidth_throttle, enet_list_clear, enet_list_insert, enet_list_remove,
-    enet_malloc, enet_packet_destroy, enet_peer_disconnect,
-    enet_peer_dispatch_incoming_reliable_commands, enet_peer_dispatch_incoming_unreliable_commands,
-    enet_peer_has_outgoing_commands, enet_peer_on_connect, enet_peer_on_disconnect, enet_peer_ping,
-    enet_peer_queue_acknowledgement, enet_peer_queue_incoming_command,
-    enet_peer_queue_outgoing_command, enet_peer_receive, enet_peer_reset, enet_peer_reset_queues,
-    enet_peer_throttle, enet_time_get, from_raw_parts_or_empty, Address, ENetAcknowledgement,
-    ENetBuffer, ENetChannel, ENetEvent, ENetHost, ENetIncomingCommand, ENetList, ENetListIterator,
-    ENetListNode, ENetOutgoingCommand, ENetPeer, ENetPeerState, PacketReceived, Socket, Vec,
-    ENET_EVENT_TYPE_CONNECT, ENET_EVENT_TYPE_DISCONNECT, ENET_EVENT_TYPE_NONE,
-    ENET_EVENT_TYPE_RECEIVE, ENET_PACKET_FLAG_RELIABLE, ENET_PACKET_FLAG_SENT,
-    ENET_PACKET_FLAG_UNRELIABLE_FRAGMENT, ENET_PACKET_FLAG_UNSEQUENCED,
-    ENET_PEER_FLAG_CONTINUE_SENDING, ENET_PEER_FLAG_NEEDS_DISPATCH,
-    ENET_PEER_STATE_ACKNOWLEDGING_CONNECT, ENET_PEER_STATE_ACKNOWLEDGING_DISCONNECT,
-    ENET_PEER_STATE_CONNECTED, ENET_PEER_STATE_CONNECTING, ENET_PEER_STATE_CONNECTION_PENDING,
+    enet_host_bandwidth_throttle, enet_host_rotate_connect_cookie_secret, enet_malloc,
+    enet_packet_release, enet_peer_acknowledge_send_token, enet_peer_check_low_watermark,
+    enet_peer_disconnect, enet_peer_dispatch_incoming_reliable_commands,
+    enet_peer_dispatch_incoming_unreliable_commands, enet_peer_needs_dispatch,
+    enet_peer_on_connect, enet_peer_on_disconnect, enet_peer_ping, enet_peer_queue_acknowledgement,
+    enet_peer_queue_incoming_command, enet_peer_queue_outgoing_command, enet_peer_receive,
+    enet_peer_reset, enet_peer_reset_keep_data, enet_peer_reset_queues, enet_peer_throttle,
+    enet_time_get, from_raw_parts_or_empty,
+    siphash::SipHasher24,
+    AcceptConnection, Address, ENetAcknowledgement, ENetBuffer, ENetChannel, ENetDisconnectReason,
+    ENetEvent, ENetHost, ENetList, ENetListIterator, ENetOutgoingCommand, ENetOutgoingQueue,
+    ENetPeer, ENetPeerState, Intercept, PacketReceived, Socket, AUTHENTICATION_TAG_MAX,
+    ENET_DISCONNECT_REASON_CONNECT_FAILED, ENET_DISCONNECT_REASON_LOCAL,
+    ENET_DISCONNECT_REASON_REFUSED, ENET_DISCONNECT_REASON_REMOTE, ENET_DISCONNECT_REASON_TIMEOUT,
+    ENET_EVENT_TYPE_CONNECT, ENET_EVENT_TYPE_CONNECT_FAILED, ENET_EVENT_TYPE_DISCONNECT,
+    ENET_EVENT_TYPE_NONE, ENET_EVENT_TYPE_RECEIVE, ENET_EVENT_TYPE_WRITABLE, ENET_LIST_END,
+    ENET_PACKET_FLAG_RELIABLE, ENET_PACKET_FLAG_SENT, ENET_PACKET_FLAG_UNRELIABLE_FRAGMENT,
+    ENET_PACKET_FLAG_UNSEQUENCED, ENET_PEER_FLAG_CONNECT_FAILED, ENET_PEER_FLAG_CONTINUE_SENDING,
+    ENET_PEER_FLAG_NEEDS_DISPATCH, ENET_PEER_STATE_ACKNOWLEDGING_CONNECT,
+    ENET_PEER_STATE_ACKNOWLEDGING_DISCONNECT, ENET_PEER_STATE_CONNECTED,
+    ENET_PEER_STATE_CONNECTING, ENET_PEER_STATE_CONNECTION_PENDING,
     ENET_PEER_STATE_CONNECTION_SUCCEEDED, ENET_PEER_STATE_DISCONNECTED,
     ENET_PEER_STATE_DISCONNECTING, ENET_PEER_STATE_DISCONNECT_LATER, ENET_PEER_STATE_ZOMBIE,
 };
 
 pub(crate) type _ENetProtocolCommand = u32;
 pub(crate) const ENET_PROTOCOL_COMMAND_MASK: _ENetProtocolCommand = 15;
-pub(crate) const ENET_PROTOCOL_COMMAND_COUNT: _ENetProtocolCommand = 13;
+pub(crate) const ENET_PROTOCOL_COMMAND_COUNT: _ENetProtocolCommand = 14;
+pub(crate) const ENET_PROTOCOL_COMMAND_CONNECT_COOKIE: _ENetProtocolCommand = 13;
 pub(crate) const ENET_PROTOCOL_COMMAND_SEND_UNRELIABLE_FRAGMENT: _ENetProtocolCommand = 12;
 pub(crate) const ENET_PROTOCOL_COMMAND_THROTTLE_CONFIGURE: _ENetProtocolCommand = 11;
 pub(crate) const ENET_PROTOCOL_COMMAND_BANDWIDTH_LIMIT: _ENetProtocolCommand = 10;
@@ -48,6 +58,12 @@ pub(crate) const ENET_PROTOCOL_COMMAND_VERIFY_CONNECT: _ENetProtocolCommand = 3;
 pub(crate) const ENET_PROTOCOL_COMMAND_CONNECT: _ENetProtocolCommand = 2;
 pub(crate) const ENET_PROTOCOL_COMMAND_ACKNOWLEDGE: _ENetProtocolCommand = 1;
 pub(crate) const ENET_PROTOCOL_COMMAND_NONE: _ENetProtocolCommand = 0;
+/// Never handled as a command. Marks the start of an encrypted datagram, so that a host without a
+/// cipher key stops reading the datagram there.
+pub(crate) const ENET_PROTOCOL_COMMAND_ENCRYPTED: _ENetProtocolCommand = 15;
+/// Never handled as a command. Marks the authentication tag following the datagram header, so that
+/// a host without an authentication key stops reading the datagram there.
+pub(crate) const ENET_PROTOCOL_COMMAND_AUTHENTICATED: _ENetProtocolCommand = 14;
 pub(crate) type ENetProtocolCommand = _ENetProtocolCommand;
 pub(crate) type _ENetProtocolFlag = u32;
 pub(crate) const ENET_PROTOCOL_HEADER_SESSION_SHIFT: _ENetProtocolFlag = 12;
@@ -97,6 +113,13 @@ pub(crate) struct ENetProtocolConnect {
 }
 #[derive(Copy, Clone)]
 #[repr(C, packed)]
+pub(crate) struct ENetProtocolConnectCookie {
+    pub(crate) header: ENetProtocolCommandHeader,
+    pub(crate) connect_id: u32,
+    pub(crate) cookie: u64,
+}
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
 pub(crate) struct ENetProtocolVerifyConnect {
     pub(crate) header: ENetProtocolCommandHeader,
     pub(crate) outgoing_peer_id: u16,
@@ -184,8 +207,16 @@ pub(crate) union ENetProtocol {
     pub(crate) send_fragment: ENetProtocolSendFragment,
     pub(crate) bandwidth_limit: ENetProtocolBandwidthLimit,
     pub(crate) throttle_configure: ENetProtocolThrottleConfigure,
+    pub(crate) connect_cookie: ENetProtocolConnectCookie,
 }
-static mut COMMAND_SIZES: [usize; 13] = [
+impl ENetProtocol {
+    /// The header every command starts with.
+    pub(crate) fn header(&self) -> ENetProtocolCommandHeader {
+        // SAFETY: every variant starts with the command header.
+        unsafe { self.header }
+    }
+}
+static COMMAND_SIZES: [usize; 14] = [
     0_i32 as usize,
     ::core::mem::size_of::<ENetProtocolAcknowledge>(),
     ::core::mem::size_of::<ENetProtocolConnect>(),
@@ -199,8 +230,9 @@ static mut COMMAND_SIZES: [usize; 13] = [
     ::core::mem::size_of::<ENetProtocolBandwidthLimit>(),
     ::core::mem::size_of::<ENetProtocolThrottleConfigure>(),
     ::core::mem::size_of::<ENetProtocolSendFragment>(),
+    ::core::mem::size_of::<ENetProtocolConnectCookie>(),
 ];
-pub(crate) unsafe fn enet_protocol_command_size(command_number: u8) -> usize {
+pub(crate) fn enet_protocol_command_size(command_number: u8) -> usize {
     COMMAND_SIZES[(command_number as i32 & ENET_PROTOCOL_COMMAND_MASK as i32) as usize]
 }
 unsafe fn enet_protocol_change_state<S: Socket>(
@@ -223,24 +255,31 @@ unsafe fn enet_protocol_dispatch_state<S: Socket>(
     state: ENetPeerState,
 ) {
     enet_protocol_change_state(host, peer, state);
-    if (*peer).flags as i32 & ENET_PEER_FLAG_NEEDS_DISPATCH as i32 == 0 {
-        enet_list_insert(
-            &mut (*host).dispatch_queue.sentinel,
-            core::ptr::addr_of_mut!((*peer).dispatch_list).cast(),
-        );
-        (*peer).flags = ((*peer).flags as i32 | ENET_PEER_FLAG_NEEDS_DISPATCH as i32) as u16;
-    }
+    enet_peer_needs_dispatch(peer);
 }
 unsafe fn enet_protocol_dispatch_incoming_commands<S: Socket>(
     host: *mut ENetHost<S>,
     event: *mut ENetEvent<S>, // SAFETY: should not be null
 ) -> bool {
-    while (*host).dispatch_queue.sentinel.next
-        != core::ptr::addr_of_mut!((*host).dispatch_queue.sentinel)
+    while let Some((type_0, peer_id, send_token)) =
+        (*host).pending_events.assume_init_mut().pop_front()
     {
-        let peer: *mut ENetPeer<S> = enet_list_remove((*host).dispatch_queue.sentinel.next).cast();
+        let peer: *mut ENetPeer<S> = (*host).peers.add(peer_id.index());
+        // The peer may have been reused for a new connection since the event was queued, and a
+        // writable event means nothing to the new connection.
+        if type_0 == ENET_EVENT_TYPE_WRITABLE && (*peer).generation != peer_id.generation() {
+            continue;
+        }
+        (*event).type_0 = type_0;
+        (*event).peer = peer;
+        (*event).peer_generation = peer_id.generation();
+        (*event).send_token = send_token;
+        return true;
+    }
+    while let Some(peer_id) = (*host).dispatch_queue.assume_init_mut().pop_front() {
+        let peer: *mut ENetPeer<S> = (*host).peers.add(peer_id);
         (*peer).flags = ((*peer).flags as i32 & !(ENET_PEER_FLAG_NEEDS_DISPATCH as i32)) as u16;
-        match (*peer).state as u32 {
+        match (*peer).state {
             3 | 4 => {
                 enet_protocol_change_state(host, peer, ENET_PEER_STATE_CONNECTED);
                 (*event).type_0 = ENET_EVENT_TYPE_CONNECT;
@@ -250,16 +289,20 @@ unsafe fn enet_protocol_dispatch_incoming_commands<S: Socket>(
             }
             9 => {
                 (*host).recalculate_bandwidth_limits = 1_i32;
-                (*event).type_0 = ENET_EVENT_TYPE_DISCONNECT;
+                (*event).type_0 =
+                    if (*peer).flags as i32 & ENET_PEER_FLAG_CONNECT_FAILED as i32 != 0 {
+                        ENET_EVENT_TYPE_CONNECT_FAILED
+                    } else {
+                        ENET_EVENT_TYPE_DISCONNECT
+                    };
                 (*event).peer = peer;
                 (*event).data = (*peer).event_data;
-                enet_peer_reset(peer);
+                (*event).disconnect_reason = (*peer).disconnect_reason;
+                enet_peer_reset_keep_data(peer);
                 return true;
             }
             5 => {
-                if (*peer).dispatched_commands.sentinel.next
-                    == core::ptr::addr_of_mut!((*peer).dispatched_commands.sentinel)
-                {
+                if (*peer).queues.dispatched_commands.is_empty() {
                     continue;
                 }
                 (*event).packet = enet_peer_receive(peer, &mut (*event).channel_id);
@@ -268,15 +311,8 @@ unsafe fn enet_protocol_dispatch_incoming_commands<S: Socket>(
                 }
                 (*event).type_0 = ENET_EVENT_TYPE_RECEIVE;
                 (*event).peer = peer;
-                if (*peer).dispatched_commands.sentinel.next
-                    != core::ptr::addr_of_mut!((*peer).dispatched_commands.sentinel)
-                {
-                    (*peer).flags =
-                        ((*peer).flags as i32 | ENET_PEER_FLAG_NEEDS_DISPATCH as i32) as u16;
-                    enet_list_insert(
-                        &mut (*host).dispatch_queue.sentinel,
-                        core::ptr::addr_of_mut!((*peer).dispatch_list).cast::<u8>(),
-                    );
+                if !(*peer).queues.dispatched_commands.is_empty() {
+                    enet_peer_needs_dispatch(peer);
                 }
                 return true;
             }
@@ -312,6 +348,7 @@ unsafe fn enet_protocol_notify_disconnect<S: Socket>(
     host: *mut ENetHost<S>,
     peer: *mut ENetPeer<S>,
     event: *mut ENetEvent<S>,
+    reason: ENetDisconnectReason,
 ) {
     if (*peer).state >= ENET_PEER_STATE_CONNECTION_PENDING as i32 as u32 {
         (*host).recalculate_bandwidth_limits = 1_i32;
@@ -321,119 +358,51 @@ unsafe fn enet_protocol_notify_disconnect<S: Socket>(
     {
         enet_peer_reset(peer);
     } else if !event.is_null() {
-        (*event).type_0 = ENET_EVENT_TYPE_DISCONNECT;
+        (*event).type_0 = if (*peer).state == ENET_PEER_STATE_CONNECTING as i32 as u32 {
+            ENET_EVENT_TYPE_CONNECT_FAILED
+        } else {
+            ENET_EVENT_TYPE_DISCONNECT
+        };
         (*event).peer = peer;
         (*event).data = 0_i32 as u32;
-        enet_peer_reset(peer);
+        (*event).disconnect_reason = reason;
+        enet_peer_reset_keep_data(peer);
     } else {
+        if (*peer).state == ENET_PEER_STATE_CONNECTING as i32 as u32 {
+            (*peer).flags = ((*peer).flags as i32 | ENET_PEER_FLAG_CONNECT_FAILED as i32) as u16;
+        }
         (*peer).event_data = 0_i32 as u32;
+        (*peer).disconnect_reason = reason;
         enet_protocol_dispatch_state(host, peer, ENET_PEER_STATE_ZOMBIE);
     };
 }
-unsafe fn enet_protocol_remove_sent_unreliable_commands<S: Socket>(
-    peer: *mut ENetPeer<S>,
-    sent_unreliable_commands: *mut ENetList,
-) {
-    let mut outgoing_command: *mut ENetOutgoingCommand;
-    if (*sent_unreliable_commands).sentinel.next
-        == core::ptr::addr_of_mut!((*sent_unreliable_commands).sentinel)
-    {
+unsafe fn enet_protocol_remove_sent_unreliable_commands<S: Socket>(peer: *mut ENetPeer<S>) {
+    if (*peer).queues.sent_unreliable_commands.is_empty() {
         return;
     }
-    loop {
-        outgoing_command = (*sent_unreliable_commands)
-            .sentinel
-            .next
-            .cast::<u8>()
-            .cast::<ENetOutgoingCommand>();
-        enet_list_remove(&mut (*outgoing_command).outgoing_command_list);
-        if !((*outgoing_command).packet).is_null() {
-            (*(*outgoing_command).packet).reference_count =
-                ((*(*outgoing_command).packet).reference_count).wrapping_sub(1);
-            if (*(*outgoing_command).packet).reference_count == 0_i32 as usize {
-                (*(*outgoing_command).packet).flags |= ENET_PACKET_FLAG_SENT as i32 as u32;
-                enet_packet_destroy((*outgoing_command).packet);
-            }
-        }
-        enet_free(
-            outgoing_command.cast(),
-            Layout::new::<ENetOutgoingCommand>(),
-        );
-        if (*sent_unreliable_commands).sentinel.next
-            == core::ptr::addr_of_mut!((*sent_unreliable_commands).sentinel)
-        {
-            break;
-        }
+    while let Some(outgoing_command) = (*peer)
+        .queues
+        .pop_outgoing_command(ENetOutgoingQueue::SentUnreliable)
+    {
+        enet_packet_release(outgoing_command.packet, ENET_PACKET_FLAG_SENT as i32 as u32);
     }
     if (*peer).state == ENET_PEER_STATE_DISCONNECT_LATER as i32 as u32
-        && enet_peer_has_outgoing_commands(peer) == 0
+        && !(*peer).queues.has_outgoing_commands()
     {
         enet_peer_disconnect(peer, (*peer).event_data);
     }
 }
-unsafe fn enet_protocol_find_sent_reliable_command(
-    list: *mut ENetList,
-    reliable_sequence_number: u16,
-    channel_id: u8,
-) -> *mut ENetOutgoingCommand {
-    let mut current_command: ENetListIterator;
-    current_command = (*list).sentinel.next;
-    while current_command != core::ptr::addr_of_mut!((*list).sentinel) {
-        let outgoing_command: *mut ENetOutgoingCommand = current_command.cast();
-        if (*outgoing_command).command.header.command as i32
-            & ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE as i32
-            != 0
-        {
-            if ((*outgoing_command).send_attempts as i32) < 1_i32 {
-                break;
-            }
-            if (*outgoing_command).reliable_sequence_number as i32
-                == reliable_sequence_number as i32
-                && (*outgoing_command).command.header.channel_id as i32 == channel_id as i32
-            {
-                return outgoing_command;
-            }
-        }
-        current_command = (*current_command).next;
-    }
-    core::ptr::null_mut()
-}
 unsafe fn enet_protocol_remove_sent_reliable_command<S: Socket>(
     peer: *mut ENetPeer<S>,
     reliable_sequence_number: u16,
     channel_id: u8,
 ) -> ENetProtocolCommand {
-    let mut outgoing_command: *mut ENetOutgoingCommand = core::ptr::null_mut();
-    let mut current_command: ENetListIterator;
-    let mut was_sent: i32 = 1_i32;
-    current_command = (*peer).sent_reliable_commands.sentinel.next;
-    while current_command != core::ptr::addr_of_mut!((*peer).sent_reliable_commands.sentinel) {
-        outgoing_command = current_command.cast();
-        if (*outgoing_command).reliable_sequence_number as i32 == reliable_sequence_number as i32
-            && (*outgoing_command).command.header.channel_id as i32 == channel_id as i32
-        {
-            break;
-        }
-        current_command = (*current_command).next;
-    }
-    if current_command == core::ptr::addr_of_mut!((*peer).sent_reliable_commands.sentinel) {
-        outgoing_command = enet_protocol_find_sent_reliable_command(
-            &mut (*peer).outgoing_commands,
-            reliable_sequence_number,
-            channel_id,
-        );
-        if outgoing_command.is_null() {
-            outgoing_command = enet_protocol_find_sent_reliable_command(
-                &mut (*peer).outgoing_send_reliable_commands,
-                reliable_sequence_number,
-                channel_id,
-            );
-        }
-        was_sent = 0_i32;
-    }
-    if outgoing_command.is_null() {
+    let Some((outgoing_command, was_sent)) = (*peer)
+        .queues
+        .remove_sent_reliable_command(reliable_sequence_number, channel_id)
+    else {
         return ENET_PROTOCOL_COMMAND_NONE;
-    }
+    };
     if (channel_id as usize) < (*peer).channel_count {
         let channel: *mut ENetChannel = ((*peer).channels).offset(channel_id as isize);
         let reliable_window: u16 =
@@ -448,45 +417,175 @@ unsafe fn enet_protocol_remove_sent_reliable_command<S: Socket>(
             }
         }
     }
-    let command_number = ((*outgoing_command).command.header.command as i32
+    if outgoing_command.send_token != 0_i32 as u64 {
+        enet_peer_acknowledge_send_token(peer, outgoing_command.send_token);
+    }
+    let command_number = (outgoing_command.command.header.command as i32
         & ENET_PROTOCOL_COMMAND_MASK as i32) as ENetProtocolCommand;
-    enet_list_remove(&mut (*outgoing_command).outgoing_command_list);
-    if !((*outgoing_command).packet).is_null() {
-        if was_sent != 0 {
+    if !(outgoing_command.packet).is_null() {
+        if was_sent {
             (*peer).reliable_data_in_transit = (*peer)
                 .reliable_data_in_transit
-                .wrapping_sub((*outgoing_command).fragment_length as u32);
-        }
-        (*(*outgoing_command).packet).reference_count =
-            ((*(*outgoing_command).packet).reference_count).wrapping_sub(1);
-        if (*(*outgoing_command).packet).reference_count == 0_i32 as usize {
-            (*(*outgoing_command).packet).flags |= ENET_PACKET_FLAG_SENT as i32 as u32;
-            enet_packet_destroy((*outgoing_command).packet);
+                .wrapping_sub(outgoing_command.fragment_length as u32);
         }
+        enet_packet_release(outgoing_command.packet, ENET_PACKET_FLAG_SENT as i32 as u32);
     }
-    enet_free(
-        outgoing_command.cast(),
-        Layout::new::<ENetOutgoingCommand>(),
-    );
-    if (*peer).sent_reliable_commands.sentinel.next
-        == core::ptr::addr_of_mut!((*peer).sent_reliable_commands.sentinel)
+    if let Some(outgoing_command) = (*peer)
+        .queues
+        .front_outgoing_command(ENetOutgoingQueue::SentReliable)
     {
-        return command_number;
-    }
-    outgoing_command = (*peer)
-        .sent_reliable_commands
-        .sentinel
-        .next
-        .cast::<u8>()
-        .cast::<ENetOutgoingCommand>();
-    (*peer).next_timeout =
-        ((*outgoing_command).sent_time).wrapping_add((*outgoing_command).round_trip_timeout);
+        (*peer).next_timeout =
+            (outgoing_command.sent_time).wrapping_add(outgoing_command.round_trip_timeout);
+    }
     command_number
 }
+/// Answers a connect command with a single command, without allocating a peer. The remote peer
+/// is still connecting, so it accepts the datagram without checking the session.
+unsafe fn enet_protocol_send_connect_response<S: Socket>(
+    host: *mut ENetHost<S>,
+    command: *const ENetProtocol,
+    response: &[u8],
+) {
+    let mut datagram = [0_u8; 6 + ::core::mem::size_of::<ENetProtocolConnectCookie>()];
+    let mut datagram_length: usize = 2;
+    datagram[..2].copy_from_slice(&(*command).connect.outgoing_peer_id.to_ne_bytes());
+    if (*host).checksum.assume_init_ref().is_some() {
+        datagram[2..6].copy_from_slice(&(*command).connect.connect_id.to_ne_bytes());
+        datagram_length = 6;
+    }
+    datagram[datagram_length..datagram_length + response.len()].copy_from_slice(response);
+    datagram_length += response.len();
+    if let Some(checksum_fn) = (*host).checksum.assume_init_ref() {
+        let checksum = checksum_fn(&[&datagram[..datagram_length]]);
+        datagram[2..6].copy_from_slice(&checksum.to_ne_bytes());
+    }
+    // The response is best effort, if it is lost the remote peer retries or times out.
+    if let Ok(sent_length) = (*host).socket.assume_init_mut().send(
+        (*host)
+            .received_address
+            .assume_init_ref()
+            .as_ref()
+            .cloned()
+            .unwrap(),
+        &datagram[..datagram_length],
+    ) {
+        (*host).total_sent_data = (*host).total_sent_data.wrapping_add(sent_length as u32);
+        (*host).total_sent_packets = ((*host).total_sent_packets).wrapping_add(1);
+    }
+}
+/// Answers a connect command with a disconnect command, without allocating a peer.
+unsafe fn enet_protocol_send_connect_rejection<S: Socket>(
+    host: *mut ENetHost<S>,
+    command: *const ENetProtocol,
+    data: u32,
+) {
+    let mut rejection = [0_u8; ::core::mem::size_of::<ENetProtocolDisconnect>()];
+    rejection[0] = (ENET_PROTOCOL_COMMAND_DISCONNECT as i32
+        | ENET_PROTOCOL_COMMAND_FLAG_UNSEQUENCED as i32) as u8;
+    rejection[1] = 0xff_i32 as u8;
+    rejection[4..8].copy_from_slice(&data.to_be_bytes());
+    enet_protocol_send_connect_response(host, command, &rejection);
+}
+/// Computes the cookie for a connect command from the received address, keyed with a secret.
+unsafe fn enet_protocol_connect_cookie<S: Socket>(
+    host: *mut ENetHost<S>,
+    command: *const ENetProtocol,
+    secret: [u64; 2],
+) -> u64 {
+    let mut hasher = SipHasher24::new_with_keys(secret[0], secret[1]);
+    (*host)
+        .received_address
+        .assume_init_ref()
+        .as_ref()
+        .unwrap()
+        .hash_address(&mut hasher);
+    hasher.write_u32((*command).connect.connect_id);
+    hasher.write_u16((*command).connect.outgoing_peer_id);
+    hasher.finish()
+}
+/// Answers a connect command with a connect cookie, without allocating a peer. The remote peer
+/// repeats the connect command along with the cookie.
+unsafe fn enet_protocol_send_connect_cookie<S: Socket>(
+    host: *mut ENetHost<S>,
+    command: *const ENetProtocol,
+) {
+    let cookie = enet_protocol_connect_cookie(host, command, (*host).connect_cookie_keys[0]);
+    let mut response = [0_u8; ::core::mem::size_of::<ENetProtocolConnectCookie>()];
+    response[0] = ENET_PROTOCOL_COMMAND_CONNECT_COOKIE as i32 as u8;
+    response[1] = 0xff_i32 as u8;
+    response[4..8].copy_from_slice(&(*command).connect.connect_id.to_ne_bytes());
+    response[8..16].copy_from_slice(&cookie.to_ne_bytes());
+    enet_protocol_send_connect_response(host, command, &response);
+}
+unsafe fn enet_protocol_verify_connect_cookie<S: Socket>(
+    host: *mut ENetHost<S>,
+    command: *const ENetProtocol,
+    cookie: u64,
+) -> bool {
+    (*host)
+        .connect_cookie_keys
+        .iter()
+        .any(|secret| enet_protocol_connect_cookie(host, command, *secret) == cookie)
+}
+/// Repeats the connect command of a connecting peer along with the cookie the foreign host
+/// answered it with, in a datagram of its own.
+unsafe fn enet_protocol_handle_connect_cookie<S: Socket>(
+    host: *mut ENetHost<S>,
+    peer: *mut ENetPeer<S>,
+    command: *const ENetProtocol,
+) -> i32 {
+    if (*peer).state != ENET_PEER_STATE_CONNECTING as i32 as u32
+        || (*command).connect_cookie.connect_id != (*peer).connect_id
+    {
+        return 0_i32;
+    }
+    let Some(connect_command) = (*peer).queues.find_connect_command() else {
+        return 0_i32;
+    };
+    let mut datagram = [0_u8;
+        8 + ::core::mem::size_of::<ENetProtocolConnectCookie>()
+            + ::core::mem::size_of::<ENetProtocolConnect>()];
+    let mut datagram_length: usize = ::core::mem::size_of::<ENetProtocolHeader>();
+    datagram[..2].copy_from_slice(
+        &((PROTOCOL_MAXIMUM_PEER_ID as i32 | ENET_PROTOCOL_HEADER_FLAG_SENT_TIME as i32) as u16)
+            .to_be_bytes(),
+    );
+    datagram[2..4]
+        .copy_from_slice(&(((*host).service_time & 0xffff_i32 as u32) as u16).to_be_bytes());
+    if (*host).checksum.assume_init_ref().is_some() {
+        datagram_length += ::core::mem::size_of::<u32>();
+    }
+    // The cookie command is echoed back as it was received.
+    copy_nonoverlapping(
+        command.cast::<u8>(),
+        datagram.as_mut_ptr().add(datagram_length),
+        ::core::mem::size_of::<ENetProtocolConnectCookie>(),
+    );
+    datagram_length += ::core::mem::size_of::<ENetProtocolConnectCookie>();
+    copy_nonoverlapping(
+        core::ptr::addr_of!(connect_command.command).cast::<u8>(),
+        datagram.as_mut_ptr().add(datagram_length),
+        ::core::mem::size_of::<ENetProtocolConnect>(),
+    );
+    datagram_length += ::core::mem::size_of::<ENetProtocolConnect>();
+    if let Some(checksum_fn) = (*host).checksum.assume_init_ref() {
+        let checksum = checksum_fn(&[&datagram[..datagram_length]]);
+        datagram[4..8].copy_from_slice(&checksum.to_ne_bytes());
+    }
+    if let Ok(sent_length) = (*host).socket.assume_init_mut().send(
+        (*peer).address.assume_init_ref().as_ref().cloned().unwrap(),
+        &datagram[..datagram_length],
+    ) {
+        (*host).total_sent_data = (*host).total_sent_data.wrapping_add(sent_length as u32);
+        (*host).total_sent_packets = ((*host).total_sent_packets).wrapping_add(1);
+    }
+    0_i32
+}
 unsafe fn enet_protocol_handle_connect<S: Socket>(
     host: *mut ENetHost<S>,
     mut _header: *mut ENetProtocolHeader,
     command: *mut ENetProtocol,
+    connect_cookie: Option<u64>,
 ) -> *mut ENetPeer<S> {
     let mut incoming_session_id: u8;
     let mut outgoing_session_id: u8;
@@ -510,8 +609,12 @@ unsafe fn enet_protocol_handle_connect<S: Socket>(
     {
         return core::ptr::null_mut();
     }
+    let mut active_peers: usize = 0_i32 as usize;
     current_peer = (*host).peers;
     while current_peer < ((*host).peers).add((*host).peer_count) {
+        if (*current_peer).state != ENET_PEER_STATE_DISCONNECTED as i32 as u32 {
+            active_peers = active_peers.wrapping_add(1);
+        }
         if (*current_peer).state == ENET_PEER_STATE_DISCONNECTED as i32 as u32 {
             if peer.is_null() {
                 peer = current_peer;
@@ -538,6 +641,38 @@ unsafe fn enet_protocol_handle_connect<S: Socket>(
         }
         current_peer = current_peer.offset(1);
     }
+    if (*host).connect_cookies {
+        if (*host)
+            .service_time
+            .wrapping_sub((*host).connect_cookie_epoch)
+            >= HOST_CONNECT_COOKIE_SECRET_INTERVAL
+        {
+            enet_host_rotate_connect_cookie_secret(host);
+        }
+        if !connect_cookie
+            .is_some_and(|cookie| enet_protocol_verify_connect_cookie(host, command, cookie))
+        {
+            enet_protocol_send_connect_cookie(host, command);
+            return core::ptr::null_mut();
+        }
+    }
+    if let Some(accept_connection) = (*host).accept_connection.assume_init_mut() {
+        match accept_connection(
+            (*host).received_address.assume_init_ref().as_ref().unwrap(),
+            channel_count,
+            u32::from_be((*command).connect.data),
+            active_peers,
+        ) {
+            AcceptConnection::Accept => {}
+            AcceptConnection::Discard => {
+                return core::ptr::null_mut();
+            }
+            AcceptConnection::Reject(data) => {
+                enet_protocol_send_connect_rejection(host, command, data);
+                return core::ptr::null_mut();
+            }
+        }
+    }
     if peer.is_null() || duplicate_peers >= (*host).duplicate_peers {
         return core::ptr::null_mut();
     }
@@ -548,6 +683,8 @@ unsafe fn enet_protocol_handle_connect<S: Socket>(
     (*peer).channel_count = channel_count;
     (*peer).state = ENET_PEER_STATE_ACKNOWLEDGING_CONNECT;
     (*peer).connect_id = (*command).connect.connect_id;
+    *(*peer).data.assume_init_mut() = None;
+    (*peer).generation = (*peer).generation.wrapping_add(1);
     *(*peer).address.assume_init_mut() = Some(
         (*host)
             .received_address
@@ -600,10 +737,12 @@ unsafe fn enet_protocol_handle_connect<S: Socket>(
         (*channel).outgoing_unreliable_sequence_number = 0_i32 as u16;
         (*channel).incoming_reliable_sequence_number = 0_i32 as u16;
         (*channel).incoming_unreliable_sequence_number = 0_i32 as u16;
-        enet_list_clear(&mut (*channel).incoming_reliable_commands);
-        enet_list_clear(&mut (*channel).incoming_unreliable_commands);
+        (*channel).incoming_reliable_commands = ENetList::new();
+        (*channel).incoming_unreliable_commands = ENetList::new();
         (*channel).used_reliable_windows = 0_i32 as u16;
         write_bytes(((*channel).reliable_windows).as_mut_ptr(), 0, 16);
+        (*channel).priority =
+            (*host).channel_priorities[channel.offset_from((*peer).channels) as usize];
         channel = channel.offset(1);
     }
     mtu = u32::from_be((*command).connect.mtu);
@@ -703,7 +842,7 @@ unsafe fn enet_protocol_handle_send_reliable<S: Socket>(
     {
         return -1_i32;
     }
-    if (enet_peer_queue_incoming_command(
+    if enet_peer_queue_incoming_command(
         peer,
         command,
         command
@@ -712,8 +851,8 @@ unsafe fn enet_protocol_handle_send_reliable<S: Socket>(
         data_length,
         ENET_PACKET_FLAG_RELIABLE as i32 as u32,
         0_i32 as u32,
-    ))
-    .is_null()
+    )
+    .is_none()
     {
         return -1_i32;
     }
@@ -762,7 +901,7 @@ unsafe fn enet_protocol_handle_send_unsequenced<S: Socket>(
     {
         return 0_i32;
     }
-    if (enet_peer_queue_incoming_command(
+    if enet_peer_queue_incoming_command(
         peer,
         command,
         command
@@ -771,8 +910,8 @@ unsafe fn enet_protocol_handle_send_unsequenced<S: Socket>(
         data_length,
         ENET_PACKET_FLAG_UNSEQUENCED as i32 as u32,
         0_i32 as u32,
-    ))
-    .is_null()
+    )
+    .is_none()
     {
         return -1_i32;
     }
@@ -800,7 +939,7 @@ unsafe fn enet_protocol_handle_send_unreliable<S: Socket>(
     {
         return -1_i32;
     }
-    if (enet_peer_queue_incoming_command(
+    if enet_peer_queue_incoming_command(
         peer,
         command,
         command
@@ -809,8 +948,8 @@ unsafe fn enet_protocol_handle_send_unreliable<S: Socket>(
         data_length,
         0_i32 as u32,
         0_i32 as u32,
-    ))
-    .is_null()
+    )
+    .is_none()
     {
         return -1_i32;
     }
@@ -825,7 +964,7 @@ unsafe fn enet_protocol_handle_send_fragment<S: Socket>(
     let mut fragment_length: u32;
     let mut start_window: u16;
     let mut current_command: ENetListIterator;
-    let mut start_command: *mut ENetIncomingCommand = core::ptr::null_mut();
+    let mut start_command: ENetListIterator = ENET_LIST_END;
     if (*command).header.channel_id as usize >= (*peer).channel_count
         || (*peer).state != ENET_PEER_STATE_CONNECTED as i32 as u32
             && (*peer).state != ENET_PEER_STATE_DISCONNECT_LATER as i32 as u32
@@ -868,85 +1007,78 @@ unsafe fn enet_protocol_handle_send_fragment<S: Socket>(
     {
         return -1_i32;
     }
-    let mut current_block_23: u64;
-    current_command = (*channel).incoming_reliable_commands.sentinel.previous;
-    while current_command != core::ptr::addr_of_mut!((*channel).incoming_reliable_commands.sentinel)
-    {
-        let incoming_command: *mut ENetIncomingCommand = current_command.cast();
+    let incoming_commands = &(*peer).queues.incoming_command_arena;
+    let queue = &(*channel).incoming_reliable_commands;
+    current_command = incoming_commands.previous(queue, ENET_LIST_END);
+    while current_command != ENET_LIST_END {
+        let incoming_command = &incoming_commands[current_command];
         if start_sequence_number >= (*channel).incoming_reliable_sequence_number as u32 {
-            if ((*incoming_command).reliable_sequence_number as i32)
+            if (incoming_command.reliable_sequence_number as i32)
                 < (*channel).incoming_reliable_sequence_number as i32
             {
-                current_block_23 = 13056961889198038528;
-            } else {
-                current_block_23 = 12147880666119273379;
-            }
-        } else {
-            if (*incoming_command).reliable_sequence_number as i32
-                >= (*channel).incoming_reliable_sequence_number as i32
-            {
-                break;
+                current_command = incoming_commands.previous(queue, current_command);
+                continue;
             }
-            current_block_23 = 12147880666119273379;
+        } else if incoming_command.reliable_sequence_number as i32
+            >= (*channel).incoming_reliable_sequence_number as i32
+        {
+            break;
         }
-        if let 12147880666119273379 = current_block_23 {
-            if (*incoming_command).reliable_sequence_number as u32 <= start_sequence_number {
-                if ((*incoming_command).reliable_sequence_number as u32) < start_sequence_number {
-                    break;
-                }
-                if (*incoming_command).command.header.command as i32
-                    & ENET_PROTOCOL_COMMAND_MASK as i32
-                    != ENET_PROTOCOL_COMMAND_SEND_FRAGMENT as i32
-                    || total_length as usize != (*(*incoming_command).packet).data_length
-                    || fragment_count != (*incoming_command).fragment_count
-                {
-                    return -1_i32;
-                }
-                start_command = incoming_command;
+        if incoming_command.reliable_sequence_number as u32 <= start_sequence_number {
+            if (incoming_command.reliable_sequence_number as u32) < start_sequence_number {
                 break;
             }
+            if incoming_command.command.header.command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32
+                != ENET_PROTOCOL_COMMAND_SEND_FRAGMENT as i32
+                || total_length as usize != (*incoming_command.packet).data_length
+                || fragment_count != incoming_command.fragment_count
+            {
+                return -1_i32;
+            }
+            start_command = current_command;
+            break;
         }
-        current_command = (*current_command).previous;
+        current_command = incoming_commands.previous(queue, current_command);
     }
-    if start_command.is_null() {
+    if start_command == ENET_LIST_END {
         let mut host_command: ENetProtocol = *command;
         host_command.header.reliable_sequence_number = start_sequence_number as u16;
-        start_command = enet_peer_queue_incoming_command(
+        let Some(incoming_command) = enet_peer_queue_incoming_command(
             peer,
             &host_command,
             core::ptr::null(),
             total_length as usize,
             ENET_PACKET_FLAG_RELIABLE as i32 as u32,
             fragment_count,
-        );
-        if start_command.is_null() {
+        ) else {
             return -1_i32;
-        }
+        };
+        start_command = incoming_command;
     }
-    if *((*start_command).fragments).offset(fragment_number.wrapping_div(32_i32 as u32) as isize)
+    let start_command = &mut (*peer).queues.incoming_command_arena[start_command];
+    if *(start_command.fragments).offset(fragment_number.wrapping_div(32_i32 as u32) as isize)
         & (1_i32 << fragment_number.wrapping_rem(32_i32 as u32)) as u32
         == 0_i32 as u32
     {
-        (*start_command).fragments_remaining =
-            ((*start_command).fragments_remaining).wrapping_sub(1);
-        let fresh32 = ((*start_command).fragments)
-            .offset(fragment_number.wrapping_div(32_i32 as u32) as isize);
+        start_command.fragments_remaining = (start_command.fragments_remaining).wrapping_sub(1);
+        let fresh32 =
+            (start_command.fragments).offset(fragment_number.wrapping_div(32_i32 as u32) as isize);
         *fresh32 |= (1_i32 << fragment_number.wrapping_rem(32_i32 as u32)) as u32;
         if fragment_offset.wrapping_add(fragment_length) as usize
-            > (*(*start_command).packet).data_length
+            > (*start_command.packet).data_length
         {
-            fragment_length = ((*(*start_command).packet).data_length)
-                .wrapping_sub(fragment_offset as usize) as u32;
+            fragment_length =
+                ((*start_command.packet).data_length).wrapping_sub(fragment_offset as usize) as u32;
         }
         copy_nonoverlapping(
             (command as *mut u8)
                 .offset(::core::mem::size_of::<ENetProtocolSendFragment>() as u64 as isize)
                 .cast_const(),
-            ((*(*start_command).packet).data).offset(fragment_offset as isize),
+            ((*start_command.packet).data).offset(fragment_offset as isize),
             fragment_length as usize,
         );
-        if (*start_command).fragments_remaining <= 0_i32 as u32 {
-            enet_peer_dispatch_incoming_reliable_commands(peer, channel, core::ptr::null_mut());
+        if start_command.fragments_remaining <= 0_i32 as u32 {
+            enet_peer_dispatch_incoming_reliable_commands(peer, channel, ENET_LIST_END);
         }
     }
     0_i32
@@ -960,7 +1092,7 @@ unsafe fn enet_protocol_handle_send_unreliable_fragment<S: Socket>(
     let mut fragment_length: u32;
     let mut reliable_window: u16;
     let mut current_command: ENetListIterator;
-    let mut start_command: *mut ENetIncomingCommand = core::ptr::null_mut();
+    let mut start_command: ENetListIterator = ENET_LIST_END;
     if (*command).header.channel_id as usize >= (*peer).channel_count
         || (*peer).state != ENET_PEER_STATE_CONNECTED as i32 as u32
             && (*peer).state != ENET_PEER_STATE_DISCONNECT_LATER as i32 as u32
@@ -1008,89 +1140,81 @@ unsafe fn enet_protocol_handle_send_unreliable_fragment<S: Socket>(
     {
         return -1_i32;
     }
-    let mut current_block_26: u64;
-    current_command = (*channel).incoming_unreliable_commands.sentinel.previous;
-    while current_command
-        != core::ptr::addr_of_mut!((*channel).incoming_unreliable_commands.sentinel)
-    {
-        let incoming_command: *mut ENetIncomingCommand = current_command.cast();
+    let incoming_commands = &(*peer).queues.incoming_command_arena;
+    let queue = &(*channel).incoming_unreliable_commands;
+    current_command = incoming_commands.previous(queue, ENET_LIST_END);
+    while current_command != ENET_LIST_END {
+        let incoming_command = &incoming_commands[current_command];
         if reliable_sequence_number >= (*channel).incoming_reliable_sequence_number as u32 {
-            if ((*incoming_command).reliable_sequence_number as i32)
+            if (incoming_command.reliable_sequence_number as i32)
                 < (*channel).incoming_reliable_sequence_number as i32
             {
-                current_block_26 = 8457315219000651999;
-            } else {
-                current_block_26 = 1109700713171191020;
-            }
-        } else {
-            if (*incoming_command).reliable_sequence_number as i32
-                >= (*channel).incoming_reliable_sequence_number as i32
-            {
-                break;
+                current_command = incoming_commands.previous(queue, current_command);
+                continue;
             }
-            current_block_26 = 1109700713171191020;
+        } else if incoming_command.reliable_sequence_number as i32
+            >= (*channel).incoming_reliable_sequence_number as i32
+        {
+            break;
+        }
+        if (incoming_command.reliable_sequence_number as u32) < reliable_sequence_number {
+            break;
         }
-        if let 1109700713171191020 = current_block_26 {
-            if ((*incoming_command).reliable_sequence_number as u32) < reliable_sequence_number {
+        if incoming_command.reliable_sequence_number as u32 <= reliable_sequence_number
+            && incoming_command.unreliable_sequence_number as u32 <= start_sequence_number
+        {
+            if (incoming_command.unreliable_sequence_number as u32) < start_sequence_number {
                 break;
             }
-            if (*incoming_command).reliable_sequence_number as u32 <= reliable_sequence_number
-                && (*incoming_command).unreliable_sequence_number as u32 <= start_sequence_number
+            if incoming_command.command.header.command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32
+                != ENET_PROTOCOL_COMMAND_SEND_UNRELIABLE_FRAGMENT as i32
+                || total_length as usize != (*incoming_command.packet).data_length
+                || fragment_count != incoming_command.fragment_count
             {
-                if ((*incoming_command).unreliable_sequence_number as u32) < start_sequence_number {
-                    break;
-                }
-                if (*incoming_command).command.header.command as i32
-                    & ENET_PROTOCOL_COMMAND_MASK as i32
-                    != ENET_PROTOCOL_COMMAND_SEND_UNRELIABLE_FRAGMENT as i32
-                    || total_length as usize != (*(*incoming_command).packet).data_length
-                    || fragment_count != (*incoming_command).fragment_count
-                {
-                    return -1_i32;
-                }
-                start_command = incoming_command;
-                break;
+                return -1_i32;
             }
+            start_command = current_command;
+            break;
         }
-        current_command = (*current_command).previous;
+        current_command = incoming_commands.previous(queue, current_command);
     }
-    if start_command.is_null() {
-        start_command = enet_peer_queue_incoming_command(
+    if start_command == ENET_LIST_END {
+        let Some(incoming_command) = enet_peer_queue_incoming_command(
             peer,
             command,
             core::ptr::null(),
             total_length as usize,
             ENET_PACKET_FLAG_UNRELIABLE_FRAGMENT as i32 as u32,
             fragment_count,
-        );
-        if start_command.is_null() {
+        ) else {
             return -1_i32;
-        }
+        };
+        start_command = incoming_command;
     }
-    if *((*start_command).fragments).offset(fragment_number.wrapping_div(32_i32 as u32) as isize)
+    let start_command = &mut (*peer).queues.incoming_command_arena[start_command];
+    if *(start_command.fragments).offset(fragment_number.wrapping_div(32_i32 as u32) as isize)
         & (1_i32 << fragment_number.wrapping_rem(32_i32 as u32)) as u32
         == 0_i32 as u32
     {
-        (*start_command).fragments_remaining =
-            ((*start_command).fragments_remaining).wrapping_sub(1);
-        let fresh33 = ((*start_command).fragments)
-            .offset(fragment_number.wrapping_div(32_i32 as u32) as isize);
+        start_command.fragments_remaining = (start_command.fragments_remaining).wrapping_sub(1);
+        let fresh33 =
+            (start_command.fragments).offset(fragment_number.wrapping_div(32_i32 as u32) as isize);
         *fresh33 |= (1_i32 << fragment_number.wrapping_rem(32_i32 as u32)) as u32;
         if fragment_offset.wrapping_add(fragment_length) as usize
-            > (*(*start_command).packet).data_length
+            > (*start_command.packet).data_length
         {
-            fragment_length = ((*(*start_command).packet).data_length)
-                .wrapping_sub(fragment_offset as usize) as u32;
+            fragment_length =
+                ((*start_command.packet).data_length).wrapping_sub(fragment_offset as usize) as u32;
         }
         copy_nonoverlapping(
             (command as *mut u8)
                 .offset(::core::mem::size_of::<ENetProtocolSendFragment>() as u64 as isize)
                 .cast_const(),
-            ((*(*start_command).packet).data).offset(fragment_offset as isize),
+            ((*start_command.packet).data).offset(fragment_offset as isize),
             fragment_length as usize,
         );
-        if (*start_command).fragments_remaining <= 0_i32 as u32 {
-            enet_peer_dispatch_incoming_unreliable_commands(peer, channel, core::ptr::null_mut());
+        if start_command.fragments_remaining <= 0_i32 as u32 {
+            enet_peer_dispatch_incoming_unreliable_commands(peer, channel, ENET_LIST_END);
         }
     }
     0_i32
@@ -1182,6 +1306,12 @@ unsafe fn enet_protocol_handle_disconnect<S: Socket>(
     {
         return 0_i32;
     }
+    if (*peer).state == ENET_PEER_STATE_CONNECTING as i32 as u32 {
+        (*peer).flags = ((*peer).flags as i32 | ENET_PEER_FLAG_CONNECT_FAILED as i32) as u16;
+        (*peer).disconnect_reason = ENET_DISCONNECT_REASON_REFUSED;
+    } else {
+        (*peer).disconnect_reason = ENET_DISCONNECT_REASON_REMOTE;
+    }
     enet_peer_reset_queues(peer);
     if (*peer).state == ENET_PEER_STATE_CONNECTION_SUCCEEDED as i32 as u32
         || (*peer).state == ENET_PEER_STATE_DISCONNECTING as i32 as u32
@@ -1316,10 +1446,10 @@ unsafe fn enet_protocol_handle_acknowledge<S: Socket>(
             if command_number as u32 != ENET_PROTOCOL_COMMAND_DISCONNECT as i32 as u32 {
                 return -1_i32;
             }
-            enet_protocol_notify_disconnect(host, peer, event);
+            enet_protocol_notify_disconnect(host, peer, event, ENET_DISCONNECT_REASON_LOCAL);
         }
         6 => {
-            if enet_peer_has_outgoing_commands(peer) == 0 {
+            if !(*peer).queues.has_outgoing_commands() {
                 enet_peer_disconnect(peer, (*peer).event_data);
             }
         }
@@ -1350,6 +1480,8 @@ unsafe fn enet_protocol_handle_verify_connect<S: Socket>(
         || (*command).verify_connect.connect_id != (*peer).connect_id
     {
         (*peer).event_data = 0_i32 as u32;
+        (*peer).flags = ((*peer).flags as i32 | ENET_PEER_FLAG_CONNECT_FAILED as i32) as u16;
+        (*peer).disconnect_reason = ENET_DISCONNECT_REASON_CONNECT_FAILED;
         enet_protocol_dispatch_state(host, peer, ENET_PEER_STATE_ZOMBIE);
         return -1_i32;
     }
@@ -1393,10 +1525,11 @@ unsafe fn enet_protocol_handle_incoming_commands<S: Socket>(
     let mut current_data: *mut u8;
     let mut header_size: usize;
     let mut peer_id: u16;
+    let mut connect_cookie: Option<u64> = None;
     if (*host).received_data_length < 2_usize {
         return false;
     }
-    let header: *mut ENetProtocolHeader = (*host).received_data.cast();
+    let mut header: *mut ENetProtocolHeader = (*host).received_data.cast();
     peer_id = u16::from_be((*header).peer_id);
     let session_id = ((peer_id as i32 & ENET_PROTOCOL_HEADER_SESSION_MASK as i32)
         >> ENET_PROTOCOL_HEADER_SESSION_SHIFT as i32) as u8;
@@ -1439,6 +1572,107 @@ unsafe fn enet_protocol_handle_incoming_commands<S: Socket>(
             return false;
         }
     }
+    if !peer.is_null() {
+        if let Some(auth_key) = (*peer).auth_key.assume_init_ref() {
+            let Some(authenticator) = (*host).authenticator.assume_init_mut() else {
+                return false;
+            };
+            let tag_size = authenticator.tag_size();
+            if header_size < (*host).received_data_length
+                && *((*host).received_data).add(header_size) as i32
+                    == ENET_PROTOCOL_COMMAND_AUTHENTICATED as i32
+            {
+                if header_size.wrapping_add(1).wrapping_add(tag_size) > (*host).received_data_length
+                {
+                    (*host).total_unauthenticated_packets =
+                        (*host).total_unauthenticated_packets.wrapping_add(1);
+                    return false;
+                }
+                let in_buffers = [
+                    super::from_raw_parts_or_empty((*host).received_data, header_size),
+                    super::from_raw_parts_or_empty(
+                        ((*host).received_data).add(header_size + 1 + tag_size),
+                        ((*host).received_data_length).wrapping_sub(header_size + 1 + tag_size),
+                    ),
+                ];
+                let mut tag = [0; AUTHENTICATION_TAG_MAX];
+                authenticator.authenticate(auth_key, &in_buffers, &mut tag[..tag_size]);
+                let received_tag = super::from_raw_parts_or_empty(
+                    ((*host).received_data).add(header_size + 1),
+                    tag_size,
+                );
+                // Compare every byte, so the time taken does not reveal how much of the tag was
+                // correct.
+                let difference = tag[..tag_size]
+                    .iter()
+                    .zip(received_tag)
+                    .fold(0_u8, |difference, (a, b)| difference | (a ^ b));
+                if difference != 0 {
+                    (*host).total_unauthenticated_packets =
+                        (*host).total_unauthenticated_packets.wrapping_add(1);
+                    return false;
+                }
+                core::ptr::copy(
+                    (*host).received_data,
+                    ((*host).received_data).add(1 + tag_size),
+                    header_size,
+                );
+                (*host).received_data = ((*host).received_data).add(1 + tag_size);
+                (*host).received_data_length =
+                    ((*host).received_data_length).wrapping_sub(1 + tag_size);
+                header = (*host).received_data.cast();
+            } else {
+                // Accepting datagrams without a tag until the first tagged one arrives would let
+                // anyone spoofing the peer's address inject commands in the meantime.
+                (*host).total_unauthenticated_packets =
+                    (*host).total_unauthenticated_packets.wrapping_add(1);
+                return false;
+            }
+        }
+        if let Some(cipher_key) = (*peer).cipher_key.assume_init_ref() {
+            if header_size >= (*host).received_data_length {
+                return false;
+            }
+            if *((*host).received_data).add(header_size) as i32
+                == ENET_PROTOCOL_COMMAND_ENCRYPTED as i32
+            {
+                let Some(cipher) = (*host).cipher.assume_init_mut() else {
+                    return false;
+                };
+                let in_data = super::from_raw_parts_or_empty(
+                    ((*host).received_data).add(header_size + 1),
+                    ((*host).received_data_length).wrapping_sub(header_size + 1),
+                );
+                let out = super::from_raw_parts_or_empty_mut(
+                    ((*host).packet_data[0_i32 as usize])
+                        .as_mut_ptr()
+                        .add(header_size),
+                    ::core::mem::size_of::<[u8; 4096]>().wrapping_sub(header_size),
+                );
+                let decrypted_size = cipher.decrypt(
+                    cipher_key,
+                    &mut (*peer).cipher_replay_window,
+                    super::from_raw_parts_or_empty((*host).received_data, header_size),
+                    in_data,
+                    out,
+                );
+                if decrypted_size == 0_i32 as usize {
+                    return false;
+                }
+                copy_nonoverlapping(
+                    header as *const u8,
+                    ((*host).packet_data[0_i32 as usize]).as_mut_ptr(),
+                    header_size,
+                );
+                (*host).received_data = ((*host).packet_data[0_i32 as usize]).as_mut_ptr();
+                (*host).received_data_length = header_size.wrapping_add(decrypted_size);
+            } else {
+                // Like unauthenticated datagrams, unencrypted datagrams could be injected by
+                // anyone spoofing the peer's address.
+                return false;
+            }
+        }
+    }
     if flags as i32 & ENET_PROTOCOL_HEADER_FLAG_COMPRESSED as i32 != 0 {
         let Some(compressor) = (*host).compressor.assume_init_mut() else {
             return false;
@@ -1534,7 +1768,10 @@ unsafe fn enet_protocol_handle_incoming_commands<S: Socket>(
             break;
         }
         current_data = current_data.add(command_size);
-        if peer.is_null() && command_number as i32 != ENET_PROTOCOL_COMMAND_CONNECT as i32 {
+        if peer.is_null()
+            && command_number as i32 != ENET_PROTOCOL_COMMAND_CONNECT as i32
+            && command_number as i32 != ENET_PROTOCOL_COMMAND_CONNECT_COOKIE as i32
+        {
             break;
         }
         (*command).header.reliable_sequence_number =
@@ -1549,7 +1786,7 @@ unsafe fn enet_protocol_handle_incoming_commands<S: Socket>(
                 if !peer.is_null() {
                     break;
                 }
-                peer = enet_protocol_handle_connect(host, header, command);
+                peer = enet_protocol_handle_connect(host, header, command, connect_cookie);
                 if peer.is_null() {
                     break;
                 }
@@ -1613,6 +1850,13 @@ unsafe fn enet_protocol_handle_incoming_commands<S: Socket>(
                     break;
                 }
             }
+            13 => {
+                if peer.is_null() {
+                    connect_cookie = Some((*command).connect_cookie.cookie);
+                } else if enet_protocol_handle_connect_cookie(host, peer, command) != 0 {
+                    break;
+                }
+            }
             _ => {
                 break;
             }
@@ -1653,37 +1897,59 @@ unsafe fn enet_protocol_receive_incoming_commands<S: Socket>(
     let mut packets: i32;
     packets = 0_i32;
     while packets < 256_i32 {
-        let mut buffer: ENetBuffer = ENetBuffer {
-            data: core::ptr::null_mut(),
-            data_length: 0,
-        };
-        buffer.data = ((*host).packet_data[0_i32 as usize]).as_mut_ptr();
-        buffer.data_length = ::core::mem::size_of::<[u8; PROTOCOL_MAXIMUM_MTU]>();
-        let received_length = match (*host)
-            .socket
-            .assume_init_mut()
-            .receive(&mut *buffer.data.cast::<[u8; 4096]>())
+        if (*host).received_batch_index >= (*host).received_batch_count {
+            let received_batch = (*host).received_batch.assume_init_mut();
+            let received_count = match (*host)
+                .socket
+                .assume_init_mut()
+                .receive_batch((*host).receive_buffers.assume_init_mut(), received_batch)
+            {
+                Ok(0) => {
+                    return Ok(false);
+                }
+                Ok(received_count) => received_count.min(received_batch.len()),
+                Err(err) => {
+                    return Err(err);
+                }
+            };
+            (*host).received_batch_index = 0_i32 as usize;
+            (*host).received_batch_count = received_count;
+        }
+        let received_index = (*host).received_batch_index;
+        (*host).received_batch_index = received_index.wrapping_add(1);
+        let received_length = match (*host).received_batch.assume_init_mut()[received_index].take()
         {
-            Ok(Some((received_address, PacketReceived::Complete(received_length)))) => {
+            Some((received_address, PacketReceived::Complete(received_length))) => {
                 *(*host).received_address.assume_init_mut() = Some(received_address);
                 received_length
             }
-            Ok(Some((_, PacketReceived::Partial))) => {
+            Some((_, PacketReceived::Partial)) | None => {
                 continue;
             }
-            Ok(None) => {
-                return Ok(false);
-            }
-            Err(err) => {
-                return Err(err);
-            }
         };
-        (*host).received_data = ((*host).packet_data[0_i32 as usize]).as_mut_ptr();
+        (*host).received_data =
+            (*host).receive_buffers.assume_init_mut()[received_index].as_mut_ptr();
         (*host).received_data_length = received_length;
         (*host).total_received_data = (*host)
             .total_received_data
             .wrapping_add(received_length as u32);
         (*host).total_received_packets = ((*host).total_received_packets).wrapping_add(1);
+        if let Some(intercept) = (*host).intercept.assume_init_mut() {
+            match intercept(
+                (*host).socket.assume_init_mut(),
+                (*host).received_address.assume_init_ref().as_ref().unwrap(),
+                from_raw_parts_or_empty((*host).received_data, (*host).received_data_length),
+            ) {
+                Intercept::Consume => {
+                    packets += 1;
+                    continue;
+                }
+                Intercept::Error(err) => {
+                    return Err(err);
+                }
+                Intercept::Ignore => {}
+            }
+        }
         if enet_protocol_handle_incoming_commands(host, event) {
             return Ok(true);
         }
@@ -1697,11 +1963,8 @@ unsafe fn enet_protocol_send_acknowledgements<S: Socket>(
 ) {
     let mut command: *mut ENetProtocol = ((*host).commands).as_mut_ptr().add((*host).command_count);
     let mut buffer: *mut ENetBuffer = ((*host).buffers).as_mut_ptr().add((*host).buffer_count);
-    let mut acknowledgement: *mut ENetAcknowledgement;
-    let mut current_acknowledgement: ENetListIterator;
     let mut reliable_sequence_number: u16;
-    current_acknowledgement = (*peer).acknowledgements.sentinel.next;
-    while current_acknowledgement != core::ptr::addr_of_mut!((*peer).acknowledgements.sentinel) {
+    while !(*peer).queues.acknowledgements.is_empty() {
         if command
             >= ((*host).commands).as_mut_ptr().offset(
                 (::core::mem::size_of::<[ENetProtocol; 32]>() as u64)
@@ -1720,29 +1983,26 @@ unsafe fn enet_protocol_send_acknowledgements<S: Socket>(
             (*peer).flags = ((*peer).flags as i32 | ENET_PEER_FLAG_CONTINUE_SENDING as i32) as u16;
             break;
         } else {
-            acknowledgement = current_acknowledgement.cast();
-            current_acknowledgement = (*current_acknowledgement).next;
+            let acknowledgement: ENetAcknowledgement =
+                (*peer).queues.pop_acknowledgement().unwrap();
             (*buffer).data = command.cast();
             (*buffer).data_length = ::core::mem::size_of::<ENetProtocolAcknowledge>();
             (*host).packet_size = (*host).packet_size.wrapping_add((*buffer).data_length);
-            reliable_sequence_number = (*acknowledgement)
+            reliable_sequence_number = acknowledgement
                 .command
                 .header
                 .reliable_sequence_number
                 .to_be();
             (*command).header.command = ENET_PROTOCOL_COMMAND_ACKNOWLEDGE as i32 as u8;
-            (*command).header.channel_id = (*acknowledgement).command.header.channel_id;
+            (*command).header.channel_id = acknowledgement.command.header.channel_id;
             (*command).header.reliable_sequence_number = reliable_sequence_number;
             (*command).acknowledge.received_reliable_sequence_number = reliable_sequence_number;
-            (*command).acknowledge.received_sent_time =
-                ((*acknowledgement).sent_time as u16).to_be();
-            if (*acknowledgement).command.header.command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32
+            (*command).acknowledge.received_sent_time = (acknowledgement.sent_time as u16).to_be();
+            if acknowledgement.command.header.command as i32 & ENET_PROTOCOL_COMMAND_MASK as i32
                 == ENET_PROTOCOL_COMMAND_DISCONNECT as i32
             {
                 enet_protocol_dispatch_state(host, peer, ENET_PEER_STATE_ZOMBIE);
             }
-            enet_list_remove(&mut (*acknowledgement).acknowledgement_list);
-            enet_free(acknowledgement.cast(), Layout::new::<ENetAcknowledgement>());
             command = command.offset(1);
             buffer = buffer.offset(1);
         }
@@ -1750,19 +2010,33 @@ unsafe fn enet_protocol_send_acknowledgements<S: Socket>(
     (*host).command_count = command.offset_from(((*host).commands).as_mut_ptr()) as i64 as usize;
     (*host).buffer_count = buffer.offset_from(((*host).buffers).as_mut_ptr()) as i64 as usize;
 }
+fn enet_protocol_connect_timed_out<S: Socket>(peer: &ENetPeer<S>, service_time: u32) -> bool {
+    peer.state == ENET_PEER_STATE_CONNECTING as i32 as u32
+        && peer.connect_timeout != 0_i32 as u32
+        && (if service_time.wrapping_sub(peer.connect_time) >= 86400000_i32 as u32 {
+            (peer.connect_time).wrapping_sub(service_time)
+        } else {
+            service_time.wrapping_sub(peer.connect_time)
+        }) >= peer.connect_timeout
+}
 unsafe fn enet_protocol_check_timeouts<S: Socket>(
     host: *mut ENetHost<S>,
     peer: *mut ENetPeer<S>,
     event: *mut ENetEvent<S>,
 ) -> i32 {
-    let mut outgoing_command: *mut ENetOutgoingCommand;
+    if enet_protocol_connect_timed_out(&*peer, (*host).service_time) {
+        enet_protocol_notify_disconnect(host, peer, event, ENET_DISCONNECT_REASON_TIMEOUT);
+        return 1_i32;
+    }
     let mut current_command: ENetListIterator;
-    current_command = (*peer).sent_reliable_commands.sentinel.next;
-    let insert_position = (*peer).outgoing_commands.sentinel.next;
-    let insert_send_reliable_position = (*peer).outgoing_send_reliable_commands.sentinel.next;
-    while current_command != core::ptr::addr_of_mut!((*peer).sent_reliable_commands.sentinel) {
-        outgoing_command = current_command.cast();
-        current_command = (*current_command).next;
+    current_command = (*peer).queues.sent_reliable_commands.begin();
+    let insert_position = (*peer).queues.outgoing_commands.begin();
+    let insert_send_reliable_position = (*peer).queues.outgoing_send_reliable_commands.begin();
+    while current_command != ENET_LIST_END {
+        let timed_out_command = current_command;
+        let outgoing_command: *mut ENetOutgoingCommand =
+            &mut (*peer).queues.outgoing_command_arena[current_command];
+        current_command = (*peer).queues.outgoing_command_arena.next(current_command);
         if (if ((*host).service_time).wrapping_sub((*outgoing_command).sent_time)
             >= 86400000_i32 as u32
         {
@@ -1795,9 +2069,11 @@ unsafe fn enet_protocol_check_timeouts<S: Socket>(
                         ((*peer).earliest_timeout).wrapping_sub((*host).service_time)
                     } else {
                         ((*host).service_time).wrapping_sub((*peer).earliest_timeout)
-                    }) >= (*peer).timeout_minimum)
+                    }) >= (*peer).timeout_minimum
+                || (*peer).state == ENET_PEER_STATE_CONNECTING as i32 as u32
+                    && (*outgoing_command).send_attempts as u32 > (*peer).connect_retry_limit)
         {
-            enet_protocol_notify_disconnect(host, peer, event);
+            enet_protocol_notify_disconnect(host, peer, event, ENET_DISCONNECT_REASON_TIMEOUT);
             return 1_i32;
         }
         (*peer).packets_lost = ((*peer).packets_lost).wrapping_add(1);
@@ -1808,23 +2084,26 @@ unsafe fn enet_protocol_check_timeouts<S: Socket>(
             (*peer).reliable_data_in_transit = (*peer)
                 .reliable_data_in_transit
                 .wrapping_sub((*outgoing_command).fragment_length as u32);
-            enet_list_insert(
+            (*peer).queues.transfer_outgoing_command(
+                ENetOutgoingQueue::SentReliable,
+                timed_out_command,
+                ENetOutgoingQueue::OutgoingSendReliable,
                 insert_send_reliable_position,
-                enet_list_remove(&mut (*outgoing_command).outgoing_command_list),
             );
         } else {
-            enet_list_insert(
+            (*peer).queues.transfer_outgoing_command(
+                ENetOutgoingQueue::SentReliable,
+                timed_out_command,
+                ENetOutgoingQueue::Outgoing,
                 insert_position,
-                enet_list_remove(&mut (*outgoing_command).outgoing_command_list),
             );
         }
-        if current_command == (*peer).sent_reliable_commands.sentinel.next
-            && ((*peer).sent_reliable_commands.sentinel.next
-                != core::ptr::addr_of_mut!((*peer).sent_reliable_commands.sentinel))
+        if current_command == (*peer).queues.sent_reliable_commands.begin()
+            && !(*peer).queues.sent_reliable_commands.is_empty()
         {
-            outgoing_command = current_command.cast();
-            (*peer).next_timeout = ((*outgoing_command).sent_time)
-                .wrapping_add((*outgoing_command).round_trip_timeout);
+            let outgoing_command = &(*peer).queues.outgoing_command_arena[current_command];
+            (*peer).next_timeout =
+                (outgoing_command.sent_time).wrapping_add(outgoing_command.round_trip_timeout);
         }
     }
     0_i32
@@ -1832,11 +2111,12 @@ unsafe fn enet_protocol_check_timeouts<S: Socket>(
 unsafe fn enet_protocol_check_outgoing_commands<S: Socket>(
     host: *mut ENetHost<S>,
     peer: *mut ENetPeer<S>,
-    sent_unreliable_commands: *mut ENetList,
 ) -> i32 {
     let mut command: *mut ENetProtocol = ((*host).commands).as_mut_ptr().add((*host).command_count);
     let mut buffer: *mut ENetBuffer = ((*host).buffers).as_mut_ptr().add((*host).buffer_count);
-    let mut outgoing_command: *mut ENetOutgoingCommand = core::ptr::null_mut();
+    let mut outgoing_command: *mut ENetOutgoingCommand;
+    let mut position: ENetListIterator;
+    let mut queue: ENetOutgoingQueue;
     let mut current_command: ENetListIterator;
     let mut current_send_reliable_command: ENetListIterator;
     let mut channel: *mut ENetChannel = core::ptr::null_mut();
@@ -1844,34 +2124,47 @@ unsafe fn enet_protocol_check_outgoing_commands<S: Socket>(
     let mut command_size: usize;
     let mut window_wrap: i32 = 0_i32;
     let mut can_ping: i32 = 1_i32;
-    current_command = (*peer).outgoing_commands.sentinel.next;
-    current_send_reliable_command = (*peer).outgoing_send_reliable_commands.sentinel.next;
-    let mut current_block_55: u64;
+    current_command = (*peer).queues.outgoing_commands.begin();
+    current_send_reliable_command = (*peer).queues.outgoing_send_reliable_commands.begin();
     loop {
-        if current_command != core::ptr::addr_of_mut!((*peer).outgoing_commands.sentinel) {
-            outgoing_command = current_command.cast();
-            if current_send_reliable_command
-                != core::ptr::addr_of_mut!((*peer).outgoing_send_reliable_commands.sentinel)
-                && ((*(current_send_reliable_command.cast::<ENetOutgoingCommand>())).queue_time)
-                    .wrapping_sub((*outgoing_command).queue_time)
-                    >= 86400000_i32 as u32
-            {
-                current_block_55 = 13678975718891345113;
-            } else {
-                current_command = (*current_command).next;
-                current_block_55 = 1856101646708284338;
-            }
+        if current_command != ENET_LIST_END
+            && (current_send_reliable_command == ENET_LIST_END
+                || (*peer).queues.outgoing_command_arena[current_command].virtual_finish_time
+                    < (*peer).queues.outgoing_command_arena[current_send_reliable_command]
+                        .virtual_finish_time
+                || (*peer).queues.outgoing_command_arena[current_command].virtual_finish_time
+                    == (*peer).queues.outgoing_command_arena[current_send_reliable_command]
+                        .virtual_finish_time
+                    && ((*peer).queues.outgoing_command_arena[current_send_reliable_command]
+                        .queue_time)
+                        .wrapping_sub(
+                            (*peer).queues.outgoing_command_arena[current_command].queue_time,
+                        )
+                        < 86400000_i32 as u32)
+        {
+            position = current_command;
+            queue = ENetOutgoingQueue::Outgoing;
+            current_command = (*peer).queues.outgoing_command_arena.next(current_command);
+        } else if current_send_reliable_command != ENET_LIST_END {
+            position = current_send_reliable_command;
+            queue = ENetOutgoingQueue::OutgoingSendReliable;
+            current_send_reliable_command = (*peer)
+                .queues
+                .outgoing_command_arena
+                .next(current_send_reliable_command);
         } else {
-            if current_send_reliable_command
-                == core::ptr::addr_of_mut!((*peer).outgoing_send_reliable_commands.sentinel)
-            {
-                break;
-            }
-            current_block_55 = 13678975718891345113;
+            break;
         }
-        if let 13678975718891345113 = current_block_55 {
-            outgoing_command = current_send_reliable_command.cast();
-            current_send_reliable_command = (*current_send_reliable_command).next;
+        outgoing_command = &mut (*peer).queues.outgoing_command_arena[position];
+        if let Some(expire_time) = (*outgoing_command).expire_time {
+            if expire_time.wrapping_sub((*host).service_time) >= 86400000_i32 as u32 {
+                enet_packet_release((*outgoing_command).packet, 0);
+                (*peer).queued_data = (*peer)
+                    .queued_data
+                    .wrapping_sub((*outgoing_command).fragment_length as usize);
+                (*peer).queues.remove_outgoing_command(queue, position);
+                continue;
+            }
         }
         if (*outgoing_command).command.header.command as i32
             & ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE as i32
@@ -1908,8 +2201,7 @@ unsafe fn enet_protocol_check_outgoing_commands<S: Socket>(
                             != 0)
                 {
                     window_wrap = 1_i32;
-                    current_send_reliable_command =
-                        &mut (*peer).outgoing_send_reliable_commands.sentinel;
+                    current_send_reliable_command = ENET_LIST_END;
                     continue;
                 }
             }
@@ -1925,8 +2217,7 @@ unsafe fn enet_protocol_check_outgoing_commands<S: Socket>(
                         (*peer).mtu
                     })
                 {
-                    current_send_reliable_command =
-                        &mut (*peer).outgoing_send_reliable_commands.sentinel;
+                    current_send_reliable_command = ENET_LIST_END;
                     continue;
                 }
             }
@@ -1966,6 +2257,11 @@ unsafe fn enet_protocol_check_outgoing_commands<S: Socket>(
                     (*channel).reliable_windows[reliable_window as usize] =
                         ((*channel).reliable_windows[reliable_window as usize]).wrapping_add(1);
                 }
+                if ((*outgoing_command).send_attempts as i32) < 1_i32 {
+                    (*peer).queued_data = (*peer)
+                        .queued_data
+                        .wrapping_sub((*outgoing_command).fragment_length as usize);
+                }
                 (*outgoing_command).send_attempts =
                     ((*outgoing_command).send_attempts).wrapping_add(1);
                 if (*outgoing_command).round_trip_timeout == 0_i32 as u32 {
@@ -1974,16 +2270,17 @@ unsafe fn enet_protocol_check_outgoing_commands<S: Socket>(
                             (4_i32 as u32).wrapping_mul((*peer).round_trip_time_variance),
                         );
                 }
-                if (*peer).sent_reliable_commands.sentinel.next
-                    == core::ptr::addr_of_mut!((*peer).sent_reliable_commands.sentinel)
-                {
+                if (*peer).queues.sent_reliable_commands.is_empty() {
                     (*peer).next_timeout =
                         ((*host).service_time).wrapping_add((*outgoing_command).round_trip_timeout);
                 }
-                enet_list_insert(
-                    &mut (*peer).sent_reliable_commands.sentinel,
-                    enet_list_remove(&mut (*outgoing_command).outgoing_command_list),
+                (*peer).queues.transfer_outgoing_command(
+                    queue,
+                    position,
+                    ENetOutgoingQueue::SentReliable,
+                    ENET_LIST_END,
                 );
+                outgoing_command = &mut (*peer).queues.outgoing_command_arena[position];
                 (*outgoing_command).sent_time = (*host).service_time;
                 (*host).header_flags = ((*host).header_flags as i32
                     | ENET_PROTOCOL_HEADER_FLAG_SENT_TIME as i32)
@@ -2007,22 +2304,17 @@ unsafe fn enet_protocol_check_outgoing_commands<S: Socket>(
                         let unreliable_sequence_number: u16 =
                             (*outgoing_command).unreliable_sequence_number;
                         loop {
-                            (*(*outgoing_command).packet).reference_count =
-                                ((*(*outgoing_command).packet).reference_count).wrapping_sub(1);
-                            if (*(*outgoing_command).packet).reference_count == 0_i32 as usize {
-                                enet_packet_destroy((*outgoing_command).packet);
-                            }
-                            enet_list_remove(&mut (*outgoing_command).outgoing_command_list);
-                            enet_free(
-                                outgoing_command.cast(),
-                                Layout::new::<ENetOutgoingCommand>(),
-                            );
-                            if current_command
-                                == core::ptr::addr_of_mut!((*peer).outgoing_commands.sentinel)
-                            {
+                            enet_packet_release((*outgoing_command).packet, 0);
+                            (*peer).queued_data = (*peer)
+                                .queued_data
+                                .wrapping_sub((*outgoing_command).fragment_length as usize);
+                            (*peer).queues.remove_outgoing_command(queue, position);
+                            if current_command == ENET_LIST_END {
                                 break;
                             }
-                            outgoing_command = current_command.cast();
+                            position = current_command;
+                            queue = ENetOutgoingQueue::Outgoing;
+                            outgoing_command = &mut (*peer).queues.outgoing_command_arena[position];
                             if (*outgoing_command).reliable_sequence_number as i32
                                 != reliable_sequence_number as i32
                                 || (*outgoing_command).unreliable_sequence_number as i32
@@ -2030,23 +2322,30 @@ unsafe fn enet_protocol_check_outgoing_commands<S: Socket>(
                             {
                                 break;
                             }
-                            current_command = (*current_command).next;
+                            current_command =
+                                (*peer).queues.outgoing_command_arena.next(current_command);
                         }
                         continue;
                     }
                 }
-                enet_list_remove(&mut (*outgoing_command).outgoing_command_list);
                 if !((*outgoing_command).packet).is_null() {
-                    enet_list_insert(
-                        &mut (*sent_unreliable_commands).sentinel,
-                        outgoing_command.cast(),
+                    (*peer).queued_data = (*peer)
+                        .queued_data
+                        .wrapping_sub((*outgoing_command).fragment_length as usize);
+                    (*peer).queues.transfer_outgoing_command(
+                        queue,
+                        position,
+                        ENetOutgoingQueue::SentUnreliable,
+                        ENET_LIST_END,
                     );
+                    outgoing_command = &mut (*peer).queues.outgoing_command_arena[position];
                 }
             }
             (*buffer).data = command.cast();
             (*buffer).data_length = command_size;
             (*host).packet_size = ((*host).packet_size).wrapping_add((*buffer).data_length);
             *command = (*outgoing_command).command;
+            (*peer).queues.sent_outgoing_command(position);
             if !((*outgoing_command).packet).is_null() {
                 buffer = buffer.offset(1);
                 (*buffer).data = ((*(*outgoing_command).packet).data)
@@ -2059,10 +2358,7 @@ unsafe fn enet_protocol_check_outgoing_commands<S: Socket>(
                 & ENET_PROTOCOL_COMMAND_FLAG_ACKNOWLEDGE as i32
                 == 0
             {
-                enet_free(
-                    outgoing_command.cast(),
-                    Layout::new::<ENetOutgoingCommand>(),
-                );
+                (*peer).queues.remove_outgoing_command(queue, position);
             }
             (*peer).packets_sent = ((*peer).packets_sent).wrapping_add(1);
             command = command.offset(1);
@@ -2072,29 +2368,81 @@ unsafe fn enet_protocol_check_outgoing_commands<S: Socket>(
     (*host).command_count = command.offset_from(((*host).commands).as_mut_ptr()) as i64 as usize;
     (*host).buffer_count = buffer.offset_from(((*host).buffers).as_mut_ptr()) as i64 as usize;
     if (*peer).state == ENET_PEER_STATE_DISCONNECT_LATER as i32 as u32
-        && enet_peer_has_outgoing_commands(peer) == 0
-        && (*sent_unreliable_commands).sentinel.next
-            == core::ptr::addr_of_mut!((*sent_unreliable_commands).sentinel)
+        && !(*peer).queues.has_outgoing_commands()
+        && (*peer).queues.sent_unreliable_commands.is_empty()
     {
         enet_peer_disconnect(peer, (*peer).event_data);
     }
+    enet_peer_check_low_watermark(peer);
     can_ping
 }
+fn enet_protocol_compression_bypassed<S: Socket>(
+    peer: &mut ENetPeer<S>,
+    service_time: u32,
+) -> bool {
+    if !peer.compression {
+        return true;
+    }
+    if peer.compression_bypassed
+        && service_time.wrapping_sub(peer.compression_bypass_epoch)
+            >= PEER_COMPRESSION_BYPASS_INTERVAL
+    {
+        peer.compression_bypassed = false;
+    }
+    peer.compression_bypassed
+}
+unsafe fn enet_protocol_record_compression<S: Socket>(
+    host: *mut ENetHost<S>,
+    peer: *mut ENetPeer<S>,
+    bytes_in: usize,
+    bytes_out: usize,
+) {
+    let won = bytes_out < bytes_in;
+    for statistics in [
+        &mut (*host).compression_statistics,
+        &mut (*peer).compression_statistics,
+    ] {
+        statistics.attempts = statistics.attempts.wrapping_add(1);
+        statistics.wins = statistics.wins.wrapping_add(u32::from(won));
+        statistics.bytes_in = statistics.bytes_in.wrapping_add(bytes_in as u32);
+        statistics.bytes_out = statistics.bytes_out.wrapping_add(bytes_out as u32);
+    }
+    if !(*host).adaptive_compression {
+        return;
+    }
+    (*peer).compression_probe_attempts = (*peer).compression_probe_attempts.wrapping_add(1);
+    (*peer).compression_probe_bytes_in = (*peer)
+        .compression_probe_bytes_in
+        .wrapping_add(bytes_in as u32);
+    (*peer).compression_probe_bytes_out = (*peer)
+        .compression_probe_bytes_out
+        .wrapping_add(bytes_out as u32);
+    if (*peer).compression_probe_attempts < PEER_COMPRESSION_PROBE_ATTEMPTS {
+        return;
+    }
+    let savings = (*peer)
+        .compression_probe_bytes_in
+        .wrapping_sub((*peer).compression_probe_bytes_out);
+    if savings.wrapping_mul(PEER_COMPRESSION_SAVINGS_SCALE)
+        < (*peer)
+            .compression_probe_bytes_in
+            .wrapping_mul(PEER_COMPRESSION_MINIMUM_SAVINGS)
+    {
+        (*peer).compression_bypassed = true;
+        (*peer).compression_bypass_epoch = (*host).service_time;
+    }
+    (*peer).compression_probe_attempts = 0_i32 as u32;
+    (*peer).compression_probe_bytes_in = 0_i32 as u32;
+    (*peer).compression_probe_bytes_out = 0_i32 as u32;
+}
 unsafe fn enet_protocol_send_outgoing_commands<S: Socket>(
     host: *mut ENetHost<S>,
     event: *mut ENetEvent<S>,
     check_for_timeouts: i32,
 ) -> Result<bool, S::Error> {
-    let mut header_data: [u8; 8] = [0; 8];
+    let mut header_data: [u8; 9 + AUTHENTICATION_TAG_MAX] = [0; 9 + AUTHENTICATION_TAG_MAX];
     let header: *mut ENetProtocolHeader = header_data.as_mut_ptr().cast();
     let mut should_compress: usize;
-    let mut sent_unreliable_commands: ENetList = ENetList {
-        sentinel: ENetListNode {
-            next: core::ptr::null_mut(),
-            previous: core::ptr::null_mut(),
-        },
-    };
-    enet_list_clear(&mut sent_unreliable_commands);
     let mut send_pass: i32 = 0_i32;
     let mut continue_sending: i32 = 0_i32;
     while send_pass <= continue_sending {
@@ -2111,41 +2459,50 @@ unsafe fn enet_protocol_send_outgoing_commands<S: Socket>(
                 (*host).header_flags = 0_i32 as u16;
                 (*host).command_count = 0_i32 as usize;
                 (*host).buffer_count = 1_i32 as usize;
-                (*host).packet_size = ::core::mem::size_of::<ENetProtocolHeader>();
-                if (*current_peer).acknowledgements.sentinel.next
-                    != core::ptr::addr_of_mut!((*current_peer).acknowledgements.sentinel)
-                {
+                let cipher_overhead = if (*current_peer).cipher_key.assume_init_ref().is_some() {
+                    (*host)
+                        .cipher
+                        .assume_init_ref()
+                        .as_ref()
+                        .map_or(0, |cipher| cipher.overhead().wrapping_add(1))
+                } else {
+                    0_i32 as usize
+                };
+                let auth_overhead = if (*current_peer).auth_key.assume_init_ref().is_some() {
+                    (*host)
+                        .authenticator
+                        .assume_init_ref()
+                        .as_ref()
+                        .map_or(0, |authenticator| authenticator.tag_size().wrapping_add(1))
+                } else {
+                    0_i32 as usize
+                };
+                (*host).packet_size = ::core::mem::size_of::<ENetProtocolHeader>()
+                    .wrapping_add(cipher_overhead)
+                    .wrapping_add(auth_overhead);
+                if !(*current_peer).queues.acknowledgements.is_empty() {
                     enet_protocol_send_acknowledgements(host, current_peer);
                 }
                 if check_for_timeouts != 0_i32
-                    && ((*current_peer).sent_reliable_commands.sentinel.next
-                        != core::ptr::addr_of_mut!((*current_peer).sent_reliable_commands.sentinel))
-                    && (((*host).service_time).wrapping_sub((*current_peer).next_timeout)
-                        < 86400000_i32 as u32)
+                    && (!(*current_peer).queues.sent_reliable_commands.is_empty()
+                        && (((*host).service_time).wrapping_sub((*current_peer).next_timeout)
+                            < 86400000_i32 as u32)
+                        || enet_protocol_connect_timed_out(&*current_peer, (*host).service_time))
                     && enet_protocol_check_timeouts(host, current_peer, event) == 1_i32
                 {
                     if !event.is_null() && (*event).type_0 != ENET_EVENT_TYPE_NONE as i32 as u32 {
+                        // Any batched datagrams are sent by the next call, so the event isn't lost
+                        // to a socket error.
                         return Ok(true);
                     }
                 } else {
-                    if ((*current_peer).outgoing_commands.sentinel.next
-                        == core::ptr::addr_of_mut!((*current_peer).outgoing_commands.sentinel)
+                    if ((*current_peer).queues.outgoing_commands.is_empty()
                         && (*current_peer)
+                            .queues
                             .outgoing_send_reliable_commands
-                            .sentinel
-                            .next
-                            == core::ptr::addr_of_mut!(
-                                (*current_peer).outgoing_send_reliable_commands.sentinel
-                            )
-                        || enet_protocol_check_outgoing_commands(
-                            host,
-                            current_peer,
-                            &mut sent_unreliable_commands,
-                        ) != 0)
-                        && (*current_peer).sent_reliable_commands.sentinel.next
-                            == core::ptr::addr_of_mut!(
-                                (*current_peer).sent_reliable_commands.sentinel
-                            )
+                            .is_empty()
+                        || enet_protocol_check_outgoing_commands(host, current_peer) != 0)
+                        && (*current_peer).queues.sent_reliable_commands.is_empty()
                         && (if ((*host).service_time)
                             .wrapping_sub((*current_peer).last_receive_time)
                             >= 86400000_i32 as u32
@@ -2158,11 +2515,7 @@ unsafe fn enet_protocol_send_outgoing_commands<S: Socket>(
                             >= ::core::mem::size_of::<ENetProtocolPing>()
                     {
                         enet_peer_ping(current_peer);
-                        enet_protocol_check_outgoing_commands(
-                            host,
-                            current_peer,
-                            &mut sent_unreliable_commands,
-                        );
+                        enet_protocol_check_outgoing_commands(host, current_peer);
                     }
                     if (*host).command_count != 0_i32 as usize {
                         if (*current_peer).packet_loss_epoch == 0_i32 as u32 {
@@ -2210,9 +2563,19 @@ unsafe fn enet_protocol_send_outgoing_commands<S: Socket>(
                             (*((*host).buffers).as_mut_ptr()).data_length = 2;
                         }
                         should_compress = 0_i32 as usize;
-                        if let Some(compressor) = (*host).compressor.assume_init_mut() {
+                        let compressor = if enet_protocol_compression_bypassed(
+                            &mut *current_peer,
+                            (*host).service_time,
+                        ) {
+                            None
+                        } else {
+                            (*host).compressor.assume_init_mut().as_mut()
+                        };
+                        if let Some(compressor) = compressor {
                             let original_size: usize = ((*host).packet_size)
-                                .wrapping_sub(::core::mem::size_of::<ENetProtocolHeader>());
+                                .wrapping_sub(::core::mem::size_of::<ENetProtocolHeader>())
+                                .wrapping_sub(cipher_overhead)
+                                .wrapping_sub(auth_overhead);
                             let mut in_buffers: [&[u8]; BUFFER_MAXIMUM as usize] =
                                 core::array::from_fn(|_| {
                                     from_raw_parts_or_empty::<u8>(core::ptr::null(), 0)
@@ -2239,6 +2602,16 @@ unsafe fn enet_protocol_send_outgoing_commands<S: Socket>(
                                     as u16;
                                 should_compress = compressed_size;
                             }
+                            enet_protocol_record_compression(
+                                host,
+                                current_peer,
+                                original_size,
+                                if should_compress > 0_i32 as usize {
+                                    should_compress
+                                } else {
+                                    original_size
+                                },
+                            );
                         }
                         if ((*current_peer).outgoing_peer_id as i32)
                             < PROTOCOL_MAXIMUM_PEER_ID as i32
@@ -2297,29 +2670,115 @@ unsafe fn enet_protocol_send_outgoing_commands<S: Socket>(
                             (*host).buffers[1_i32 as usize].data_length = should_compress;
                             (*host).buffer_count = 2_i32 as usize;
                         }
+                        let mut encrypted = true;
+                        if let Some(cipher_key) = (*current_peer).cipher_key.assume_init_ref() {
+                            let mut in_buffers: [&[u8]; BUFFER_MAXIMUM as usize] =
+                                core::array::from_fn(|_| {
+                                    from_raw_parts_or_empty::<u8>(core::ptr::null(), 0)
+                                });
+                            #[allow(clippy::needless_range_loop)]
+                            for i in 0..((*host).buffer_count).wrapping_sub(1) {
+                                let buffer = ((*host).buffers).as_mut_ptr().add(1 + i);
+                                in_buffers[i] = super::from_raw_parts_or_empty(
+                                    (*buffer).data,
+                                    (*buffer).data_length,
+                                );
+                            }
+                            let encrypted_size =
+                                (*host)
+                                    .cipher
+                                    .assume_init_mut()
+                                    .as_mut()
+                                    .map_or(0, |cipher| {
+                                        cipher.encrypt(
+                                            cipher_key,
+                                            &mut (*current_peer).cipher_nonce_counter,
+                                            &header_data
+                                                [..(*host).buffers[0_i32 as usize].data_length],
+                                            &in_buffers[0..((*host).buffer_count).wrapping_sub(1)],
+                                            &mut (*host).packet_data[0_i32 as usize][1..],
+                                        )
+                                    });
+                            if encrypted_size > 0_i32 as usize {
+                                (*host).packet_data[0_i32 as usize][0] =
+                                    ENET_PROTOCOL_COMMAND_ENCRYPTED as i32 as u8;
+                                (*host).buffers[1_i32 as usize].data =
+                                    ((*host).packet_data[0_i32 as usize]).as_mut_ptr();
+                                (*host).buffers[1_i32 as usize].data_length =
+                                    encrypted_size.wrapping_add(1);
+                                (*host).buffer_count = 2_i32 as usize;
+                            } else {
+                                // Never fall back to sending the datagram unencrypted, it is
+                                // treated as lost instead.
+                                encrypted = false;
+                            }
+                        }
+                        if let (Some(auth_key), Some(authenticator)) = (
+                            (*current_peer).auth_key.assume_init_ref(),
+                            (*host).authenticator.assume_init_mut(),
+                        ) {
+                            let header_length = (*host).buffers[0_i32 as usize].data_length;
+                            let tag_size = authenticator.tag_size();
+                            let mut in_buffers: [&[u8]; BUFFER_MAXIMUM as usize] =
+                                core::array::from_fn(|_| {
+                                    from_raw_parts_or_empty::<u8>(core::ptr::null(), 0)
+                                });
+                            in_buffers[0] =
+                                from_raw_parts_or_empty(header_data.as_ptr(), header_length);
+                            #[allow(clippy::needless_range_loop)]
+                            for i in 1..(*host).buffer_count {
+                                let buffer = &(*host).buffers[i];
+                                in_buffers[i] =
+                                    from_raw_parts_or_empty(buffer.data, buffer.data_length);
+                            }
+                            let mut tag = [0; AUTHENTICATION_TAG_MAX];
+                            authenticator.authenticate(
+                                auth_key,
+                                &in_buffers[..(*host).buffer_count],
+                                &mut tag[..tag_size],
+                            );
+                            header_data[header_length] =
+                                ENET_PROTOCOL_COMMAND_AUTHENTICATED as i32 as u8;
+                            header_data[header_length + 1..header_length + 1 + tag_size]
+                                .copy_from_slice(&tag[..tag_size]);
+                            (*host).buffers[0_i32 as usize].data_length =
+                                header_length.wrapping_add(1).wrapping_add(tag_size);
+                        }
                         (*current_peer).last_send_time = (*host).service_time;
-                        let mut conglomerate_buffer = Vec::new();
-                        conglomerate_buffer.reserve_exact((*host).buffer_count);
-                        for buffer_index in 0..(*host).buffer_count {
-                            let buffer = &(*host).buffers[buffer_index];
-                            conglomerate_buffer.extend_from_slice(super::from_raw_parts_or_empty(
-                                buffer.data,
-                                buffer.data_length,
-                            ));
+                        let mut out_buffers: [&[u8]; BUFFER_MAXIMUM as usize] =
+                            core::array::from_fn(|_| {
+                                from_raw_parts_or_empty::<u8>(core::ptr::null(), 0)
+                            });
+                        #[allow(clippy::needless_range_loop)]
+                        for i in 0..(*host).buffer_count {
+                            let buffer = &(*host).buffers[i];
+                            out_buffers[i] =
+                                from_raw_parts_or_empty(buffer.data, buffer.data_length);
                         }
-                        let sent_length = (*host).socket.assume_init_mut().send(
-                            (*current_peer)
-                                .address
-                                .assume_init_ref()
-                                .as_ref()
-                                .cloned()
-                                .unwrap(),
-                            &conglomerate_buffer,
-                        );
-                        enet_protocol_remove_sent_unreliable_commands(
-                            current_peer,
-                            &mut sent_unreliable_commands,
-                        );
+                        let address = (*current_peer)
+                            .address
+                            .assume_init_ref()
+                            .as_ref()
+                            .cloned()
+                            .unwrap();
+                        let sent_length = if !encrypted {
+                            Ok(0)
+                        } else if (*host).send_batch_size > 1_i32 as usize {
+                            // Sent data is counted once the batch is sent.
+                            enet_protocol_queue_datagram(
+                                host,
+                                address,
+                                &out_buffers[..(*host).buffer_count],
+                            )
+                            .map(|()| 0)
+                        } else {
+                            (*host).socket.assume_init_mut().send_vectored(
+                                address,
+                                &out_buffers[..(*host).buffer_count],
+                                (*host).send_scratch.assume_init_mut(),
+                            )
+                        };
+                        enet_protocol_remove_sent_unreliable_commands(current_peer);
                         match sent_length {
                             Err(err) => return Err(err),
                             Ok(sent_length) => {
@@ -2338,8 +2797,45 @@ unsafe fn enet_protocol_send_outgoing_commands<S: Socket>(
         }
         send_pass += 1;
     }
+    enet_protocol_send_batch(host)?;
     Ok(false)
 }
+unsafe fn enet_protocol_queue_datagram<S: Socket>(
+    host: *mut ENetHost<S>,
+    address: S::Address,
+    buffers: &[&[u8]],
+) -> Result<(), S::Error> {
+    let send_batch = (*host).send_batch.assume_init_mut();
+    let batch_buffer = &mut (*host).send_batch_buffers.assume_init_mut()[send_batch.len()];
+    let mut length = 0_i32 as usize;
+    for buffer in buffers {
+        batch_buffer[length..length + buffer.len()].copy_from_slice(buffer);
+        length = length.wrapping_add(buffer.len());
+    }
+    send_batch.push((address, length));
+    if send_batch.len() >= (*host).send_batch_size {
+        enet_protocol_send_batch(host)?;
+    }
+    Ok(())
+}
+unsafe fn enet_protocol_send_batch<S: Socket>(host: *mut ENetHost<S>) -> Result<(), S::Error> {
+    let send_batch = (*host).send_batch.assume_init_mut();
+    if send_batch.is_empty() {
+        return Ok(());
+    }
+    let result = (*host)
+        .socket
+        .assume_init_mut()
+        .send_batch((*host).send_batch_buffers.assume_init_ref(), send_batch);
+    if let Ok(sent_count) = result {
+        for (_, length) in send_batch.iter().take(sent_count) {
+            (*host).total_sent_data = ((*host).total_sent_data).wrapping_add(*length as u32);
+        }
+    }
+    // Datagrams the socket didn't accept are dropped, as if sent with a result of 0.
+    send_batch.clear();
+    result.map(|_| ())
+}
 pub(crate) unsafe fn enet_host_flush<S: Socket>(host: *mut ENetHost<S>) {
     (*host).service_time = enet_time_get(host);
     // TODO: enet ignores the error here, but is that really what we want?
@@ -2396,3 +2892,59 @@ pub(crate) unsafe fn enet_host_service<S: Socket>(
     }
     Ok(false)
 }
+pub(crate) unsafe fn enet_host_service_delay<S: Socket>(host: *mut ENetHost<S>) -> Option<u32> {
+    let time = enet_time_get(host);
+    let mut next_service: Option<u32> = None;
+    let mut schedule = |deadline: u32| {
+        let delay = if deadline.wrapping_sub(time) >= 86400000_i32 as u32 {
+            0_i32 as u32
+        } else {
+            deadline.wrapping_sub(time)
+        };
+        next_service = Some(next_service.map_or(delay, |next_service| next_service.min(delay)));
+    };
+    if !(*host).dispatch_queue.assume_init_ref().is_empty()
+        || !(*host).pending_events.assume_init_ref().is_empty()
+        || (*host).received_batch_index < (*host).received_batch_count
+        || !(*host).send_batch.assume_init_ref().is_empty()
+    {
+        schedule(time);
+    }
+    if (*host).incoming_bandwidth != 0_i32 as u32 || (*host).outgoing_bandwidth != 0_i32 as u32 {
+        schedule(((*host).bandwidth_throttle_epoch).wrapping_add(HOST_BANDWIDTH_THROTTLE_INTERVAL));
+    }
+    let mut current_peer: *mut ENetPeer<S> = (*host).peers;
+    while current_peer < ((*host).peers).add((*host).peer_count) {
+        if (*current_peer).state == ENET_PEER_STATE_DISCONNECTED as i32 as u32
+            || (*current_peer).state == ENET_PEER_STATE_ZOMBIE as i32 as u32
+        {
+            current_peer = current_peer.offset(1);
+            continue;
+        }
+        if !(*current_peer).queues.acknowledgements.is_empty() {
+            schedule(time);
+        }
+        if (*current_peer).state == ENET_PEER_STATE_CONNECTING as i32 as u32
+            && (*current_peer).connect_timeout != 0_i32 as u32
+        {
+            schedule(((*current_peer).connect_time).wrapping_add((*current_peer).connect_timeout));
+        }
+        if (*current_peer).queues.sent_reliable_commands.is_empty() {
+            schedule(
+                ((*current_peer).last_receive_time).wrapping_add((*current_peer).ping_interval),
+            );
+        }
+        let outgoing_commands = &(*current_peer).queues.outgoing_command_arena;
+        let mut current_command: ENetListIterator =
+            (*current_peer).queues.sent_reliable_commands.begin();
+        while current_command != ENET_LIST_END {
+            let outgoing_command = &outgoing_commands[current_command];
+            schedule(
+                (outgoing_command.sent_time).wrapping_add(outgoing_command.round_trip_timeout),
+            );
+            current_command = outgoing_commands.next(current_command);
+        }
+        current_peer = current_peer.offset(1);
+    }
+    next_service
+}
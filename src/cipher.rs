@@ -0,0 +1,273 @@
+/// An interface for encrypting ENet datagrams, with a key for each peer set by
+/// [`Peer::set_cipher_key`](`crate::Peer::set_cipher_key`).
+///
+/// Datagrams are encrypted after compression, and decrypted before decompression. The datagram
+/// header is needed to find the peer a datagram belongs to, so it is not encrypted, but should be
+/// authenticated along with the encrypted data.
+pub trait Cipher {
+    /// Check if `key` can be used with this cipher.
+    fn valid_key(&self, key: &[u8]) -> bool;
+    /// The maximum number of bytes added to a datagram by [`Cipher::encrypt`].
+    fn overhead(&self) -> usize;
+    /// Encrypt the incoming buffers with `key`, authenticating `header`. Returns the size of the
+    /// data written to `out`, or `0` on failure.
+    ///
+    /// `nonce_counter` holds the last nonce sent to the peer. Ciphers carrying a nonce in each
+    /// datagram should count nonces per peer, so the peer's [`ReplayWindow`] isn't skipped over
+    /// by datagrams sent to other peers.
+    fn encrypt(
+        &mut self,
+        key: &[u8],
+        nonce_counter: &mut NonceCounter,
+        header: &[u8],
+        in_buffers: &[&[u8]],
+        out: &mut [u8],
+    ) -> usize;
+    /// Decrypt the buffer with `key`, authenticating `header`. Returns the size of the data written
+    /// to `out`, or `0` if the buffer could not be authenticated.
+    ///
+    /// `replay_window` holds the nonces recently received from the peer. Ciphers carrying a nonce
+    /// in each datagram should discard datagrams whose nonce was already received, and record the
+    /// nonce of each datagram which decrypts.
+    fn decrypt(
+        &mut self,
+        key: &[u8],
+        replay_window: &mut ReplayWindow,
+        header: &[u8],
+        in_data: &[u8],
+        out: &mut [u8],
+    ) -> usize;
+}
+
+/// The last nonce sent to a peer, passed to [`Cipher::encrypt`] so nonces can be counted per peer.
+///
+/// The counter is cleared along with the peer's key.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NonceCounter {
+    last: Option<u128>,
+}
+
+impl NonceCounter {
+    /// Create an empty counter, for a peer which has not been sent anything.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { last: None }
+    }
+
+    /// The last nonce sent to the peer, or [`None`] if nothing was sent yet.
+    #[must_use]
+    pub fn last(&self) -> Option<u128> {
+        self.last
+    }
+
+    /// Record `nonce` as sent to the peer.
+    pub fn set_last(&mut self, nonce: u128) {
+        self.last = Some(nonce);
+    }
+}
+
+/// The nonces recently received from a peer, passed to [`Cipher::decrypt`] so replayed datagrams
+/// can be discarded.
+///
+/// Nonces are expected to increase, but may arrive out of order. A nonce is accepted if it is
+/// higher than any received so far, or if it is among the [`ReplayWindow::SIZE`] nonces below the
+/// highest and was not received yet. The window is cleared along with the peer's key.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReplayWindow {
+    highest: Option<u128>,
+    received: u128,
+}
+
+impl ReplayWindow {
+    /// How far below the highest nonce received a nonce may be and still be accepted.
+    pub const SIZE: u32 = u128::BITS;
+
+    /// Create an empty window, which accepts any nonce.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            highest: None,
+            received: 0,
+        }
+    }
+
+    /// Check if a datagram with `nonce` should be accepted.
+    #[must_use]
+    pub fn check(&self, nonce: u128) -> bool {
+        let Some(highest) = self.highest else {
+            return true;
+        };
+        if nonce > highest {
+            return true;
+        }
+        let age = highest - nonce;
+        age < u128::from(Self::SIZE) && self.received & (1 << age) == 0
+    }
+
+    /// Record `nonce` as received. Only call this once the datagram has been authenticated, so
+    /// forged datagrams can't move the window.
+    pub fn accept(&mut self, nonce: u128) {
+        match self.highest {
+            Some(highest) if nonce <= highest => {
+                let age = highest - nonce;
+                if age < u128::from(Self::SIZE) {
+                    self.received |= 1 << age;
+                }
+            }
+            Some(highest) => {
+                let shift = nonce - highest;
+                self.received = if shift < u128::from(Self::SIZE) {
+                    self.received << shift | 1
+                } else {
+                    1
+                };
+                self.highest = Some(nonce);
+            }
+            None => {
+                self.received = 1;
+                self.highest = Some(nonce);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "chacha20poly1305")]
+const CHACHA20POLY1305_NONCE_SIZE: usize = 12;
+#[cfg(feature = "chacha20poly1305")]
+const CHACHA20POLY1305_TAG_SIZE: usize = 16;
+
+/// The ChaCha20-Poly1305 AEAD, with 32 byte keys.
+///
+/// Each datagram carries a 12 byte nonce and a 16 byte authentication tag. A nonce is an 8 byte
+/// stream followed by a 4 byte counter. Each peer is given a stream of its own when its key is set,
+/// and again whenever its counter wraps. Streams are counted up from a per-host seed, so the seed
+/// should be random. Two hosts sharing a key with the same seed would reuse nonces. Datagrams whose
+/// nonce was already received from the peer are discarded, see [`ReplayWindow`].
+#[cfg(feature = "chacha20poly1305")]
+#[cfg_attr(docsrs, doc(cfg(feature = "chacha20poly1305")))]
+pub struct ChaCha20Poly1305 {
+    next_nonce_stream: u64,
+}
+
+#[cfg(feature = "chacha20poly1305")]
+impl ChaCha20Poly1305 {
+    /// Create a ChaCha20-Poly1305 cipher, seeding nonces from the operating system's random number
+    /// generator.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RandomUnavailable`](`crate::error::RandomUnavailable`) if the operating system
+    /// could not provide random bytes.
+    #[cfg(feature = "std")]
+    pub fn new() -> Result<Self, crate::error::RandomUnavailable> {
+        let mut seed = [0; 8];
+        getrandom::getrandom(&mut seed).map_err(|_| crate::error::RandomUnavailable)?;
+        Ok(Self::with_seed(u64::from_le_bytes(seed)))
+    }
+
+    /// Create a ChaCha20-Poly1305 cipher, seeding nonces with a random value provided by the
+    /// caller.
+    #[must_use]
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            next_nonce_stream: seed,
+        }
+    }
+}
+
+#[cfg(feature = "chacha20poly1305")]
+impl Cipher for ChaCha20Poly1305 {
+    fn valid_key(&self, key: &[u8]) -> bool {
+        key.len() == 32
+    }
+
+    fn overhead(&self) -> usize {
+        CHACHA20POLY1305_NONCE_SIZE + CHACHA20POLY1305_TAG_SIZE
+    }
+
+    fn encrypt(
+        &mut self,
+        key: &[u8],
+        nonce_counter: &mut NonceCounter,
+        header: &[u8],
+        in_buffers: &[&[u8]],
+        out: &mut [u8],
+    ) -> usize {
+        use chacha20poly1305::{aead::AeadInPlace, KeyInit};
+
+        let in_length: usize = in_buffers.iter().map(|in_buffer| in_buffer.len()).sum();
+        let out_length = in_length + self.overhead();
+        if !self.valid_key(key) || out.len() < out_length {
+            return 0;
+        }
+        // Moving on to the next stream when the counter wraps, rather than carrying into the
+        // stream, keeps nonces from running into the stream of another peer.
+        let sequence = match nonce_counter.last() {
+            Some(last) if (last + 1) as u32 != 0 => last + 1,
+            _ => {
+                let stream = self.next_nonce_stream;
+                self.next_nonce_stream = self.next_nonce_stream.wrapping_add(1);
+                u128::from(stream) << 32
+            }
+        };
+        nonce_counter.set_last(sequence);
+        let (nonce, out) = out.split_at_mut(CHACHA20POLY1305_NONCE_SIZE);
+        nonce[..8].copy_from_slice(&((sequence >> 32) as u64).to_le_bytes());
+        nonce[8..].copy_from_slice(&(sequence as u32).to_le_bytes());
+        let (data, tag) = out[..in_length + CHACHA20POLY1305_TAG_SIZE].split_at_mut(in_length);
+        let mut offset = 0;
+        for in_buffer in in_buffers {
+            data[offset..offset + in_buffer.len()].copy_from_slice(in_buffer);
+            offset += in_buffer.len();
+        }
+        let cipher = chacha20poly1305::ChaCha20Poly1305::new(key.into());
+        cipher
+            .encrypt_in_place_detached((&*nonce).into(), header, data)
+            .map_or(0, |encrypted_tag| {
+                tag.copy_from_slice(&encrypted_tag);
+                out_length
+            })
+    }
+
+    fn decrypt(
+        &mut self,
+        key: &[u8],
+        replay_window: &mut ReplayWindow,
+        header: &[u8],
+        in_data: &[u8],
+        out: &mut [u8],
+    ) -> usize {
+        use chacha20poly1305::{aead::AeadInPlace, KeyInit};
+
+        if !self.valid_key(key) || in_data.len() < self.overhead() {
+            return 0;
+        }
+        let out_length = in_data.len() - self.overhead();
+        if out.len() < out_length {
+            return 0;
+        }
+        let (nonce, in_data) = in_data.split_at(CHACHA20POLY1305_NONCE_SIZE);
+        // The stream only changes when the key is set or the counter wraps, and later streams are
+        // higher, so this increases with every datagram the peer sends.
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&nonce[..8]);
+        let mut counter = [0; 4];
+        counter.copy_from_slice(&nonce[8..]);
+        let sequence =
+            u128::from(u64::from_le_bytes(seed)) << 32 | u128::from(u32::from_le_bytes(counter));
+        if !replay_window.check(sequence) {
+            return 0;
+        }
+        let (in_data, tag) = in_data.split_at(out_length);
+        out[..out_length].copy_from_slice(in_data);
+        let cipher = chacha20poly1305::ChaCha20Poly1305::new(key.into());
+        if cipher
+            .decrypt_in_place_detached(nonce.into(), header, &mut out[..out_length], tag.into())
+            .is_err()
+        {
+            return 0;
+        }
+        replay_window.accept(sequence);
+        out_length
+    }
+}
@@ -1,10 +1,39 @@
-use core::mem::zeroed;
+use core::fmt::Debug;
 
 use crate::{
-    consts::BUFFER_MAXIMUM, enet_range_coder_compress, enet_range_coder_create,
-    enet_range_coder_decompress, enet_range_coder_destroy, ENetBuffer, ENetRangeCoder,
+    error::InvalidDictionary, range_coder_compress, range_coder_decompress,
+    range_coder_model_is_valid, Box, RangeCoderModel, RangeCoderSymbol, Vec, RANGE_CODER_SYMBOLS,
 };
 
+/// The most symbols a [`RangeCoderDictionary`] may hold, leaving the rest of the range coder's 4096
+/// symbols for the packets being compressed. The model is reset once it runs out of symbols, so a
+/// full dictionary would be thrown away part way through the first packet.
+const RANGE_CODER_DICTIONARY_SYMBOLS: usize = 2048;
+
+/// The size of a serialized [`RangeCoderDictionary`] before its symbols: the model's next symbol,
+/// which is also the number of symbols, its predicted symbol, and its order.
+const RANGE_CODER_DICTIONARY_HEADER_SIZE: usize = 5;
+
+/// The size of each serialized symbol of a [`RangeCoderDictionary`].
+const RANGE_CODER_DICTIONARY_SYMBOL_SIZE: usize = 16;
+
+/// Compression statistics for a host or peer, acquired with
+/// [`HostStatistics::compression`](`crate::HostStatistics::compression`) or
+/// [`Peer::compression_statistics`](`crate::Peer::compression_statistics`).
+///
+/// Only datagrams which were given to the compressor are counted. The totals wrap on overflow.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionStatistics {
+    /// Number of datagrams given to the compressor.
+    pub attempts: u32,
+    /// Number of datagrams which were smaller after compression, and sent compressed.
+    pub wins: u32,
+    /// Total bytes given to the compressor.
+    pub bytes_in: u32,
+    /// Total bytes sent for datagrams given to the compressor, whether compressed or not.
+    pub bytes_out: u32,
+}
+
 /// An interface for compressing ENet packets.
 pub trait Compressor {
     /// Compress the incoming buffers.
@@ -14,16 +43,52 @@ pub trait Compressor {
 }
 
 /// The built-in range coder compression provided by ENet.
-pub struct RangeCoder(*mut ENetRangeCoder);
-
-unsafe impl Send for RangeCoder {}
-unsafe impl Sync for RangeCoder {}
+///
+/// The range coder builds an adaptive model of each packet from scratch, so it does little for
+/// small packets. [`RangeCoder::with_dictionary`] starts each packet from a model trained on
+/// representative packets instead.
+pub struct RangeCoder {
+    symbols: Box<[RangeCoderSymbol]>,
+    dictionary: Option<RangeCoderDictionary>,
+}
 
 impl RangeCoder {
     /// Create a new range coder compressor.
     #[must_use]
     pub fn new() -> Self {
-        Self(unsafe { enet_range_coder_create().cast() })
+        Self {
+            symbols: core::iter::repeat(RangeCoderSymbol::default())
+                .take(RANGE_CODER_SYMBOLS)
+                .collect(),
+            dictionary: None,
+        }
+    }
+
+    /// Create a new range coder compressor which starts each packet from the model in
+    /// `dictionary`.
+    ///
+    /// Both hosts must use the same dictionary, or packets will fail to decompress and be
+    /// discarded. Packets compressed without a dictionary cannot be decompressed with one, and
+    /// vice versa.
+    #[must_use]
+    pub fn with_dictionary(dictionary: RangeCoderDictionary) -> Self {
+        Self {
+            dictionary: Some(dictionary),
+            ..Self::new()
+        }
+    }
+
+    /// Get the dictionary used by this range coder, if any.
+    #[must_use]
+    pub fn dictionary(&self) -> Option<&RangeCoderDictionary> {
+        self.dictionary.as_ref()
+    }
+
+    fn load_dictionary(&mut self) -> Option<RangeCoderModel> {
+        self.dictionary.as_ref().map(|dictionary| {
+            self.symbols[..dictionary.symbols.len()].copy_from_slice(&dictionary.symbols);
+            dictionary.model
+        })
     }
 }
 
@@ -35,41 +100,198 @@ impl Default for RangeCoder {
 
 impl Compressor for RangeCoder {
     fn compress(&mut self, in_buffers: &[&[u8]], in_limit: usize, out: &mut [u8]) -> usize {
-        unsafe {
-            let mut buffers: [ENetBuffer; BUFFER_MAXIMUM as usize] =
-                core::array::from_fn(|_| zeroed());
-            for (i, in_buffer) in in_buffers.iter().enumerate() {
-                buffers[i] = ENetBuffer {
-                    data: in_buffer.as_ptr().cast_mut(),
-                    data_length: in_buffer.len(),
-                };
+        let mut model = self.load_dictionary();
+        range_coder_compress(&mut self.symbols, model.as_mut(), in_buffers, in_limit, out)
+    }
+
+    fn decompress(&mut self, in_data: &[u8], out: &mut [u8]) -> usize {
+        let model = self.load_dictionary();
+        range_coder_decompress(&mut self.symbols, model.as_ref(), in_data, out)
+    }
+}
+
+/// A model for [`RangeCoder::with_dictionary`], trained on representative packets.
+///
+/// Training is deterministic, so hosts training a dictionary from the same samples, in the same
+/// order, end up with the same dictionary.
+#[derive(Clone)]
+pub struct RangeCoderDictionary {
+    symbols: Vec<RangeCoderSymbol>,
+    model: RangeCoderModel,
+}
+
+impl RangeCoderDictionary {
+    /// Train a dictionary from `samples`, see [`RangeCoderTrainer`].
+    pub fn train<'a>(samples: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut trainer = RangeCoderTrainer::new();
+        for sample in samples {
+            trainer.add_sample(sample);
+        }
+        trainer.dictionary()
+    }
+
+    /// The number of symbols in the dictionary's model, out of a maximum of 2048.
+    #[must_use]
+    pub fn symbol_count(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Serialize the dictionary, to be loaded with [`RangeCoderDictionary::from_bytes`], so it
+    /// can be trained once and shipped with both hosts.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            RANGE_CODER_DICTIONARY_HEADER_SIZE
+                + self.symbols.len() * RANGE_CODER_DICTIONARY_SYMBOL_SIZE,
+        );
+        bytes.extend_from_slice(&(self.model.next_symbol as u16).to_le_bytes());
+        bytes.extend_from_slice(&self.model.predicted.to_le_bytes());
+        bytes.push(self.model.order as u8);
+        for symbol in &self.symbols {
+            bytes.push(symbol.value);
+            bytes.push(symbol.count);
+            for field in [
+                symbol.under,
+                symbol.left,
+                symbol.right,
+                symbol.symbols,
+                symbol.escapes,
+                symbol.total,
+                symbol.parent,
+            ] {
+                bytes.extend_from_slice(&field.to_le_bytes());
             }
-            enet_range_coder_compress(
-                self.0.cast(),
-                buffers.as_ptr(),
-                in_buffers.len(),
-                in_limit,
-                out.as_mut_ptr(),
-                out.len(),
-            )
         }
+        bytes
     }
 
-    fn decompress(&mut self, in_data: &[u8], out: &mut [u8]) -> usize {
-        unsafe {
-            enet_range_coder_decompress(
-                self.0.cast(),
-                in_data.as_ptr(),
-                in_data.len(),
-                out.as_mut_ptr(),
-                out.len(),
-            )
+    /// Load a dictionary serialized with [`RangeCoderDictionary::to_bytes`].
+    ///
+    /// The model is checked before it is used, so a corrupted or hostile dictionary is rejected
+    /// rather than making the range coder panic or loop forever.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidDictionary`] if `bytes` is truncated, holds more than 2048 symbols, or
+    /// holds a model the range coder could not have built.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidDictionary> {
+        if bytes.len() < RANGE_CODER_DICTIONARY_HEADER_SIZE {
+            return Err(InvalidDictionary);
+        }
+        let (header, body) = bytes.split_at(RANGE_CODER_DICTIONARY_HEADER_SIZE);
+        let model = RangeCoderModel {
+            next_symbol: usize::from(u16::from_le_bytes([header[0], header[1]])),
+            predicted: u16::from_le_bytes([header[2], header[3]]),
+            order: usize::from(header[4]),
+        };
+        if model.next_symbol > RANGE_CODER_DICTIONARY_SYMBOLS
+            || body.len() != model.next_symbol * RANGE_CODER_DICTIONARY_SYMBOL_SIZE
+        {
+            return Err(InvalidDictionary);
+        }
+        let symbols: Vec<RangeCoderSymbol> = body
+            .chunks_exact(RANGE_CODER_DICTIONARY_SYMBOL_SIZE)
+            .map(|symbol| {
+                let field =
+                    |offset: usize| u16::from_le_bytes([symbol[offset], symbol[offset + 1]]);
+                RangeCoderSymbol {
+                    value: symbol[0],
+                    count: symbol[1],
+                    under: field(2),
+                    left: field(4),
+                    right: field(6),
+                    symbols: field(8),
+                    escapes: field(10),
+                    total: field(12),
+                    parent: field(14),
+                }
+            })
+            .collect();
+        if !range_coder_model_is_valid(&symbols, &model) {
+            return Err(InvalidDictionary);
+        }
+        Ok(Self { symbols, model })
+    }
+}
+
+impl Debug for RangeCoderDictionary {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RangeCoderDictionary")
+            .field("symbolCount", &self.symbols.len())
+            .finish()
+    }
+}
+
+/// Trains a [`RangeCoderDictionary`] from captured traffic.
+///
+/// Samples should be the data the range coder sees, which is the contents of a datagram after its
+/// header: the ENet commands along with the packets they carry. A [`Compressor`] wrapping a
+/// [`RangeCoder`] can record these from a running host.
+///
+/// Samples are added until the model is full, so the most representative samples should come
+/// first.
+pub struct RangeCoderTrainer {
+    range_coder: RangeCoder,
+    dictionary: RangeCoderDictionary,
+}
+
+impl RangeCoderTrainer {
+    /// Create a trainer with an empty dictionary.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            range_coder: RangeCoder::new(),
+            dictionary: RangeCoderDictionary {
+                symbols: Vec::new(),
+                model: RangeCoderModel::default(),
+            },
+        }
+    }
+
+    /// Train the dictionary on `sample`. Returns `false`, leaving the dictionary unchanged, if the
+    /// sample would not fit in the dictionary.
+    pub fn add_sample(&mut self, sample: &[u8]) -> bool {
+        self.range_coder.dictionary = Some(self.dictionary.clone());
+        let mut model = self.range_coder.load_dictionary().unwrap_or_default();
+        // The range coder's model carries over between calls, so update it a byte at a time to
+        // stop before it fills up and resets.
+        let mut out = [0; 64];
+        for value in sample {
+            range_coder_compress(
+                &mut self.range_coder.symbols,
+                Some(&mut model),
+                &[core::slice::from_ref(value)],
+                1,
+                &mut out,
+            );
+            if model.next_symbol > RANGE_CODER_DICTIONARY_SYMBOLS {
+                return false;
+            }
         }
+        self.dictionary = RangeCoderDictionary {
+            symbols: self.range_coder.symbols[..model.next_symbol].to_vec(),
+            model,
+        };
+        true
+    }
+
+    /// Get the dictionary trained so far.
+    #[must_use]
+    pub fn dictionary(&self) -> RangeCoderDictionary {
+        self.dictionary.clone()
+    }
+}
+
+impl Default for RangeCoderTrainer {
+    fn default() -> Self {
+        RangeCoderTrainer::new()
     }
 }
 
-impl Drop for RangeCoder {
-    fn drop(&mut self) {
-        unsafe { enet_range_coder_destroy(self.0.cast::<u8>()) };
+impl Debug for RangeCoderTrainer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RangeCoderTrainer")
+            .field("dictionary", &self.dictionary)
+            .finish()
     }
 }
@@ -94,11 +94,18 @@ impl<A: crate::Address> crate::Address for Address<A> {
     fn is_broadcast(&self) -> bool {
         A::is_broadcast(&self.address)
     }
+
+    fn hash_address(&self, state: &mut dyn core::hash::Hasher) {
+        state.write_usize(self.id.0);
+        state.write_usize(self.generation);
+        A::hash_address(&self.address, state);
+    }
 }
 
 /// A trait for implementing connection based sockets, similar to [`Socket`](`crate::Socket`).
 ///
-/// An implementation for [`std::net::TcpStream`] is provided out of the box.
+/// An implementation for [`std::net::TcpStream`] is provided out of the box. Since TCP is stream
+/// based, it should be wrapped in [`connected::Framed`] to preserve datagram boundaries.
 ///
 /// An implementation of [`ReadWrite`](`crate::ReadWrite`) is provided with an address type of `()`.
 #[allow(clippy::missing_errors_doc)]
@@ -127,6 +134,9 @@ pub trait Connection {
     /// The return value should be `Ok(None)` if no packet was received. If a packet was received,
     /// the amount of bytes received should be returned. Packets received may be complete or
     /// partial. See [`PacketReceived`](`crate::PacketReceived`) for more info.
+    ///
+    /// [`connected::Host::service`] calls this repeatedly until it returns `Ok(None)`. Returning an
+    /// error (for example, when the remote end has closed the connection) disconnects the peer.
     fn receive(
         &mut self,
         buffer: &mut [u8; crate::MTU_MAX],
@@ -634,8 +644,9 @@ pub struct Host<C: Connection + 'static> {
 impl<C: Connection> Host<C> {
     /// Creates a host for communicating to connection based peers.
     ///
-    /// Supports [`std::net::TcpStream`] out of the box, but other transport protocols can be
-    /// provided by implementing the [`connected::Connection`] trait.
+    /// Supports [`std::net::TcpStream`] out of the box (wrapped in [`connected::Framed`]), but
+    /// other transport protocols can be provided by implementing the [`connected::Connection`]
+    /// trait.
     ///
     /// # Errors
     ///
@@ -648,6 +659,13 @@ impl<C: Connection> Host<C> {
     ///   is equal to `Some(0)`.
     /// - If [`HostSettings::peer_limit`](`crate::HostSettings::peer_limit`) is equal to `0` or
     ///   greater than [`consts::PROTOCOL_MAXIMUM_PEER_ID`](`crate::consts::PROTOCOL_MAXIMUM_PEER_ID`).
+    /// - If [`HostSettings::maximum_packet_size`](`crate::HostSettings::maximum_packet_size`) is
+    ///   equal to `0`.
+    /// - If [`HostSettings::maximum_waiting_data`](`crate::HostSettings::maximum_waiting_data`) is
+    ///   equal to `0`.
+    /// - If [`HostSettings::duplicate_peers`](`crate::HostSettings::duplicate_peers`) is equal to
+    ///   `0` or greater than
+    ///   [`consts::PROTOCOL_MAXIMUM_PEER_ID`](`crate::consts::PROTOCOL_MAXIMUM_PEER_ID`).
     pub fn new(settings: crate::HostSettings) -> Result<Self, crate::error::BadParameter> {
         let mut peers = Vec::new();
         peers.reserve_exact(settings.peer_limit);
@@ -748,6 +766,18 @@ impl<C: Connection> Host<C> {
 
     fn handle_event(&mut self, event: crate::EventNoRef) -> Event<C> {
         let now = self.host.now();
+        // Failed connections are reported like any other disconnection.
+        let event = match event {
+            crate::EventNoRef::ConnectFailed { peer, reason } => crate::EventNoRef::Disconnect {
+                peer,
+                data: match reason {
+                    crate::DisconnectReason::Refused(data) => data,
+                    _ => 0,
+                },
+                reason,
+            },
+            event => event,
+        };
         match event {
             crate::EventNoRef::Connect { peer, data } => {
                 let peer = self.host.peer_mut(peer);
@@ -792,7 +822,7 @@ impl<C: Connection> Host<C> {
                     data,
                 }
             }
-            crate::EventNoRef::Disconnect { peer, data } => {
+            crate::EventNoRef::Disconnect { peer, data, .. } => {
                 let peer = self.host.peer_mut(peer);
                 let connection_id = peer
                     .address()
@@ -849,6 +879,13 @@ impl<C: Connection> Host<C> {
                     packet,
                 }
             }
+            crate::EventNoRef::ConnectFailed { .. } => unreachable!(),
+            // Tracked sends, queue watermarks and host senders are not exposed through connected
+            // peers.
+            crate::EventNoRef::Acknowledged { .. }
+            | crate::EventNoRef::Dropped { .. }
+            | crate::EventNoRef::Writable { .. }
+            | crate::EventNoRef::SendFailed { .. } => unreachable!(),
         }
     }
 
@@ -910,18 +947,26 @@ impl<C: Connection> Host<C> {
                     ..
                 } => {
                     let mut buffer = [0; crate::MTU_MAX];
-                    match connection.receive(&mut buffer) {
-                        Ok(Some(crate::PacketReceived::Complete(size))) => {
-                            self.host
-                                .socket_mut()
-                                .write(address.clone(), (buffer[0..size]).to_vec());
-                        }
-                        Err(_) => {
-                            peer.reset();
-                            disconnect_event = Some(peer.id);
-                            break;
+                    let mut failed = false;
+                    loop {
+                        match connection.receive(&mut buffer) {
+                            Ok(Some(crate::PacketReceived::Complete(size))) => {
+                                self.host
+                                    .socket_mut()
+                                    .write(address.clone(), (buffer[0..size]).to_vec());
+                            }
+                            Ok(Some(crate::PacketReceived::Partial)) => {}
+                            Ok(None) => break,
+                            Err(_) => {
+                                failed = true;
+                                break;
+                            }
                         }
-                        _ => {}
+                    }
+                    if failed {
+                        peer.reset();
+                        disconnect_event = Some(peer.id);
+                        break;
                     }
                 }
             }
@@ -1150,6 +1195,75 @@ impl<C: Connection> Host<C> {
         self.host.set_mtu(mtu)
     }
 
+    /// See [`Host::maximum_packet_size`](`crate::Host::maximum_packet_size`).
+    #[must_use]
+    pub fn maximum_packet_size(&self) -> usize {
+        self.host.maximum_packet_size()
+    }
+
+    /// See [`Host::set_maximum_packet_size`](`crate::Host::set_maximum_packet_size`).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`error::BadParameter`](`crate::error::BadParameter`) if `maximum_packet_size` is
+    /// `0`.
+    pub fn set_maximum_packet_size(
+        &mut self,
+        maximum_packet_size: usize,
+    ) -> Result<(), crate::error::BadParameter> {
+        self.host.set_maximum_packet_size(maximum_packet_size)
+    }
+
+    /// See [`Host::maximum_waiting_data`](`crate::Host::maximum_waiting_data`).
+    #[must_use]
+    pub fn maximum_waiting_data(&self) -> usize {
+        self.host.maximum_waiting_data()
+    }
+
+    /// See [`Host::set_maximum_waiting_data`](`crate::Host::set_maximum_waiting_data`).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`error::BadParameter`](`crate::error::BadParameter`) if `maximum_waiting_data` is
+    /// `0`.
+    pub fn set_maximum_waiting_data(
+        &mut self,
+        maximum_waiting_data: usize,
+    ) -> Result<(), crate::error::BadParameter> {
+        self.host.set_maximum_waiting_data(maximum_waiting_data)
+    }
+
+    /// See [`Host::duplicate_peers`](`crate::Host::duplicate_peers`).
+    #[must_use]
+    pub fn duplicate_peers(&self) -> usize {
+        self.host.duplicate_peers()
+    }
+
+    /// See [`Host::set_duplicate_peers`](`crate::Host::set_duplicate_peers`).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`error::BadParameter`](`crate::error::BadParameter`) if `duplicate_peers` is `0`
+    /// or greater than
+    /// [`consts::PROTOCOL_MAXIMUM_PEER_ID`](`crate::consts::PROTOCOL_MAXIMUM_PEER_ID`).
+    pub fn set_duplicate_peers(
+        &mut self,
+        duplicate_peers: usize,
+    ) -> Result<(), crate::error::BadParameter> {
+        self.host.set_duplicate_peers(duplicate_peers)
+    }
+
+    /// See [`Host::statistics`](`crate::Host::statistics`).
+    #[must_use]
+    pub fn statistics(&self) -> crate::HostStatistics {
+        self.host.statistics()
+    }
+
+    /// See [`Host::reset_statistics`](`crate::Host::reset_statistics`).
+    pub fn reset_statistics(&mut self) {
+        self.host.reset_statistics();
+    }
+
     /// See [`Host::now`](`crate::Host::now`).
     #[must_use]
     pub fn now(&self) -> Duration {
@@ -1157,6 +1271,199 @@ impl<C: Connection> Host<C> {
     }
 }
 
+/// The amount of bytes used by [`connected::Framed`] to prefix each datagram with its length.
+const FRAME_HEADER_SIZE: usize = 2;
+
+/// The maximum amount of bytes [`connected::Framed`] will hold onto while waiting for the
+/// underlying connection to accept more data. Datagrams sent past this limit are dropped, and ENet
+/// will resend them if they were reliable.
+const FRAME_WRITE_LIMIT: usize = crate::MTU_MAX * 64;
+
+/// Errors returned by [`connected::Framed`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FramedError<E: crate::SocketError> {
+    /// The underlying connection returned an error.
+    Connection(E),
+    /// A frame was received with a length larger than [`MTU_MAX`](`crate::MTU_MAX`), so the stream
+    /// can no longer be trusted.
+    FrameTooLarge,
+}
+
+#[cfg(feature = "std")]
+impl<E: crate::SocketError> std::error::Error for FramedError<E> {}
+
+impl<E: crate::SocketError> core::fmt::Debug for FramedError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            FramedError::Connection(err) => f.debug_tuple("Connection").field(&err).finish(),
+            FramedError::FrameTooLarge => f.write_str("FrameTooLarge"),
+        }
+    }
+}
+
+impl<E: crate::SocketError> core::fmt::Display for FramedError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            FramedError::Connection(_) => f.write_str("The underlying connection failed."),
+            FramedError::FrameTooLarge => {
+                f.write_str("Received a frame larger than the maximum ENet datagram size.")
+            }
+        }
+    }
+}
+
+/// A [`connected::Connection`] wrapper for stream based connections (such as TCP).
+///
+/// Streams do not preserve message boundaries, so a single read may contain part of an ENet
+/// datagram, or several of them. This wrapper prefixes each outgoing datagram with its length,
+/// buffers partial reads and writes, and only hands whole datagrams to [`connected::Host`].
+///
+/// Both sides of the connection must use [`connected::Framed`].
+///
+/// ```no_run
+/// # #[cfg(feature = "connected")]
+/// # {
+/// use std::net::TcpStream;
+///
+/// use rusty_enet::connected::{ConnectionKind, Framed, Host};
+///
+/// let mut host = Host::<Framed<TcpStream>>::new(Default::default()).unwrap();
+/// let stream = TcpStream::connect("127.0.0.1:6060").unwrap();
+/// host.add_connection(ConnectionKind::Initiator {
+///     connection: Framed::new(stream),
+///     channel_count: 1,
+///     data: 0,
+/// })
+/// .unwrap();
+/// # }
+/// ```
+pub struct Framed<C: Connection> {
+    connection: C,
+    read_buffer: Vec<u8>,
+    write_buffer: Vec<u8>,
+}
+
+impl<C: Connection> Framed<C> {
+    /// Wrap a stream based connection, framing each datagram sent over it.
+    pub fn new(connection: C) -> Self {
+        Self {
+            connection,
+            read_buffer: Vec::new(),
+            write_buffer: Vec::new(),
+        }
+    }
+
+    /// Get a reference to the underlying connection.
+    pub fn get_ref(&self) -> &C {
+        &self.connection
+    }
+
+    /// Get a mutable reference to the underlying connection.
+    ///
+    /// Reading from or writing to the connection directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.connection
+    }
+
+    /// Unwrap the underlying connection, discarding any buffered data.
+    pub fn into_inner(self) -> C {
+        self.connection
+    }
+
+    /// The amount of bytes still waiting to be written to the underlying connection.
+    #[must_use]
+    pub fn pending_write(&self) -> usize {
+        self.write_buffer.len()
+    }
+
+    fn flush(&mut self) -> Result<(), FramedError<C::Error>> {
+        while !self.write_buffer.is_empty() {
+            let sent_length = self
+                .connection
+                .send(&self.write_buffer)
+                .map_err(FramedError::Connection)?;
+            if sent_length == 0 {
+                break;
+            }
+            self.write_buffer.drain(0..sent_length);
+        }
+        Ok(())
+    }
+
+    fn take_frame(
+        &mut self,
+        buffer: &mut [u8; crate::MTU_MAX],
+    ) -> Result<Option<usize>, FramedError<C::Error>> {
+        if self.read_buffer.len() < FRAME_HEADER_SIZE {
+            return Ok(None);
+        }
+        let frame_length = u16::from_be_bytes([self.read_buffer[0], self.read_buffer[1]]) as usize;
+        if frame_length > crate::MTU_MAX {
+            return Err(FramedError::FrameTooLarge);
+        }
+        if self.read_buffer.len() < FRAME_HEADER_SIZE + frame_length {
+            return Ok(None);
+        }
+        buffer[..frame_length].copy_from_slice(
+            &self.read_buffer[FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + frame_length],
+        );
+        self.read_buffer.drain(0..FRAME_HEADER_SIZE + frame_length);
+        Ok(Some(frame_length))
+    }
+}
+
+impl<C: Connection> Connection for Framed<C> {
+    type Address = C::Address;
+    type Error = FramedError<C::Error>;
+
+    fn init(&mut self) -> Result<Self::Address, Self::Error> {
+        self.connection.init().map_err(FramedError::Connection)
+    }
+
+    fn send(&mut self, buffer: &[u8]) -> Result<usize, Self::Error> {
+        self.flush()?;
+        if self.write_buffer.len() + FRAME_HEADER_SIZE + buffer.len() > FRAME_WRITE_LIMIT {
+            return Ok(0);
+        }
+        let Ok(frame_length) = u16::try_from(buffer.len()) else {
+            return Ok(0);
+        };
+        self.write_buffer
+            .extend_from_slice(&frame_length.to_be_bytes());
+        self.write_buffer.extend_from_slice(buffer);
+        self.flush()?;
+        Ok(buffer.len())
+    }
+
+    fn receive(
+        &mut self,
+        buffer: &mut [u8; crate::MTU_MAX],
+    ) -> Result<Option<crate::PacketReceived>, Self::Error> {
+        self.flush()?;
+        loop {
+            if let Some(frame_length) = self.take_frame(buffer)? {
+                return Ok(Some(crate::PacketReceived::Complete(frame_length)));
+            }
+            match self
+                .connection
+                .receive(buffer)
+                .map_err(FramedError::Connection)?
+            {
+                Some(crate::PacketReceived::Complete(0)) | None => return Ok(None),
+                Some(crate::PacketReceived::Complete(recv_length)) => {
+                    self.read_buffer.extend_from_slice(&buffer[..recv_length]);
+                }
+                Some(crate::PacketReceived::Partial) => {}
+            }
+        }
+    }
+}
+
+/// Reads and writes raw bytes, without preserving datagram boundaries. Use
+/// [`connected::Framed<TcpStream>`] rather than using this implementation directly.
+///
+/// A read of zero bytes means the remote end closed the stream, and is reported as an
+/// [`io::ErrorKind::UnexpectedEof`] error.
 #[cfg(feature = "std")]
 impl Connection for TcpStream {
     type Address = SocketAddr;
@@ -1183,6 +1490,7 @@ impl Connection for TcpStream {
     ) -> Result<Option<crate::PacketReceived>, Self::Error> {
         use std::io::{ErrorKind, Read};
         match self.read(buffer) {
+            Ok(0) => Err(ErrorKind::UnexpectedEof.into()),
             Ok(recv_length) => Ok(Some(crate::PacketReceived::Complete(recv_length))),
             Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(None),
             Err(err) => Err(err),
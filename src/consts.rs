@@ -27,10 +27,15 @@ pub const PEER_PACKET_THROTTLE_COUNTER: u32 = 7;
 pub const PEER_PACKET_THROTTLE_SCALE: u32 = 32;
 pub const PEER_DEFAULT_PACKET_THROTTLE: u32 = 32;
 pub const PEER_DEFAULT_ROUND_TRIP_TIME: u32 = 500;
+pub const PEER_COMPRESSION_PROBE_ATTEMPTS: u32 = 32;
+pub const PEER_COMPRESSION_MINIMUM_SAVINGS: u32 = 2;
+pub const PEER_COMPRESSION_SAVINGS_SCALE: u32 = 32;
+pub const PEER_COMPRESSION_BYPASS_INTERVAL: u32 = 10000;
 pub const HOST_DEFAULT_MAXIMUM_WAITING_DATA: u32 = 32 * 1024 * 1024;
 pub const HOST_DEFAULT_MAXIMUM_PACKET_SIZE: u32 = 32 * 1024 * 1024;
 pub const HOST_DEFAULT_MTU: u32 = 1392;
 pub const HOST_BANDWIDTH_THROTTLE_INTERVAL: u32 = 1000;
+pub const HOST_CONNECT_COOKIE_SECRET_INTERVAL: u32 = 10000;
 pub const HOST_SEND_BUFFER_SIZE: u32 = 256 * 1024;
 pub const HOST_RECEIVE_BUFFER_SIZE: u32 = 256 * 1024;
 
@@ -52,6 +52,12 @@ pub enum PeerSendError {
     FragmentsExceeded,
     /// Cannot send to peer because the packet failed to queue.
     FailedToQueue,
+    /// Cannot track delivery of a packet which is not reliable, see
+    /// [`Peer::send_tracked`](`crate::Peer::send_tracked`).
+    NotReliable,
+    /// Cannot expire or supersede a packet which is reliable, see
+    /// [`Peer::send_with_settings`](`crate::Peer::send_with_settings`).
+    NotUnreliable,
 }
 
 #[cfg(feature = "std")]
@@ -75,6 +81,12 @@ impl core::fmt::Display for PeerSendError {
             PeerSendError::FailedToQueue => {
                 f.write_str("Cannot send to an ENet peer because the packet failed to queue.")
             }
+            PeerSendError::NotReliable => f.write_str(
+                "Cannot track delivery to an ENet peer because the packet is not reliable.",
+            ),
+            PeerSendError::NotUnreliable => f.write_str(
+                "Cannot expire or supersede a packet sent to an ENet peer because the packet is reliable.",
+            ),
         }
     }
 }
@@ -100,6 +112,33 @@ impl core::fmt::Display for BadParameter {
     }
 }
 
+/// Failed to draw random bytes from the operating system.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RandomUnavailable;
+
+#[cfg(feature = "std")]
+impl std::error::Error for RandomUnavailable {}
+
+impl core::fmt::Display for RandomUnavailable {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str("Failed to draw random bytes from the operating system.")
+    }
+}
+
+/// Failed to load a [`RangeCoderDictionary`](`crate::RangeCoderDictionary`) because it is
+/// malformed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidDictionary;
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidDictionary {}
+
+impl core::fmt::Display for InvalidDictionary {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str("Failed to load a range coder dictionary because it is malformed.")
+    }
+}
+
 /// Failed to connect because there were no available ENet peer slots.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct NoAvailablePeers;
@@ -1,4 +1,4 @@
-use crate::{Packet, Peer, PeerID, Socket};
+use crate::{error::PeerSendError, Packet, Peer, PeerID, SendToken, Socket};
 
 /// An ENet event returned by [`Host::service`](`crate::Host::service`).
 #[derive(Debug)]
@@ -16,6 +16,17 @@ pub enum Event<'a, S: Socket> {
         peer: &'a mut Peer<S>,
         /// Data associated with the event, sent by the peer on disconnect.
         data: u32,
+        /// Why the peer disconnected.
+        reason: DisconnectReason,
+    },
+    /// A connection started with [`Host::connect`](`crate::Host::connect`) could not be
+    /// established. The peer has been reset.
+    ConnectFailed {
+        /// Peer that generated the event.
+        peer: &'a mut Peer<S>,
+        /// Why the connection failed: [`DisconnectReason::Refused`],
+        /// [`DisconnectReason::Timeout`], or [`DisconnectReason::ConnectFailed`].
+        reason: DisconnectReason,
     },
     /// A peer sent a packet to us.
     Receive {
@@ -26,6 +37,43 @@ pub enum Event<'a, S: Socket> {
         /// The actual packet data.
         packet: Packet,
     },
+    /// A peer acknowledged every fragment of a packet sent with
+    /// [`Peer::send_tracked`].
+    Acknowledged {
+        /// Peer that generated the event. The peer may have been reset and reused for a new
+        /// connection since, in which case [`Host::get_peer_mut`](`crate::Host::get_peer_mut`)
+        /// returns [`None`] for this ID.
+        peer: PeerID,
+        /// The token returned when the packet was sent.
+        token: SendToken,
+    },
+    /// A packet sent with [`Peer::send_tracked`] was discarded before the peer acknowledged it,
+    /// because the peer disconnected or was reset.
+    Dropped {
+        /// Peer that generated the event. The peer may have been reused for a new connection
+        /// since, in which case [`Host::get_peer_mut`](`crate::Host::get_peer_mut`) returns
+        /// [`None`] for this ID.
+        peer: PeerID,
+        /// The token returned when the packet was sent.
+        token: SendToken,
+    },
+    /// The data queued for a peer drained to its low watermark, after rising above its high
+    /// watermark. See [`Peer::set_queue_watermarks`].
+    Writable {
+        /// Peer that generated the event.
+        peer: &'a mut Peer<S>,
+    },
+    /// A packet queued with [`HostSender::send`](`crate::HostSender::send`) could not be sent.
+    SendFailed {
+        /// Peer the packet was sent to. The peer may have been reused for a new connection
+        /// since, in which case [`Host::get_peer_mut`](`crate::Host::get_peer_mut`) returns
+        /// [`None`] for this ID.
+        peer: PeerID,
+        /// Channel the packet was sent on.
+        channel_id: u8,
+        /// Why the packet could not be sent.
+        error: PeerSendError,
+    },
 }
 
 impl<S: Socket> Event<'_, S> {
@@ -37,9 +85,14 @@ impl<S: Socket> Event<'_, S> {
                 peer: peer.id(),
                 data,
             },
-            Self::Disconnect { peer, data } => EventNoRef::Disconnect {
+            Self::Disconnect { peer, data, reason } => EventNoRef::Disconnect {
                 peer: peer.id(),
                 data,
+                reason,
+            },
+            Self::ConnectFailed { peer, reason } => EventNoRef::ConnectFailed {
+                peer: peer.id(),
+                reason,
             },
             Self::Receive {
                 peer,
@@ -50,6 +103,18 @@ impl<S: Socket> Event<'_, S> {
                 channel_id,
                 packet,
             },
+            Self::Acknowledged { peer, token } => EventNoRef::Acknowledged { peer, token },
+            Self::Dropped { peer, token } => EventNoRef::Dropped { peer, token },
+            Self::Writable { peer } => EventNoRef::Writable { peer: peer.id() },
+            Self::SendFailed {
+                peer,
+                channel_id,
+                error,
+            } => EventNoRef::SendFailed {
+                peer,
+                channel_id,
+                error,
+            },
         }
     }
 }
@@ -72,6 +137,17 @@ pub enum EventNoRef {
         peer: PeerID,
         /// Data associated with the event, sent by the peer on disconnect.
         data: u32,
+        /// Why the peer disconnected.
+        reason: DisconnectReason,
+    },
+    /// A connection started with [`Host::connect`](`crate::Host::connect`) could not be
+    /// established. The peer has been reset.
+    ConnectFailed {
+        /// Peer that generated the event.
+        peer: PeerID,
+        /// Why the connection failed: [`DisconnectReason::Refused`],
+        /// [`DisconnectReason::Timeout`], or [`DisconnectReason::ConnectFailed`].
+        reason: DisconnectReason,
     },
     /// A peer sent a packet to us.
     Receive {
@@ -82,4 +158,58 @@ pub enum EventNoRef {
         /// The actual packet data.
         packet: Packet,
     },
+    /// A peer acknowledged every fragment of a packet sent with
+    /// [`Peer::send_tracked`].
+    Acknowledged {
+        /// Peer that generated the event.
+        peer: PeerID,
+        /// The token returned when the packet was sent.
+        token: SendToken,
+    },
+    /// A packet sent with [`Peer::send_tracked`] was discarded before the peer acknowledged it,
+    /// because the peer disconnected or was reset.
+    Dropped {
+        /// Peer that generated the event.
+        peer: PeerID,
+        /// The token returned when the packet was sent.
+        token: SendToken,
+    },
+    /// The data queued for a peer drained to its low watermark, after rising above its high
+    /// watermark. See [`Peer::set_queue_watermarks`].
+    Writable {
+        /// Peer that generated the event.
+        peer: PeerID,
+    },
+    /// A packet queued with [`HostSender::send`](`crate::HostSender::send`) could not be sent.
+    SendFailed {
+        /// Peer the packet was sent to.
+        peer: PeerID,
+        /// Channel the packet was sent on.
+        channel_id: u8,
+        /// Why the packet could not be sent.
+        error: PeerSendError,
+    },
+}
+
+/// Why a peer disconnected, see [`Event::Disconnect`], or why a connection could not be
+/// established, see [`Event::ConnectFailed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The peer disconnected from us. The inner value is the data it sent on disconnect, see
+    /// [`Peer::disconnect`].
+    Remote(u32),
+    /// The peer refused our connection attempt. The inner value is the data it sent with the
+    /// refusal, see [`AcceptConnection::Reject`](`crate::AcceptConnection::Reject`).
+    Refused(u32),
+    /// We disconnected from the peer with [`Peer::disconnect`] or [`Peer::disconnect_later`], and
+    /// the peer acknowledged it.
+    ///
+    /// [`Peer::disconnect_now`] and [`Peer::reset`] disconnect without generating an event.
+    Local,
+    /// The peer stopped acknowledging reliable commands, see [`Peer::set_timeout`], or did not
+    /// answer our connection attempt in time, see
+    /// [`ConnectSettings`](`crate::ConnectSettings`).
+    Timeout,
+    /// The peer's answer to our connection attempt did not match the request.
+    ConnectFailed,
 }
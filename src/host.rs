@@ -1,20 +1,32 @@
 use core::{fmt::Debug, mem::zeroed, time::Duration};
+#[cfg(feature = "std")]
+use std::sync::mpsc;
 
+#[cfg(feature = "std")]
+use crate::VecDeque;
 use crate::{Box, Vec};
 
 use crate::{
     consts::{
+        HOST_DEFAULT_MAXIMUM_PACKET_SIZE, HOST_DEFAULT_MAXIMUM_WAITING_DATA,
         PROTOCOL_MAXIMUM_CHANNEL_COUNT, PROTOCOL_MAXIMUM_MTU, PROTOCOL_MAXIMUM_PEER_ID,
         PROTOCOL_MINIMUM_MTU,
     },
     enet_host_bandwidth_limit, enet_host_broadcast, enet_host_channel_limit,
     enet_host_check_events, enet_host_compress, enet_host_connect, enet_host_create,
-    enet_host_destroy, enet_host_flush, enet_host_service,
-    error::{BadParameter, HostNewError, NoAvailablePeers},
-    time_since_epoch, Compressor, ENetEvent, ENetHost, ENetPeer, Event, Packet, Peer, PeerID,
-    PeerState, Socket, ENET_EVENT_TYPE_CONNECT, ENET_EVENT_TYPE_DISCONNECT,
-    ENET_EVENT_TYPE_RECEIVE,
+    enet_host_destroy, enet_host_flush, enet_host_rotate_connect_cookie_secret, enet_host_service,
+    enet_host_service_delay,
+    error::{BadParameter, HostNewError, NoAvailablePeers, PeerSendError},
+    time_since_epoch, Authenticator, Cipher, CompressionStatistics, Compressor, DisconnectReason,
+    ENetEvent, ENetHost, ENetPeer, Event, Packet, Peer, PeerID, PeerState, SendToken, Socket,
+    WaitableSocket, AUTHENTICATION_TAG_MAX, ENET_DISCONNECT_REASON_CONNECT_FAILED,
+    ENET_DISCONNECT_REASON_LOCAL, ENET_DISCONNECT_REASON_REFUSED, ENET_DISCONNECT_REASON_REMOTE,
+    ENET_DISCONNECT_REASON_TIMEOUT, ENET_EVENT_TYPE_ACKNOWLEDGED, ENET_EVENT_TYPE_CONNECT,
+    ENET_EVENT_TYPE_CONNECT_FAILED, ENET_EVENT_TYPE_DISCONNECT, ENET_EVENT_TYPE_DROPPED,
+    ENET_EVENT_TYPE_RECEIVE, ENET_EVENT_TYPE_WRITABLE,
 };
+#[cfg(feature = "std")]
+use crate::{HostSender, SenderCommand};
 
 /// Settings for a newly created host, passed into [`Host::new`].
 #[allow(clippy::type_complexity)]
@@ -35,6 +47,19 @@ pub struct HostSettings {
     pub outgoing_bandwidth_limit: Option<u32>,
     /// The compressor to use when sending and receiving packets, or [`None`] for no compression.
     pub compressor: Option<Box<dyn Compressor>>,
+    /// Stop compressing a peer's datagrams for a while when compression saves too little.
+    ///
+    /// See [`Host::set_adaptive_compression`] for more info.
+    pub adaptive_compression: bool,
+    /// The cipher to use when sending and receiving packets, or [`None`] for no encryption.
+    ///
+    /// Datagrams are only encrypted for peers with a key, see [`Peer::set_cipher_key`].
+    pub cipher: Option<Box<dyn Cipher>>,
+    /// The authenticator to use when sending and receiving packets, or [`None`] for no
+    /// authentication.
+    ///
+    /// Datagrams are only authenticated for peers with a key, see [`Peer::set_auth_key`].
+    pub authenticator: Option<Box<dyn Authenticator>>,
     /// The checksum function to use when sending and receiving packets, or [`None`] for no
     /// checksum.
     pub checksum: Option<Box<dyn Fn(&[&[u8]]) -> u32>>,
@@ -43,6 +68,45 @@ pub struct HostSettings {
     pub time: Box<dyn Fn() -> Duration>,
     /// Seed the host with a specific random seed, or set to [`None`] to use a random seed.
     pub seed: Option<u32>,
+    /// The maximum size of a packet, in bytes, that can be sent or received. Cannot be 0.
+    ///
+    /// See [`Host::set_maximum_packet_size`] for more info.
+    pub maximum_packet_size: usize,
+    /// The maximum amount of received data, in bytes, that may be buffered for a peer while
+    /// waiting to be dispatched. Cannot be 0.
+    ///
+    /// See [`Host::set_maximum_waiting_data`] for more info.
+    pub maximum_waiting_data: usize,
+    /// The maximum number of peers that may be connected from the same host. Cannot be 0.
+    ///
+    /// See [`Host::set_duplicate_peers`] for more info.
+    pub duplicate_peers: usize,
+    /// The maximum number of datagrams received from the socket at once, with
+    /// [`Socket::receive_batch`]. Cannot be 0.
+    ///
+    /// The host allocates a buffer of [`MTU_MAX`](`crate::MTU_MAX`) bytes for each datagram in
+    /// the batch. Larger batches only help with sockets able to receive many datagrams in a
+    /// single system call, such as [`std::net::UdpSocket`] with the `mmsg` feature on Linux.
+    pub receive_batch_size: usize,
+    /// The maximum number of datagrams sent to the socket at once, with [`Socket::send_batch`].
+    /// Cannot be 0.
+    ///
+    /// With a batch size of 1, each datagram is sent as soon as it is built, with
+    /// [`Socket::send_vectored`]. Larger batches copy datagrams into buffers of
+    /// [`MTU_MAX`](`crate::MTU_MAX`) bytes allocated by the host, and only help with sockets able
+    /// to send many datagrams in a single system call, such as [`std::net::UdpSocket`] with the
+    /// `mmsg` feature on Linux.
+    pub send_batch_size: usize,
+    /// Require connecting hosts to echo a connect cookie before a peer is allocated for them.
+    ///
+    /// See [`Host::set_connect_cookies`] for more info.
+    pub connect_cookies: bool,
+    /// The secret connect cookies are derived from, or [`None`] to draw one from the operating
+    /// system's random number generator when connect cookies are first enabled.
+    ///
+    /// Independent of [`HostSettings::seed`]. Without the `std` feature there is no random number
+    /// generator, so a secret must be provided to enable connect cookies.
+    pub connect_cookie_secret: Option<[u8; 16]>,
 }
 
 impl Default for HostSettings {
@@ -53,19 +117,94 @@ impl Default for HostSettings {
             incoming_bandwidth_limit: None,
             outgoing_bandwidth_limit: None,
             compressor: None,
+            adaptive_compression: true,
+            cipher: None,
+            authenticator: None,
             checksum: None,
             time: Box::new(time_since_epoch),
             seed: None,
+            maximum_packet_size: HOST_DEFAULT_MAXIMUM_PACKET_SIZE as usize,
+            maximum_waiting_data: HOST_DEFAULT_MAXIMUM_WAITING_DATA as usize,
+            duplicate_peers: PROTOCOL_MAXIMUM_PEER_ID as usize,
+            receive_batch_size: 1,
+            send_batch_size: 1,
+            connect_cookies: false,
+            connect_cookie_secret: None,
         }
     }
 }
 
+/// Settings for an outgoing connection, passed into [`Host::connect_with_settings`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectSettings {
+    /// How long to wait for the foreign host to accept the connection before giving up, or
+    /// [`None`] to wait until the peer times out as usual (see [`Peer::set_timeout`]).
+    ///
+    /// Rounded down to whole milliseconds, with a minimum of 1 millisecond.
+    pub timeout: Option<Duration>,
+    /// How many times the connection request may be resent before giving up, or [`None`] to
+    /// resend it until the peer times out.
+    pub retries: Option<u32>,
+}
+
+/// The result of an intercept callback, see [`Host::set_intercept`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intercept<E> {
+    /// The datagram was not handled by the callback, and ENet should process it as usual.
+    Ignore,
+    /// The datagram was handled by the callback, and ENet should discard it.
+    Consume,
+    /// The callback failed. The error is returned from [`Host::service`].
+    Error(E),
+}
+
+/// The result of an accept connection callback, see [`Host::set_accept_connection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcceptConnection {
+    /// Accept the connection, allocating a peer for it if one is available.
+    Accept,
+    /// Silently discard the connection request. The remote host will keep retrying until its
+    /// connection attempt times out.
+    Discard,
+    /// Reject the connection request, answering with a disconnect carrying the provided data.
+    /// The remote host receives an [`Event::Disconnect`] with this data.
+    Reject(u32),
+}
+
+/// Traffic statistics for a host, acquired with [`Host::statistics`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HostStatistics {
+    /// Total bytes sent by the host. Reset with [`Host::reset_statistics`].
+    pub total_sent_data: u32,
+    /// Total UDP packets sent by the host. Reset with [`Host::reset_statistics`].
+    pub total_sent_packets: u32,
+    /// Total bytes received by the host. Reset with [`Host::reset_statistics`].
+    pub total_received_data: u32,
+    /// Total UDP packets received by the host. Reset with [`Host::reset_statistics`].
+    pub total_received_packets: u32,
+    /// Total UDP packets received by the host which were discarded because they failed
+    /// authentication, see [`Peer::set_auth_key`]. Reset with [`Host::reset_statistics`].
+    pub total_unauthenticated_packets: u32,
+    /// Compression statistics for datagrams sent by the host. Reset with
+    /// [`Host::reset_statistics`].
+    pub compression: CompressionStatistics,
+    /// Number of peers currently connected to the host.
+    pub connected_peers: usize,
+    /// Number of connected peers which have an incoming bandwidth limit.
+    pub bandwidth_limited_peers: usize,
+}
+
 /// A host for communicating with peers.
 ///
 /// Requires a [`Socket`] implementation.
 pub struct Host<S: Socket> {
     host: *mut ENetHost<S>,
     peers: Vec<Peer<S>>,
+    #[cfg(feature = "std")]
+    sender: Option<(mpsc::Sender<SenderCommand>, mpsc::Receiver<SenderCommand>)>,
+    /// Sends queued with [`HostSender::send`] which failed, to be returned as events.
+    #[cfg(feature = "std")]
+    pub(crate) send_failures: VecDeque<(PeerID, u8, PeerSendError)>,
 }
 
 unsafe impl<S: Socket> Send for Host<S> {}
@@ -85,6 +224,16 @@ impl<S: Socket> Host<S> {
     /// - If [`HostSettings::outgoing_bandwidth_limit`] is equal to `Some(0)`.
     /// - If [`HostSettings::peer_limit`] is equal to `0` or greater than
     ///   [`PROTOCOL_MAXIMUM_PEER_ID`].
+    /// - If [`HostSettings::maximum_packet_size`] is equal to `0`.
+    /// - If [`HostSettings::maximum_waiting_data`] is equal to `0`.
+    /// - If [`HostSettings::duplicate_peers`] is equal to `0` or greater than
+    ///   [`PROTOCOL_MAXIMUM_PEER_ID`].
+    /// - If [`HostSettings::receive_batch_size`] is equal to `0`.
+    /// - If [`HostSettings::send_batch_size`] is equal to `0`.
+    /// - If the [`Authenticator::tag_size`] of [`HostSettings::authenticator`] is equal to `0` or
+    ///   greater than [`AUTHENTICATION_TAG_MAX`].
+    /// - If [`HostSettings::connect_cookies`] is `true` but [`HostSettings::connect_cookie_secret`]
+    ///   is [`None`] and no secret could be drawn from the operating system.
     ///
     /// Returns [`HostNewError::FailedToInitializeSocket`] if the call to [`Socket::init`] fails.
     pub fn new(socket: S, settings: HostSettings) -> Result<Host<S>, HostNewError<S>> {
@@ -112,6 +261,60 @@ impl<S: Socket> Host<S> {
                 parameter: "settings.peer_limit",
             }));
         }
+        if settings.maximum_packet_size == 0 {
+            return Err(HostNewError::BadParameter(BadParameter {
+                method: "Host::new",
+                parameter: "settings.maximum_packet_size",
+            }));
+        }
+        if settings.maximum_waiting_data == 0 {
+            return Err(HostNewError::BadParameter(BadParameter {
+                method: "Host::new",
+                parameter: "settings.maximum_waiting_data",
+            }));
+        }
+        if settings.duplicate_peers == 0
+            || settings.duplicate_peers > PROTOCOL_MAXIMUM_PEER_ID as usize
+        {
+            return Err(HostNewError::BadParameter(BadParameter {
+                method: "Host::new",
+                parameter: "settings.duplicate_peers",
+            }));
+        }
+        if settings.receive_batch_size == 0 {
+            return Err(HostNewError::BadParameter(BadParameter {
+                method: "Host::new",
+                parameter: "settings.receive_batch_size",
+            }));
+        }
+        if settings.send_batch_size == 0 {
+            return Err(HostNewError::BadParameter(BadParameter {
+                method: "Host::new",
+                parameter: "settings.send_batch_size",
+            }));
+        }
+        if settings
+            .authenticator
+            .as_ref()
+            .is_some_and(|authenticator| {
+                authenticator.tag_size() == 0 || authenticator.tag_size() > AUTHENTICATION_TAG_MAX
+            })
+        {
+            return Err(HostNewError::BadParameter(BadParameter {
+                method: "Host::new",
+                parameter: "settings.authenticator",
+            }));
+        }
+        let mut connect_cookie_secret = settings.connect_cookie_secret;
+        if settings.connect_cookies {
+            connect_cookie_secret = connect_cookie_secret.or_else(random_connect_cookie_secret);
+            if connect_cookie_secret.is_none() {
+                return Err(HostNewError::BadParameter(BadParameter {
+                    method: "Host::new",
+                    parameter: "settings.connect_cookie_secret",
+                }));
+            }
+        }
         unsafe {
             let host = enet_host_create::<S>(
                 socket,
@@ -121,6 +324,8 @@ impl<S: Socket> Host<S> {
                 settings.outgoing_bandwidth_limit.unwrap_or(0),
                 settings.time,
                 settings.seed,
+                settings.receive_batch_size,
+                settings.send_batch_size,
             )
             .map_err(|err| HostNewError::FailedToInitializeSocket(err))?;
             let mut peers = Vec::new();
@@ -131,10 +336,31 @@ impl<S: Socket> Host<S> {
             if let Some(compressor) = settings.compressor {
                 enet_host_compress(host, Some(compressor));
             }
+            if let Some(authenticator) = settings.authenticator {
+                *(*host).authenticator.assume_init_mut() = Some(authenticator);
+            }
+            if let Some(cipher) = settings.cipher {
+                *(*host).cipher.assume_init_mut() = Some(cipher);
+            }
             if let Some(checksum) = settings.checksum {
                 *(*host).checksum.assume_init_mut() = Some(checksum);
             }
-            Ok(Self { host, peers })
+            (*host).maximum_packet_size = settings.maximum_packet_size;
+            (*host).maximum_waiting_data = settings.maximum_waiting_data;
+            (*host).duplicate_peers = settings.duplicate_peers;
+            (*host).adaptive_compression = settings.adaptive_compression;
+            (*host).connect_cookie_secret = connect_cookie_secret;
+            let mut host = Self {
+                host,
+                peers,
+                #[cfg(feature = "std")]
+                sender: None,
+                #[cfg(feature = "std")]
+                send_failures: VecDeque::new(),
+            };
+            host.set_connect_cookies(settings.connect_cookies)
+                .map_err(HostNewError::BadParameter)?;
+            Ok(host)
         }
     }
 
@@ -151,10 +377,35 @@ impl<S: Socket> Host<S> {
         address: S::Address,
         channel_count: usize,
         data: u32,
+    ) -> Result<&mut Peer<S>, NoAvailablePeers> {
+        self.connect_with_settings(address, channel_count, data, ConnectSettings::default())
+    }
+
+    /// Like [`Host::connect`], but gives up on the connection as specified by `settings`.
+    ///
+    /// If the connection is refused, or the foreign host does not answer in time, the peer is
+    /// reset and an [`Event::ConnectFailed`] is generated.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NoAvailablePeers`] if all peer slots have been filled.
+    pub fn connect_with_settings(
+        &mut self,
+        address: S::Address,
+        channel_count: usize,
+        data: u32,
+        settings: ConnectSettings,
     ) -> Result<&mut Peer<S>, NoAvailablePeers> {
         unsafe {
             let peer = enet_host_connect(self.host, address, channel_count, data);
             if !peer.is_null() {
+                if let Some(timeout) = settings.timeout {
+                    (*peer).connect_timeout = u32::try_from(timeout.as_millis())
+                        .map_or(u32::MAX, |timeout| timeout.max(1));
+                }
+                if let Some(retries) = settings.retries {
+                    (*peer).connect_retry_limit = retries;
+                }
                 Ok(self.peer_mut(self.peer_index(peer)))
             } else {
                 Err(NoAvailablePeers)
@@ -164,6 +415,14 @@ impl<S: Socket> Host<S> {
 
     /// Checks for any queued events on the host and dispatches one if available.
     pub fn check_events(&mut self) -> Option<Event<S>> {
+        self.apply_sender_commands();
+        if let Some((peer, channel_id, error)) = self.pop_send_failure() {
+            return Some(Event::SendFailed {
+                peer,
+                channel_id,
+                error,
+            });
+        }
         unsafe {
             let mut event: ENetEvent<S> = zeroed();
             if enet_host_check_events(self.host, &mut event) {
@@ -176,13 +435,23 @@ impl<S: Socket> Host<S> {
 
     /// Checks for events on the host and shuttles packets between the host and its peers.
     ///
-    /// Should be called fairly regularly for adequate performance.
+    /// Should be called fairly regularly for adequate performance. Commands queued with a
+    /// [`HostSender`](`crate::HostSender`) are applied first, and any of their sends which failed
+    /// are returned as [`Event::SendFailed`] before other events.
     ///
     /// # Errors
     ///
     /// Returns any error reported by the socket implementation during [`Socket::receive`] or
     /// [`Socket::send`].
     pub fn service(&mut self) -> Result<Option<Event<S>>, S::Error> {
+        self.apply_sender_commands();
+        if let Some((peer, channel_id, error)) = self.pop_send_failure() {
+            return Ok(Some(Event::SendFailed {
+                peer,
+                channel_id,
+                error,
+            }));
+        }
         unsafe {
             let mut event: ENetEvent<S> = zeroed();
             if enet_host_service(self.host, &mut event)? {
@@ -193,13 +462,82 @@ impl<S: Socket> Host<S> {
         }
     }
 
+    /// Like [`Host::service`], but blocks until an event occurs or until `timeout` has elapsed,
+    /// similar to `enet_host_service` in the C library.
+    ///
+    /// While waiting, the host sleeps until either the socket becomes readable or the next
+    /// protocol deadline (a ping, resend, or timeout) is reached. See [`Host::service_delay`].
+    ///
+    /// Requires the socket to implement [`WaitableSocket`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error reported by the socket implementation during [`Socket::receive`],
+    /// [`Socket::send`], or [`WaitableSocket::wait_readable`].
+    pub fn service_timeout(&mut self, timeout: Duration) -> Result<Option<Event<S>>, S::Error>
+    where
+        S: WaitableSocket,
+    {
+        let start = self.now();
+        unsafe {
+            let mut event: ENetEvent<S> = zeroed();
+            loop {
+                self.apply_sender_commands();
+                if let Some((peer, channel_id, error)) = self.pop_send_failure() {
+                    return Ok(Some(Event::SendFailed {
+                        peer,
+                        channel_id,
+                        error,
+                    }));
+                }
+                if enet_host_service(self.host, &mut event)? {
+                    break;
+                }
+                let elapsed = self.now().saturating_sub(start);
+                if elapsed >= timeout {
+                    return Ok(None);
+                }
+                let wait = (timeout - elapsed).min(self.service_delay().unwrap_or(Duration::MAX));
+                self.socket_mut().wait_readable(wait)?;
+            }
+            Ok(Some(self.create_event(&event)))
+        }
+    }
+
+    /// Get the amount of time until the host next needs to be serviced to keep up with protocol
+    /// deadlines (pings, resends, timeouts, and bandwidth throttling), or [`None`] if there is
+    /// nothing to do until more data is received.
+    ///
+    /// Useful for event loops which wait on the socket themselves, rather than using
+    /// [`Host::service_timeout`].
+    #[must_use]
+    pub fn service_delay(&self) -> Option<Duration> {
+        unsafe {
+            enet_host_service_delay(self.host).map(|delay| Duration::from_millis(delay.into()))
+        }
+    }
+
     /// Sends any queued packets on the host specified to its designated peers.
+    ///
+    /// Commands queued with a [`HostSender`](`crate::HostSender`) are applied first.
     pub fn flush(&mut self) {
+        self.apply_sender_commands();
         unsafe {
             enet_host_flush(self.host);
         }
     }
 
+    /// Get a handle for queuing packets on this host from other threads.
+    ///
+    /// Every handle returned by this method, and every clone of one, feeds the same queue. See
+    /// [`HostSender`].
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn sender(&mut self) -> HostSender {
+        let (commands, _) = self.sender.get_or_insert_with(mpsc::channel);
+        HostSender::new(commands.clone())
+    }
+
     /// Get a reference to the underlying socket.
     #[must_use]
     pub fn socket(&self) -> &S {
@@ -225,23 +563,26 @@ impl<S: Socket> Host<S> {
     ///
     /// # Panics
     ///
-    /// Panics if the peer ID is outside the bounds of peers allocated for this host. Use
-    /// [`Host::get_peer`] for a non-panicking version.
+    /// Panics if the peer ID is outside the bounds of peers allocated for this host, or if the
+    /// peer has since been reused for a new connection. Use [`Host::get_peer`] for a
+    /// non-panicking version.
     #[must_use]
     pub fn peer(&self, peer: PeerID) -> &Peer<S> {
-        self.peers
-            .get(peer.0)
-            .expect("Expected the peer id to be in bounds.")
+        self.get_peer(peer)
+            .expect("Expected the peer id to be in bounds and not stale.")
     }
 
-    /// Get a reference to a single peer.
+    /// Get a reference to a single peer, or [`None`] if the peer ID is out of bounds or the peer
+    /// has since been reused for a new connection.
     ///
     /// # Note
     ///
     /// Acquires the peer object, even if the peer is not in a connected state. See [`Peer::state`].
     #[must_use]
     pub fn get_peer(&self, peer: PeerID) -> Option<&Peer<S>> {
-        self.peers.get(peer.0)
+        self.peers
+            .get(peer.index())
+            .filter(|current_peer| current_peer.id() == peer)
     }
 
     /// Get a mutable reference to a single peer.
@@ -252,22 +593,25 @@ impl<S: Socket> Host<S> {
     ///
     /// # Panics
     ///
-    /// Panics if the peer ID is outside the bounds of peers allocated for this host. Use
-    /// [`Host::get_peer_mut`] for a non-panicking version.
+    /// Panics if the peer ID is outside the bounds of peers allocated for this host, or if the
+    /// peer has since been reused for a new connection. Use [`Host::get_peer_mut`] for a
+    /// non-panicking version.
     pub fn peer_mut(&mut self, peer: PeerID) -> &mut Peer<S> {
-        self.peers
-            .get_mut(peer.0)
-            .expect("Expected the peer id to be in bounds.")
+        self.get_peer_mut(peer)
+            .expect("Expected the peer id to be in bounds and not stale.")
     }
 
-    /// Get a mutable reference to a single peer, if it exists.
+    /// Get a mutable reference to a single peer, or [`None`] if the peer ID is out of bounds or
+    /// the peer has since been reused for a new connection.
     ///
     /// # Note
     ///
     /// Acquires the peer object, even if the peer is not in a connected state. See [`Peer::state`].
     #[must_use]
     pub fn get_peer_mut(&mut self, peer: PeerID) -> Option<&mut Peer<S>> {
-        self.peers.get_mut(peer.0)
+        self.peers
+            .get_mut(peer.index())
+            .filter(|current_peer| current_peer.id() == peer)
     }
 
     /// Iterate over all peer objects.
@@ -335,6 +679,54 @@ impl<S: Socket> Host<S> {
         Ok(())
     }
 
+    /// Get the priority given to a channel of future connections, or [`None`] if `channel_id` is
+    /// not less than [`PROTOCOL_MAXIMUM_CHANNEL_COUNT`]. See [`Host::set_channel_priority`].
+    #[must_use]
+    pub fn channel_priority(&self, channel_id: u8) -> Option<u8> {
+        unsafe {
+            (*self.host)
+                .channel_priorities
+                .get(channel_id as usize)
+                .copied()
+        }
+    }
+
+    /// Set the priority of a channel for future connections, both incoming and outgoing. Every
+    /// channel has a priority of `0` by default. Use [`Peer::set_channel_priority`] to change the
+    /// priority for an existing connection.
+    ///
+    /// Priorities share the bandwidth of each peer by weighted fair queuing: while several
+    /// priorities have packets queued, each is sent a share of the data in proportion to its
+    /// priority plus one, so a channel with priority `3` gets four times the bandwidth of a
+    /// channel with priority `0`, and lower priority channels still make progress when higher
+    /// priority channels have more queued than the link can carry. A small packet on a channel
+    /// with nothing else queued jumps ahead of a bulk transfer on a lower priority channel,
+    /// including reliable packets waiting for room in the peer's window. Packets on the same
+    /// channel are always sent in order, channels with the same priority are sent in the order
+    /// their packets were queued, and protocol commands, such as pings and disconnections, are
+    /// sent with priority `0`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BadParameter`] if `channel_id` is not less than
+    /// [`PROTOCOL_MAXIMUM_CHANNEL_COUNT`].
+    pub fn set_channel_priority(
+        &mut self,
+        channel_id: u8,
+        priority: u8,
+    ) -> Result<(), BadParameter> {
+        if channel_id as u32 >= PROTOCOL_MAXIMUM_CHANNEL_COUNT {
+            return Err(BadParameter {
+                method: "Host::set_channel_priority",
+                parameter: "channel_id",
+            });
+        }
+        unsafe {
+            (*self.host).channel_priorities[channel_id as usize] = priority;
+        }
+        Ok(())
+    }
+
     /// Get the host's current bandwidth limit as (`incoming bandwidth`, `outgoing bandwidth`) in
     /// bytes/second. Returns [`None`] if there is no limit.
     #[must_use]
@@ -418,6 +810,246 @@ impl<S: Socket> Host<S> {
         Ok(())
     }
 
+    /// Set a callback to inspect every datagram received by the host, before ENet handles it, or
+    /// [`None`] to remove the callback.
+    ///
+    /// The callback receives the socket, the address of the sender, and the raw datagram. It may
+    /// respond using the socket, for example to answer LAN discovery or server browser queries on
+    /// the same port as ENet. See [`Intercept`] for how the return value is handled.
+    #[allow(clippy::type_complexity)]
+    pub fn set_intercept(
+        &mut self,
+        intercept: Option<Box<dyn FnMut(&mut S, &S::Address, &[u8]) -> Intercept<S::Error> + Send>>,
+    ) {
+        unsafe {
+            *(*self.host).intercept.assume_init_mut() = intercept;
+        }
+    }
+
+    /// Set a callback to decide whether to accept incoming connection requests, or [`None`] to
+    /// accept all of them.
+    ///
+    /// The callback is called before a peer is allocated for the connection, with the address of
+    /// the remote host, the number of channels it requested, the data it sent with the request (see
+    /// [`Host::connect`]), and the number of peers currently in use by this host. See
+    /// [`AcceptConnection`] for how the return value is handled.
+    ///
+    /// Useful for ban lists, version checks, or rejecting connections when the server is full,
+    /// without using up a peer.
+    #[allow(clippy::type_complexity)]
+    pub fn set_accept_connection(
+        &mut self,
+        accept_connection: Option<
+            Box<dyn FnMut(&S::Address, usize, u32, usize) -> AcceptConnection + Send>,
+        >,
+    ) {
+        unsafe {
+            *(*self.host).accept_connection.assume_init_mut() = accept_connection;
+        }
+    }
+
+    /// Get the maximum size of a packet, in bytes, that can be sent or received.
+    #[must_use]
+    pub fn maximum_packet_size(&self) -> usize {
+        unsafe { (*self.host).maximum_packet_size }
+    }
+
+    /// Set the maximum size of a packet, in bytes, that can be sent or received. Cannot be 0.
+    ///
+    /// Sending a larger packet fails with
+    /// [`PeerSendError::PacketTooLarge`](`crate::error::PeerSendError::PacketTooLarge`), and
+    /// incoming packets (or fragments of packets) claiming a larger size are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BadParameter`] if `maximum_packet_size` is `0`.
+    pub fn set_maximum_packet_size(
+        &mut self,
+        maximum_packet_size: usize,
+    ) -> Result<(), BadParameter> {
+        if maximum_packet_size == 0 {
+            return Err(BadParameter {
+                method: "Host::set_maximum_packet_size",
+                parameter: "maximum_packet_size",
+            });
+        }
+        unsafe {
+            (*self.host).maximum_packet_size = maximum_packet_size;
+        }
+        Ok(())
+    }
+
+    /// Get the maximum amount of received data, in bytes, that may be buffered for a peer.
+    #[must_use]
+    pub fn maximum_waiting_data(&self) -> usize {
+        unsafe { (*self.host).maximum_waiting_data }
+    }
+
+    /// Set the maximum amount of received data, in bytes, that may be buffered for a peer while
+    /// waiting to be dispatched. Cannot be 0.
+    ///
+    /// Once a peer reaches this limit, further incoming packets from it are discarded until
+    /// buffered packets are dispatched.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BadParameter`] if `maximum_waiting_data` is `0`.
+    pub fn set_maximum_waiting_data(
+        &mut self,
+        maximum_waiting_data: usize,
+    ) -> Result<(), BadParameter> {
+        if maximum_waiting_data == 0 {
+            return Err(BadParameter {
+                method: "Host::set_maximum_waiting_data",
+                parameter: "maximum_waiting_data",
+            });
+        }
+        unsafe {
+            (*self.host).maximum_waiting_data = maximum_waiting_data;
+        }
+        Ok(())
+    }
+
+    /// Get the maximum number of peers that may be connected from the same host.
+    #[must_use]
+    pub fn duplicate_peers(&self) -> usize {
+        unsafe { (*self.host).duplicate_peers }
+    }
+
+    /// Set the maximum number of peers that may be connected from the same host (as determined
+    /// by [`Address::same_host`](`crate::Address::same_host`)). Further incoming connections from
+    /// that host are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BadParameter`] if `duplicate_peers` is `0` or greater than
+    /// [`PROTOCOL_MAXIMUM_PEER_ID`].
+    pub fn set_duplicate_peers(&mut self, duplicate_peers: usize) -> Result<(), BadParameter> {
+        if duplicate_peers == 0 || duplicate_peers > PROTOCOL_MAXIMUM_PEER_ID as usize {
+            return Err(BadParameter {
+                method: "Host::set_duplicate_peers",
+                parameter: "duplicate_peers",
+            });
+        }
+        unsafe {
+            (*self.host).duplicate_peers = duplicate_peers;
+        }
+        Ok(())
+    }
+
+    /// Check if connecting hosts must echo a connect cookie before a peer is allocated for them.
+    #[must_use]
+    pub fn connect_cookies(&self) -> bool {
+        unsafe { (*self.host).connect_cookies }
+    }
+
+    /// Require connecting hosts to echo a connect cookie before a peer is allocated for them.
+    ///
+    /// Without cookies, every connection request occupies a peer until it times out, so a flood
+    /// of requests with spoofed source addresses can fill every peer slot. With cookies enabled,
+    /// the host answers a connection request with a cookie, a keyed hash of the remote address
+    /// (see [`Address::hash_address`](`crate::Address::hash_address`)) and the request, and
+    /// keeps no state for it. A peer is only allocated once the remote host sends the request
+    /// again along with the cookie, proving that it receives datagrams sent to its address.
+    ///
+    /// The key used to create cookies is derived from
+    /// [`HostSettings::connect_cookie_secret`], drawn from the operating system's random number
+    /// generator the first time cookies are enabled if there is none, and rotated every few
+    /// seconds. Hosts using this
+    /// library answer cookies automatically, but other ENet implementations cannot connect to a
+    /// host with cookies enabled. Disabled by default, in which case the protocol is unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BadParameter`] if `connect_cookies` is `true` but the host has no connect cookie
+    /// secret and none could be drawn from the operating system, see
+    /// [`HostSettings::connect_cookie_secret`].
+    pub fn set_connect_cookies(&mut self, connect_cookies: bool) -> Result<(), BadParameter> {
+        unsafe {
+            if connect_cookies && (*self.host).connect_cookie_secret.is_none() {
+                (*self.host).connect_cookie_secret = random_connect_cookie_secret();
+            }
+            if connect_cookies && (*self.host).connect_cookie_secret.is_none() {
+                return Err(BadParameter {
+                    method: "Host::set_connect_cookies",
+                    parameter: "connect_cookies",
+                });
+            }
+            if connect_cookies && !(*self.host).connect_cookies {
+                enet_host_rotate_connect_cookie_secret(self.host);
+                enet_host_rotate_connect_cookie_secret(self.host);
+            }
+            (*self.host).connect_cookies = connect_cookies;
+        }
+        Ok(())
+    }
+
+    /// Check if compression is skipped for peers whose datagrams it saves too little on.
+    #[must_use]
+    pub fn adaptive_compression(&self) -> bool {
+        unsafe { (*self.host).adaptive_compression }
+    }
+
+    /// Skip compression for peers whose datagrams it saves too little on.
+    ///
+    /// Every [`PEER_COMPRESSION_PROBE_ATTEMPTS`](`crate::consts::PEER_COMPRESSION_PROBE_ATTEMPTS`)
+    /// datagrams given to the compressor, the host checks how many bytes compression saved on
+    /// them. If it saved less than
+    /// [`PEER_COMPRESSION_MINIMUM_SAVINGS`](`crate::consts::PEER_COMPRESSION_MINIMUM_SAVINGS`) /
+    /// [`PEER_COMPRESSION_SAVINGS_SCALE`](`crate::consts::PEER_COMPRESSION_SAVINGS_SCALE`) of
+    /// their size, datagrams sent to the peer are not compressed for the next
+    /// [`PEER_COMPRESSION_BYPASS_INTERVAL`](`crate::consts::PEER_COMPRESSION_BYPASS_INTERVAL`)
+    /// milliseconds, after which compression is tried again. See
+    /// [`Peer::compression_bypassed`].
+    ///
+    /// Enabled by default. Compressing datagrams which do not get smaller costs CPU time, but
+    /// does not change what is sent.
+    pub fn set_adaptive_compression(&mut self, adaptive_compression: bool) {
+        unsafe {
+            (*self.host).adaptive_compression = adaptive_compression;
+            if !adaptive_compression {
+                for peer in &self.peers {
+                    (*peer.0).compression_bypassed = false;
+                    (*peer.0).compression_probe_attempts = 0;
+                    (*peer.0).compression_probe_bytes_in = 0;
+                    (*peer.0).compression_probe_bytes_out = 0;
+                }
+            }
+        }
+    }
+
+    /// Get traffic statistics for this host.
+    ///
+    /// The totals are counted since the host was created, or since the last call to
+    /// [`Host::reset_statistics`], and wrap on overflow.
+    #[must_use]
+    pub fn statistics(&self) -> HostStatistics {
+        unsafe {
+            HostStatistics {
+                total_sent_data: (*self.host).total_sent_data,
+                total_sent_packets: (*self.host).total_sent_packets,
+                total_received_data: (*self.host).total_received_data,
+                total_received_packets: (*self.host).total_received_packets,
+                total_unauthenticated_packets: (*self.host).total_unauthenticated_packets,
+                compression: (*self.host).compression_statistics,
+                connected_peers: (*self.host).connected_peers,
+                bandwidth_limited_peers: (*self.host).bandwidth_limited_peers,
+            }
+        }
+    }
+
+    /// Reset the sent and received totals returned by [`Host::statistics`] to zero.
+    pub fn reset_statistics(&mut self) {
+        unsafe {
+            (*self.host).total_sent_data = 0;
+            (*self.host).total_sent_packets = 0;
+            (*self.host).total_received_data = 0;
+            (*self.host).total_received_packets = 0;
+            (*self.host).total_unauthenticated_packets = 0;
+            (*self.host).compression_statistics = CompressionStatistics::default();
+        }
+    }
+
     /// Get the time according to this host, as provided by
     /// [`HostSettings::time`](`crate::HostSettings::time`).
     #[must_use]
@@ -425,6 +1057,24 @@ impl<S: Socket> Host<S> {
         unsafe { (*self.host).time.assume_init_ref()() }
     }
 
+    fn apply_sender_commands(&mut self) {
+        #[cfg(feature = "std")]
+        while let Some(command) = self
+            .sender
+            .as_ref()
+            .and_then(|(_, commands)| commands.try_recv().ok())
+        {
+            self.apply_sender_command(command);
+        }
+    }
+
+    fn pop_send_failure(&mut self) -> Option<(PeerID, u8, PeerSendError)> {
+        #[cfg(feature = "std")]
+        return self.send_failures.pop_front();
+        #[cfg(not(feature = "std"))]
+        None
+    }
+
     fn create_event<'a>(&'a mut self, event: &ENetEvent<S>) -> Event<'a, S> {
         match event.type_0 {
             ENET_EVENT_TYPE_CONNECT => Event::Connect {
@@ -434,18 +1084,75 @@ impl<S: Socket> Host<S> {
             ENET_EVENT_TYPE_DISCONNECT => Event::Disconnect {
                 peer: self.peer_mut(self.peer_index(event.peer)),
                 data: event.data,
+                reason: Self::disconnect_reason(event),
+            },
+            ENET_EVENT_TYPE_CONNECT_FAILED => Event::ConnectFailed {
+                peer: self.peer_mut(self.peer_index(event.peer)),
+                reason: Self::disconnect_reason(event),
             },
             ENET_EVENT_TYPE_RECEIVE => Event::Receive {
                 peer: self.peer_mut(self.peer_index(event.peer)),
                 channel_id: event.channel_id,
                 packet: Packet::new_from_ptr(event.packet),
             },
+            ENET_EVENT_TYPE_ACKNOWLEDGED => Event::Acknowledged {
+                peer: self.queued_event_peer_index(event),
+                token: SendToken::new(event.send_token),
+            },
+            ENET_EVENT_TYPE_DROPPED => Event::Dropped {
+                peer: self.queued_event_peer_index(event),
+                token: SendToken::new(event.send_token),
+            },
+            ENET_EVENT_TYPE_WRITABLE => Event::Writable {
+                peer: self.peer_mut(self.peer_index(event.peer)),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn disconnect_reason(event: &ENetEvent<S>) -> DisconnectReason {
+        match event.disconnect_reason {
+            ENET_DISCONNECT_REASON_REMOTE => DisconnectReason::Remote(event.data),
+            ENET_DISCONNECT_REASON_REFUSED => DisconnectReason::Refused(event.data),
+            ENET_DISCONNECT_REASON_LOCAL => DisconnectReason::Local,
+            ENET_DISCONNECT_REASON_TIMEOUT => DisconnectReason::Timeout,
+            ENET_DISCONNECT_REASON_CONNECT_FAILED => DisconnectReason::ConnectFailed,
             _ => unreachable!(),
         }
     }
 
     fn peer_index(&self, peer: *const ENetPeer<S>) -> PeerID {
-        PeerID(unsafe { peer.offset_from((*self.host).peers) as usize })
+        unsafe {
+            PeerID::new(
+                peer.offset_from((*self.host).peers) as usize,
+                (*peer).generation,
+            )
+        }
+    }
+
+    /// The peer of an event queued before it was dispatched, with the generation it had when the
+    /// event was queued.
+    fn queued_event_peer_index(&self, event: &ENetEvent<S>) -> PeerID {
+        unsafe {
+            PeerID::new(
+                event.peer.offset_from((*self.host).peers) as usize,
+                event.peer_generation,
+            )
+        }
+    }
+}
+
+/// Draws a connect cookie secret from the operating system's random number generator.
+fn random_connect_cookie_secret() -> Option<[u8; 16]> {
+    #[cfg(feature = "std")]
+    {
+        let mut secret = [0; 16];
+        getrandom::getrandom(&mut secret).ok()?;
+        Some(secret)
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        None
     }
 }
 
@@ -485,17 +1192,31 @@ impl<S: Socket> Debug for Host<S> {
             .field("checksum", &host.checksum)
             .field("time", &host.time)
             .field("compressor", &host.compressor)
+            .field("cipher", &host.cipher)
+            .field("authenticator", &host.authenticator)
+            .field("intercept", &host.intercept)
+            .field("acceptConnection", &host.accept_connection)
             .field("packetData", &host.packet_data)
             .field("receivedAddress", &host.received_address)
             .field("receivedData", &host.received_data)
             .field("receivedDataLength", &host.received_data_length)
+            .field("receivedBatchIndex", &host.received_batch_index)
+            .field("receivedBatchCount", &host.received_batch_count)
             .field("totalSentData", &host.total_sent_data)
             .field("totalSentPackets", &host.total_sent_packets)
             .field("totalReceivedData", &host.total_received_data)
             .field("totalReceivedPackets", &host.total_received_packets)
+            .field(
+                "totalUnauthenticatedPackets",
+                &host.total_unauthenticated_packets,
+            )
             .field("connectedPeers", &host.connected_peers)
             .field("bandwidthLimitedPeers", &host.bandwidth_limited_peers)
             .field("duplicatePeers", &host.duplicate_peers)
+            .field("connectCookies", &host.connect_cookies)
+            .field("connectCookieEpoch", &host.connect_cookie_epoch)
+            .field("adaptiveCompression", &host.adaptive_compression)
+            .field("compressionStatistics", &host.compression_statistics)
             .field("maximumPacketSize", &host.maximum_packet_size)
             .field("maximumWaitingData", &host.maximum_waiting_data)
             .field("peers", &self.peers)
@@ -141,27 +141,39 @@ extern crate std;
 extern crate alloc;
 
 mod address;
+mod authenticator;
 mod c;
+mod cipher;
 mod compressor;
 mod crc32;
 mod event;
 mod host;
 mod packet;
 mod peer;
+mod range_coder;
 mod read_write;
+#[cfg(feature = "std")]
+mod sender;
+mod siphash;
 mod socket;
 mod time;
 mod version;
 
 pub use address::*;
+pub use authenticator::*;
 pub(crate) use c::*;
+pub use cipher::*;
 pub use compressor::*;
 pub use crc32::*;
 pub use event::*;
 pub use host::*;
 pub use packet::*;
 pub use peer::*;
+pub(crate) use range_coder::*;
 pub use read_write::*;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub use sender::*;
 pub use socket::*;
 pub use time::*;
 pub use version::*;
@@ -170,6 +182,9 @@ pub use version::*;
 #[cfg_attr(docsrs, doc(cfg(feature = "connected")))]
 pub mod connected;
 pub mod error;
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub mod tokio;
 
 /// Constants provided by ENet.
 #[allow(missing_docs)]
@@ -180,7 +195,15 @@ pub mod consts;
 mod test;
 
 #[cfg(feature = "std")]
-pub(crate) use std::{boxed::Box, collections::VecDeque, vec::Vec};
+pub(crate) use std::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    vec::Vec,
+};
 
 #[cfg(not(feature = "std"))]
-pub(crate) use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+pub(crate) use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    vec::Vec,
+};
@@ -1,4 +1,4 @@
-use core::fmt::Debug;
+use core::{fmt::Debug, sync::atomic::Ordering};
 
 use crate::{
     enet_packet_create, enet_packet_destroy, Box, ENetPacket, Vec, ENET_PACKET_FLAG_NO_ALLOCATE,
@@ -80,7 +80,7 @@ impl Packet {
         unsafe {
             (*packet).free_callback = Some(Self::drop_packet_data::<P>);
             (*packet).user_data = raw_packet.user_data as *mut _;
-            (*packet).reference_count += 1;
+            (*packet).reference_count.fetch_add(1, Ordering::Relaxed);
         }
         Self { packet }
     }
@@ -122,7 +122,7 @@ impl Packet {
     /// Get this packet's [`PacketKind`].
     #[must_use]
     pub fn kind(&self) -> PacketKind {
-        let flags = unsafe { (*self.packet).flags | !ENET_PACKET_FLAG_SENT };
+        let flags = unsafe { (*self.packet).flags & !ENET_PACKET_FLAG_SENT };
         let sequenced = flags & ENET_PACKET_FLAG_UNSEQUENCED == 0;
         if flags & ENET_PACKET_FLAG_RELIABLE != 0 {
             PacketKind::Reliable
@@ -141,7 +141,7 @@ impl Packet {
 
     pub(crate) fn new_from_ptr(packet: *mut ENetPacket) -> Self {
         unsafe {
-            (*packet).reference_count += 1;
+            (*packet).reference_count.fetch_add(1, Ordering::Relaxed);
         }
         Self { packet }
     }
@@ -158,7 +158,9 @@ impl Packet {
 impl Clone for Packet {
     fn clone(&self) -> Self {
         unsafe {
-            (*self.packet).reference_count += 1;
+            (*self.packet)
+                .reference_count
+                .fetch_add(1, Ordering::Relaxed);
         }
         Self {
             packet: self.packet,
@@ -169,8 +171,11 @@ impl Clone for Packet {
 impl Drop for Packet {
     fn drop(&mut self) {
         unsafe {
-            (*self.packet).reference_count -= 1;
-            if (*self.packet).reference_count == 0 {
+            if (*self.packet)
+                .reference_count
+                .fetch_sub(1, Ordering::AcqRel)
+                == 1
+            {
                 enet_packet_destroy(self.packet);
             }
         }
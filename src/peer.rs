@@ -1,27 +1,89 @@
-use core::{fmt::Debug, time::Duration};
+use core::{any::Any, fmt::Debug, time::Duration};
 
 use crate::{
     consts::{PROTOCOL_MAXIMUM_MTU, PROTOCOL_MAXIMUM_PEER_ID, PROTOCOL_MINIMUM_MTU},
     enet_peer_disconnect, enet_peer_disconnect_later, enet_peer_disconnect_now, enet_peer_ping,
-    enet_peer_ping_interval, enet_peer_reset, enet_peer_send, enet_peer_throttle_configure,
-    enet_peer_timeout,
+    enet_peer_ping_interval, enet_peer_reset, enet_peer_send, enet_peer_set_channel_priority,
+    enet_peer_throttle_configure, enet_peer_timeout,
     error::{BadParameter, PeerSendError},
-    ENetPeer, Packet, Socket, ENET_PEER_STATE_ACKNOWLEDGING_CONNECT,
-    ENET_PEER_STATE_ACKNOWLEDGING_DISCONNECT, ENET_PEER_STATE_CONNECTED,
-    ENET_PEER_STATE_CONNECTING, ENET_PEER_STATE_CONNECTION_PENDING,
+    Box, CompressionStatistics, ENetPeer, NonceCounter, Packet, PacketKind, ReplayWindow, Socket,
+    ENET_PEER_STATE_ACKNOWLEDGING_CONNECT, ENET_PEER_STATE_ACKNOWLEDGING_DISCONNECT,
+    ENET_PEER_STATE_CONNECTED, ENET_PEER_STATE_CONNECTING, ENET_PEER_STATE_CONNECTION_PENDING,
     ENET_PEER_STATE_CONNECTION_SUCCEEDED, ENET_PEER_STATE_DISCONNECTED,
     ENET_PEER_STATE_DISCONNECTING, ENET_PEER_STATE_DISCONNECT_LATER, ENET_PEER_STATE_ZOMBIE,
 };
 
-/// A newtype around a `usize`, representing a unique identifier for a peer.
+/// A unique identifier for a peer, made up of the index of the peer in its
+/// [`Host`](`crate::Host`), and a generation which changes each time the peer is reused for a new
+/// connection.
+///
+/// A [`PeerID`] held onto after its connection has ended does not refer to a new connection using
+/// the same peer, [`Host::get_peer`](`crate::Host::get_peer`) returns [`None`] instead.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct PeerID(pub usize);
+pub struct PeerID {
+    index: usize,
+    generation: u32,
+}
 
 impl PeerID {
-    /// The minimum valid value a [`PeerID`] can be.
+    /// The minimum valid index a [`PeerID`] can have.
     pub const MIN: usize = 0;
-    /// The maximum valid value a [`PeerID`] can be.
+    /// The maximum valid index a [`PeerID`] can have.
     pub const MAX: usize = PROTOCOL_MAXIMUM_PEER_ID as usize;
+
+    pub(crate) const fn new(index: usize, generation: u32) -> Self {
+        Self { index, generation }
+    }
+
+    /// The index of the peer in its [`Host`](`crate::Host`), which is shared by every connection
+    /// using the same peer.
+    #[must_use]
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The generation of the peer, incremented each time the peer is reused for a new connection.
+    #[must_use]
+    pub const fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// Identifies a reliable packet sent with [`Peer::send_tracked`].
+///
+/// Once the peer has acknowledged every fragment of the packet, an
+/// [`Event::Acknowledged`](`crate::Event::Acknowledged`) with this token is generated. If the
+/// packet is discarded before then, because the peer disconnected or was reset, an
+/// [`Event::Dropped`](`crate::Event::Dropped`) is generated instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SendToken(u64);
+
+impl SendToken {
+    pub(crate) const fn new(token: u64) -> Self {
+        Self(token)
+    }
+}
+
+/// Settings for an unreliable packet, passed into [`Peer::send_with_settings`].
+///
+/// Only apply while the packet is waiting in the peer's outgoing queue, for example because the
+/// datagrams sent to the peer are full, or are being throttled. They are ignored for
+/// [`PacketKind::Unreliable`] packets which are sent reliably, because they are too large to fit
+/// within the MTU.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SendSettings {
+    /// How long the packet may wait to be sent before it is discarded, or [`None`] to wait
+    /// indefinitely.
+    ///
+    /// Rounded down to whole milliseconds. Packets with a time to live of a day or more never
+    /// expire.
+    pub time_to_live: Option<Duration>,
+    /// Discard any packets queued on the same channel with the same key, which have not been sent
+    /// yet, or [`None`] to keep them.
+    ///
+    /// Packets are only discarded if this packet is queued successfully. The remaining fragments of
+    /// a packet which was partially sent, because sending a datagram failed, are discarded too.
+    pub supersede_key: Option<u32>,
 }
 
 /// The state of a [`Peer`].
@@ -52,7 +114,12 @@ impl<S: Socket> Peer<S> {
     /// Get the [`PeerID`] of this peer.
     #[must_use]
     pub fn id(&self) -> PeerID {
-        PeerID(unsafe { self.0.offset_from((*(*self.0).host).peers) as usize })
+        unsafe {
+            PeerID::new(
+                self.0.offset_from((*(*self.0).host).peers) as usize,
+                (*self.0).generation,
+            )
+        }
     }
 
     /// Sends a ping request to a peer.
@@ -72,7 +139,63 @@ impl<S: Socket> Peer<S> {
     ///
     /// May return any of the [`PeerSendError`] variants on failure.
     pub fn send(&mut self, channel_id: u8, packet: &Packet) -> Result<(), PeerSendError> {
-        unsafe { enet_peer_send(self.0, channel_id, packet.packet) }
+        unsafe { enet_peer_send(self.0, channel_id, packet.packet, 0, None, None) }
+    }
+
+    /// Like [`Peer::send`], but with [`SendSettings`] for an unreliable packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PeerSendError::NotUnreliable`] if the packet is
+    /// [`PacketKind::Reliable`] and `settings` is not the default, otherwise may return any of
+    /// the [`PeerSendError`] variants on failure.
+    pub fn send_with_settings(
+        &mut self,
+        channel_id: u8,
+        packet: &Packet,
+        settings: SendSettings,
+    ) -> Result<(), PeerSendError> {
+        if packet.kind() == PacketKind::Reliable && settings != SendSettings::default() {
+            return Err(PeerSendError::NotUnreliable);
+        }
+        unsafe {
+            enet_peer_send(
+                self.0,
+                channel_id,
+                packet.packet,
+                0,
+                settings
+                    .time_to_live
+                    .and_then(|time_to_live| u32::try_from(time_to_live.as_millis()).ok())
+                    .filter(|&time_to_live| time_to_live < 86_400_000),
+                settings.supersede_key,
+            )
+        }
+    }
+
+    /// Like [`Peer::send`], but returns a [`SendToken`] for tracking delivery of the packet. See
+    /// [`SendToken`] for the events generated.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PeerSendError::NotReliable`] if the packet is not [`PacketKind::Reliable`],
+    /// otherwise may return any of the
+    /// [`PeerSendError`] variants on failure.
+    pub fn send_tracked(
+        &mut self,
+        channel_id: u8,
+        packet: &Packet,
+    ) -> Result<SendToken, PeerSendError> {
+        if packet.kind() != PacketKind::Reliable {
+            return Err(PeerSendError::NotReliable);
+        }
+        unsafe {
+            let host = (*self.0).host;
+            let send_token = (*host).send_token.wrapping_add(1);
+            enet_peer_send(self.0, channel_id, packet.packet, send_token, None, None)?;
+            (*host).send_token = send_token;
+            Ok(SendToken::new(send_token))
+        }
     }
 
     /// Request a disconnection from a peer.
@@ -234,6 +357,107 @@ impl<S: Socket> Peer<S> {
         unsafe { (*self.0).channel_count }
     }
 
+    /// Bytes of packet data queued to be sent to this peer, which have not been sent yet.
+    ///
+    /// Reliable packets being resent are not counted again, see [`Peer::reliable_in_flight`].
+    #[must_use]
+    pub fn queued_bytes(&self) -> usize {
+        unsafe { (*self.0).queued_data }
+    }
+
+    /// Bytes of reliable packet data sent to this peer, which have not been acknowledged yet.
+    #[must_use]
+    pub fn reliable_in_flight(&self) -> u32 {
+        unsafe { (*self.0).reliable_data_in_transit }
+    }
+
+    /// The maximum bytes of reliable packet data which may be in flight to this peer at once,
+    /// before being scaled down by the packet throttle. Negotiated from the bandwidth limits of
+    /// both hosts, see [`Host::set_bandwidth_limit`](`crate::Host::set_bandwidth_limit`).
+    #[must_use]
+    pub fn window_size(&self) -> u32 {
+        unsafe { (*self.0).window_size }
+    }
+
+    /// Get the (`low`, `high`) watermarks for this peer's queued data. See
+    /// [`Peer::set_queue_watermarks`].
+    #[must_use]
+    pub fn queue_watermarks(&self) -> (usize, usize) {
+        unsafe {
+            (
+                (*self.0).queue_low_watermark,
+                (*self.0).queue_high_watermark,
+            )
+        }
+    }
+
+    /// Set watermarks for this peer's queued data, see [`Peer::queued_bytes`].
+    ///
+    /// Once the queued data rises above `high` and then drains to `low` or below, an
+    /// [`Event::Writable`](`crate::Event::Writable`) is generated. Producers sending a large
+    /// amount of data can stop sending while the queued data is above `high`, and continue on
+    /// the event, instead of queueing all of it at once.
+    ///
+    /// The watermarks are reset when the peer is, and are disabled by default, as if `high` was
+    /// [`usize::MAX`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BadParameter`] if `low` is greater than `high`.
+    pub fn set_queue_watermarks(&mut self, low: usize, high: usize) -> Result<(), BadParameter> {
+        if low > high {
+            return Err(BadParameter {
+                method: "Peer::set_queue_watermarks",
+                parameter: "low",
+            });
+        }
+        unsafe {
+            (*self.0).queue_low_watermark = low;
+            (*self.0).queue_high_watermark = high;
+            (*self.0).queue_above_high_watermark = (*self.0).queued_data > high;
+        }
+        Ok(())
+    }
+
+    /// Get the priority of one of this peer's channels, or [`None`] if the channel does not
+    /// exist. See [`Host::set_channel_priority`](`crate::Host::set_channel_priority`).
+    #[must_use]
+    pub fn channel_priority(&self, channel_id: u8) -> Option<u8> {
+        unsafe {
+            if (channel_id as usize) < (*self.0).channel_count {
+                Some((*(*self.0).channels.add(channel_id as usize)).priority)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Set the priority of one of this peer's channels. See
+    /// [`Host::set_channel_priority`](`crate::Host::set_channel_priority`).
+    ///
+    /// Packets already queued on the channel move to the new priority, and stay in order with
+    /// each other and with packets sent afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BadParameter`] if `channel_id` is not less than [`Peer::channel_count`].
+    pub fn set_channel_priority(
+        &mut self,
+        channel_id: u8,
+        priority: u8,
+    ) -> Result<(), BadParameter> {
+        unsafe {
+            if channel_id as usize >= (*self.0).channel_count {
+                return Err(BadParameter {
+                    method: "Peer::set_channel_priority",
+                    parameter: "channel_id",
+                });
+            }
+            enet_peer_set_channel_priority(self.0, channel_id, priority);
+        }
+        Ok(())
+    }
+
     /// Downstream bandwidth of the client in bytes/second.
     #[must_use]
     pub fn incoming_bandwidth(&self) -> u32 {
@@ -283,6 +507,36 @@ impl<S: Socket> Peer<S> {
         unsafe { (*self.0).packet_loss_variance }
     }
 
+    /// Compression statistics for datagrams sent to this peer.
+    #[must_use]
+    pub fn compression_statistics(&self) -> CompressionStatistics {
+        unsafe { (*self.0).compression_statistics }
+    }
+
+    /// Check if datagrams sent to this peer may be compressed. See [`Peer::set_compression`].
+    #[must_use]
+    pub fn compression(&self) -> bool {
+        unsafe { (*self.0).compression }
+    }
+
+    /// Allow or prevent compressing datagrams sent to this peer, for example when the peer's
+    /// traffic is already compressed. Has no effect if the host has no
+    /// [`HostSettings::compressor`](`crate::HostSettings::compressor`).
+    ///
+    /// Enabled by default, and enabled again when the peer disconnects.
+    pub fn set_compression(&mut self, compression: bool) {
+        unsafe {
+            (*self.0).compression = compression;
+        }
+    }
+
+    /// Check if compression is currently skipped for this peer because it was saving too little.
+    /// See [`Host::set_adaptive_compression`](`crate::Host::set_adaptive_compression`).
+    #[must_use]
+    pub fn compression_bypassed(&self) -> bool {
+        unsafe { (*self.0).compression_bypassed }
+    }
+
     /// Ping interval. See [`Peer::set_ping_interval`].
     #[must_use]
     pub fn ping_interval(&self) -> Duration {
@@ -311,13 +565,122 @@ impl<S: Socket> Peer<S> {
     pub fn address(&self) -> Option<S::Address> {
         unsafe { (*self.0).address.assume_init_ref().clone() }
     }
+
+    /// Get a reference to the user data attached to this peer with [`Peer::set_user_data`], or
+    /// [`None`] if there is no user data or it is not of type `T`.
+    ///
+    /// User data belongs to a single connection. It remains available while handling the peer's
+    /// [`Event::Disconnect`](`crate::Event::Disconnect`), and is dropped when the peer is reset
+    /// or reused for a new connection.
+    #[must_use]
+    pub fn user_data<T: Any>(&self) -> Option<&T> {
+        unsafe { (*self.0).data.assume_init_ref().as_ref()?.downcast_ref() }
+    }
+
+    /// Get a mutable reference to the user data attached to this peer with
+    /// [`Peer::set_user_data`], or [`None`] if there is no user data or it is not of type `T`.
+    #[must_use]
+    pub fn user_data_mut<T: Any>(&mut self) -> Option<&mut T> {
+        unsafe { (*self.0).data.assume_init_mut().as_mut()?.downcast_mut() }
+    }
+
+    /// Attach user data to this peer, dropping any previous user data.
+    ///
+    /// See [`Peer::user_data`] for how long user data lives.
+    pub fn set_user_data<T: Any + Send + Sync>(&mut self, user_data: T) {
+        unsafe {
+            *(*self.0).data.assume_init_mut() = Some(Box::new(user_data));
+        }
+    }
+
+    /// Remove the user data attached to this peer and return it, or [`None`] if there is no user
+    /// data or it is not of type `T`, in which case it is left in place.
+    pub fn take_user_data<T: Any>(&mut self) -> Option<T> {
+        let data = unsafe { (*self.0).data.assume_init_mut() };
+        if data.as_ref()?.is::<T>() {
+            data.take()?.downcast().ok().map(|user_data| *user_data)
+        } else {
+            None
+        }
+    }
+
+    /// Set the key used to encrypt datagrams sent to and received from this peer, or [`None`] to
+    /// stop encrypting them. Requires a [`HostSettings::cipher`](`crate::HostSettings::cipher`).
+    ///
+    /// The key is usually agreed on with an application level key exchange after connecting, and
+    /// both hosts should set the same key. Once the key is set, datagrams from the peer which are
+    /// not encrypted or fail to decrypt are discarded, and a peer discards encrypted datagrams
+    /// until it has set its own key. Reliable packets sent by either peer while only one of them
+    /// has set its key are resent.
+    ///
+    /// The key is cleared when the peer disconnects.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BadParameter`] if the host has no cipher, or if `key` is not valid for the
+    /// host's cipher (see [`Cipher::valid_key`](`crate::Cipher::valid_key`)).
+    pub fn set_cipher_key(&mut self, key: Option<&[u8]>) -> Result<(), BadParameter> {
+        unsafe {
+            if let Some(key) = key {
+                let valid_key = (*(*self.0).host)
+                    .cipher
+                    .assume_init_ref()
+                    .as_ref()
+                    .is_some_and(|cipher| cipher.valid_key(key));
+                if !valid_key {
+                    return Err(BadParameter {
+                        method: "Peer::set_cipher_key",
+                        parameter: "key",
+                    });
+                }
+            }
+            *(*self.0).cipher_key.assume_init_mut() = key.map(Box::from);
+            (*self.0).cipher_replay_window = ReplayWindow::new();
+            (*self.0).cipher_nonce_counter = NonceCounter::new();
+        }
+        Ok(())
+    }
+
+    /// Set the key used to authenticate datagrams sent to and received from this peer, or [`None`]
+    /// to stop authenticating them. Requires a
+    /// [`HostSettings::authenticator`](`crate::HostSettings::authenticator`).
+    ///
+    /// Like [`Peer::set_cipher_key`], both hosts should set the same key. Once the key is set,
+    /// datagrams from the peer which are not authenticated or fail to authenticate are discarded,
+    /// and a peer discards authenticated datagrams until it has set its own key. Reliable packets
+    /// sent by either peer while only one of them has set its key are resent.
+    ///
+    /// The key is cleared when the peer disconnects.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BadParameter`] if the host has no authenticator, or if `key` is not valid for the
+    /// host's authenticator (see [`Authenticator::valid_key`](`crate::Authenticator::valid_key`)).
+    pub fn set_auth_key(&mut self, key: Option<&[u8]>) -> Result<(), BadParameter> {
+        unsafe {
+            if let Some(key) = key {
+                let valid_key = (*(*self.0).host)
+                    .authenticator
+                    .assume_init_ref()
+                    .as_ref()
+                    .is_some_and(|authenticator| authenticator.valid_key(key));
+                if !valid_key {
+                    return Err(BadParameter {
+                        method: "Peer::set_auth_key",
+                        parameter: "key",
+                    });
+                }
+            }
+            *(*self.0).auth_key.assume_init_mut() = key.map(Box::from);
+        }
+        Ok(())
+    }
 }
 
 impl<S: Socket> Debug for Peer<S> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let peer = unsafe { &(*self.0) };
         f.debug_struct("Peer")
-            .field("dispatchList", &core::ptr::addr_of!(peer.dispatch_list))
             .field("host", &peer.host)
             .field("outgoingPeerID", &peer.outgoing_peer_id)
             .field("incomingPeerID", &peer.incoming_peer_id)
@@ -326,6 +689,9 @@ impl<S: Socket> Debug for Peer<S> {
             .field("incomingSessionID", &peer.incoming_session_id)
             .field("address", &peer.address)
             .field("data", &peer.data)
+            .field("generation", &peer.generation)
+            .field("cipherReplayWindow", &peer.cipher_replay_window)
+            .field("cipherNonceCounter", &peer.cipher_nonce_counter)
             .field("state", &peer.state)
             .field("channels", &peer.channels)
             .field("channelCount", &peer.channel_count)
@@ -345,11 +711,17 @@ impl<S: Socket> Debug for Peer<S> {
             .field("lastReceiveTime", &peer.last_receive_time)
             .field("nextTimeout", &peer.next_timeout)
             .field("earliestTimeout", &peer.earliest_timeout)
+            .field("connectTime", &peer.connect_time)
+            .field("connectTimeout", &peer.connect_timeout)
+            .field("connectRetryLimit", &peer.connect_retry_limit)
             .field("packetLossEpoch", &peer.packet_loss_epoch)
             .field("packetsSent", &peer.packets_sent)
             .field("packetsLost", &peer.packets_lost)
             .field("packetLoss", &peer.packet_loss)
             .field("packetLossVariance", &peer.packet_loss_variance)
+            .field("compression", &peer.compression)
+            .field("compressionStatistics", &peer.compression_statistics)
+            .field("compressionBypassed", &peer.compression_bypassed)
             .field("packetThrottle", &peer.packet_throttle)
             .field("packetThrottleLimit", &peer.packet_throttle_limit)
             .field("packetThrottleCounter", &peer.packet_throttle_counter)
@@ -388,23 +760,23 @@ impl<S: Socket> Debug for Peer<S> {
             )
             .field(
                 "acknowledgements",
-                &core::ptr::addr_of!(peer.acknowledgements),
+                &core::ptr::addr_of!(peer.queues.acknowledgements),
             )
             .field(
                 "sentReliableCommands",
-                &core::ptr::addr_of!(peer.sent_reliable_commands),
+                &core::ptr::addr_of!(peer.queues.sent_reliable_commands),
             )
             .field(
                 "outgoingSendReliableCommands",
-                &core::ptr::addr_of!(peer.outgoing_send_reliable_commands),
+                &core::ptr::addr_of!(peer.queues.outgoing_send_reliable_commands),
             )
             .field(
                 "outgoingCommands",
-                &core::ptr::addr_of!(peer.outgoing_commands),
+                &core::ptr::addr_of!(peer.queues.outgoing_commands),
             )
             .field(
                 "dispatchedCommands",
-                &core::ptr::addr_of!(peer.dispatched_commands),
+                &core::ptr::addr_of!(peer.queues.dispatched_commands),
             )
             .field("flags", &peer.flags)
             .field("reserved", &peer.reserved)
@@ -412,7 +784,12 @@ impl<S: Socket> Debug for Peer<S> {
             .field("outgoingUnsequencedGroup", &peer.outgoing_unsequenced_group)
             .field("unsequencedWindow", &peer.unsequenced_window)
             .field("eventData", &peer.event_data)
+            .field("disconnectReason", &peer.disconnect_reason)
             .field("totalWaitingData", &peer.total_waiting_data)
+            .field("queuedData", &peer.queued_data)
+            .field("queueLowWatermark", &peer.queue_low_watermark)
+            .field("queueHighWatermark", &peer.queue_high_watermark)
+            .field("queueAboveHighWatermark", &peer.queue_above_high_watermark)
             .finish()
     }
 }
@@ -0,0 +1,655 @@
+//! A safe implementation of ENet's range coder, producing the same output as the original.
+//!
+//! The model is a set of context trees stored in a fixed array of symbols, where `left`, `right`
+//! and `symbols` are offsets from the symbol they belong to, and `parent` is an index into the
+//! array. The layout matches ENet's, so a model built by one implementation can be used by the
+//! other.
+
+use core::cmp::Ordering;
+
+use crate::Vec;
+
+const CONTEXT_SYMBOL_MINIMUM: u16 = 1;
+const CONTEXT_ESCAPE_MINIMUM: u16 = 1;
+const SUBCONTEXT_ORDER: usize = 2;
+const RANGE_CODER_BOTTOM: u32 = 1 << 16;
+const SUBCONTEXT_SYMBOL_DELTA: u8 = 2;
+const SUBCONTEXT_ESCAPE_DELTA: u16 = 5;
+const CONTEXT_SYMBOL_DELTA: u8 = 3;
+const RANGE_CODER_TOP: u32 = 1 << 24;
+
+/// The number of symbols in a range coder's model.
+pub(crate) const RANGE_CODER_SYMBOLS: usize = 4096;
+
+/// The index of the root context, which codes any symbol not found in a subcontext.
+const ROOT: usize = 0;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RangeCoderSymbol {
+    pub(crate) value: u8,
+    pub(crate) count: u8,
+    pub(crate) under: u16,
+    pub(crate) left: u16,
+    pub(crate) right: u16,
+    pub(crate) symbols: u16,
+    pub(crate) escapes: u16,
+    pub(crate) total: u16,
+    pub(crate) parent: u16,
+}
+
+/// The state of the adaptive model between calls, used to start from a model primed with a
+/// dictionary instead of an empty one. A `next_symbol` of `0` means no model has been built yet.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RangeCoderModel {
+    pub(crate) next_symbol: usize,
+    pub(crate) predicted: u16,
+    pub(crate) order: usize,
+}
+
+/// Where the next symbol found should be recorded, either as the prediction for the next value or
+/// as the parent of the symbol found in the previous context.
+#[derive(Clone, Copy)]
+enum Parent {
+    Predicted,
+    Symbol(usize),
+}
+
+struct Model<'a> {
+    symbols: &'a mut [RangeCoderSymbol],
+    next_symbol: usize,
+    predicted: u16,
+    order: usize,
+}
+
+impl<'a> Model<'a> {
+    fn new(symbols: &'a mut [RangeCoderSymbol], model: Option<&RangeCoderModel>) -> Self {
+        match model {
+            Some(model) if model.next_symbol > 0 => Self {
+                symbols,
+                next_symbol: model.next_symbol,
+                predicted: model.predicted,
+                order: model.order,
+            },
+            _ => {
+                let mut model = Self {
+                    symbols,
+                    next_symbol: 0,
+                    predicted: 0,
+                    order: 0,
+                };
+                model.reset();
+                model
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.next_symbol = 1;
+        self.symbols[ROOT] = RangeCoderSymbol {
+            escapes: CONTEXT_ESCAPE_MINIMUM,
+            total: CONTEXT_ESCAPE_MINIMUM + 256 * CONTEXT_SYMBOL_MINIMUM,
+            ..RangeCoderSymbol::default()
+        };
+        self.predicted = 0;
+        self.order = 0;
+    }
+
+    fn state(&self) -> RangeCoderModel {
+        RangeCoderModel {
+            next_symbol: self.next_symbol,
+            predicted: self.predicted,
+            order: self.order,
+        }
+    }
+
+    fn create_symbol(&mut self, value: u8, delta: u8) -> usize {
+        let symbol = self.next_symbol;
+        self.next_symbol += 1;
+        self.symbols[symbol] = RangeCoderSymbol {
+            value,
+            count: delta,
+            under: u16::from(delta),
+            ..RangeCoderSymbol::default()
+        };
+        symbol
+    }
+
+    fn set_parent(&mut self, parent: Parent, symbol: usize) {
+        match parent {
+            Parent::Predicted => self.predicted = symbol as u16,
+            Parent::Symbol(parent) => self.symbols[parent].parent = symbol as u16,
+        }
+    }
+
+    fn rescale(&mut self, mut symbol: usize) -> u16 {
+        let mut total: u16 = 0;
+        loop {
+            let count = self.symbols[symbol].count;
+            self.symbols[symbol].count = count - (count >> 1);
+            self.symbols[symbol].under = u16::from(self.symbols[symbol].count);
+            if self.symbols[symbol].left != 0 {
+                let under = self.rescale(symbol + usize::from(self.symbols[symbol].left));
+                self.symbols[symbol].under = self.symbols[symbol].under.wrapping_add(under);
+            }
+            total = total.wrapping_add(self.symbols[symbol].under);
+            if self.symbols[symbol].right == 0 {
+                break;
+            }
+            symbol += usize::from(self.symbols[symbol].right);
+        }
+        total
+    }
+
+    fn rescale_context(&mut self, context: usize, minimum: u16) {
+        let context_symbols = self.symbols[context].symbols;
+        let total = if context_symbols != 0 {
+            self.rescale(context + usize::from(context_symbols))
+        } else {
+            0
+        };
+        let escapes = self.symbols[context].escapes;
+        let escapes = escapes - (escapes >> 1);
+        self.symbols[context].escapes = escapes;
+        self.symbols[context].total = total.wrapping_add(escapes).wrapping_add(minimum);
+    }
+
+    /// Find `value` in a context's tree, adding it if it is missing. Returns the symbol, and the
+    /// `under` and `count` of the value if it was found.
+    fn find_symbol(&mut self, context: usize, value: u8, delta: u8) -> (usize, u16, u16) {
+        let mut under: u16 = 0;
+        let mut count: u16 = 0;
+        if self.symbols[context].symbols == 0 {
+            let symbol = self.create_symbol(value, delta);
+            self.symbols[context].symbols = (symbol - context) as u16;
+            return (symbol, under, count);
+        }
+        let mut node = context + usize::from(self.symbols[context].symbols);
+        loop {
+            match value.cmp(&self.symbols[node].value) {
+                Ordering::Less => {
+                    self.symbols[node].under =
+                        self.symbols[node].under.wrapping_add(u16::from(delta));
+                    if self.symbols[node].left != 0 {
+                        node += usize::from(self.symbols[node].left);
+                    } else {
+                        let symbol = self.create_symbol(value, delta);
+                        self.symbols[node].left = (symbol - node) as u16;
+                        return (symbol, under, count);
+                    }
+                }
+                Ordering::Greater => {
+                    under = under.wrapping_add(self.symbols[node].under);
+                    if self.symbols[node].right != 0 {
+                        node += usize::from(self.symbols[node].right);
+                    } else {
+                        let symbol = self.create_symbol(value, delta);
+                        self.symbols[node].right = (symbol - node) as u16;
+                        return (symbol, under, count);
+                    }
+                }
+                Ordering::Equal => {
+                    let symbol = &mut self.symbols[node];
+                    count = count.wrapping_add(u16::from(symbol.count));
+                    under = under
+                        .wrapping_add(symbol.under)
+                        .wrapping_sub(u16::from(symbol.count));
+                    symbol.under = symbol.under.wrapping_add(u16::from(delta));
+                    symbol.count = symbol.count.wrapping_add(delta);
+                    return (node, under, count);
+                }
+            }
+        }
+    }
+
+    /// Move on to the next value, resetting the model if it is about to run out of symbols.
+    fn advance(&mut self) {
+        if self.order >= SUBCONTEXT_ORDER {
+            self.predicted = self.symbols[usize::from(self.predicted)].parent;
+        } else {
+            self.order += 1;
+        }
+        if self.next_symbol >= self.symbols.len() - SUBCONTEXT_ORDER {
+            self.reset();
+        }
+    }
+}
+
+/// Check that a model from outside the range coder, such as a loaded dictionary, can be used
+/// without reading outside of `symbols`, looping forever, or running out of symbols part way
+/// through a value. `symbols` must hold fewer than half of [`RANGE_CODER_SYMBOLS`] symbols.
+///
+/// Each symbol's `parent` chain must reach the root within one more step than the deepest
+/// subcontext, the symbols in each context's tree must be exactly one step further from the root
+/// than the context, and every symbol may be in at most one tree.
+pub(crate) fn range_coder_model_is_valid(
+    symbols: &[RangeCoderSymbol],
+    model: &RangeCoderModel,
+) -> bool {
+    if model.next_symbol == 0 {
+        return symbols.is_empty() && model.predicted == 0 && model.order == 0;
+    }
+    if model.next_symbol != symbols.len() || model.order > SUBCONTEXT_ORDER {
+        return false;
+    }
+    let mut orders = Vec::with_capacity(symbols.len());
+    for symbol in 0..symbols.len() {
+        let mut order = 0;
+        let mut current = symbol;
+        while current != ROOT {
+            order += 1;
+            if order > SUBCONTEXT_ORDER + 1 {
+                return false;
+            }
+            current = usize::from(symbols[current].parent);
+            if current >= symbols.len() {
+                return false;
+            }
+        }
+        orders.push(order);
+    }
+    match orders.get(usize::from(model.predicted)) {
+        Some(&order) if order <= model.order => {}
+        _ => return false,
+    }
+    let mut claimed = Vec::new();
+    claimed.resize(symbols.len(), false);
+    let mut nodes = Vec::new();
+    for context in 0..symbols.len() {
+        nodes.push((context, symbols[context].symbols));
+        while let Some((node, offset)) = nodes.pop() {
+            if offset == 0 {
+                continue;
+            }
+            let child = node + usize::from(offset);
+            if child >= symbols.len() || claimed[child] || orders[child] != orders[context] + 1 {
+                return false;
+            }
+            claimed[child] = true;
+            nodes.push((child, symbols[child].left));
+            nodes.push((child, symbols[child].right));
+        }
+    }
+    true
+}
+
+struct Encoder<'a> {
+    low: u32,
+    range: u32,
+    out: &'a mut [u8],
+    position: usize,
+}
+
+impl Encoder<'_> {
+    fn encode(&mut self, under: u16, count: u16, total: u16) -> Option<()> {
+        self.range = self.range.checked_div(u32::from(total))?;
+        self.low = self
+            .low
+            .wrapping_add(u32::from(under).wrapping_mul(self.range));
+        self.range = self.range.wrapping_mul(u32::from(count));
+        loop {
+            if self.low ^ self.low.wrapping_add(self.range) >= RANGE_CODER_TOP {
+                if self.range >= RANGE_CODER_BOTTOM {
+                    break;
+                }
+                self.range = self.low.wrapping_neg() & (RANGE_CODER_BOTTOM - 1);
+            }
+            self.output((self.low >> 24) as u8)?;
+            self.range <<= 8;
+            self.low <<= 8;
+        }
+        Some(())
+    }
+
+    fn flush(&mut self) -> Option<()> {
+        while self.low != 0 {
+            self.output((self.low >> 24) as u8)?;
+            self.low <<= 8;
+        }
+        Some(())
+    }
+
+    fn output(&mut self, value: u8) -> Option<()> {
+        *self.out.get_mut(self.position)? = value;
+        self.position += 1;
+        Some(())
+    }
+}
+
+struct Decoder<'a> {
+    low: u32,
+    code: u32,
+    range: u32,
+    in_data: &'a [u8],
+    position: usize,
+}
+
+impl Decoder<'_> {
+    fn input(&mut self) -> u32 {
+        // Missing bytes past the end of the input are read as zeros.
+        let value = self.in_data.get(self.position).copied().unwrap_or(0);
+        self.position += 1;
+        u32::from(value)
+    }
+
+    fn read_frequency(&mut self, total: u16) -> Option<u16> {
+        self.range = self.range.checked_div(u32::from(total))?;
+        Some((self.code.wrapping_sub(self.low).checked_div(self.range)?) as u16)
+    }
+
+    fn decode(&mut self, under: u16, count: u16) {
+        self.low = self
+            .low
+            .wrapping_add(u32::from(under).wrapping_mul(self.range));
+        self.range = self.range.wrapping_mul(u32::from(count));
+        loop {
+            if self.low ^ self.low.wrapping_add(self.range) >= RANGE_CODER_TOP {
+                if self.range >= RANGE_CODER_BOTTOM {
+                    break;
+                }
+                self.range = self.low.wrapping_neg() & (RANGE_CODER_BOTTOM - 1);
+            }
+            self.code = (self.code << 8) | self.input();
+            self.range <<= 8;
+            self.low <<= 8;
+        }
+    }
+}
+
+/// Compress the incoming buffers into `out` with the model in `symbols`, which must hold
+/// [`RANGE_CODER_SYMBOLS`] symbols. Returns the size of the compressed data, or `0` if it did not
+/// fit in `out`.
+///
+/// If `model` has been built, compression starts from it, and it is updated on success. Empty
+/// buffers are skipped, where the original read a byte past their end.
+pub(crate) fn range_coder_compress(
+    symbols: &mut [RangeCoderSymbol],
+    model: Option<&mut RangeCoderModel>,
+    in_buffers: &[&[u8]],
+    in_limit: usize,
+    out: &mut [u8],
+) -> usize {
+    if symbols.len() != RANGE_CODER_SYMBOLS || in_buffers.is_empty() || in_limit == 0 {
+        return 0;
+    }
+    let mut state = Model::new(symbols, model.as_deref());
+    let mut encoder = Encoder {
+        low: 0,
+        range: !0,
+        out,
+        position: 0,
+    };
+    for &value in in_buffers.iter().flat_map(|in_buffer| in_buffer.iter()) {
+        if compress_value(&mut state, &mut encoder, value).is_none() {
+            return 0;
+        }
+        state.advance();
+    }
+    if encoder.flush().is_none() {
+        return 0;
+    }
+    if let Some(model) = model {
+        *model = state.state();
+    }
+    encoder.position
+}
+
+fn compress_value(model: &mut Model, encoder: &mut Encoder, value: u8) -> Option<()> {
+    let mut parent = Parent::Predicted;
+    let mut subcontext = usize::from(model.predicted);
+    while subcontext != ROOT {
+        let (symbol, under, count) = model.find_symbol(subcontext, value, SUBCONTEXT_SYMBOL_DELTA);
+        model.set_parent(parent, symbol);
+        parent = Parent::Symbol(symbol);
+        let context = &mut model.symbols[subcontext];
+        let total = context.total;
+        if count > 0 {
+            encoder.encode(context.escapes.wrapping_add(under), count, total)?;
+        } else {
+            if context.escapes > 0 && context.escapes < total {
+                encoder.encode(0, context.escapes, total)?;
+            }
+            context.escapes = context.escapes.wrapping_add(SUBCONTEXT_ESCAPE_DELTA);
+            context.total = context.total.wrapping_add(SUBCONTEXT_ESCAPE_DELTA);
+        }
+        context.total = context
+            .total
+            .wrapping_add(u16::from(SUBCONTEXT_SYMBOL_DELTA));
+        if count > 0xff - 2 * u16::from(SUBCONTEXT_SYMBOL_DELTA)
+            || u32::from(context.total) > RANGE_CODER_BOTTOM - 0x100
+        {
+            model.rescale_context(subcontext, 0);
+        }
+        if count > 0 {
+            return Some(());
+        }
+        subcontext = usize::from(model.symbols[subcontext].parent);
+    }
+    let (symbol, under, count) = model.find_symbol(ROOT, value, CONTEXT_SYMBOL_DELTA);
+    let under = under.wrapping_add(u16::from(value) * CONTEXT_SYMBOL_MINIMUM);
+    let count = count.wrapping_add(CONTEXT_SYMBOL_MINIMUM);
+    model.set_parent(parent, symbol);
+    let root = &mut model.symbols[ROOT];
+    encoder.encode(root.escapes.wrapping_add(under), count, root.total)?;
+    root.total = root.total.wrapping_add(u16::from(CONTEXT_SYMBOL_DELTA));
+    if count > 0xff - 2 * u16::from(CONTEXT_SYMBOL_DELTA) + CONTEXT_SYMBOL_MINIMUM
+        || u32::from(root.total) > RANGE_CODER_BOTTOM - 0x100
+    {
+        model.rescale_context(ROOT, 256 * CONTEXT_SYMBOL_MINIMUM);
+    }
+    Some(())
+}
+
+/// Decompress `in_data` into `out` with the model in `symbols`, which must hold
+/// [`RANGE_CODER_SYMBOLS`] symbols. Returns the size of the decompressed data, or `0` if the data
+/// was invalid or did not fit in `out`.
+///
+/// If `model` has been built, decompression starts from it.
+pub(crate) fn range_coder_decompress(
+    symbols: &mut [RangeCoderSymbol],
+    model: Option<&RangeCoderModel>,
+    in_data: &[u8],
+    out: &mut [u8],
+) -> usize {
+    if symbols.len() != RANGE_CODER_SYMBOLS || in_data.is_empty() {
+        return 0;
+    }
+    let mut model = Model::new(symbols, model);
+    let mut decoder = Decoder {
+        low: 0,
+        code: 0,
+        range: !0,
+        in_data,
+        position: 0,
+    };
+    for _ in 0..4 {
+        decoder.code = (decoder.code << 8) | decoder.input();
+    }
+    let mut out_position = 0;
+    loop {
+        let Some(value) = decompress_value(&mut model, &mut decoder) else {
+            return 0;
+        };
+        let Some(value) = value else {
+            break;
+        };
+        let Some(out_value) = out.get_mut(out_position) else {
+            return 0;
+        };
+        *out_value = value;
+        out_position += 1;
+        model.advance();
+    }
+    out_position
+}
+
+/// Decode the next value, or [`None`] at the end of the data. Returns [`None`] if the data was
+/// invalid.
+fn decompress_value(model: &mut Model, decoder: &mut Decoder) -> Option<Option<u8>> {
+    let mut subcontext = usize::from(model.predicted);
+    let mut decoded = None;
+    while subcontext != ROOT {
+        let context = model.symbols[subcontext];
+        if context.escapes > 0 && context.escapes < context.total {
+            let code = decoder.read_frequency(context.total)?;
+            if code < context.escapes {
+                decoder.decode(0, context.escapes);
+            } else {
+                let code = code - context.escapes;
+                let (symbol, under, count) = decode_subcontext_symbol(model, subcontext, code)?;
+                decoder.decode(context.escapes.wrapping_add(under), count);
+                let context = &mut model.symbols[subcontext];
+                context.total = context
+                    .total
+                    .wrapping_add(u16::from(SUBCONTEXT_SYMBOL_DELTA));
+                if count > 0xff - 2 * u16::from(SUBCONTEXT_SYMBOL_DELTA)
+                    || u32::from(context.total) > RANGE_CODER_BOTTOM - 0x100
+                {
+                    model.rescale_context(subcontext, 0);
+                }
+                decoded = Some(symbol);
+                break;
+            }
+        }
+        subcontext = usize::from(model.symbols[subcontext].parent);
+    }
+    let bottom = if let Some(symbol) = decoded {
+        symbol
+    } else {
+        let root = model.symbols[ROOT];
+        let code = decoder.read_frequency(root.total)?;
+        if code < root.escapes {
+            decoder.decode(0, root.escapes);
+            return Some(None);
+        }
+        let code = code - root.escapes;
+        let (symbol, under, count) = decode_root_symbol(model, code);
+        decoder.decode(root.escapes.wrapping_add(under), count);
+        let root = &mut model.symbols[ROOT];
+        root.total = root.total.wrapping_add(u16::from(CONTEXT_SYMBOL_DELTA));
+        if count > 0xff - 2 * u16::from(CONTEXT_SYMBOL_DELTA) + CONTEXT_SYMBOL_MINIMUM
+            || u32::from(root.total) > RANGE_CODER_BOTTOM - 0x100
+        {
+            model.rescale_context(ROOT, 256 * CONTEXT_SYMBOL_MINIMUM);
+        }
+        symbol
+    };
+    let value = model.symbols[bottom].value;
+    // Add the value to the contexts which escaped, as the encoder did.
+    let mut parent = Parent::Predicted;
+    let mut patch = usize::from(model.predicted);
+    while patch != subcontext {
+        let (symbol, _, count) = model.find_symbol(patch, value, SUBCONTEXT_SYMBOL_DELTA);
+        model.set_parent(parent, symbol);
+        parent = Parent::Symbol(symbol);
+        let context = &mut model.symbols[patch];
+        if count == 0 {
+            context.escapes = context.escapes.wrapping_add(SUBCONTEXT_ESCAPE_DELTA);
+            context.total = context.total.wrapping_add(SUBCONTEXT_ESCAPE_DELTA);
+        }
+        context.total = context
+            .total
+            .wrapping_add(u16::from(SUBCONTEXT_SYMBOL_DELTA));
+        if count > 0xff - 2 * u16::from(SUBCONTEXT_SYMBOL_DELTA)
+            || u32::from(context.total) > RANGE_CODER_BOTTOM - 0x100
+        {
+            model.rescale_context(patch, 0);
+        }
+        patch = usize::from(model.symbols[patch].parent);
+    }
+    model.set_parent(parent, bottom);
+    Some(Some(value))
+}
+
+/// Find the symbol in a subcontext whose range contains `code`. Returns [`None`] if there is no
+/// such symbol, which only happens with invalid data.
+fn decode_subcontext_symbol(
+    model: &mut Model,
+    subcontext: usize,
+    code: u16,
+) -> Option<(usize, u16, u16)> {
+    let mut under: u16 = 0;
+    if model.symbols[subcontext].symbols == 0 {
+        return None;
+    }
+    let mut node = subcontext + usize::from(model.symbols[subcontext].symbols);
+    loop {
+        let symbol = &mut model.symbols[node];
+        let after = under.wrapping_add(symbol.under);
+        let before = u16::from(symbol.count);
+        if code >= after {
+            under = under.wrapping_add(symbol.under);
+            if symbol.right == 0 {
+                return None;
+            }
+            node += usize::from(symbol.right);
+        } else if i32::from(code) < i32::from(after) - i32::from(before) {
+            symbol.under = symbol
+                .under
+                .wrapping_add(u16::from(SUBCONTEXT_SYMBOL_DELTA));
+            if symbol.left == 0 {
+                return None;
+            }
+            node += usize::from(symbol.left);
+        } else {
+            let count = u16::from(symbol.count);
+            symbol.under = symbol
+                .under
+                .wrapping_add(u16::from(SUBCONTEXT_SYMBOL_DELTA));
+            symbol.count = symbol.count.wrapping_add(SUBCONTEXT_SYMBOL_DELTA);
+            return Some((node, after.wrapping_sub(before), count));
+        }
+    }
+}
+
+/// Find the symbol in the root context whose range contains `code`, adding it if it is missing.
+fn decode_root_symbol(model: &mut Model, code: u16) -> (usize, u16, u16) {
+    let minimum = i32::from(CONTEXT_SYMBOL_MINIMUM);
+    let code_i32 = i32::from(code);
+    if model.symbols[ROOT].symbols == 0 {
+        let value = (code_i32 / minimum) as u8;
+        let under = (code_i32 - code_i32 % minimum) as u16;
+        let symbol = model.create_symbol(value, CONTEXT_SYMBOL_DELTA);
+        model.symbols[ROOT].symbols = symbol as u16;
+        return (symbol, under, CONTEXT_SYMBOL_MINIMUM);
+    }
+    let mut under: u16 = 0;
+    let mut node = usize::from(model.symbols[ROOT].symbols);
+    loop {
+        let symbol = model.symbols[node];
+        let after = (i32::from(under)
+            + i32::from(symbol.under)
+            + (i32::from(symbol.value) + 1) * minimum) as u16;
+        let before = (i32::from(symbol.count) + minimum) as u16;
+        if code >= after {
+            under = under.wrapping_add(symbol.under);
+            if symbol.right != 0 {
+                node += usize::from(symbol.right);
+            } else {
+                let distance = code_i32 - i32::from(after);
+                let value = (i32::from(symbol.value) + 1 + distance / minimum) as u8;
+                let under = (code_i32 - distance % minimum) as u16;
+                let created = model.create_symbol(value, CONTEXT_SYMBOL_DELTA);
+                model.symbols[node].right = (created - node) as u16;
+                return (created, under, CONTEXT_SYMBOL_MINIMUM);
+            }
+        } else if code_i32 < i32::from(after) - i32::from(before) {
+            model.symbols[node].under = symbol.under.wrapping_add(u16::from(CONTEXT_SYMBOL_DELTA));
+            if symbol.left != 0 {
+                node += usize::from(symbol.left);
+            } else {
+                let distance = i32::from(after) - i32::from(before) - code_i32 - 1;
+                let value = (i32::from(symbol.value) - 1 - distance / minimum) as u8;
+                let under = (code_i32 - distance % minimum) as u16;
+                let created = model.create_symbol(value, CONTEXT_SYMBOL_DELTA);
+                model.symbols[node].left = (created - node) as u16;
+                return (created, under, CONTEXT_SYMBOL_MINIMUM);
+            }
+        } else {
+            let symbol = &mut model.symbols[node];
+            let count = CONTEXT_SYMBOL_MINIMUM.wrapping_add(u16::from(symbol.count));
+            symbol.under = symbol.under.wrapping_add(u16::from(CONTEXT_SYMBOL_DELTA));
+            symbol.count = symbol.count.wrapping_add(CONTEXT_SYMBOL_DELTA);
+            return (node, after.wrapping_sub(before), count);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::mpsc;
+
+use crate::{error::PeerSendError, Host, Packet, PeerID, Socket};
+
+pub(crate) enum SenderCommand {
+    Send {
+        peer: PeerID,
+        channel_id: u8,
+        packet: Packet,
+        result: Option<mpsc::Sender<Result<(), PeerSendError>>>,
+    },
+    Broadcast {
+        channel_id: u8,
+        packet: Packet,
+    },
+    Disconnect {
+        peer: PeerID,
+        data: u32,
+    },
+    DisconnectNow {
+        peer: PeerID,
+        data: u32,
+    },
+    DisconnectLater {
+        peer: PeerID,
+        data: u32,
+    },
+}
+
+/// A cloneable handle for queuing packets on a [`Host`] from any thread. Created with
+/// [`Host::sender`].
+///
+/// Commands are applied in order, at the start of the host's next call to [`Host::service`],
+/// [`Host::service_timeout`], [`Host::check_events`], or [`Host::flush`]. Commands queued after
+/// the host has been dropped are discarded.
+#[derive(Clone)]
+pub struct HostSender {
+    commands: mpsc::Sender<SenderCommand>,
+}
+
+impl HostSender {
+    pub(crate) fn new(commands: mpsc::Sender<SenderCommand>) -> Self {
+        Self { commands }
+    }
+
+    /// Queue a packet to be sent to a peer. See [`Peer::send`](`crate::Peer::send`).
+    ///
+    /// If the send fails once the host has applied it, the host returns an
+    /// [`Event::SendFailed`](`crate::Event::SendFailed`). Sends to a peer ID which is out of
+    /// bounds, or which has since been reused for a new connection, fail with
+    /// [`PeerSendError::NotConnected`]. Use [`HostSender::send_with_result`] to receive the result
+    /// on the sending thread instead.
+    pub fn send(&self, peer: PeerID, channel_id: u8, packet: Packet) {
+        _ = self.commands.send(SenderCommand::Send {
+            peer,
+            channel_id,
+            packet,
+            result: None,
+        });
+    }
+
+    /// Like [`HostSender::send`], but the returned receiver yields the result of the send once
+    /// the host has applied it, and failures are not reported as events. If the host is dropped
+    /// before applying the send, the receiver yields no result.
+    ///
+    /// Waiting on the receiver from the thread that services the host will block forever.
+    #[allow(clippy::must_use_candidate)]
+    pub fn send_with_result(
+        &self,
+        peer: PeerID,
+        channel_id: u8,
+        packet: Packet,
+    ) -> mpsc::Receiver<Result<(), PeerSendError>> {
+        let (result, receiver) = mpsc::channel();
+        _ = self.commands.send(SenderCommand::Send {
+            peer,
+            channel_id,
+            packet,
+            result: Some(result),
+        });
+        receiver
+    }
+
+    /// Queue a packet to be sent to all peers. See [`Host::broadcast`].
+    pub fn broadcast(&self, channel_id: u8, packet: Packet) {
+        _ = self
+            .commands
+            .send(SenderCommand::Broadcast { channel_id, packet });
+    }
+
+    /// Queue a disconnection from a peer. See [`Peer::disconnect`](`crate::Peer::disconnect`).
+    ///
+    /// Ignored if the peer ID is out of bounds, or has since been reused for a new connection.
+    pub fn disconnect(&self, peer: PeerID, data: u32) {
+        _ = self.commands.send(SenderCommand::Disconnect { peer, data });
+    }
+
+    /// Queue an immediate disconnection from a peer. See
+    /// [`Peer::disconnect_now`](`crate::Peer::disconnect_now`).
+    ///
+    /// Ignored if the peer ID is out of bounds, or has since been reused for a new connection.
+    pub fn disconnect_now(&self, peer: PeerID, data: u32) {
+        _ = self
+            .commands
+            .send(SenderCommand::DisconnectNow { peer, data });
+    }
+
+    /// Queue a disconnection from a peer after all of its queued packets are sent. See
+    /// [`Peer::disconnect_later`](`crate::Peer::disconnect_later`).
+    ///
+    /// Ignored if the peer ID is out of bounds, or has since been reused for a new connection.
+    pub fn disconnect_later(&self, peer: PeerID, data: u32) {
+        _ = self
+            .commands
+            .send(SenderCommand::DisconnectLater { peer, data });
+    }
+}
+
+impl<S: Socket> Host<S> {
+    pub(crate) fn apply_sender_command(&mut self, command: SenderCommand) {
+        match command {
+            SenderCommand::Send {
+                peer,
+                channel_id,
+                packet,
+                result,
+            } => {
+                let sent = self
+                    .get_peer_mut(peer)
+                    .map_or(Err(PeerSendError::NotConnected), |peer| {
+                        peer.send(channel_id, &packet)
+                    });
+                match (result, sent) {
+                    (Some(result), sent) => _ = result.send(sent),
+                    (None, Err(error)) => self.send_failures.push_back((peer, channel_id, error)),
+                    (None, Ok(())) => {}
+                }
+            }
+            SenderCommand::Broadcast { channel_id, packet } => {
+                self.broadcast(channel_id, &packet);
+            }
+            SenderCommand::Disconnect { peer, data } => {
+                if let Some(peer) = self.get_peer_mut(peer) {
+                    peer.disconnect(data);
+                }
+            }
+            SenderCommand::DisconnectNow { peer, data } => {
+                if let Some(peer) = self.get_peer_mut(peer) {
+                    peer.disconnect_now(data);
+                }
+            }
+            SenderCommand::DisconnectLater { peer, data } => {
+                if let Some(peer) = self.get_peer_mut(peer) {
+                    peer.disconnect_later(data);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use core::hash::Hasher;
+
+/// SipHash-2-4, a keyed hash used where a short MAC is needed, such as connect cookies.
+///
+/// Matches the reference implementation. The standard library's `SipHasher` is deprecated, and
+/// makes no promises about its algorithm.
+pub(crate) struct SipHasher24 {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    tail: u64,
+    tail_length: usize,
+    length: usize,
+}
+
+impl SipHasher24 {
+    pub(crate) fn new_with_keys(key0: u64, key1: u64) -> Self {
+        Self {
+            v0: key0 ^ 0x736f_6d65_7073_6575,
+            v1: key1 ^ 0x646f_7261_6e64_6f6d,
+            v2: key0 ^ 0x6c79_6765_6e65_7261,
+            v3: key1 ^ 0x7465_6462_7974_6573,
+            tail: 0,
+            tail_length: 0,
+            length: 0,
+        }
+    }
+
+    fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13) ^ self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16) ^ self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21) ^ self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17) ^ self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    fn compress(&mut self, message: u64) {
+        self.v3 ^= message;
+        self.round();
+        self.round();
+        self.v0 ^= message;
+    }
+}
+
+impl Hasher for SipHasher24 {
+    fn write(&mut self, bytes: &[u8]) {
+        self.length = self.length.wrapping_add(bytes.len());
+        for &byte in bytes {
+            self.tail |= u64::from(byte) << (8 * self.tail_length);
+            self.tail_length += 1;
+            if self.tail_length == 8 {
+                self.compress(self.tail);
+                self.tail = 0;
+                self.tail_length = 0;
+            }
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        let mut state = Self { ..*self };
+        state.compress(state.tail | ((state.length as u64 & 0xff) << 56));
+        state.v2 ^= 0xff;
+        state.round();
+        state.round();
+        state.round();
+        state.round();
+        state.v0 ^ state.v1 ^ state.v2 ^ state.v3
+    }
+}
@@ -4,7 +4,14 @@ use std::{
     net::{SocketAddr, UdpSocket},
 };
 
-use crate::{consts::PROTOCOL_MAXIMUM_MTU, Address};
+use core::time::Duration;
+
+use crate::{consts::PROTOCOL_MAXIMUM_MTU, Address, Vec};
+
+#[cfg(all(feature = "mmsg", target_os = "linux"))]
+mod linux;
+#[cfg(all(feature = "std", unix))]
+mod unix;
 
 // This macro allows the same doc comment to apply to both variants.
 macro_rules! socket_error {
@@ -83,6 +90,56 @@ pub trait Socket: Sized {
     /// Try to send data. Should return the number of bytes successfully sent, or an error.
     fn send(&mut self, address: Self::Address, buffer: &[u8]) -> Result<usize, Self::Error>;
 
+    /// Try to send data gathered from multiple buffers as a single datagram. Should return the
+    /// number of bytes successfully sent, or an error.
+    ///
+    /// ENet builds each datagram out of several buffers (a header, followed by commands and packet
+    /// data), and always sends through this function. `scratch` is a buffer owned by the host and
+    /// reused between calls. The default implementation copies the buffers into it and calls
+    /// [`Socket::send`], so nothing is allocated once it has grown to fit a datagram.
+    /// Implementations with access to a gather write (such as `sendmsg`) can override it to skip
+    /// the copy entirely.
+    fn send_vectored(
+        &mut self,
+        address: Self::Address,
+        buffers: &[&[u8]],
+        scratch: &mut Vec<u8>,
+    ) -> Result<usize, Self::Error> {
+        scratch.clear();
+        for buffer in buffers {
+            scratch.extend_from_slice(buffer);
+        }
+        self.send(address, scratch)
+    }
+
+    /// Try to send multiple datagrams at once, sending the first `datagrams[n].1` bytes of
+    /// `buffers[n]` to the address `datagrams[n].0`. Should return the number of datagrams sent.
+    ///
+    /// ENet only calls this function when
+    /// [`HostSettings::send_batch_size`](`crate::HostSettings::send_batch_size`) is greater than
+    /// 1, with up to that many datagrams. Datagrams past the returned count are dropped, and ENet
+    /// will resend them if they were reliable.
+    ///
+    /// The default implementation calls [`Socket::send`] in a loop. Implementations able to send
+    /// many datagrams in a single system call (such as `sendmmsg`) can override it. If an error
+    /// occurs after at least one datagram was sent, the number of datagrams sent should be
+    /// returned instead of the error.
+    fn send_batch(
+        &mut self,
+        buffers: &[[u8; MTU_MAX]],
+        datagrams: &[(Self::Address, usize)],
+    ) -> Result<usize, Self::Error> {
+        let mut sent_count = 0;
+        for (buffer, (address, length)) in buffers.iter().zip(datagrams) {
+            match self.send(address.clone(), &buffer[..*length]) {
+                Ok(_) => sent_count += 1,
+                Err(err) if sent_count == 0 => return Err(err),
+                Err(_) => break,
+            }
+        }
+        Ok(sent_count)
+    }
+
     /// Try to receive data from the socket into a buffer of size [`MTU_MAX`].
     ///
     /// A received packet should be written into the provided buffer. If a packet is received that
@@ -96,6 +153,56 @@ pub trait Socket: Sized {
         &mut self,
         buffer: &mut [u8; MTU_MAX],
     ) -> Result<Option<(Self::Address, PacketReceived)>, Self::Error>;
+
+    /// Try to receive multiple datagrams at once, writing the `n`th datagram into `buffers[n]` and
+    /// its address and size into `received[n]`. Should return the number of datagrams received,
+    /// or `Ok(0)` if none were received.
+    ///
+    /// ENet calls this function with as many buffers as
+    /// [`HostSettings::receive_batch_size`](`crate::HostSettings::receive_batch_size`), and
+    /// processes the received datagrams in order before receiving again. Any entry of `received`
+    /// left as [`None`] is skipped.
+    ///
+    /// If an error occurs after at least one datagram was received, those datagrams should be
+    /// returned rather than the error.
+    ///
+    /// The default implementation calls [`Socket::receive`] in a loop. An error after the first
+    /// datagram ends the batch and is discarded, so only errors which persist, and are returned
+    /// again by the next call, are reported. Implementations able to receive many datagrams in a
+    /// single system call (such as `recvmmsg`) can override it.
+    fn receive_batch(
+        &mut self,
+        buffers: &mut [[u8; MTU_MAX]],
+        received: &mut [Option<(Self::Address, PacketReceived)>],
+    ) -> Result<usize, Self::Error> {
+        let mut received_count = 0;
+        for (buffer, received) in buffers.iter_mut().zip(received.iter_mut()) {
+            match self.receive(buffer) {
+                Ok(Some(datagram)) => {
+                    *received = Some(datagram);
+                    received_count += 1;
+                }
+                Ok(None) => break,
+                Err(err) if received_count == 0 => return Err(err),
+                Err(_) => break,
+            }
+        }
+        Ok(received_count)
+    }
+}
+
+/// An optional extension to [`Socket`], allowing ENet to block until data is available to receive.
+///
+/// Required for [`Host::service_timeout`](`crate::Host::service_timeout`).
+///
+/// An implementation for [`std::net::UdpSocket`] is provided out of the box.
+#[allow(clippy::missing_errors_doc)]
+pub trait WaitableSocket: Socket {
+    /// Block until the socket has data ready to be received, or until `timeout` has elapsed.
+    ///
+    /// Should return `Ok(true)` if data is ready to be received, or `Ok(false)` if the timeout
+    /// elapsed first. Returning early is allowed, ENet will simply call this function again.
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool, Self::Error>;
 }
 
 /// Return type of [`Socket::receive`], representing either a complete packet, or a partial
@@ -116,6 +223,8 @@ pub enum PacketReceived {
     Partial,
 }
 
+/// With the `mmsg` feature on Linux, datagrams are sent with `sendmsg`, and sent and received in
+/// batches with `sendmmsg` and `recvmmsg`.
 #[cfg(feature = "std")]
 impl Socket for UdpSocket {
     type Address = SocketAddr;
@@ -136,6 +245,41 @@ impl Socket for UdpSocket {
         }
     }
 
+    #[cfg(all(feature = "mmsg", target_os = "linux"))]
+    fn send_vectored(
+        &mut self,
+        address: SocketAddr,
+        buffers: &[&[u8]],
+        scratch: &mut Vec<u8>,
+    ) -> Result<usize, io::Error> {
+        if buffers.len() > linux::SEND_VECTORED_MAXIMUM {
+            scratch.clear();
+            for buffer in buffers {
+                scratch.extend_from_slice(buffer);
+            }
+            return self.send(address, scratch);
+        }
+        linux::send_vectored(self, address, buffers)
+    }
+
+    #[cfg(all(feature = "mmsg", target_os = "linux"))]
+    fn send_batch(
+        &mut self,
+        buffers: &[[u8; MTU_MAX]],
+        datagrams: &[(SocketAddr, usize)],
+    ) -> Result<usize, io::Error> {
+        linux::send_batch(self, buffers, datagrams)
+    }
+
+    #[cfg(all(feature = "mmsg", target_os = "linux"))]
+    fn receive_batch(
+        &mut self,
+        buffers: &mut [[u8; MTU_MAX]],
+        received: &mut [Option<(SocketAddr, PacketReceived)>],
+    ) -> Result<usize, io::Error> {
+        linux::receive_batch(self, buffers, received)
+    }
+
     fn receive(
         &mut self,
         buffer: &mut [u8; MTU_MAX],
@@ -150,3 +294,55 @@ impl Socket for UdpSocket {
         }
     }
 }
+
+/// Uses `poll` on Unix. Elsewhere, the standard library has no poll/select, so this briefly
+/// switches the socket to blocking mode and peeks with a read timeout.
+#[cfg(feature = "std")]
+impl WaitableSocket for UdpSocket {
+    #[cfg(unix)]
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool, io::Error> {
+        if timeout.is_zero() {
+            return Ok(false);
+        }
+        unix::wait_readable(self, timeout)
+    }
+
+    #[cfg(not(unix))]
+    fn wait_readable(&mut self, timeout: Duration) -> Result<bool, io::Error> {
+        /// Puts the socket back into non-blocking mode with its previous read timeout however the
+        /// wait ends.
+        struct BlockingGuard<'a> {
+            socket: &'a UdpSocket,
+            read_timeout: Option<Duration>,
+        }
+
+        impl Drop for BlockingGuard<'_> {
+            fn drop(&mut self) {
+                let _ = self.socket.set_read_timeout(self.read_timeout);
+                let _ = self.socket.set_nonblocking(true);
+            }
+        }
+
+        if timeout.is_zero() {
+            return Ok(false);
+        }
+        // A blocking peek with a read timeout waits on the same readiness as poll, without
+        // consuming the datagram.
+        let guard = BlockingGuard {
+            socket: self,
+            read_timeout: self.read_timeout()?,
+        };
+        guard.socket.set_nonblocking(false)?;
+        guard.socket.set_read_timeout(Some(timeout))?;
+        match guard.socket.peek_from(&mut [0; 1]) {
+            Ok(_) => Ok(true),
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                Ok(false)
+            }
+            // Windows reports peeking at a datagram larger than the buffer as WSAEMSGSIZE.
+            #[cfg(windows)]
+            Err(err) if err.raw_os_error() == Some(10040) => Ok(true),
+            Err(err) => Err(err),
+        }
+    }
+}
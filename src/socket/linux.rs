@@ -0,0 +1,208 @@
+use std::{
+    io::{self, ErrorKind},
+    mem::{size_of, zeroed},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket},
+    os::fd::AsRawFd,
+    ptr::null_mut,
+};
+
+use crate::{consts::BUFFER_MAXIMUM, PacketReceived, MTU_MAX};
+
+/// The most datagrams received by a single call to `recvmmsg`, bounding the stack space used for
+/// message headers and addresses.
+const RECEIVE_BATCH_MAXIMUM: usize = 64;
+
+/// The most buffers gathered by a single call to `sendmsg`, enough for any datagram built by ENet.
+pub(super) const SEND_VECTORED_MAXIMUM: usize = BUFFER_MAXIMUM as usize;
+
+pub(super) fn send_vectored(
+    socket: &UdpSocket,
+    address: SocketAddr,
+    buffers: &[&[u8]],
+) -> io::Result<usize> {
+    let (mut address, address_length) = socket_address_storage(address);
+    // SAFETY: these are plain C structs, for which all zeroes is a valid value.
+    let mut iovecs: [libc::iovec; SEND_VECTORED_MAXIMUM] = unsafe { zeroed() };
+    let mut message: libc::msghdr = unsafe { zeroed() };
+    for (iovec, buffer) in iovecs.iter_mut().zip(buffers) {
+        // sendmsg only reads from the buffers, despite the mutable pointer.
+        iovec.iov_base = buffer.as_ptr().cast_mut().cast();
+        iovec.iov_len = buffer.len();
+    }
+    message.msg_name = core::ptr::addr_of_mut!(address).cast();
+    message.msg_namelen = address_length;
+    message.msg_iov = iovecs.as_mut_ptr();
+    message.msg_iovlen = buffers.len().min(SEND_VECTORED_MAXIMUM);
+    // SAFETY: the message points to a live address, and to live buffers of the given lengths.
+    let sent_length = unsafe { libc::sendmsg(socket.as_raw_fd(), &message, libc::MSG_DONTWAIT) };
+    if sent_length < 0 {
+        let err = io::Error::last_os_error();
+        return if err.kind() == ErrorKind::WouldBlock {
+            Ok(0)
+        } else {
+            Err(err)
+        };
+    }
+    Ok(sent_length as usize)
+}
+
+/// The most datagrams sent by a single call to `sendmmsg`, bounding the stack space used for
+/// message headers and addresses.
+const SEND_BATCH_MAXIMUM: usize = 64;
+
+pub(super) fn send_batch(
+    socket: &UdpSocket,
+    buffers: &[[u8; MTU_MAX]],
+    datagrams: &[(SocketAddr, usize)],
+) -> io::Result<usize> {
+    let datagram_count = buffers.len().min(datagrams.len());
+    let mut sent_count = 0;
+    while sent_count < datagram_count {
+        let batch_size = (datagram_count - sent_count).min(SEND_BATCH_MAXIMUM);
+        // SAFETY: these are plain C structs, for which all zeroes is a valid value.
+        let mut addresses: [libc::sockaddr_storage; SEND_BATCH_MAXIMUM] = unsafe { zeroed() };
+        let mut iovecs: [libc::iovec; SEND_BATCH_MAXIMUM] = unsafe { zeroed() };
+        let mut messages: [libc::mmsghdr; SEND_BATCH_MAXIMUM] = unsafe { zeroed() };
+        for index in 0..batch_size {
+            let (address, length) = datagrams[sent_count + index];
+            let (address, address_length) = socket_address_storage(address);
+            addresses[index] = address;
+            // sendmmsg only reads from the buffers, despite the mutable pointer.
+            iovecs[index].iov_base = buffers[sent_count + index].as_ptr().cast_mut().cast();
+            iovecs[index].iov_len = length.min(MTU_MAX);
+            messages[index].msg_hdr.msg_name = core::ptr::addr_of_mut!(addresses[index]).cast();
+            messages[index].msg_hdr.msg_namelen = address_length;
+            messages[index].msg_hdr.msg_iov = core::ptr::addr_of_mut!(iovecs[index]);
+            messages[index].msg_hdr.msg_iovlen = 1;
+        }
+        // SAFETY: every message points to a live address, and to a live buffer of its length.
+        let batch_sent_count = unsafe {
+            libc::sendmmsg(
+                socket.as_raw_fd(),
+                messages.as_mut_ptr(),
+                batch_size as u32,
+                libc::MSG_DONTWAIT,
+            )
+        };
+        if batch_sent_count < 0 {
+            let err = io::Error::last_os_error();
+            return if sent_count > 0 || err.kind() == ErrorKind::WouldBlock {
+                Ok(sent_count)
+            } else {
+                Err(err)
+            };
+        }
+        sent_count += batch_sent_count as usize;
+        if (batch_sent_count as usize) < batch_size {
+            break;
+        }
+    }
+    Ok(sent_count)
+}
+
+pub(super) fn receive_batch(
+    socket: &UdpSocket,
+    buffers: &mut [[u8; MTU_MAX]],
+    received: &mut [Option<(SocketAddr, PacketReceived)>],
+) -> io::Result<usize> {
+    let batch_size = buffers.len().min(received.len()).min(RECEIVE_BATCH_MAXIMUM);
+    // SAFETY: these are plain C structs, for which all zeroes is a valid value.
+    let mut addresses: [libc::sockaddr_storage; RECEIVE_BATCH_MAXIMUM] = unsafe { zeroed() };
+    let mut iovecs: [libc::iovec; RECEIVE_BATCH_MAXIMUM] = unsafe { zeroed() };
+    let mut messages: [libc::mmsghdr; RECEIVE_BATCH_MAXIMUM] = unsafe { zeroed() };
+    for index in 0..batch_size {
+        iovecs[index].iov_base = buffers[index].as_mut_ptr().cast();
+        iovecs[index].iov_len = MTU_MAX;
+        messages[index].msg_hdr.msg_name = core::ptr::addr_of_mut!(addresses[index]).cast();
+        messages[index].msg_hdr.msg_namelen = size_of::<libc::sockaddr_storage>() as u32;
+        messages[index].msg_hdr.msg_iov = core::ptr::addr_of_mut!(iovecs[index]);
+        messages[index].msg_hdr.msg_iovlen = 1;
+    }
+    // SAFETY: every message points to a live address, and to a live buffer of MTU_MAX bytes.
+    let received_count = unsafe {
+        libc::recvmmsg(
+            socket.as_raw_fd(),
+            messages.as_mut_ptr(),
+            batch_size as u32,
+            libc::MSG_DONTWAIT,
+            null_mut(),
+        )
+    };
+    if received_count < 0 {
+        let err = io::Error::last_os_error();
+        return if err.kind() == ErrorKind::WouldBlock {
+            Ok(0)
+        } else {
+            Err(err)
+        };
+    }
+    let received_count = received_count as usize;
+    for index in 0..received_count {
+        let message = &messages[index];
+        received[index] = socket_address(&addresses[index]).map(|address| {
+            if message.msg_hdr.msg_flags & libc::MSG_TRUNC != 0 {
+                (address, PacketReceived::Partial)
+            } else {
+                (address, PacketReceived::Complete(message.msg_len as usize))
+            }
+        });
+    }
+    Ok(received_count)
+}
+
+fn socket_address_storage(address: SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    // SAFETY: a plain C struct, for which all zeroes is a valid value.
+    let mut storage: libc::sockaddr_storage = unsafe { zeroed() };
+    match address {
+        SocketAddr::V4(address) => {
+            // SAFETY: sockaddr_storage is large enough and aligned for any address family.
+            let storage =
+                unsafe { &mut *(core::ptr::addr_of_mut!(storage)).cast::<libc::sockaddr_in>() };
+            storage.sin_family = libc::AF_INET as libc::sa_family_t;
+            storage.sin_port = address.port().to_be();
+            storage.sin_addr.s_addr = u32::from(*address.ip()).to_be();
+        }
+        SocketAddr::V6(address) => {
+            // SAFETY: sockaddr_storage is large enough and aligned for any address family.
+            let storage =
+                unsafe { &mut *(core::ptr::addr_of_mut!(storage)).cast::<libc::sockaddr_in6>() };
+            storage.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            storage.sin6_port = address.port().to_be();
+            storage.sin6_flowinfo = address.flowinfo();
+            storage.sin6_addr.s6_addr = address.ip().octets();
+            storage.sin6_scope_id = address.scope_id();
+        }
+    }
+    let length = match address {
+        SocketAddr::V4(_) => size_of::<libc::sockaddr_in>(),
+        SocketAddr::V6(_) => size_of::<libc::sockaddr_in6>(),
+    };
+    (storage, length as libc::socklen_t)
+}
+
+fn socket_address(address: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    match i32::from(address.ss_family) {
+        libc::AF_INET => {
+            // SAFETY: the address family guarantees the storage holds a sockaddr_in.
+            let address =
+                unsafe { &*(address as *const libc::sockaddr_storage).cast::<libc::sockaddr_in>() };
+            Some(SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr)),
+                u16::from_be(address.sin_port),
+            )))
+        }
+        libc::AF_INET6 => {
+            // SAFETY: the address family guarantees the storage holds a sockaddr_in6.
+            let address = unsafe {
+                &*(address as *const libc::sockaddr_storage).cast::<libc::sockaddr_in6>()
+            };
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(address.sin6_addr.s6_addr),
+                u16::from_be(address.sin6_port),
+                address.sin6_flowinfo,
+                address.sin6_scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,28 @@
+use std::{
+    io::{self, ErrorKind},
+    net::UdpSocket,
+    os::fd::AsRawFd,
+    time::Duration,
+};
+
+pub(super) fn wait_readable(socket: &UdpSocket, timeout: Duration) -> io::Result<bool> {
+    let mut poll_fd = libc::pollfd {
+        fd: socket.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // Round up, so a timeout below one millisecond doesn't become a busy loop.
+    let timeout = i32::try_from(timeout.as_nanos().div_ceil(1_000_000)).unwrap_or(i32::MAX);
+    // SAFETY: the pollfd is live for the duration of the call, and the count matches.
+    let ready_count = unsafe { libc::poll(&mut poll_fd, 1, timeout) };
+    if ready_count < 0 {
+        let err = io::Error::last_os_error();
+        return if err.kind() == ErrorKind::Interrupted {
+            Ok(false)
+        } else {
+            Err(err)
+        };
+    }
+    // Errors and hangups also count as readable, so the following receive can report them.
+    Ok(ready_count > 0)
+}
@@ -1,4 +1,10 @@
 use crate as enet;
+use core::{
+    cell::{Cell, RefCell},
+    time::Duration,
+};
+
+use crate::{Box, Vec};
 
 #[allow(dead_code)]
 mod network;
@@ -22,11 +28,11 @@ fn events() {
     assert_eq!(events.len(), 2);
     assert!(events[0].is_connect_and(|event| event.to == host1
         && event.from == host2
-        && event.peer == enet::PeerID(0)
+        && event.peer.index() == 0
         && event.data == 0));
     assert!(events[1].is_connect_and(|event| event.to == host2
         && event.from == host1
-        && event.peer == enet::PeerID(0)
+        && event.peer.index() == 0
         && event.data == 5));
 
     network.send(
@@ -46,12 +52,16 @@ fn events() {
     network.disconnect(host1, host2, 10);
     let events = network.update(1);
     assert_eq!(events.len(), 1);
-    assert!(events[0]
-        .is_disconnect_and(|event| event.from == host1 && event.to == host2 && event.data == 10));
+    assert!(events[0].is_disconnect_and(|event| event.from == host1
+        && event.to == host2
+        && event.data == 10
+        && event.reason == enet::DisconnectReason::Remote(10)));
     let events = network.update(1);
     assert_eq!(events.len(), 1);
-    assert!(events[0]
-        .is_disconnect_and(|event| event.from == host2 && event.to == host1 && event.data == 0));
+    assert!(events[0].is_disconnect_and(|event| event.from == host2
+        && event.to == host1
+        && event.data == 0
+        && event.reason == enet::DisconnectReason::Local));
 }
 
 #[test]
@@ -180,10 +190,2635 @@ fn timeout() {
     assert_eq!(events.len(), 0);
     let events = network.update(1);
     assert_eq!(events.len(), 1);
-    assert!(events[0].is_disconnect_and(|event| event.from == 0));
+    assert!(events[0].is_disconnect_and(
+        |event| event.from == 0 && event.reason == enet::DisconnectReason::Timeout
+    ));
     let events = network.update(26383);
     assert_eq!(events.len(), 0);
     let events = network.update(1);
     assert_eq!(events.len(), 1);
-    assert!(events[0].is_disconnect_and(|event| event.from == 1));
+    assert!(events[0].is_disconnect_and(
+        |event| event.from == 1 && event.reason == enet::DisconnectReason::Timeout
+    ));
+}
+
+#[test]
+fn statistics() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 255, 5);
+    network.update(2);
+    network.send(
+        host1,
+        host2,
+        0,
+        &enet::Packet::reliable("hello world".as_bytes()),
+    );
+    network.update(1);
+
+    let statistics = network.host(host1).statistics();
+    assert_eq!(statistics.connected_peers, 1);
+    assert_eq!(statistics.bandwidth_limited_peers, 0);
+    assert!(statistics.total_sent_packets > 0);
+    assert!(statistics.total_received_packets > 0);
+    let statistics = network.host(host2).statistics();
+    assert!(statistics.total_received_data > 11);
+
+    network.host_mut(host2).reset_statistics();
+    assert_eq!(
+        network.host(host2).statistics(),
+        enet::HostStatistics {
+            connected_peers: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn maximum_packet_size() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        maximum_packet_size: 4000,
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 255, 5);
+    network.update(2);
+
+    let peer = network.resolve_peer(host2, host1);
+    assert_eq!(
+        network
+            .host_mut(host2)
+            .peer_mut(peer)
+            .send(0, &enet::Packet::reliable(vec![0; 4001])),
+        Err(enet::error::PeerSendError::PacketTooLarge)
+    );
+
+    network.send(host1, host2, 0, &enet::Packet::reliable(vec![0; 4000]));
+    let events = network.update(10);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_receive_and(|event| event.packet.data().len() == 4000));
+
+    network.send(host1, host2, 0, &enet::Packet::reliable(vec![0; 4001]));
+    let events = network.update(1000);
+    assert!(events.iter().all(|event| !event.is_receive()));
+}
+
+#[test]
+fn duplicate_peers() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 4,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 4,
+        duplicate_peers: 2,
+        ..Default::default()
+    });
+
+    for _ in 0..3 {
+        network.connect(host1, host2, 255, 0);
+    }
+    let events = network.update(1000);
+    assert_eq!(
+        events
+            .iter()
+            .filter(|event| event.is_connect() && event.to() == host2)
+            .count(),
+        2
+    );
+    assert_eq!(network.host(host2).statistics().connected_peers, 2);
+}
+
+#[test]
+fn intercept() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    network
+        .host_mut(host2)
+        .set_intercept(Some(Box::new(|socket, address, data| {
+            if data == "ping".as_bytes() {
+                enet::Socket::send(socket, *address, "pong".as_bytes()).unwrap();
+                enet::Intercept::Consume
+            } else {
+                enet::Intercept::Ignore
+            }
+        })));
+    let pongs = Arc::new(AtomicUsize::new(0));
+    let intercept_pongs = pongs.clone();
+    network
+        .host_mut(host1)
+        .set_intercept(Some(Box::new(move |_, _, data| {
+            if data == "pong".as_bytes() {
+                intercept_pongs.fetch_add(1, Ordering::SeqCst);
+                enet::Intercept::Consume
+            } else {
+                enet::Intercept::Ignore
+            }
+        })));
+
+    network.connect(host1, host2, 255, 5);
+    let events = network.update(2);
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|event| event.is_connect()));
+
+    enet::Socket::send(
+        network.host_mut(host1).socket_mut(),
+        host2,
+        "ping".as_bytes(),
+    )
+    .unwrap();
+    let events = network.update(2);
+    assert!(events.is_empty());
+    assert_eq!(pongs.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn service_timeout() {
+    use std::{
+        net::UdpSocket,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    let mut host = enet::Host::new(
+        UdpSocket::bind("127.0.0.1:0").unwrap(),
+        enet::HostSettings::default(),
+    )
+    .unwrap();
+
+    let start = Instant::now();
+    assert!(host
+        .service_timeout(Duration::from_millis(50))
+        .unwrap()
+        .is_none());
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    let address = host.socket().local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let mut host = enet::Host::new(
+            UdpSocket::bind("127.0.0.1:0").unwrap(),
+            enet::HostSettings::default(),
+        )
+        .unwrap();
+        host.connect(address, 1, 0).unwrap();
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(500) {
+            host.service_timeout(Duration::from_millis(100)).unwrap();
+        }
+    });
+    let start = Instant::now();
+    let event = host.service_timeout(Duration::from_secs(10)).unwrap();
+    assert!(matches!(event, Some(enet::Event::Connect { .. })));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    client.join().unwrap();
+
+    // Waiting leaves the socket non-blocking with its read timeout, whether or not anything
+    // arrived.
+    let mut socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(3)))
+        .unwrap();
+    socket.set_nonblocking(true).unwrap();
+    assert!(!enet::WaitableSocket::wait_readable(&mut socket, Duration::from_millis(10)).unwrap());
+    assert_eq!(socket.read_timeout().unwrap(), Some(Duration::from_secs(3)));
+    assert_eq!(
+        socket.recv_from(&mut [0; 1]).unwrap_err().kind(),
+        std::io::ErrorKind::WouldBlock
+    );
+}
+
+#[test]
+fn receive_batch() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        receive_batch_size: 4,
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 1, 0);
+    network.update(2);
+
+    for i in 0..10_u8 {
+        network.send(host1, host2, 0, &enet::Packet::reliable(&[i][..]));
+        // Flush each packet into its own datagram.
+        network.host_mut(host1).flush();
+    }
+    let events = network.update(1);
+    assert_eq!(events.len(), 10);
+    for (i, event) in events.iter().enumerate() {
+        assert!(event.is_receive_and(|event| event.packet.data() == [i as u8]));
+    }
+}
+
+#[test]
+fn send_vectored() {
+    use std::net::UdpSocket;
+
+    let mut scratch = Vec::new();
+    for local in ["127.0.0.1:0", "[::1]:0"] {
+        // Skip address families the machine doesn't support.
+        let Ok(mut sender) = UdpSocket::bind(local) else {
+            continue;
+        };
+        let receiver = UdpSocket::bind(local).unwrap();
+        let buffers: [&[u8]; 3] = [&[1, 2], &[], &[3, 4, 5]];
+        let sent_length = enet::Socket::send_vectored(
+            &mut sender,
+            receiver.local_addr().unwrap(),
+            &buffers,
+            &mut scratch,
+        )
+        .unwrap();
+        assert_eq!(sent_length, 5);
+        let mut buffer = [0; 16];
+        let (recv_length, address) = receiver.recv_from(&mut buffer).unwrap();
+        assert_eq!(buffer[..recv_length], [1, 2, 3, 4, 5]);
+        assert_eq!(address, sender.local_addr().unwrap());
+    }
+}
+
+#[cfg(all(feature = "mmsg", target_os = "linux"))]
+#[test]
+fn receive_batch_recvmmsg() {
+    use std::net::UdpSocket;
+
+    let mut receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    enet::Socket::init(
+        &mut receiver,
+        enet::SocketOptions {
+            receive_buffer: 0,
+            send_buffer: 0,
+        },
+    )
+    .unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let receiver_address = receiver.local_addr().unwrap();
+    sender.send_to(&[1; 10], receiver_address).unwrap();
+    sender.send_to(&[2; 20], receiver_address).unwrap();
+    sender
+        .send_to(&[3; enet::MTU_MAX + 1], receiver_address)
+        .unwrap();
+
+    let mut buffers = vec![[0; enet::MTU_MAX]; 4];
+    let mut received = vec![None, None, None, None];
+    let received_count =
+        enet::Socket::receive_batch(&mut receiver, &mut buffers, &mut received).unwrap();
+    assert_eq!(received_count, 3);
+    let sender_address = sender.local_addr().unwrap();
+    assert!(matches!(
+        received[0],
+        Some((address, enet::PacketReceived::Complete(10))) if address == sender_address
+    ));
+    assert_eq!(buffers[0][..10], [1; 10]);
+    assert!(matches!(
+        received[1],
+        Some((address, enet::PacketReceived::Complete(20))) if address == sender_address
+    ));
+    assert_eq!(buffers[1][..20], [2; 20]);
+    assert!(matches!(
+        received[2],
+        Some((_, enet::PacketReceived::Partial))
+    ));
+    assert!(received[3].is_none());
+
+    let received_count =
+        enet::Socket::receive_batch(&mut receiver, &mut buffers, &mut received).unwrap();
+    assert_eq!(received_count, 0);
+}
+
+#[cfg(all(feature = "mmsg", target_os = "linux"))]
+#[test]
+fn send_batch_sendmmsg() {
+    use std::net::UdpSocket;
+
+    let mut sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let receiver1 = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let receiver2 = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut buffers = vec![[0; enet::MTU_MAX]; 3];
+    buffers[0][..10].fill(1);
+    buffers[1][..20].fill(2);
+    buffers[2][..30].fill(3);
+    let datagrams = [
+        (receiver1.local_addr().unwrap(), 10),
+        (receiver2.local_addr().unwrap(), 20),
+        (receiver1.local_addr().unwrap(), 30),
+    ];
+    assert_eq!(
+        enet::Socket::send_batch(&mut sender, &buffers, &datagrams).unwrap(),
+        3
+    );
+
+    let mut buffer = [0; enet::MTU_MAX];
+    let sender_address = sender.local_addr().unwrap();
+    for (receiver, expected) in [
+        (&receiver1, &[1; 10][..]),
+        (&receiver2, &[2; 20][..]),
+        (&receiver1, &[3; 30][..]),
+    ] {
+        let (recv_length, address) = receiver.recv_from(&mut buffer).unwrap();
+        assert_eq!(address, sender_address);
+        assert_eq!(&buffer[..recv_length], expected);
+    }
+}
+
+fn send_batch_statistics(send_batch_size: usize) -> enet::HostStatistics {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 3,
+        send_batch_size,
+        ..Default::default()
+    });
+    let others: Vec<usize> = (0..3)
+        .map(|_| {
+            network.create_host(enet::HostSettings {
+                peer_limit: 1,
+                ..Default::default()
+            })
+        })
+        .collect();
+    for &other in &others {
+        network.connect(host1, other, 1, 0);
+    }
+    network.update(2);
+
+    // One datagram per peer, built in the same pass.
+    network.host_mut(host1).reset_statistics();
+    for (i, &other) in others.iter().enumerate() {
+        network.send(host1, other, 0, &enet::Packet::reliable(&[i as u8][..]));
+    }
+    network.host_mut(host1).flush();
+    let events = network.update(1);
+    assert_eq!(events.len(), 3);
+    for (i, &other) in others.iter().enumerate() {
+        assert!(events.iter().any(|event| {
+            event.to() == other && event.is_receive_and(|event| event.packet.data() == [i as u8])
+        }));
+    }
+    network.host(host1).statistics()
+}
+
+#[test]
+fn send_batch() {
+    let statistics = send_batch_statistics(2);
+    assert_eq!(statistics.total_sent_packets, 3);
+    assert_eq!(statistics, send_batch_statistics(1));
+}
+
+#[test]
+fn user_data() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    struct UserData(Arc<AtomicUsize>);
+
+    impl Drop for UserData {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 1, 0);
+    network.update(2);
+    let peer = network.resolve_peer(host2, host1);
+    let drops = Arc::new(AtomicUsize::new(0));
+    network
+        .host_mut(host2)
+        .peer_mut(peer)
+        .set_user_data(UserData(drops.clone()));
+    assert!(network.host(host2).peer(peer).user_data::<u32>().is_none());
+    assert!(network
+        .host(host2)
+        .peer(peer)
+        .user_data::<UserData>()
+        .is_some());
+
+    // User data is still available after the disconnect event.
+    network.disconnect(host1, host2, 0);
+    let events = network.update(2);
+    assert!(events
+        .iter()
+        .any(|event| event.to() == host2 && event.is_disconnect()));
+    assert!(network
+        .host(host2)
+        .peer(peer)
+        .user_data::<UserData>()
+        .is_some());
+    assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+    // A new connection in the same slot does not inherit the user data.
+    network.connect(host1, host2, 1, 0);
+    network.update(2);
+    let peer = network.resolve_peer(host2, host1);
+    assert!(network
+        .host(host2)
+        .peer(peer)
+        .user_data::<UserData>()
+        .is_none());
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+    network
+        .host_mut(host2)
+        .peer_mut(peer)
+        .set_user_data(UserData(drops.clone()));
+    let user_data = network
+        .host_mut(host2)
+        .peer_mut(peer)
+        .take_user_data::<UserData>();
+    assert!(user_data.is_some());
+    assert!(network
+        .host(host2)
+        .peer(peer)
+        .user_data::<UserData>()
+        .is_none());
+    drop(user_data);
+    assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+    network
+        .host_mut(host2)
+        .peer_mut(peer)
+        .set_user_data(UserData(drops.clone()));
+    network.host_mut(host2).peer_mut(peer).reset();
+    assert_eq!(drops.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn stale_peer_id() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 1, 0);
+    network.update(2);
+    let old_peer = network.resolve_peer(host2, host1);
+    assert!(network.host(host2).get_peer(old_peer).is_some());
+
+    // The peer remains addressable until it is reused.
+    network.disconnect(host1, host2, 0);
+    network.update(2);
+    assert!(network.host(host2).get_peer(old_peer).is_some());
+
+    network.connect(host1, host2, 1, 0);
+    network.update(2);
+    let new_peer = network.resolve_peer(host2, host1);
+    assert_eq!(new_peer.index(), old_peer.index());
+    assert_ne!(new_peer.generation(), old_peer.generation());
+    assert!(network.host(host2).get_peer(old_peer).is_none());
+    assert!(network.host_mut(host2).get_peer_mut(old_peer).is_none());
+    assert_eq!(network.host(host2).peer(new_peer).id(), new_peer);
+}
+
+#[test]
+fn host_sender() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 1, 0);
+    network.update(2);
+    let peer = network.resolve_peer(host1, host2);
+    let sender = network.host_mut(host1).sender();
+
+    let stale_peer = enet::PeerID::new(peer.index(), peer.generation().wrapping_add(1));
+    let (result, stale_result) = std::thread::spawn({
+        let sender = sender.clone();
+        move || {
+            let result =
+                sender.send_with_result(peer, 0, enet::Packet::reliable("hello".as_bytes()));
+            sender.broadcast(0, enet::Packet::reliable("world".as_bytes()));
+            let stale_result =
+                sender.send_with_result(stale_peer, 0, enet::Packet::reliable([].as_slice()));
+            sender.send(stale_peer, 1, enet::Packet::reliable([].as_slice()));
+            (result, stale_result)
+        }
+    })
+    .join()
+    .unwrap();
+
+    // Nothing is applied until the host is serviced.
+    assert!(result.try_recv().is_err());
+    let events = network.update(1);
+    assert_eq!(result.try_recv(), Ok(Ok(())));
+    assert_eq!(
+        stale_result.try_recv(),
+        Ok(Err(enet::error::PeerSendError::NotConnected))
+    );
+    // Only the fire-and-forget send reports its failure as an event.
+    assert_eq!(events.len(), 3);
+    assert_eq!(
+        events
+            .iter()
+            .filter(|event| event.is_send_failed_and(|event| {
+                event.to == host1
+                    && event.peer == stale_peer
+                    && event.channel_id == 1
+                    && event.error == enet::error::PeerSendError::NotConnected
+            }))
+            .count(),
+        1
+    );
+    let received = events
+        .iter()
+        .filter(|event| event.is_receive())
+        .collect::<Vec<_>>();
+    assert_eq!(received.len(), 2);
+    assert!(received[0].is_receive_and(|event| event.packet.data() == b"hello"));
+    assert!(received[1].is_receive_and(|event| event.packet.data() == b"world"));
+
+    sender.disconnect(peer, 3);
+    let events = network.update(1);
+    assert_eq!(events.len(), 1);
+    assert!(events[0]
+        .is_disconnect_and(|event| event.from == host1 && event.to == host2 && event.data == 3));
+}
+
+#[test]
+fn packet_shared_across_threads() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    struct Data(Vec<u8>, Arc<AtomicUsize>);
+
+    impl AsRef<[u8]> for Data {
+        fn as_ref(&self) -> &[u8] {
+            &self.0
+        }
+    }
+
+    impl Drop for Data {
+        fn drop(&mut self) {
+            self.1.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 1, 0);
+    network.update(2);
+
+    // The host holds a reference per fragment while other threads clone and drop the packet.
+    let drops = Arc::new(AtomicUsize::new(0));
+    let data: Vec<u8> = (0..10000).map(|i| i as u8).collect();
+    let packet = enet::Packet::reliable(Box::new(Data(data.clone(), drops.clone())));
+    network.send(host1, host2, 0, &packet);
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let packet = packet.clone();
+            std::thread::spawn(move || {
+                for _ in 0..10000 {
+                    drop(packet.clone());
+                }
+                packet
+            })
+        })
+        .collect();
+    let events = network.update(100);
+    for thread in threads {
+        assert_eq!(thread.join().unwrap().data(), data.as_slice());
+    }
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_receive_and(|event| event.packet.data() == data.as_slice()));
+    assert_eq!(drops.load(Ordering::SeqCst), 0);
+    drop(packet);
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn connect_failed() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+
+    // The connection request is abandoned once the handshake timeout expires.
+    network.connect_with_settings(
+        host1,
+        host2,
+        1,
+        0,
+        enet::ConnectSettings {
+            timeout: Some(Duration::from_millis(1000)),
+            ..Default::default()
+        },
+    );
+    network.conditions(host1, host2, NetworkConditions::disconnected());
+    let events = network.update(1000);
+    assert!(events.is_empty());
+    let events = network.update(1);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_connect_failed_and(|event| event.from == host2
+        && event.to == host1
+        && event.reason == enet::DisconnectReason::Timeout));
+    assert!(network
+        .host_mut(host1)
+        .peers()
+        .all(|peer| peer.state() == enet::PeerState::Disconnected));
+
+    // The connection request is abandoned once it has been resent too many times. It is sent
+    // after 0ms, then resent after 500ms and 1500ms, and the last attempt expires at 3500ms.
+    network.connect_with_settings(
+        host1,
+        host2,
+        1,
+        0,
+        enet::ConnectSettings {
+            retries: Some(2),
+            ..Default::default()
+        },
+    );
+    network.conditions(host1, host2, NetworkConditions::disconnected());
+    let events = network.update(3500);
+    assert!(events.is_empty());
+    let events = network.update(1);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_connect_failed_and(|event| event.from == host2
+        && event.to == host1
+        && event.reason == enet::DisconnectReason::Timeout));
+
+    // Without settings, the connection request is abandoned once the peer times out.
+    network.connect(host1, host2, 1, 0);
+    network.conditions(host1, host2, NetworkConditions::disconnected());
+    let events = network.update(5000);
+    assert!(events.is_empty());
+    let events = network.update(60000);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_connect_failed_and(|event| event.from == host2
+        && event.to == host1
+        && event.reason == enet::DisconnectReason::Timeout));
+}
+
+#[test]
+fn send_tracked() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 1, 0);
+    network.update(10);
+
+    let peer = network.resolve_peer(host1, host2);
+    assert_eq!(
+        network
+            .host_mut(host1)
+            .peer_mut(peer)
+            .send_tracked(0, &enet::Packet::unreliable(&[1, 2, 3][..])),
+        Err(enet::error::PeerSendError::NotReliable)
+    );
+
+    // A packet is acknowledged once.
+    let token1 = network.send_tracked(host1, host2, 0, &enet::Packet::reliable(&[1, 2, 3][..]));
+    let events = network.update(10);
+    assert_eq!(events.len(), 2);
+    assert!(events
+        .iter()
+        .any(|event| event.is_receive_and(|event| event.to == host2)));
+    assert!(events.iter().any(
+        |event| event.is_acknowledged_and(|event| event.from == host2
+            && event.to == host1
+            && event.token == token1)
+    ));
+
+    // A fragmented packet is acknowledged once, after every fragment.
+    let token2 = network.send_tracked(host1, host2, 0, &enet::Packet::reliable(vec![0; 10000]));
+    assert_ne!(token1, token2);
+    let events = network.update(100);
+    assert_eq!(events.len(), 2);
+    assert!(events
+        .iter()
+        .any(|event| event.is_receive_and(|event| event.packet.data().len() == 10000)));
+    assert!(events
+        .iter()
+        .any(|event| event.is_acknowledged_and(|event| event.token == token2)));
+
+    // Packets which are never acknowledged are dropped when the peer is reset.
+    network.conditions(host1, host2, NetworkConditions::disconnected());
+    let token3 = network.send_tracked(host1, host2, 0, &enet::Packet::reliable(&[4][..]));
+    let token4 = network.send_tracked(host1, host2, 0, &enet::Packet::reliable(vec![0; 10000]));
+    network.update(10);
+    network.disconnect_now(host1, host2, 0);
+    let events = network.update(1);
+    assert_eq!(events.len(), 2);
+    assert!(events[0]
+        .is_dropped_and(|event| event.from == host2 && event.to == host1 && event.token == token3));
+    assert!(events[1].is_dropped_and(|event| event.token == token4));
+}
+
+#[test]
+fn send_tracked_reconnect() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host3 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 1, 0);
+    network.update(10);
+    let old_peer = network.resolve_peer(host1, host2);
+    network.conditions(host1, host2, NetworkConditions::disconnected());
+    let token = network.send_tracked(host1, host2, 0, &enet::Packet::reliable(&[1][..]));
+    network.update(10);
+
+    // Reusing the peer before the dropped event is dispatched does not report it against the new
+    // connection.
+    network.disconnect_now(host1, host2, 0);
+    network.connect(host1, host3, 1, 0);
+    let events = network.update(10);
+    assert!(events[0].is_dropped_and(|event| event.from == host2
+        && event.peer == old_peer
+        && event.token == token));
+    let new_peer = network.resolve_peer(host1, host3);
+    assert_eq!(new_peer.index(), old_peer.index());
+    assert_ne!(new_peer, old_peer);
+    assert!(network.host(host1).get_peer(old_peer).is_none());
+}
+
+#[test]
+fn send_settings() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 2, 0);
+    network.update(10);
+
+    let peer = network.resolve_peer(host1, host2);
+    let peer = network.host_mut(host1).peer_mut(peer);
+    let settings = enet::SendSettings {
+        supersede_key: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(
+        peer.send_with_settings(0, &enet::Packet::reliable(&[1][..]), settings),
+        Err(enet::error::PeerSendError::NotUnreliable)
+    );
+    assert_eq!(
+        peer.send_with_settings(
+            0,
+            &enet::Packet::reliable(&[1][..]),
+            enet::SendSettings::default()
+        ),
+        Ok(())
+    );
+    let events = network.update(10);
+    assert_eq!(events.len(), 1);
+
+    // Packets are discarded once their time to live expires, including fragmented packets.
+    let settings = enet::SendSettings {
+        time_to_live: Some(Duration::from_millis(10)),
+        ..Default::default()
+    };
+    network.send_with_settings(
+        host1,
+        host2,
+        0,
+        &enet::Packet::unreliable(&[1][..]),
+        settings,
+    );
+    network.send_with_settings(
+        host1,
+        host2,
+        0,
+        &enet::Packet::always_unreliable(vec![1; 10000]),
+        settings,
+    );
+    network.skip(11);
+    let events = network.update(10);
+    assert!(events.is_empty());
+    network.send_with_settings(
+        host1,
+        host2,
+        0,
+        &enet::Packet::unreliable(&[2][..]),
+        settings,
+    );
+    network.send_with_settings(
+        host1,
+        host2,
+        0,
+        &enet::Packet::always_unreliable(vec![2; 10000]),
+        settings,
+    );
+    network.skip(10);
+    let events = network.update(10);
+    assert_eq!(events.len(), 2);
+    assert!(events[0].is_receive_and(|event| event.packet.data() == [2]));
+    assert!(events[1].is_receive_and(|event| event.packet.data() == [2; 10000]));
+
+    // Packets are replaced by newer packets on the same channel with the same key.
+    let supersede = |supersede_key| enet::SendSettings {
+        supersede_key: Some(supersede_key),
+        ..Default::default()
+    };
+    let packet = |data: u8| enet::Packet::unreliable(vec![data; 10]);
+    network.send_with_settings(host1, host2, 0, &packet(1), supersede(1));
+    network.send_with_settings(
+        host1,
+        host2,
+        0,
+        &enet::Packet::always_unreliable(vec![2; 10000]),
+        supersede(1),
+    );
+    network.send_with_settings(host1, host2, 0, &packet(3), supersede(2));
+    network.send_with_settings(host1, host2, 1, &packet(4), supersede(1));
+    network.send(host1, host2, 0, &packet(5));
+    network.send_with_settings(host1, host2, 0, &packet(6), supersede(1));
+    let events = network.update(10);
+    let received: Vec<(u8, u8)> = events
+        .iter()
+        .filter_map(|event| {
+            let received = Cell::new(None);
+            event.is_receive_and(|event| {
+                received.set(Some((event.channel_id, event.packet.data()[0])));
+                true
+            });
+            received.get()
+        })
+        .collect();
+    assert_eq!(received, [(0, 3), (1, 4), (0, 5), (0, 6)]);
+
+    // A send which fails does not discard any packets.
+    network.send_with_settings(host1, host2, 0, &packet(7), supersede(1));
+    network
+        .host_mut(host1)
+        .set_maximum_packet_size(100)
+        .unwrap();
+    let peer = network.resolve_peer(host1, host2);
+    let peer = network.host_mut(host1).peer_mut(peer);
+    assert_eq!(
+        peer.send_with_settings(0, &enet::Packet::unreliable(vec![8; 101]), supersede(1)),
+        Err(enet::error::PeerSendError::PacketTooLarge)
+    );
+    assert_eq!(peer.queued_bytes(), 10);
+    network
+        .host_mut(host1)
+        .set_maximum_packet_size(10000)
+        .unwrap();
+    let events = network.update(10);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_receive_and(|event| event.packet.data() == [7; 10]));
+
+    // The rest of a packet which was partially sent, when a send failed, is discarded too.
+    network.send_with_settings(
+        host1,
+        host2,
+        0,
+        &enet::Packet::always_unreliable(vec![9; 10000]),
+        supersede(1),
+    );
+    network.fail_send(host1, 1);
+    network.update(1);
+    let peer = network.resolve_peer(host1, host2);
+    let queued_bytes = network.host(host1).peer(peer).queued_bytes();
+    assert!(queued_bytes > 0 && queued_bytes < 10000);
+    network.send_with_settings(host1, host2, 0, &packet(10), supersede(1));
+    assert_eq!(network.host(host1).peer(peer).queued_bytes(), 10);
+    let events = network.update(10);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_receive_and(|event| event.packet.data() == [10; 10]));
+}
+
+#[test]
+fn queue_watermarks() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 1, 0);
+    network.update(10);
+    network.conditions(host1, host2, NetworkConditions::latency(10));
+
+    let peer = network.resolve_peer(host1, host2);
+    let peer_mut = network.host_mut(host1).peer_mut(peer);
+    assert_eq!(peer_mut.queue_watermarks(), (0, usize::MAX));
+    assert!(peer_mut.set_queue_watermarks(2, 1).is_err());
+    peer_mut.set_queue_watermarks(16000, 64000).unwrap();
+    assert_eq!(peer_mut.queued_bytes(), 0);
+    assert_eq!(peer_mut.reliable_in_flight(), 0);
+    let window_size = peer_mut.window_size();
+    assert!(window_size > 0);
+
+    // A producer sending 1MB stops while the queue is above the high watermark, and continues
+    // once it is writable again.
+    let mut sent = 0;
+    let mut received = 0;
+    let mut writable = 0;
+    let mut blocked = false;
+    while received < 1000 {
+        let peer_mut = network.host_mut(host1).peer_mut(peer);
+        while !blocked && sent < 1000 {
+            peer_mut
+                .send(0, &enet::Packet::reliable(vec![0; 1000]))
+                .unwrap();
+            sent += 1;
+            blocked = peer_mut.queued_bytes() > 64000;
+        }
+        assert!(peer_mut.queued_bytes() <= 65000);
+        assert!(peer_mut.reliable_in_flight() <= window_size);
+        for event in network.update(1) {
+            if event.is_writable() {
+                assert!(blocked);
+                assert_eq!(event.to(), host1);
+                assert!(network.host(host1).peer(peer).queued_bytes() <= 16000);
+                blocked = false;
+                writable += 1;
+            } else if event.is_receive() {
+                received += 1;
+            }
+        }
+    }
+    assert!(writable > 1);
+    network.update(100);
+    let peer_mut = network.host_mut(host1).peer_mut(peer);
+    assert_eq!(peer_mut.queued_bytes(), 0);
+    assert_eq!(peer_mut.reliable_in_flight(), 0);
+
+    // Disconnecting discards the queued data, so none is reported while disconnecting.
+    for _ in 0..100 {
+        peer_mut
+            .send(0, &enet::Packet::reliable(vec![0; 1000]))
+            .unwrap();
+    }
+    assert!(peer_mut.queued_bytes() > 64000);
+    network.disconnect(host1, host2, 0);
+    assert_eq!(network.host(host1).peer(peer).queued_bytes(), 0);
+    let events = network.update(1);
+    assert!(events.is_empty());
+    let peer = network.host(host1).peer(peer);
+    assert_eq!(peer.state(), enet::PeerState::Disconnecting);
+    assert_eq!(peer.queued_bytes(), 0);
+}
+
+/// Sends a small packet on channel 0 every 10ms while 1MB is queued on channel 1, and returns
+/// the highest latency of the small packets, and how long the bulk transfer took.
+fn channel_priority_latency(priority: u8) -> (u32, u32) {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    network
+        .host_mut(host1)
+        .set_channel_priority(0, priority)
+        .unwrap();
+
+    network.connect(host1, host2, 2, 0);
+    network.update(10);
+    let peer = network.resolve_peer(host1, host2);
+    assert_eq!(
+        network.host(host1).peer(peer).channel_priority(0),
+        Some(priority)
+    );
+    network.conditions(host1, host2, NetworkConditions::latency(10));
+
+    for _ in 0..100 {
+        network.send(host1, host2, 1, &enet::Packet::reliable(vec![0; 10000]));
+    }
+    let maximum_latency = Cell::new(0);
+    let mut bulk_received = 0;
+    let mut frame = 0_u32;
+    while bulk_received < 100 {
+        if frame % 10 == 0 {
+            network.send(
+                host1,
+                host2,
+                0,
+                &enet::Packet::reliable(&frame.to_le_bytes()[..]),
+            );
+        }
+        for event in network.update(1) {
+            if event.is_receive_and(|event| event.channel_id == 1) {
+                bulk_received += 1;
+            }
+            event.is_receive_and(|event| {
+                if event.channel_id == 0 {
+                    let sent = u32::from_le_bytes(event.packet.data().try_into().unwrap());
+                    maximum_latency.set(maximum_latency.get().max(frame - sent));
+                }
+                false
+            });
+        }
+        frame += 1;
+        assert!(frame < 60000);
+    }
+    (maximum_latency.get(), frame)
+}
+#[test]
+fn channel_priority() {
+    // Without priorities, small packets wait behind the bulk transfer.
+    let (latency, duration) = channel_priority_latency(0);
+    assert!(latency > 100);
+
+    // With priorities, small packets only wait for the link, and the bulk transfer is not slowed.
+    let (prioritized_latency, prioritized_duration) = channel_priority_latency(1);
+    assert!(prioritized_latency <= 20);
+    assert!(prioritized_duration <= duration + 10);
+
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    assert!(network
+        .host_mut(host1)
+        .set_channel_priority(255, 1)
+        .is_err());
+    assert_eq!(network.host(host1).channel_priority(255), None);
+    network.connect(host1, host2, 2, 0);
+    network.update(10);
+    let peer = network.resolve_peer(host1, host2);
+    let peer = network.host_mut(host1).peer_mut(peer);
+    assert_eq!(peer.channel_priority(1), Some(0));
+    peer.set_channel_priority(1, 2).unwrap();
+    assert_eq!(peer.channel_priority(1), Some(2));
+    assert!(peer.set_channel_priority(2, 2).is_err());
+    assert_eq!(peer.channel_priority(2), None);
+}
+
+#[test]
+fn channel_priority_queued() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    network.connect(host1, host2, 2, 0);
+    network.update(10);
+    let peer = network.resolve_peer(host1, host2);
+
+    // Packets queued before a priority change must not be overtaken by packets queued after it,
+    // or the older unreliable packets would be discarded as out of order.
+    for priority in [2, 0] {
+        for i in 0..10_u8 {
+            if i == 5 {
+                network
+                    .host_mut(host1)
+                    .peer_mut(peer)
+                    .set_channel_priority(1, priority)
+                    .unwrap();
+            }
+            network.send(host1, host2, 1, &enet::Packet::unreliable(&[i][..]));
+            network.send(host1, host2, 1, &enet::Packet::reliable(vec![i; 2000]));
+        }
+        let unreliable = RefCell::new(Vec::new());
+        let reliable = RefCell::new(Vec::new());
+        for event in network.update(10) {
+            event.is_receive_and(|event| {
+                let data = event.packet.data();
+                if data.len() == 1 {
+                    unreliable.borrow_mut().push(data[0]);
+                } else {
+                    reliable.borrow_mut().push(data[0]);
+                }
+                false
+            });
+        }
+        assert_eq!(unreliable.into_inner(), (0..10).collect::<Vec<_>>());
+        assert_eq!(reliable.into_inner(), (0..10).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn channel_priority_fair() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    network.host_mut(host1).set_channel_priority(0, 3).unwrap();
+    network.connect(host1, host2, 2, 0);
+    network.update(10);
+    network.conditions(host1, host2, NetworkConditions::latency(10));
+
+    // The high priority channel always has more queued than the link can carry, and the bulk
+    // transfer still gets its share: a quarter of what the high priority channel gets.
+    for _ in 0..100 {
+        network.send(host1, host2, 1, &enet::Packet::reliable(vec![0; 10000]));
+    }
+    let mut high_sent = 0;
+    let high_received = Cell::new(0);
+    let bulk_received = Cell::new(0);
+    for _ in 0..500 {
+        while high_sent - high_received.get() < 100_000 {
+            network.send(host1, host2, 0, &enet::Packet::reliable(vec![0; 1000]));
+            high_sent += 1000;
+        }
+        for event in network.update(1) {
+            event.is_receive_and(|event| {
+                let received = if event.channel_id == 0 {
+                    &high_received
+                } else {
+                    &bulk_received
+                };
+                received.set(received.get() + event.packet.data().len());
+                false
+            });
+        }
+    }
+    let (high_received, bulk_received) = (high_received.get(), bulk_received.get());
+    assert!(high_received > 1_000_000);
+    assert!(bulk_received * 5 >= high_received);
+    assert!(bulk_received * 3 <= high_received);
+}
+
+#[test]
+fn accept_connection() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        checksum: Some(Box::new(enet::crc32)),
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        checksum: Some(Box::new(enet::crc32)),
+        ..Default::default()
+    });
+    network.host_mut(host2).set_accept_connection(Some(Box::new(
+        |address, channel_count, data, peer_count| {
+            assert_eq!(*address, 0);
+            assert_eq!(channel_count, 2);
+            assert_eq!(peer_count, 0);
+            match data {
+                1 => enet::AcceptConnection::Reject(42),
+                2 => enet::AcceptConnection::Discard,
+                _ => enet::AcceptConnection::Accept,
+            }
+        },
+    )));
+
+    // A rejected connection is answered with a disconnect.
+    network.connect(host1, host2, 2, 1);
+    let events = network.update(2);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_connect_failed_and(
+        |event| event.to == host1 && event.reason == enet::DisconnectReason::Refused(42)
+    ));
+    assert!(network
+        .host_mut(host2)
+        .peers()
+        .all(|peer| peer.state() == enet::PeerState::Disconnected));
+
+    // A discarded connection is never answered, and times out.
+    network.connect(host1, host2, 2, 2);
+    let events = network.update(1000);
+    assert!(events.is_empty());
+    assert!(network
+        .host_mut(host2)
+        .peers()
+        .all(|peer| peer.state() == enet::PeerState::Disconnected));
+    let events = network.update(60000);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_connect_failed_and(
+        |event| event.to == host1 && event.reason == enet::DisconnectReason::Timeout
+    ));
+
+    network.connect(host1, host2, 2, 3);
+    let events = network.update(2);
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|event| event.is_connect()));
+}
+
+/// A toy cipher, which XORs data with the key and appends a checksum of the key, header, and data.
+struct XorCipher;
+
+impl enet::Cipher for XorCipher {
+    fn valid_key(&self, key: &[u8]) -> bool {
+        !key.is_empty()
+    }
+
+    fn overhead(&self) -> usize {
+        4
+    }
+
+    fn encrypt(
+        &mut self,
+        key: &[u8],
+        _nonce_counter: &mut enet::NonceCounter,
+        header: &[u8],
+        in_buffers: &[&[u8]],
+        out: &mut [u8],
+    ) -> usize {
+        let data = in_buffers.concat();
+        if out.len() < data.len() + 4 {
+            return 0;
+        }
+        for (i, byte) in data.iter().enumerate() {
+            out[i] = byte ^ key[i % key.len()];
+        }
+        let tag = enet::crc32(&[key, header, &data]);
+        out[data.len()..data.len() + 4].copy_from_slice(&tag.to_le_bytes());
+        data.len() + 4
+    }
+
+    fn decrypt(
+        &mut self,
+        key: &[u8],
+        _replay_window: &mut enet::ReplayWindow,
+        header: &[u8],
+        in_data: &[u8],
+        out: &mut [u8],
+    ) -> usize {
+        if in_data.len() < 4 || out.len() < in_data.len() - 4 {
+            return 0;
+        }
+        let (in_data, tag) = in_data.split_at(in_data.len() - 4);
+        for (i, byte) in in_data.iter().enumerate() {
+            out[i] = byte ^ key[i % key.len()];
+        }
+        if enet::crc32(&[key, header, &out[..in_data.len()]]).to_le_bytes() != tag {
+            return 0;
+        }
+        in_data.len()
+    }
+}
+
+fn cipher(cipher: fn() -> Box<dyn enet::Cipher>, key: &[u8], wrong_key: &[u8]) {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        cipher: Some(cipher()),
+        checksum: Some(Box::new(enet::crc32)),
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        cipher: Some(cipher()),
+        checksum: Some(Box::new(enet::crc32)),
+        ..Default::default()
+    });
+    let plaintext = Arc::new(AtomicBool::new(false));
+    let intercept_plaintext = plaintext.clone();
+    network
+        .host_mut(host2)
+        .set_intercept(Some(Box::new(move |_, _, data| {
+            if data.windows(6).any(|window| window == "secret".as_bytes()) {
+                intercept_plaintext.store(true, Ordering::SeqCst);
+            }
+            enet::Intercept::Ignore
+        })));
+
+    network.connect(host1, host2, 1, 0);
+    let events = network.update(2);
+    assert_eq!(events.len(), 2);
+
+    let peer1 = network.resolve_peer(host1, host2);
+    let peer2 = network.resolve_peer(host2, host1);
+    assert!(network
+        .host_mut(host1)
+        .peer_mut(peer1)
+        .set_cipher_key(Some(&[]))
+        .is_err());
+
+    // The packets sent before host2 sets its key are resent once it has, in both directions.
+    network
+        .host_mut(host1)
+        .peer_mut(peer1)
+        .set_cipher_key(Some(key))
+        .unwrap();
+    network.send(
+        host1,
+        host2,
+        0,
+        &enet::Packet::reliable("secret 1".as_bytes()),
+    );
+    network.send(host2, host1, 0, &enet::Packet::reliable("hello".as_bytes()));
+    let events = network.update(2);
+    assert!(events.is_empty());
+    network
+        .host_mut(host2)
+        .peer_mut(peer2)
+        .set_cipher_key(Some(key))
+        .unwrap();
+    let events = network.update(2000);
+    assert_eq!(events.len(), 2);
+    assert!(events
+        .iter()
+        .any(|event| event.is_receive_and(|event| event.packet.data() == "secret 1".as_bytes())));
+    assert!(events
+        .iter()
+        .any(|event| event.is_receive_and(|event| event.packet.data() == "hello".as_bytes())));
+
+    network.send(
+        host1,
+        host2,
+        0,
+        &enet::Packet::reliable("secret 2".as_bytes()),
+    );
+    let events = network.update(1);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_receive_and(|event| event.packet.data() == "secret 2".as_bytes()));
+    assert!(!plaintext.load(Ordering::SeqCst));
+
+    // Datagrams which are not encrypted with the key are discarded.
+    network
+        .host_mut(host1)
+        .peer_mut(peer1)
+        .set_cipher_key(Some(wrong_key))
+        .unwrap();
+    network.send(
+        host1,
+        host2,
+        0,
+        &enet::Packet::unreliable("secret 3".as_bytes()),
+    );
+    network.host_mut(host1).flush();
+    network
+        .host_mut(host1)
+        .peer_mut(peer1)
+        .set_cipher_key(None)
+        .unwrap();
+    network.send(
+        host1,
+        host2,
+        0,
+        &enet::Packet::unreliable("secret 4".as_bytes()),
+    );
+    let events = network.update(10);
+    assert!(events.is_empty());
+    assert!(plaintext.load(Ordering::SeqCst));
+}
+
+#[test]
+fn cipher_xor() {
+    cipher(|| Box::new(XorCipher), &[1, 2, 3, 4], &[5, 6, 7, 8]);
+}
+
+#[cfg(feature = "chacha20poly1305")]
+#[test]
+fn cipher_chacha20poly1305() {
+    cipher(
+        || Box::new(enet::ChaCha20Poly1305::new().unwrap()),
+        &[1; 32],
+        &[2; 32],
+    );
+}
+
+#[test]
+fn authenticator() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        authenticator: Some(Box::new(enet::SipHash::new())),
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        authenticator: Some(Box::new(enet::SipHash::new())),
+        ..Default::default()
+    });
+
+    network.connect(host1, host2, 1, 0);
+    let events = network.update(2);
+    assert_eq!(events.len(), 2);
+
+    let peer1 = network.resolve_peer(host1, host2);
+    let peer2 = network.resolve_peer(host2, host1);
+    assert!(network
+        .host_mut(host1)
+        .peer_mut(peer1)
+        .set_auth_key(Some(&[0; 8]))
+        .is_err());
+
+    // Once host1 has set its key, it discards datagrams which are not authenticated, even before
+    // any authenticated datagram has arrived. The packets sent before host2 sets its key are
+    // resent once it has.
+    network
+        .host_mut(host1)
+        .peer_mut(peer1)
+        .set_auth_key(Some(&[1; 16]))
+        .unwrap();
+    network.send(
+        host1,
+        host2,
+        0,
+        &enet::Packet::reliable("hello 1".as_bytes()),
+    );
+    network.send(
+        host2,
+        host1,
+        0,
+        &enet::Packet::reliable("hello 2".as_bytes()),
+    );
+    let events = network.update(2);
+    assert!(events.is_empty());
+    assert!(
+        network
+            .host(host1)
+            .statistics()
+            .total_unauthenticated_packets
+            > 0
+    );
+    network
+        .host_mut(host2)
+        .peer_mut(peer2)
+        .set_auth_key(Some(&[1; 16]))
+        .unwrap();
+    let events = network.update(2000);
+    assert_eq!(events.len(), 2);
+    assert!(events
+        .iter()
+        .any(|event| event.is_receive_and(|event| event.packet.data() == "hello 1".as_bytes())));
+    assert!(events
+        .iter()
+        .any(|event| event.is_receive_and(|event| event.packet.data() == "hello 2".as_bytes())));
+    assert_eq!(
+        network
+            .host(host2)
+            .statistics()
+            .total_unauthenticated_packets,
+        0
+    );
+
+    // Datagrams authenticated with another key, or not at all, are counted and discarded.
+    network
+        .host_mut(host1)
+        .peer_mut(peer1)
+        .set_auth_key(Some(&[2; 16]))
+        .unwrap();
+    network.send(
+        host1,
+        host2,
+        0,
+        &enet::Packet::unreliable("hello 3".as_bytes()),
+    );
+    network.host_mut(host1).flush();
+    network
+        .host_mut(host1)
+        .peer_mut(peer1)
+        .set_auth_key(None)
+        .unwrap();
+    network.send(
+        host1,
+        host2,
+        0,
+        &enet::Packet::unreliable("hello 4".as_bytes()),
+    );
+    let events = network.update(1);
+    assert!(events.is_empty());
+    assert_eq!(
+        network
+            .host(host2)
+            .statistics()
+            .total_unauthenticated_packets,
+        2
+    );
+}
+
+/// Small packets resembling game state updates, with a few entities whose positions drift.
+fn game_state_packets(seed: u64, count: usize) -> Vec<Vec<u8>> {
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(seed);
+    let mut positions = [[0_i16; 3]; 32];
+    (0..count)
+        .map(|tick| {
+            let mut packet = vec![rng.random_range(0..4)];
+            packet.extend_from_slice(&(tick as u32).to_le_bytes());
+            let entity_count = rng.random_range(3..16);
+            packet.push(entity_count);
+            for _ in 0..entity_count {
+                let entity = rng.random_range(0..positions.len());
+                packet.extend_from_slice(&(entity as u16).to_le_bytes());
+                for position in &mut positions[entity] {
+                    *position = position.wrapping_add(rng.random_range(-2..=2));
+                    packet.extend_from_slice(&position.to_le_bytes());
+                }
+                packet.push(if rng.random_bool(0.9) { 100 } else { 50 });
+                packet.push(rng.random_range(0..4));
+            }
+            packet
+        })
+        .collect()
+}
+
+#[test]
+fn range_coder_dictionary() {
+    use enet::Compressor;
+
+    let dictionary =
+        enet::RangeCoderDictionary::train(game_state_packets(0, 1000).iter().map(Vec::as_slice));
+    assert!(dictionary.symbol_count() > 0);
+
+    let mut range_coder = enet::RangeCoder::new();
+    let mut dictionary_range_coder = enet::RangeCoder::with_dictionary(dictionary.clone());
+    let mut other_dictionary_range_coder = enet::RangeCoder::with_dictionary(dictionary);
+    let mut original_size = 0;
+    let mut compressed_size = 0;
+    let mut dictionary_compressed_size = 0;
+    for packet in game_state_packets(1, 1000) {
+        let mut out = [0; 4096];
+        let mut decompressed = [0; 4096];
+        original_size += packet.len();
+        compressed_size += range_coder.compress(&[&packet], packet.len(), &mut out);
+
+        let size = dictionary_range_coder.compress(&[&packet], packet.len(), &mut out);
+        assert!(size > 0);
+        dictionary_compressed_size += size;
+        let decompressed_size =
+            other_dictionary_range_coder.decompress(&out[..size], &mut decompressed);
+        assert_eq!(&decompressed[..decompressed_size], packet.as_slice());
+        let decompressed_size = range_coder.decompress(&out[..size], &mut decompressed);
+        assert_ne!(&decompressed[..decompressed_size], packet.as_slice());
+    }
+    // At the time of writing, the range coder compresses these packets to 75% of their original
+    // size, and 54% with a dictionary.
+    assert!(compressed_size * 100 < original_size * 80);
+    assert!(dictionary_compressed_size * 100 < compressed_size * 80);
+}
+
+#[test]
+fn range_coder_dictionary_bytes() {
+    use enet::Compressor;
+    use rand::{Rng, SeedableRng};
+
+    let empty = enet::RangeCoderTrainer::new().dictionary();
+    let loaded = enet::RangeCoderDictionary::from_bytes(&empty.to_bytes()).unwrap();
+    assert_eq!(loaded.symbol_count(), 0);
+
+    let dictionary =
+        enet::RangeCoderDictionary::train(game_state_packets(0, 1000).iter().map(Vec::as_slice));
+    let bytes = dictionary.to_bytes();
+    let loaded = enet::RangeCoderDictionary::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded.symbol_count(), dictionary.symbol_count());
+    assert_eq!(loaded.to_bytes(), bytes);
+    let mut range_coder = enet::RangeCoder::with_dictionary(dictionary);
+    let mut loaded_range_coder = enet::RangeCoder::with_dictionary(loaded);
+    for packet in game_state_packets(1, 100) {
+        let mut out = [0; 4096];
+        let mut loaded_out = [0; 4096];
+        let size = range_coder.compress(&[&packet], packet.len(), &mut out);
+        let loaded_size = loaded_range_coder.compress(&[&packet], packet.len(), &mut loaded_out);
+        assert_eq!(out[..size], loaded_out[..loaded_size]);
+    }
+
+    // Truncated, oversized, and with a child, parent, or prediction outside of the model.
+    let symbol = |index: usize, offset: usize| 5 + index * 16 + offset;
+    assert!(enet::RangeCoderDictionary::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(enet::RangeCoderDictionary::from_bytes(&[]).is_err());
+    let mut oversized = bytes[..5].to_vec();
+    oversized[..2].copy_from_slice(&2049_u16.to_le_bytes());
+    oversized.resize(5 + 2049 * 16, 0);
+    assert!(enet::RangeCoderDictionary::from_bytes(&oversized).is_err());
+    for (offset, value) in [
+        (symbol(0, 8), 0xffff),
+        (symbol(1, 14), 0xffff),
+        (symbol(1, 14), 1),
+        (2, 0xffff),
+    ] {
+        let mut malformed = bytes.clone();
+        malformed[offset..offset + 2].copy_from_slice(&u16::to_le_bytes(value));
+        assert!(enet::RangeCoderDictionary::from_bytes(&malformed).is_err());
+    }
+    let mut malformed = bytes.clone();
+    malformed[4] = 3;
+    assert!(enet::RangeCoderDictionary::from_bytes(&malformed).is_err());
+
+    // Whatever corrupted dictionaries get through must not break the range coder.
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let packets = game_state_packets(2, 10);
+    for _ in 0..1000 {
+        let mut corrupted = bytes.clone();
+        for _ in 0..rng.random_range(1..8) {
+            let offset = rng.random_range(0..corrupted.len());
+            corrupted[offset] = rng.random();
+        }
+        let Ok(dictionary) = enet::RangeCoderDictionary::from_bytes(&corrupted) else {
+            continue;
+        };
+        let mut range_coder = enet::RangeCoder::with_dictionary(dictionary);
+        for packet in &packets {
+            let mut out = [0; 4096];
+            let mut decompressed = [0; 4096];
+            let size = range_coder.compress(&[packet], packet.len(), &mut out);
+            range_coder.decompress(&out[..size], &mut decompressed);
+            range_coder.decompress(packet, &mut decompressed);
+        }
+    }
+}
+
+#[test]
+fn range_coder_dictionary_host() {
+    let dictionary =
+        enet::RangeCoderDictionary::train(game_state_packets(0, 100).iter().map(Vec::as_slice));
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        compressor: Some(Box::new(enet::RangeCoder::with_dictionary(
+            dictionary.clone(),
+        ))),
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        compressor: Some(Box::new(enet::RangeCoder::with_dictionary(dictionary))),
+        ..Default::default()
+    });
+    network.connect(host1, host2, 1, 0);
+    let events = network.update(2);
+    assert_eq!(events.len(), 2);
+
+    let packets = game_state_packets(1, 100);
+    for packet in &packets {
+        network.send(host1, host2, 0, &enet::Packet::reliable(packet.as_slice()));
+    }
+    let events = network.update(10);
+    assert_eq!(events.len(), packets.len());
+    for (event, packet) in events.iter().zip(&packets) {
+        assert!(event.is_receive_and(|event| event.packet.data() == packet.as_slice()));
+    }
+}
+
+/// The transpiled range coder, which the safe implementation must match exactly.
+struct ReferenceRangeCoder {
+    range_coder: *mut crate::ENetRangeCoder,
+}
+
+impl ReferenceRangeCoder {
+    fn new() -> Self {
+        let mut range_coder = Self {
+            range_coder: unsafe { crate::enet_range_coder_create().cast() },
+        };
+        // The symbols start uninitialized, but are compared with the safe implementation's.
+        range_coder.set_symbols(&[crate::RangeCoderSymbol::default(); crate::RANGE_CODER_SYMBOLS]);
+        range_coder
+    }
+
+    fn symbols(&self) -> Vec<crate::RangeCoderSymbol> {
+        unsafe { &(*self.range_coder).symbols }
+            .iter()
+            .map(|symbol| crate::RangeCoderSymbol {
+                value: symbol.value,
+                count: symbol.count,
+                under: symbol.under,
+                left: symbol.left,
+                right: symbol.right,
+                symbols: symbol.symbols,
+                escapes: symbol.escapes,
+                total: symbol.total,
+                parent: symbol.parent,
+            })
+            .collect()
+    }
+
+    fn set_symbols(&mut self, symbols: &[crate::RangeCoderSymbol]) {
+        for (reference, symbol) in unsafe { &mut (*self.range_coder).symbols }
+            .iter_mut()
+            .zip(symbols)
+        {
+            *reference = crate::ENetSymbol {
+                value: symbol.value,
+                count: symbol.count,
+                under: symbol.under,
+                left: symbol.left,
+                right: symbol.right,
+                symbols: symbol.symbols,
+                escapes: symbol.escapes,
+                total: symbol.total,
+                parent: symbol.parent,
+            };
+        }
+    }
+
+    fn compress(
+        &mut self,
+        model: Option<&mut crate::RangeCoderModel>,
+        in_buffers: &[&[u8]],
+        out: &mut [u8],
+    ) -> usize {
+        let buffers: Vec<crate::ENetBuffer> = in_buffers
+            .iter()
+            .map(|in_buffer| crate::ENetBuffer {
+                data: in_buffer.as_ptr().cast_mut(),
+                data_length: in_buffer.len(),
+            })
+            .collect();
+        let mut reference_model = model.as_deref().map(|model| crate::ENetRangeCoderModel {
+            next_symbol: model.next_symbol,
+            predicted: model.predicted,
+            order: model.order,
+        });
+        let size = unsafe {
+            crate::enet_range_coder_compress(
+                self.range_coder.cast(),
+                reference_model.as_mut(),
+                buffers.as_ptr(),
+                buffers.len(),
+                in_buffers.iter().map(|in_buffer| in_buffer.len()).sum(),
+                out.as_mut_ptr(),
+                out.len(),
+            )
+        };
+        if let (Some(model), Some(reference_model)) = (model, reference_model) {
+            *model = crate::RangeCoderModel {
+                next_symbol: reference_model.next_symbol,
+                predicted: reference_model.predicted,
+                order: reference_model.order,
+            };
+        }
+        size
+    }
+
+    fn decompress(
+        &mut self,
+        model: Option<&crate::RangeCoderModel>,
+        in_data: &[u8],
+        out: &mut [u8],
+    ) -> usize {
+        let reference_model = model.map(|model| crate::ENetRangeCoderModel {
+            next_symbol: model.next_symbol,
+            predicted: model.predicted,
+            order: model.order,
+        });
+        unsafe {
+            crate::enet_range_coder_decompress(
+                self.range_coder.cast(),
+                reference_model.as_ref(),
+                in_data.as_ptr(),
+                in_data.len(),
+                out.as_mut_ptr(),
+                out.len(),
+            )
+        }
+    }
+}
+
+impl Drop for ReferenceRangeCoder {
+    fn drop(&mut self) {
+        unsafe { crate::enet_range_coder_destroy(self.range_coder.cast()) };
+    }
+}
+
+/// Runs the safe range coder alongside the reference, checking that every call returns the same
+/// result, writes the same output, and leaves the same model behind.
+struct DifferentialRangeCoder {
+    symbols: Vec<crate::RangeCoderSymbol>,
+    reference: ReferenceRangeCoder,
+    dictionary: Option<(Vec<crate::RangeCoderSymbol>, crate::RangeCoderModel)>,
+}
+
+impl DifferentialRangeCoder {
+    fn new() -> Self {
+        Self {
+            symbols: vec![crate::RangeCoderSymbol::default(); crate::RANGE_CODER_SYMBOLS],
+            reference: ReferenceRangeCoder::new(),
+            dictionary: None,
+        }
+    }
+
+    fn load_dictionary(&mut self) -> Option<crate::RangeCoderModel> {
+        self.dictionary.as_ref().map(|(symbols, model)| {
+            self.symbols[..symbols.len()].copy_from_slice(symbols);
+            self.reference.set_symbols(symbols);
+            *model
+        })
+    }
+
+    /// Prime the model with `sample`, and start every following call from it.
+    fn train(&mut self, sample: &[u8]) {
+        let mut model = crate::RangeCoderModel::default();
+        let mut reference_model = model;
+        let mut out = vec![0; sample.len() * 2 + 64];
+        let mut reference_out = out.clone();
+        let size = crate::range_coder_compress(
+            &mut self.symbols,
+            Some(&mut model),
+            &[sample],
+            sample.len(),
+            &mut out,
+        );
+        let reference_size =
+            self.reference
+                .compress(Some(&mut reference_model), &[sample], &mut reference_out);
+        assert_eq!(size, reference_size);
+        assert_eq!(model, reference_model);
+        assert!(self.symbols == self.reference.symbols());
+        self.dictionary = Some((self.symbols[..model.next_symbol].to_vec(), model));
+    }
+
+    fn compress(&mut self, in_buffers: &[&[u8]], out_limit: usize) -> Vec<u8> {
+        let mut model = self.load_dictionary();
+        let mut reference_model = model;
+        let mut out = vec![0xaa; out_limit];
+        let mut reference_out = out.clone();
+        let in_limit = in_buffers.iter().map(|in_buffer| in_buffer.len()).sum();
+        let size = crate::range_coder_compress(
+            &mut self.symbols,
+            model.as_mut(),
+            in_buffers,
+            in_limit,
+            &mut out,
+        );
+        let reference_size =
+            self.reference
+                .compress(reference_model.as_mut(), in_buffers, &mut reference_out);
+        assert_eq!(size, reference_size);
+        assert_eq!(out, reference_out);
+        assert_eq!(model, reference_model);
+        assert!(self.symbols == self.reference.symbols());
+        out.truncate(size);
+        out
+    }
+
+    fn decompress(&mut self, in_data: &[u8], out_limit: usize) -> Vec<u8> {
+        let model = self.load_dictionary();
+        let mut out = vec![0xaa; out_limit];
+        let mut reference_out = out.clone();
+        let size =
+            crate::range_coder_decompress(&mut self.symbols, model.as_ref(), in_data, &mut out);
+        let reference_size = self
+            .reference
+            .decompress(model.as_ref(), in_data, &mut reference_out);
+        assert_eq!(size, reference_size);
+        assert_eq!(out, reference_out);
+        assert!(self.symbols == self.reference.symbols());
+        out.truncate(size);
+        out
+    }
+
+    /// Compress `data` split into `in_buffers`, and check it decompresses back to `data`.
+    fn round_trip(&mut self, in_buffers: &[&[u8]]) -> Vec<u8> {
+        let data = in_buffers.concat();
+        let compressed = self.compress(in_buffers, data.len() * 2 + 64);
+        if !data.is_empty() {
+            assert!(!compressed.is_empty());
+            assert_eq!(self.decompress(&compressed, data.len()), data);
+            // One byte short of the decompressed size fails.
+            assert!(self.decompress(&compressed, data.len() - 1).is_empty());
+            // Any output limit too small for the compressed data fails.
+            assert!(self.compress(in_buffers, compressed.len() - 1).is_empty());
+        }
+        compressed
+    }
+}
+
+/// Inputs for the range coder: random bytes, compressible data, and long runs which fill up and
+/// reset the model.
+fn range_coder_inputs(seed: u64) -> Vec<Vec<u8>> {
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(seed);
+    let mut inputs = Vec::new();
+    for length in [1, 2, 3, 4, 5, 16, 255, 256, 1000, 4000] {
+        inputs.push((0..length).map(|_| rng.random()).collect());
+        inputs.push(vec![rng.random(); length]);
+        inputs.push((0..length).map(|i| (i % 7) as u8).collect());
+        inputs.push((0..length).map(|_| rng.random_range(0..4)).collect());
+    }
+    for _ in 0..100 {
+        let length = rng.random_range(1..1500);
+        let alphabet = rng.random_range(1..=256);
+        inputs.push(
+            (0..length)
+                .map(|_| rng.random_range(0..alphabet) as u8)
+                .collect(),
+        );
+    }
+    inputs.push((0..30000).map(|_| rng.random()).collect());
+    inputs.push(
+        (0..30000)
+            .map(|i| (i as u8) ^ rng.random_range(0..2))
+            .collect(),
+    );
+    inputs.extend(game_state_packets(seed, 100));
+    inputs
+}
+
+#[test]
+fn range_coder_matches_reference() {
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut range_coder = DifferentialRangeCoder::new();
+    for input in range_coder_inputs(0) {
+        range_coder.round_trip(&[&input]);
+        // The same data split across several buffers compresses identically.
+        let first = rng.random_range(1..=input.len());
+        let second = rng.random_range(first..=input.len());
+        let (head, tail) = input.split_at(first);
+        let (middle, tail) = tail.split_at(second - first);
+        let mut in_buffers = vec![head];
+        in_buffers.extend(
+            [middle, tail]
+                .into_iter()
+                .filter(|buffer| !buffer.is_empty()),
+        );
+        assert_eq!(
+            range_coder.round_trip(&in_buffers),
+            range_coder.round_trip(&[&input])
+        );
+    }
+    assert!(range_coder.compress(&[], 64).is_empty());
+    assert!(range_coder.compress(&[&[]], 64).is_empty());
+    assert!(range_coder.decompress(&[], 64).is_empty());
+}
+
+#[test]
+fn range_coder_matches_reference_with_dictionary() {
+    let mut range_coder = DifferentialRangeCoder::new();
+    range_coder.train(&game_state_packets(0, 20).concat());
+    for input in range_coder_inputs(1) {
+        range_coder.round_trip(&[&input]);
+    }
+}
+
+#[test]
+fn range_coder_matches_reference_on_invalid_data() {
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(2);
+    let mut range_coder = DifferentialRangeCoder::new();
+    let mut dictionary_range_coder = DifferentialRangeCoder::new();
+    dictionary_range_coder.train(&game_state_packets(0, 20).concat());
+    for range_coder in [&mut range_coder, &mut dictionary_range_coder] {
+        for input in range_coder_inputs(3)
+            .into_iter()
+            .filter(|input| input.len() < 300)
+            .take(60)
+        {
+            let compressed = range_coder.round_trip(&[&input]);
+            // Truncated data.
+            for length in 0..compressed.len() {
+                range_coder.decompress(&compressed[..length], input.len() * 2 + 64);
+            }
+            // Corrupted data.
+            for _ in 0..20 {
+                let mut corrupted = compressed.clone();
+                let index = rng.random_range(0..corrupted.len());
+                corrupted[index] ^= 1 << rng.random_range(0..8);
+                range_coder.decompress(&corrupted, input.len() * 2 + 64);
+                range_coder.decompress(&corrupted, rng.random_range(0..input.len() * 2 + 64));
+            }
+        }
+        // Garbage, which either fails or decompresses to something.
+        for _ in 0..1000 {
+            let length = rng.random_range(1..200);
+            let garbage: Vec<u8> = (0..length).map(|_| rng.random()).collect();
+            range_coder.decompress(&garbage, 4096);
+            range_coder.decompress(&garbage, rng.random_range(0..64));
+        }
+        // Adversarial data which never reaches an end of stream, and keeps decoding new symbols
+        // until the output is full.
+        for value in [0, 0x7f, 0x80, 0xff] {
+            range_coder.decompress(&[value; 4096], 65536);
+            range_coder.decompress(&[value], 65536);
+        }
+    }
+}
+
+#[test]
+fn compression_statistics() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        compressor: Some(Box::new(enet::RangeCoder::new())),
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        compressor: Some(Box::new(enet::RangeCoder::new())),
+        ..Default::default()
+    });
+    network.connect(host1, host2, 1, 0);
+    let events = network.update(2);
+    assert_eq!(events.len(), 2);
+    let peer1 = network.resolve_peer(host1, host2);
+    let before = network.host(host1).peer(peer1).compression_statistics();
+
+    network.send(
+        host1,
+        host2,
+        0,
+        &enet::Packet::reliable([7; 1000].as_slice()),
+    );
+    let events = network.update(1);
+    assert_eq!(events.len(), 1);
+    let statistics = network.host(host1).peer(peer1).compression_statistics();
+    assert_eq!(statistics.attempts, before.attempts + 1);
+    assert_eq!(statistics.wins, before.wins + 1);
+    assert!(statistics.bytes_in - before.bytes_in > 1000);
+    assert!(statistics.bytes_out - before.bytes_out < 100);
+    assert_eq!(network.host(host1).statistics().compression, statistics);
+
+    // Compression can be turned off for a peer.
+    network
+        .host_mut(host1)
+        .peer_mut(peer1)
+        .set_compression(false);
+    network.send(
+        host1,
+        host2,
+        0,
+        &enet::Packet::reliable([7; 1000].as_slice()),
+    );
+    let events = network.update(1);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_receive_and(|event| event.packet.data() == [7; 1000].as_slice()));
+    assert_eq!(network.host(host1).statistics().compression, statistics);
+}
+
+#[test]
+fn adaptive_compression() {
+    use rand::{RngCore, SeedableRng};
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        compressor: Some(Box::new(enet::RangeCoder::new())),
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        compressor: Some(Box::new(enet::RangeCoder::new())),
+        ..Default::default()
+    });
+    network.connect(host1, host2, 1, 0);
+    let events = network.update(2);
+    assert_eq!(events.len(), 2);
+    let peer1 = network.resolve_peer(host1, host2);
+
+    // Random data does not compress, so compression is skipped after a while.
+    let mut send_random_packet = |network: &mut Network| {
+        let mut data = [0; 200];
+        rng.fill_bytes(&mut data);
+        network.send(host1, host2, 0, &enet::Packet::unreliable(data.as_slice()));
+        network.host_mut(host1).flush();
+    };
+    let mut sent = 0;
+    while !network.host(host1).peer(peer1).compression_bypassed() {
+        send_random_packet(&mut network);
+        sent += 1;
+        assert!(sent <= enet::consts::PEER_COMPRESSION_PROBE_ATTEMPTS);
+    }
+    let statistics = network.host(host1).peer(peer1).compression_statistics();
+    send_random_packet(&mut network);
+    assert_eq!(
+        network.host(host1).peer(peer1).compression_statistics(),
+        statistics
+    );
+
+    // Compression is tried again later.
+    network.update(enet::consts::PEER_COMPRESSION_BYPASS_INTERVAL as usize);
+    send_random_packet(&mut network);
+    assert!(!network.host(host1).peer(peer1).compression_bypassed());
+    assert!(
+        network
+            .host(host1)
+            .peer(peer1)
+            .compression_statistics()
+            .attempts
+            > statistics.attempts
+    );
+
+    // Without adaptive compression, every datagram is compressed.
+    network.host_mut(host1).set_adaptive_compression(false);
+    let attempts = network
+        .host(host1)
+        .peer(peer1)
+        .compression_statistics()
+        .attempts;
+    for _ in 0..enet::consts::PEER_COMPRESSION_PROBE_ATTEMPTS * 2 {
+        send_random_packet(&mut network);
+    }
+    assert!(!network.host(host1).peer(peer1).compression_bypassed());
+    assert_eq!(
+        network
+            .host(host1)
+            .peer(peer1)
+            .compression_statistics()
+            .attempts,
+        attempts + enet::consts::PEER_COMPRESSION_PROBE_ATTEMPTS * 2
+    );
+}
+
+#[test]
+fn siphash() {
+    use core::hash::Hasher;
+
+    // Reference vectors for SipHash-2-4 with key 00..0f and message 00..(length - 1).
+    let hash = |message: &[u8]| {
+        let mut hasher = crate::siphash::SipHasher24::new_with_keys(
+            0x0706_0504_0302_0100,
+            0x0f0e_0d0c_0b0a_0908,
+        );
+        hasher.write(message);
+        hasher.finish()
+    };
+    let message: Vec<u8> = (0..64).collect();
+    assert_eq!(hash(&message[..0]), 0x726f_db47_dd0e_0e31);
+    assert_eq!(hash(&message[..1]), 0x74f8_39c5_93dc_67fd);
+    assert_eq!(hash(&message[..8]), 0x93f5_f579_9a93_2462);
+    assert_eq!(hash(&message[..15]), 0xa129_ca61_49be_45e5);
+    assert_eq!(hash(&message[..63]), 0x958a_324c_eb06_4572);
+
+    // Writes are streamed, so splitting the message doesn't change the hash.
+    let mut hasher =
+        crate::siphash::SipHasher24::new_with_keys(0x0706_0504_0302_0100, 0x0f0e_0d0c_0b0a_0908);
+    hasher.write(&message[..5]);
+    hasher.write(&message[5..15]);
+    assert_eq!(hasher.finish(), hash(&message[..15]));
+}
+
+#[test]
+fn connect_cookies() {
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        checksum: Some(Box::new(enet::crc32)),
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        checksum: Some(Box::new(enet::crc32)),
+        connect_cookies: true,
+        ..Default::default()
+    });
+    assert!(network.host(host2).connect_cookies());
+
+    // The cookie adds a round trip to the handshake.
+    network.connect(host1, host2, 255, 5);
+    let events = network.update(3);
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|event| event.is_connect()));
+
+    network.send(host1, host2, 0, &enet::Packet::reliable("hello".as_bytes()));
+    let events = network.update(1);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_receive_and(|event| event.packet.data() == "hello".as_bytes()));
+}
+
+#[test]
+fn connect_cookies_spoofed() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    let host2 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        connect_cookies: true,
+        ..Default::default()
+    });
+    let cookies = Arc::new(AtomicUsize::new(0));
+    let intercept_cookies = cookies.clone();
+    network
+        .host_mut(host1)
+        .set_intercept(Some(Box::new(move |_, _, data| {
+            if data.get(2) == Some(&13) {
+                intercept_cookies.fetch_add(1, Ordering::SeqCst);
+            }
+            enet::Intercept::Ignore
+        })));
+
+    // A connect command sent by a host which never answers the cookie.
+    let mut connect = vec![0x0f, 0xff, 0x82, 0xff, 0x00, 0x01, 0x00, 0x00, 0xff, 0xff];
+    for value in [1392_u32, 65536, 1, 0, 0, 5000, 2, 2, 1234, 0] {
+        connect.extend_from_slice(&value.to_be_bytes());
+    }
+
+    network.conditions(host1, host2, NetworkConditions::perfect());
+    enet::Socket::send(network.host_mut(host1).socket_mut(), host2, &connect).unwrap();
+    network.update(10);
+    assert_eq!(cookies.load(Ordering::SeqCst), 1);
+    assert!(network
+        .host_mut(host2)
+        .peers()
+        .all(|peer| peer.state() == enet::PeerState::Disconnected));
+
+    // Without cookies, the same connect command allocates a peer.
+    network.host_mut(host2).set_connect_cookies(false).unwrap();
+    enet::Socket::send(network.host_mut(host1).socket_mut(), host2, &connect).unwrap();
+    network.update(10);
+    assert_eq!(cookies.load(Ordering::SeqCst), 1);
+    assert!(network
+        .host_mut(host2)
+        .peers()
+        .any(|peer| peer.state() == enet::PeerState::AcknowledgingConnect));
+}
+
+#[test]
+fn connect_cookie_secret() {
+    use std::sync::{Arc, Mutex};
+
+    // Every host in the test network uses the same seed, so cookies must not depend on it.
+    let mut network = Network::new();
+    let host1 = network.create_host(enet::HostSettings {
+        peer_limit: 1,
+        ..Default::default()
+    });
+    // The second host draws its secret when cookies are enabled after it is created.
+    let cookie_hosts = [
+        (true, None),
+        (false, None),
+        (true, Some([7; 16])),
+        (true, Some([7; 16])),
+    ]
+    .map(|(connect_cookies, connect_cookie_secret)| {
+        let host = network.create_host(enet::HostSettings {
+            peer_limit: 1,
+            connect_cookies,
+            connect_cookie_secret,
+            ..Default::default()
+        });
+        network.host_mut(host).set_connect_cookies(true).unwrap();
+        host
+    });
+    let cookies = Arc::new(Mutex::new(Vec::new()));
+    let intercept_cookies = cookies.clone();
+    network
+        .host_mut(host1)
+        .set_intercept(Some(Box::new(move |_, _, data| {
+            if data.get(2) == Some(&13) {
+                intercept_cookies
+                    .lock()
+                    .unwrap()
+                    .push(data[data.len() - 8..].to_vec());
+            }
+            enet::Intercept::Ignore
+        })));
+
+    let mut connect = vec![0x0f, 0xff, 0x82, 0xff, 0x00, 0x01, 0x00, 0x00, 0xff, 0xff];
+    for value in [1392_u32, 65536, 1, 0, 0, 5000, 2, 2, 1234, 0] {
+        connect.extend_from_slice(&value.to_be_bytes());
+    }
+    for host in cookie_hosts {
+        network.conditions(host1, host, NetworkConditions::perfect());
+        enet::Socket::send(network.host_mut(host1).socket_mut(), host, &connect).unwrap();
+        network.update(10);
+    }
+    let cookies = cookies.lock().unwrap();
+    assert_eq!(cookies.len(), 4);
+    assert_ne!(cookies[0], cookies[1]);
+    assert_ne!(cookies[0], cookies[2]);
+    assert_eq!(cookies[2], cookies[3]);
+}
+
+#[cfg(feature = "connected")]
+struct Stream {
+    readable: Vec<u8>,
+    written: Vec<u8>,
+    write_budget: usize,
+}
+
+#[cfg(feature = "connected")]
+impl enet::connected::Connection for Stream {
+    type Address = ();
+    type Error = core::convert::Infallible;
+
+    fn init(&mut self) -> Result<Self::Address, Self::Error> {
+        Ok(())
+    }
+
+    fn send(&mut self, buffer: &[u8]) -> Result<usize, Self::Error> {
+        let sent_length = buffer.len().min(self.write_budget);
+        self.written.extend_from_slice(&buffer[..sent_length]);
+        self.write_budget -= sent_length;
+        Ok(sent_length)
+    }
+
+    fn receive(
+        &mut self,
+        buffer: &mut [u8; enet::MTU_MAX],
+    ) -> Result<Option<enet::PacketReceived>, Self::Error> {
+        if self.readable.is_empty() {
+            return Ok(None);
+        }
+        let recv_length = self.readable.len().min(3);
+        buffer[..recv_length].copy_from_slice(&self.readable[..recv_length]);
+        self.readable.drain(0..recv_length);
+        Ok(Some(enet::PacketReceived::Complete(recv_length)))
+    }
+}
+
+#[cfg(feature = "connected")]
+#[test]
+fn framed() {
+    use enet::connected::Connection;
+
+    let mut sender = enet::connected::Framed::new(Stream {
+        readable: Vec::new(),
+        written: Vec::new(),
+        write_budget: 6,
+    });
+    assert_eq!(sender.send(&[1, 2, 3, 4, 5]).unwrap(), 5);
+    assert_eq!(sender.send(&[6, 7]).unwrap(), 2);
+    assert_eq!(sender.pending_write(), 5);
+    sender.get_mut().write_budget = usize::MAX;
+    assert_eq!(sender.send(&[]).unwrap(), 0);
+    assert_eq!(sender.pending_write(), 0);
+
+    // Every complete frame is returned by its own call, without empty polls in between.
+    let mut receiver = enet::connected::Framed::new(Stream {
+        readable: sender.into_inner().written,
+        written: Vec::new(),
+        write_budget: 0,
+    });
+    let mut buffer = [0; enet::MTU_MAX];
+    let mut received = Vec::new();
+    while let Some(enet::PacketReceived::Complete(length)) = receiver.receive(&mut buffer).unwrap()
+    {
+        received.push(buffer[..length].to_vec());
+    }
+    assert_eq!(received, [vec![1, 2, 3, 4, 5], vec![6, 7], vec![]]);
+}
+
+#[cfg(feature = "connected")]
+#[test]
+fn framed_tcp_stream() {
+    use std::net::{TcpListener, TcpStream};
+
+    use enet::connected::{ConnectionKind, EventNoRef, Framed, Host};
+
+    fn service_until(
+        mut hosts: [&mut Host<Framed<TcpStream>>; 2],
+        mut condition: impl FnMut(usize, EventNoRef) -> bool,
+    ) {
+        for _ in 0..500 {
+            for (index, host) in hosts.iter_mut().enumerate() {
+                while let Some(event) = host.service() {
+                    if condition(index, event.no_ref()) {
+                        return;
+                    }
+                }
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        panic!("condition was never met");
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server_stream, _) = listener.accept().unwrap();
+
+    let mut client = Host::<Framed<TcpStream>>::new(enet::HostSettings::default()).unwrap();
+    let mut server = Host::<Framed<TcpStream>>::new(enet::HostSettings::default()).unwrap();
+    let client_peer = client
+        .add_connection(ConnectionKind::Initiator {
+            connection: Framed::new(client_stream),
+            channel_count: 1,
+            data: 7,
+        })
+        .unwrap()
+        .id();
+    server
+        .add_connection(ConnectionKind::Receiver {
+            connection: Framed::new(server_stream),
+            timeout: Duration::from_secs(5),
+        })
+        .unwrap();
+
+    let mut connected = 0;
+    service_until([&mut client, &mut server], |_, event| {
+        if matches!(event, EventNoRef::Connect { .. }) {
+            connected += 1;
+        }
+        connected == 2
+    });
+
+    // Large packets are sent in separate datagrams, so several frames arrive at once.
+    for index in 0..8_u8 {
+        client
+            .peer_mut(client_peer)
+            .send(0, &enet::Packet::reliable(vec![index; 1000].as_slice()))
+            .unwrap();
+    }
+    client.flush();
+    let mut received = Vec::new();
+    service_until([&mut client, &mut server], |index, event| {
+        if let (1, EventNoRef::Receive { packet, .. }) = (index, event) {
+            received.push(packet.data()[0]);
+        }
+        received.len() == 8
+    });
+    assert_eq!(received, (0..8).collect::<Vec<_>>());
+
+    // Closing the stream is noticed immediately, instead of waiting for a timeout.
+    drop(client);
+    let mut disconnected = false;
+    for _ in 0..500 {
+        if let Some(event) = server.service() {
+            disconnected = matches!(event.no_ref(), EventNoRef::Disconnect { .. });
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    assert!(disconnected);
+}
+
+#[cfg(feature = "chacha20poly1305")]
+#[test]
+fn chacha20poly1305_replay() {
+    use enet::Cipher;
+
+    let key = [1; 32];
+    let mut sender = enet::ChaCha20Poly1305::with_seed(0);
+    let mut receiver = enet::ChaCha20Poly1305::with_seed(1);
+    let mut nonce_counter = enet::NonceCounter::new();
+    let mut replay_window = enet::ReplayWindow::new();
+    let mut encrypt = || {
+        let mut datagram = vec![0; 5 + sender.overhead()];
+        let size = sender.encrypt(&key, &mut nonce_counter, &[], &[b"hello"], &mut datagram);
+        datagram.truncate(size);
+        datagram
+    };
+    let mut out = [0; 5];
+
+    let first = encrypt();
+    let second = encrypt();
+    let datagrams = (0..enet::ReplayWindow::SIZE)
+        .map(|_| encrypt())
+        .collect::<Vec<_>>();
+
+    // Datagrams may arrive out of order, but each is only accepted once.
+    assert_eq!(
+        receiver.decrypt(&key, &mut replay_window, &[], &second, &mut out),
+        5
+    );
+    assert_eq!(&out, b"hello");
+    assert_eq!(
+        receiver.decrypt(&key, &mut replay_window, &[], &first, &mut out),
+        5
+    );
+    assert_eq!(
+        receiver.decrypt(&key, &mut replay_window, &[], &second, &mut out),
+        0
+    );
+    assert_eq!(
+        receiver.decrypt(&key, &mut replay_window, &[], &first, &mut out),
+        0
+    );
+
+    // A forged datagram does not move the window.
+    let mut forged = datagrams[datagrams.len() - 1].clone();
+    forged[12] ^= 1;
+    assert_eq!(
+        receiver.decrypt(&key, &mut replay_window, &[], &forged, &mut out),
+        0
+    );
+
+    // Nonces which fall out of the window are discarded.
+    for datagram in &datagrams {
+        assert_eq!(
+            receiver.decrypt(&key, &mut replay_window, &[], datagram, &mut out),
+            5
+        );
+    }
+    assert!(!replay_window.check(0));
+    assert!(!replay_window.check(1));
+}
+
+#[cfg(feature = "chacha20poly1305")]
+#[test]
+fn chacha20poly1305_many_peers() {
+    use enet::Cipher;
+
+    // Nonces are counted per peer, so datagrams sent to many other peers in between don't push a
+    // reordered datagram out of its peer's window.
+    let key = [1; 32];
+    let peer_count = enet::ReplayWindow::SIZE as usize * 2;
+    let mut sender = enet::ChaCha20Poly1305::with_seed(0);
+    let mut receiver = enet::ChaCha20Poly1305::with_seed(1);
+    let mut nonce_counters = vec![enet::NonceCounter::new(); peer_count];
+    let mut datagrams = vec![vec![]; peer_count];
+    for _ in 0..4 {
+        for (nonce_counter, datagrams) in nonce_counters.iter_mut().zip(&mut datagrams) {
+            let mut datagram = vec![0; 5 + sender.overhead()];
+            let size = sender.encrypt(&key, nonce_counter, &[], &[b"hello"], &mut datagram);
+            datagram.truncate(size);
+            datagrams.push(datagram);
+        }
+    }
+
+    let mut out = [0; 5];
+    for datagrams in &datagrams {
+        let mut replay_window = enet::ReplayWindow::new();
+        for datagram in datagrams.iter().rev() {
+            assert_eq!(
+                receiver.decrypt(&key, &mut replay_window, &[], datagram, &mut out),
+                5
+            );
+        }
+        for datagram in datagrams {
+            assert_eq!(
+                receiver.decrypt(&key, &mut replay_window, &[], datagram, &mut out),
+                0
+            );
+        }
+    }
 }
@@ -1,7 +1,6 @@
 use std::{
     collections::HashMap,
-    convert::Infallible,
-    io::{copy, Cursor},
+    io::{self, copy, Cursor},
     ops::{Deref, DerefMut},
     sync::{mpsc, Arc, RwLock},
     time::Duration,
@@ -16,6 +15,8 @@ use crate::{Box, Vec};
 pub struct Socket {
     sender: mpsc::Sender<(usize, Vec<u8>)>,
     receiver: mpsc::Receiver<(usize, Vec<u8>)>,
+    /// How many more datagrams can be sent before a send fails, see [`Network::fail_send`].
+    send_limit: Option<usize>,
 }
 
 impl Socket {
@@ -26,10 +27,12 @@ impl Socket {
             Socket {
                 sender: sender1,
                 receiver: receiver1,
+                send_limit: None,
             },
             Socket {
                 sender: sender2,
                 receiver: receiver2,
+                send_limit: None,
             },
         )
     }
@@ -49,13 +52,21 @@ impl Socket {
 
 impl enet::Socket for Socket {
     type Address = usize;
-    type Error = Infallible;
+    type Error = io::Error;
 
     fn init(&mut self, _socket_options: enet::SocketOptions) -> Result<(), Self::Error> {
         Ok(())
     }
 
     fn send(&mut self, address: Self::Address, buffer: &[u8]) -> Result<usize, Self::Error> {
+        match self.send_limit {
+            Some(0) => {
+                self.send_limit = None;
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            Some(send_limit) => self.send_limit = Some(send_limit - 1),
+            None => {}
+        }
         Socket::send(self, address, buffer);
         Ok(buffer.len())
     }
@@ -91,6 +102,10 @@ impl enet::Address for usize {
     fn is_broadcast(&self) -> bool {
         false
     }
+
+    fn hash_address(&self, state: &mut dyn core::hash::Hasher) {
+        state.write_usize(*self);
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -125,6 +140,14 @@ impl NetworkConditions {
         }
     }
 
+    pub const fn latency(round_trip_time: u32) -> Self {
+        Self {
+            round_trip_time,
+            round_trip_time_variance: 0,
+            loss_chance: 0.,
+        }
+    }
+
     pub const fn disconnected() -> Self {
         Self {
             round_trip_time: 0,
@@ -149,6 +172,8 @@ pub struct Network {
     hosts: Vec<enet::Host<Socket>>,
     conditions: HashMap<(usize, usize), NetworkConditions>,
     connections: HashMap<(usize, usize), enet::PeerID>,
+    /// The address of every peer which has connected, kept after it disconnects.
+    peer_addresses: HashMap<(usize, enet::PeerID), usize>,
     time: Arc<RwLock<u32>>,
 }
 
@@ -161,6 +186,7 @@ impl Network {
             hosts: Vec::default(),
             conditions: HashMap::default(),
             connections: HashMap::default(),
+            peer_addresses: HashMap::default(),
             time: Arc::default(),
         }
     }
@@ -205,21 +231,40 @@ impl Network {
                 loop {
                     self.send_and_receive(now);
                     let host = &mut self.hosts[host_index];
-                    if let Some(event) = host.service().unwrap() {
+                    // A failed send ends the service, see Network::fail_send.
+                    let Ok(event) = host.service() else {
+                        break;
+                    };
+                    if let Some(event) = event {
                         let peer_index: usize;
                         match &event {
                             enet::Event::Connect { peer, .. } => {
                                 peer_index = peer.address().unwrap();
                                 self.connections.insert((host_index, peer_index), peer.id());
+                                self.peer_addresses
+                                    .insert((host_index, peer.id()), peer_index);
                             }
-                            enet::Event::Disconnect { peer, .. } => {
+                            enet::Event::Disconnect { peer, .. }
+                            | enet::Event::ConnectFailed { peer, .. } => {
                                 peer_index = peer.address().unwrap();
                                 self.conditions.remove(&(host_index, peer_index));
                                 self.connections.remove(&(host_index, peer_index));
                             }
-                            enet::Event::Receive { peer, .. } => {
+                            enet::Event::Receive { peer, .. } | enet::Event::Writable { peer } => {
                                 peer_index = peer.address().unwrap();
                             }
+                            enet::Event::Acknowledged { peer, .. }
+                            | enet::Event::Dropped { peer, .. } => {
+                                peer_index = self.peer_addresses[&(host_index, *peer)];
+                            }
+                            enet::Event::SendFailed { peer, .. } => {
+                                // The peer may never have connected, if its ID was stale.
+                                peer_index = self
+                                    .peer_addresses
+                                    .get(&(host_index, *peer))
+                                    .copied()
+                                    .unwrap_or(host_index);
+                            }
                         }
                         events.push(Event {
                             from: peer_index,
@@ -237,6 +282,17 @@ impl Network {
         events
     }
 
+    /// Fail the next send from a host after `sent` more datagrams have been sent.
+    pub fn fail_send(&mut self, host: usize, sent: usize) {
+        self.hosts[host].socket_mut().send_limit = Some(sent);
+    }
+
+    /// Advance n frames without servicing any hosts.
+    pub fn skip(&mut self, frames: usize) {
+        let mut time = self.time.write().unwrap();
+        *time = time.wrapping_add(frames as u32);
+    }
+
     pub fn create_host(&mut self, mut settings: enet::HostSettings) -> usize {
         let index = self.hosts.len();
         let time = self.time.clone();
@@ -258,6 +314,20 @@ impl Network {
         self.conditions(from, to, NetworkConditions::perfect());
     }
 
+    pub fn connect_with_settings(
+        &mut self,
+        from: usize,
+        to: usize,
+        channel_count: usize,
+        data: u32,
+        settings: enet::ConnectSettings,
+    ) {
+        self.hosts[from]
+            .connect_with_settings(to, channel_count, data, settings)
+            .unwrap();
+        self.conditions(from, to, NetworkConditions::perfect());
+    }
+
     pub fn conditions(&mut self, host1: usize, host2: usize, conditions: NetworkConditions) {
         self.conditions.insert((host1, host2), conditions);
         self.conditions.insert((host2, host1), conditions);
@@ -287,6 +357,43 @@ impl Network {
             .unwrap();
     }
 
+    pub fn send_tracked(
+        &mut self,
+        from: usize,
+        to: usize,
+        channel_id: u8,
+        packet: &enet::Packet,
+    ) -> enet::SendToken {
+        let peer = self.resolve_peer(from, to);
+        self.hosts[from]
+            .peer_mut(peer)
+            .send_tracked(channel_id, packet)
+            .unwrap()
+    }
+
+    pub fn send_with_settings(
+        &mut self,
+        from: usize,
+        to: usize,
+        channel_id: u8,
+        packet: &enet::Packet,
+        settings: enet::SendSettings,
+    ) {
+        let peer = self.resolve_peer(from, to);
+        self.hosts[from]
+            .peer_mut(peer)
+            .send_with_settings(channel_id, packet, settings)
+            .unwrap();
+    }
+
+    pub fn host(&self, host: usize) -> &enet::Host<Socket> {
+        &self.hosts[host]
+    }
+
+    pub fn host_mut(&mut self, host: usize) -> &mut enet::Host<Socket> {
+        &mut self.hosts[host]
+    }
+
     pub fn round_trip_time(&self, from: usize, to: usize) -> Duration {
         let peer = self.resolve_peer(from, to);
         self.hosts[from].peer(peer).round_trip_time()
@@ -339,6 +446,15 @@ pub struct EventDisconnect {
     pub to: usize,
     pub peer: enet::PeerID,
     pub data: u32,
+    pub reason: enet::DisconnectReason,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventConnectFailed {
+    pub from: usize,
+    pub to: usize,
+    pub peer: enet::PeerID,
+    pub reason: enet::DisconnectReason,
 }
 
 #[derive(Debug, Clone)]
@@ -350,6 +466,22 @@ pub struct EventReceive {
     pub packet: enet::Packet,
 }
 
+#[derive(Debug, Clone)]
+pub struct EventSendToken {
+    pub from: usize,
+    pub to: usize,
+    pub peer: enet::PeerID,
+    pub token: enet::SendToken,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventSendFailed {
+    pub to: usize,
+    pub peer: enet::PeerID,
+    pub channel_id: u8,
+    pub error: enet::error::PeerSendError,
+}
+
 impl Event {
     pub const fn from(&self) -> usize {
         self.from
@@ -381,12 +513,30 @@ impl Event {
     }
 
     pub fn is_disconnect_and(&self, and: impl Fn(EventDisconnect) -> bool) -> bool {
-        if let enet::EventNoRef::Disconnect { peer, data } = &self.event {
+        if let enet::EventNoRef::Disconnect { peer, data, reason } = &self.event {
             and(EventDisconnect {
                 from: self.from,
                 to: self.to,
                 peer: *peer,
                 data: *data,
+                reason: *reason,
+            })
+        } else {
+            false
+        }
+    }
+
+    pub const fn is_connect_failed(&self) -> bool {
+        matches!(&self.event, enet::EventNoRef::ConnectFailed { .. })
+    }
+
+    pub fn is_connect_failed_and(&self, and: impl Fn(EventConnectFailed) -> bool) -> bool {
+        if let enet::EventNoRef::ConnectFailed { peer, reason } = &self.event {
+            and(EventConnectFailed {
+                from: self.from,
+                to: self.to,
+                peer: *peer,
+                reason: *reason,
             })
         } else {
             false
@@ -415,4 +565,52 @@ impl Event {
             false
         }
     }
+
+    pub fn is_acknowledged_and(&self, and: impl Fn(EventSendToken) -> bool) -> bool {
+        if let enet::EventNoRef::Acknowledged { peer, token } = &self.event {
+            and(EventSendToken {
+                from: self.from,
+                to: self.to,
+                peer: *peer,
+                token: *token,
+            })
+        } else {
+            false
+        }
+    }
+
+    pub fn is_dropped_and(&self, and: impl Fn(EventSendToken) -> bool) -> bool {
+        if let enet::EventNoRef::Dropped { peer, token } = &self.event {
+            and(EventSendToken {
+                from: self.from,
+                to: self.to,
+                peer: *peer,
+                token: *token,
+            })
+        } else {
+            false
+        }
+    }
+
+    pub fn is_send_failed_and(&self, and: impl Fn(EventSendFailed) -> bool) -> bool {
+        if let enet::EventNoRef::SendFailed {
+            peer,
+            channel_id,
+            error,
+        } = &self.event
+        {
+            and(EventSendFailed {
+                to: self.to,
+                peer: *peer,
+                channel_id: *channel_id,
+                error: *error,
+            })
+        } else {
+            false
+        }
+    }
+
+    pub const fn is_writable(&self) -> bool {
+        matches!(&self.event, enet::EventNoRef::Writable { .. })
+    }
 }
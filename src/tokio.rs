@@ -0,0 +1,407 @@
+//! An async front-end for ENet, built on [tokio](https://tokio.rs).
+//!
+//! [`tokio::new`](`new`) creates a host over a [`tokio::net::UdpSocket`]
+//! and splits it into three parts:
+//! - A [`tokio::Driver`](`Driver`), which owns the host and must be run as a task. It services the
+//!   host whenever the socket becomes readable, a command arrives, or a protocol timer fires (see
+//!   [`Host::service_delay`](`crate::Host::service_delay`)).
+//! - A [`tokio::Handle`](`Handle`), which can be cloned and sent across threads to connect,
+//!   send packets, and disconnect.
+//! - A [`tokio::Events`](`Events`) stream of [`EventNoRef`](`crate::EventNoRef`).
+//!
+//! ```no_run
+//! # #[cfg(feature = "tokio")]
+//! # async fn example() -> std::io::Result<()> {
+//! use rusty_enet as enet;
+//!
+//! let socket = ::tokio::net::UdpSocket::bind("0.0.0.0:6060").await?;
+//! let (driver, handle, mut events) = enet::tokio::new(socket, enet::HostSettings::default())
+//!     .expect("Failed to create host.");
+//! ::tokio::spawn(driver.run());
+//! while let Some(event) = events.next().await {
+//!     if let enet::EventNoRef::Receive { peer, channel_id, packet } = event {
+//!         _ = handle.send(peer, channel_id, packet).await;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use std::{
+    io::{self, ErrorKind},
+    net::SocketAddr,
+};
+
+use ::tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    error::{HostNewError, NoAvailablePeers, PeerSendError},
+    EventNoRef, HostSettings, Packet, PacketReceived, PeerID, SocketOptions, MTU_MAX,
+};
+
+/// A [`Socket`](`crate::Socket`) adapter for [`tokio::net::UdpSocket`].
+#[derive(Debug)]
+pub struct UdpSocket(::tokio::net::UdpSocket);
+
+impl UdpSocket {
+    /// Wrap a tokio UDP socket for use with ENet.
+    #[must_use]
+    pub fn new(socket: ::tokio::net::UdpSocket) -> Self {
+        Self(socket)
+    }
+
+    /// Get a reference to the underlying tokio socket.
+    #[must_use]
+    pub fn get_ref(&self) -> &::tokio::net::UdpSocket {
+        &self.0
+    }
+}
+
+impl crate::Socket for UdpSocket {
+    type Address = SocketAddr;
+    type Error = io::Error;
+
+    fn init(&mut self, _socket_options: SocketOptions) -> Result<(), io::Error> {
+        self.0.set_broadcast(true)
+    }
+
+    fn send(&mut self, address: SocketAddr, buffer: &[u8]) -> Result<usize, io::Error> {
+        match self.0.try_send_to(buffer, address) {
+            Ok(sent_length) => Ok(sent_length),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(0),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn receive(
+        &mut self,
+        buffer: &mut [u8; MTU_MAX],
+    ) -> Result<Option<(SocketAddr, PacketReceived)>, io::Error> {
+        match self.0.try_recv_from(buffer) {
+            Ok((recv_length, recv_addr)) => {
+                Ok(Some((recv_addr, PacketReceived::Complete(recv_length))))
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+enum Command {
+    Connect {
+        address: SocketAddr,
+        channel_count: usize,
+        data: u32,
+        result: oneshot::Sender<Result<PeerID, NoAvailablePeers>>,
+    },
+    Send {
+        peer: PeerID,
+        channel_id: u8,
+        packet: Packet,
+        result: oneshot::Sender<Result<(), PeerSendError>>,
+    },
+    Broadcast {
+        channel_id: u8,
+        packet: Packet,
+    },
+    Disconnect {
+        peer: PeerID,
+        data: u32,
+    },
+    DisconnectNow {
+        peer: PeerID,
+        data: u32,
+    },
+    DisconnectLater {
+        peer: PeerID,
+        data: u32,
+    },
+}
+
+/// Create an async host over a tokio UDP socket.
+///
+/// See the [module level docs](`self`) for more information.
+///
+/// # Errors
+///
+/// Returns the same errors as [`Host::new`](`crate::Host::new`).
+pub fn new(
+    socket: ::tokio::net::UdpSocket,
+    settings: HostSettings,
+) -> Result<(Driver, Handle, Events), HostNewError<UdpSocket>> {
+    let host = crate::Host::new(UdpSocket::new(socket), settings)?;
+    let (command_sender, command_receiver) = mpsc::unbounded_channel();
+    let (event_sender, event_receiver) = mpsc::unbounded_channel();
+    Ok((
+        Driver {
+            host,
+            commands: command_receiver,
+            events: event_sender,
+        },
+        Handle {
+            commands: command_sender,
+        },
+        Events {
+            events: event_receiver,
+        },
+    ))
+}
+
+/// Owns an ENet host and services it. Created with [`tokio::new`](`new`).
+///
+/// Nothing is sent or received until [`Driver::run`] is awaited, typically with
+/// [`tokio::spawn`](https://docs.rs/tokio/latest/tokio/fn.spawn.html).
+pub struct Driver {
+    host: crate::Host<UdpSocket>,
+    commands: mpsc::UnboundedReceiver<Command>,
+    events: mpsc::UnboundedSender<EventNoRef>,
+}
+
+impl Driver {
+    /// Get a reference to the underlying host.
+    #[must_use]
+    pub fn host(&self) -> &crate::Host<UdpSocket> {
+        &self.host
+    }
+
+    /// Get a mutable reference to the underlying host, for configuration before running.
+    pub fn host_mut(&mut self) -> &mut crate::Host<UdpSocket> {
+        &mut self.host
+    }
+
+    /// Service the host until every [`Handle`] and the [`Events`] stream have been dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error reported by the socket.
+    pub async fn run(mut self) -> Result<(), io::Error> {
+        let mut commands_closed = false;
+        loop {
+            while let Ok(command) = self.commands.try_recv() {
+                self.apply(command);
+            }
+            while let Some(event) = self.host.service()? {
+                _ = self.events.send(event.no_ref());
+            }
+            if commands_closed && self.events.is_closed() {
+                return Ok(());
+            }
+            let service_delay = self.host.service_delay();
+            ::tokio::select! {
+                result = self.host.socket().0.readable() => result?,
+                command = self.commands.recv(), if !commands_closed => {
+                    if let Some(command) = command {
+                        self.apply(command);
+                    } else {
+                        commands_closed = true;
+                    }
+                }
+                () = ::tokio::time::sleep(service_delay.unwrap_or(Duration::ZERO)),
+                    if service_delay.is_some() => {}
+            }
+        }
+    }
+
+    fn apply(&mut self, command: Command) {
+        match command {
+            Command::Connect {
+                address,
+                channel_count,
+                data,
+                result,
+            } => {
+                _ = result.send(
+                    self.host
+                        .connect(address, channel_count, data)
+                        .map(|peer| peer.id()),
+                );
+            }
+            Command::Send {
+                peer,
+                channel_id,
+                packet,
+                result,
+            } => {
+                _ = result.send(
+                    self.host
+                        .get_peer_mut(peer)
+                        .map_or(Err(PeerSendError::NotConnected), |peer| {
+                            peer.send(channel_id, &packet)
+                        }),
+                );
+            }
+            Command::Broadcast { channel_id, packet } => {
+                self.host.broadcast(channel_id, &packet);
+            }
+            Command::Disconnect { peer, data } => {
+                if let Some(peer) = self.host.get_peer_mut(peer) {
+                    peer.disconnect(data);
+                }
+            }
+            Command::DisconnectNow { peer, data } => {
+                if let Some(peer) = self.host.get_peer_mut(peer) {
+                    peer.disconnect_now(data);
+                }
+            }
+            Command::DisconnectLater { peer, data } => {
+                if let Some(peer) = self.host.get_peer_mut(peer) {
+                    peer.disconnect_later(data);
+                }
+            }
+        }
+    }
+}
+
+/// A cloneable handle for controlling a [`Driver`] from any task or thread.
+///
+/// Commands are applied in order, the next time the driver wakes up.
+#[derive(Clone)]
+pub struct Handle {
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl Handle {
+    /// See [`Host::connect`](`crate::Host::connect`).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NoAvailablePeers`] if all peer slots have been filled, or if the driver has
+    /// stopped.
+    pub async fn connect(
+        &self,
+        address: SocketAddr,
+        channel_count: usize,
+        data: u32,
+    ) -> Result<PeerID, NoAvailablePeers> {
+        let (result, receiver) = oneshot::channel();
+        _ = self.commands.send(Command::Connect {
+            address,
+            channel_count,
+            data,
+            result,
+        });
+        receiver.await.unwrap_or(Err(NoAvailablePeers))
+    }
+
+    /// See [`Peer::send`](`crate::Peer::send`).
+    ///
+    /// # Errors
+    ///
+    /// May return any of the [`PeerSendError`] variants on failure. Returns
+    /// [`PeerSendError::NotConnected`] if the driver has stopped.
+    pub async fn send(
+        &self,
+        peer: PeerID,
+        channel_id: u8,
+        packet: Packet,
+    ) -> Result<(), PeerSendError> {
+        let (result, receiver) = oneshot::channel();
+        _ = self.commands.send(Command::Send {
+            peer,
+            channel_id,
+            packet,
+            result,
+        });
+        receiver.await.unwrap_or(Err(PeerSendError::NotConnected))
+    }
+
+    /// See [`Host::broadcast`](`crate::Host::broadcast`).
+    pub fn broadcast(&self, channel_id: u8, packet: Packet) {
+        _ = self
+            .commands
+            .send(Command::Broadcast { channel_id, packet });
+    }
+
+    /// See [`Peer::disconnect`](`crate::Peer::disconnect`).
+    pub fn disconnect(&self, peer: PeerID, data: u32) {
+        _ = self.commands.send(Command::Disconnect { peer, data });
+    }
+
+    /// See [`Peer::disconnect_now`](`crate::Peer::disconnect_now`).
+    pub fn disconnect_now(&self, peer: PeerID, data: u32) {
+        _ = self.commands.send(Command::DisconnectNow { peer, data });
+    }
+
+    /// See [`Peer::disconnect_later`](`crate::Peer::disconnect_later`).
+    pub fn disconnect_later(&self, peer: PeerID, data: u32) {
+        _ = self.commands.send(Command::DisconnectLater { peer, data });
+    }
+}
+
+/// A stream of events produced by a [`Driver`].
+///
+/// Implements [`futures_core::Stream`].
+pub struct Events {
+    events: mpsc::UnboundedReceiver<EventNoRef>,
+}
+
+impl Events {
+    /// Wait for the next event, or return [`None`] if the driver has stopped.
+    pub async fn next(&mut self) -> Option<EventNoRef> {
+        self.events.recv().await
+    }
+}
+
+impl futures_core::Stream for Events {
+    type Item = EventNoRef;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<EventNoRef>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{self as enet, EventNoRef, Packet};
+
+    #[::tokio::test]
+    async fn loopback() {
+        let (driver1, _handle1, mut events1) = enet::tokio::new(
+            ::tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap(),
+            enet::HostSettings::default(),
+        )
+        .unwrap();
+        let address = driver1.host().socket().get_ref().local_addr().unwrap();
+        let (driver2, handle2, mut events2) = enet::tokio::new(
+            ::tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap(),
+            enet::HostSettings::default(),
+        )
+        .unwrap();
+        ::tokio::spawn(driver1.run());
+        ::tokio::spawn(driver2.run());
+
+        ::tokio::time::timeout(Duration::from_secs(5), async move {
+            let peer = handle2.connect(address, 1, 7).await.unwrap();
+            assert!(matches!(
+                events1.next().await,
+                Some(EventNoRef::Connect { data: 7, .. })
+            ));
+            assert!(matches!(
+                events2.next().await,
+                Some(EventNoRef::Connect { .. })
+            ));
+            handle2
+                .send(peer, 0, Packet::reliable("hello".as_bytes()))
+                .await
+                .unwrap();
+            let Some(EventNoRef::Receive { packet, .. }) = events1.next().await else {
+                panic!("Expected a receive event.");
+            };
+            assert_eq!(packet.data(), "hello".as_bytes());
+            handle2.disconnect(peer, 3);
+            assert!(matches!(
+                events1.next().await,
+                Some(EventNoRef::Disconnect { data: 3, .. })
+            ));
+        })
+        .await
+        .unwrap();
+    }
+}